@@ -1,6 +1,6 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use std::hint::black_box;
-use logos_collab::protocol::{AwarenessState, PeerInfo, SyncMessage};
+use logos_collab::protocol::{AwarenessState, PeerInfo, SyncMessage, DEFAULT_COMPRESSION_THRESHOLD};
 use logos_collab::broadcast::BroadcastGroup;
 use logos_collab::presence::{
     AwarenessMessage, CursorColor, CursorRenderData, PresenceRoom, Vec2,
@@ -11,6 +11,7 @@ use logos_collab::storage::{
 };
 use uuid::Uuid;
 use std::sync::Arc;
+use yrs::{Text, Transact, WriteTxn};
 
 fn bench_delta_encode(c: &mut Criterion) {
     let peer = Uuid::new_v4();
@@ -371,6 +372,54 @@ fn bench_lz4_decompress_1kb(c: &mut Criterion) {
     });
 }
 
+/// A SyncStep2 carrying a Yrs update of about `size` bytes of pasted text.
+fn paste_message(size: usize) -> SyncMessage {
+    let line = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ";
+    let text = line.repeat(size / line.len() + 1);
+    let doc = yrs::Doc::new();
+    let mut txn = doc.transact_mut();
+    txn.get_or_insert_text("content").insert(&mut txn, 0, &text[..size]);
+    SyncMessage::sync_step2(Uuid::new_v4(), Uuid::new_v4(), txn.encode_update_v1())
+}
+
+fn bench_wire_compression_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wire_encode");
+    for size in [1024, 16 * 1024, 256 * 1024] {
+        let msg = paste_message(size);
+        let plain = msg.encode().unwrap().len();
+        let compressed = msg.encode_with(Some(DEFAULT_COMPRESSION_THRESHOLD)).unwrap().len();
+        // Bandwidth side of the trade-off; criterion reports the time side
+        println!("wire_encode/{size}: {plain} bytes plain, {compressed} bytes lz4");
+
+        group.throughput(Throughput::Bytes(plain as u64));
+        group.bench_with_input(BenchmarkId::new("plain", size), &msg, |b, msg| {
+            b.iter(|| black_box(msg.encode().unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("lz4", size), &msg, |b, msg| {
+            b.iter(|| black_box(msg.encode_with(Some(DEFAULT_COMPRESSION_THRESHOLD)).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_wire_compression_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wire_decode");
+    for size in [1024, 16 * 1024, 256 * 1024] {
+        let msg = paste_message(size);
+        let plain = msg.encode().unwrap();
+        let compressed = msg.encode_with(Some(DEFAULT_COMPRESSION_THRESHOLD)).unwrap();
+
+        group.throughput(Throughput::Bytes(plain.len() as u64));
+        group.bench_with_input(BenchmarkId::new("plain", size), &plain, |b, bytes| {
+            b.iter(|| black_box(SyncMessage::decode(bytes).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("lz4", size), &compressed, |b, bytes| {
+            b.iter(|| black_box(SyncMessage::decode(bytes).unwrap()))
+        });
+    }
+    group.finish();
+}
+
 fn bench_wal_append(c: &mut Criterion) {
     let doc_id = Uuid::new_v4();
     let payload = vec![42u8; 64];
@@ -451,6 +500,8 @@ criterion_group!(
     bench_save_snapshot,
     bench_lz4_compress_1kb,
     bench_lz4_decompress_1kb,
+    bench_wire_compression_encode,
+    bench_wire_compression_decode,
     bench_wal_append,
     bench_wal_flush_1000,
     bench_store_load_deltas,
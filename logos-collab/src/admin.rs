@@ -0,0 +1,356 @@
+//! HTTP admin and metrics endpoint for a running [`SyncServer`].
+//!
+//! Listens next to the WebSocket port and speaks just enough HTTP/1.1 for
+//! Prometheus and `curl`: one request per connection, no bodies.
+//!
+//! | Method | Path | Response |
+//! |--------|------|----------|
+//! | GET  | `/metrics` | Prometheus text exposition of [`ServerStats`] |
+//! | GET  | `/rooms` | Open rooms and their peers (JSON) |
+//! | GET  | `/rooms/{doc_id}` | One room (JSON) |
+//! | POST | `/rooms/{doc_id}/snapshot` | Snapshot the room now (500 if storage fails) |
+//! | POST | `/rooms/{doc_id}/close` | Snapshot, drop and disconnect the room |
+//! | POST | `/rooms/{doc_id}/peers/{peer_id}/evict` | Disconnect one peer |
+//! | GET  | `/export` | Archive of every stored document (see [`crate::storage::archive`]) |
+//! | GET  | `/export/{doc_id}` | Archive of one document |
+//!
+//! Rates are left to Prometheus: messages and bytes are exported as
+//! counters, to be read with `rate()`.
+//!
+//! There is no authentication; bind the listener to a private interface.
+//!
+//! Reference: Prometheus text exposition format 0.0.4
+
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use uuid::Uuid;
+
+use crate::server::{RoomInfo, ServerStats, SyncServer};
+use crate::storage::{Archive, ArchiveError, StoreError};
+
+/// Largest request head accepted, in bytes.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Admin HTTP listener bound to a [`SyncServer`].
+pub struct AdminServer {
+    listener: TcpListener,
+    server: Arc<SyncServer>,
+}
+
+impl AdminServer {
+    /// Bind the admin listener to `addr`.
+    pub async fn bind(addr: &str, server: Arc<SyncServer>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        log::info!("Admin endpoint listening on {}", listener.local_addr()?);
+        Ok(Self { listener, server })
+    }
+
+    /// Address the listener is bound to.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve admin requests until the listener fails.
+    pub async fn run(self) -> std::io::Result<()> {
+        loop {
+            let (stream, addr) = self.listener.accept().await?;
+            let server = self.server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve(stream, &server).await {
+                    log::debug!("Admin connection from {addr}: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// A response to an admin request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).unwrap_or_default(),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        #[derive(Serialize)]
+        struct Error {
+            error: String,
+        }
+        Self::json(status, &Error { error: message.into() })
+    }
+
+    fn archive(result: Result<Archive, ArchiveError>) -> Self {
+        match result.and_then(|archive| archive.encode()) {
+            Ok(body) => Self { status: 200, content_type: "application/octet-stream", body },
+            Err(ArchiveError::Store(StoreError::NotFound(doc_id))) => {
+                Self::error(404, format!("document {doc_id} is not stored"))
+            }
+            Err(e) => Self::error(500, e.to_string()),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Outcome of an admin action.
+#[derive(Serialize)]
+struct Done {
+    doc_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peer_id: Option<Uuid>,
+    action: &'static str,
+}
+
+/// Read one request from `stream`, answer it and close.
+async fn serve(mut stream: TcpStream, server: &SyncServer) -> std::io::Result<()> {
+    let response = match tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await {
+        Ok(Ok(Some((method, path)))) => route(server, &method, &path).await,
+        Ok(Ok(None)) => Response::error(413, "request too large"),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::InvalidData => Response::error(400, e.to_string()),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Ok(()),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.shutdown().await
+}
+
+/// Read a request head, returning its method and path, or `None` if it
+/// exceeds [`MAX_REQUEST_BYTES`].
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<(String, String)>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut request = httparse::Request::new(&mut headers);
+        match request.parse(&buf) {
+            Ok(httparse::Status::Complete(_)) => {
+                let method = request.method.unwrap_or_default().to_string();
+                let path = request.path.unwrap_or_default().to_string();
+                return Ok(Some((method, path)));
+            }
+            Ok(httparse::Status::Partial) if buf.len() >= MAX_REQUEST_BYTES => return Ok(None),
+            Ok(httparse::Status::Partial) => {}
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Answer `method` on `path` (query strings are ignored).
+pub async fn route(server: &SyncServer, method: &str, path: &str) -> Response {
+    let path = path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match (method, segments.as_slice()) {
+        ("GET", ["metrics"]) => {
+            let stats = server.stats().await;
+            let rooms = server.list_rooms().await;
+            Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                body: render_metrics(&stats, &rooms).into_bytes(),
+            }
+        }
+        ("GET", ["rooms"]) => Response::json(200, &server.list_rooms().await),
+        ("GET", ["rooms", doc_id]) => {
+            let Some(doc_id) = parse_id(doc_id) else {
+                return Response::error(400, "invalid document id");
+            };
+            match server.list_rooms().await.into_iter().find(|room| room.doc_id == doc_id) {
+                Some(room) => Response::json(200, &room),
+                None => Response::error(404, format!("room {doc_id} is not open")),
+            }
+        }
+        ("POST", ["rooms", doc_id, "snapshot"]) => {
+            let Some(doc_id) = parse_id(doc_id) else {
+                return Response::error(400, "invalid document id");
+            };
+            match server.snapshot_room(doc_id).await {
+                Ok(()) => Response::json(200, &Done { doc_id, peer_id: None, action: "snapshot" }),
+                Err(e @ StoreError::NotFound(_)) => Response::error(404, e.to_string()),
+                Err(e) => Response::error(500, e.to_string()),
+            }
+        }
+        ("POST", ["rooms", doc_id, "close"]) => {
+            let Some(doc_id) = parse_id(doc_id) else {
+                return Response::error(400, "invalid document id");
+            };
+            if server.close_room(doc_id).await {
+                Response::json(200, &Done { doc_id, peer_id: None, action: "close" })
+            } else {
+                Response::error(404, format!("room {doc_id} is not open"))
+            }
+        }
+        ("POST", ["rooms", doc_id, "peers", peer_id, "evict"]) => {
+            let (Some(doc_id), Some(peer_id)) = (parse_id(doc_id), parse_id(peer_id)) else {
+                return Response::error(400, "invalid document or peer id");
+            };
+            if server.evict_peer(doc_id, peer_id).await {
+                Response::json(200, &Done { doc_id, peer_id: Some(peer_id), action: "evict" })
+            } else {
+                Response::error(404, format!("peer {peer_id} is not in room {doc_id}"))
+            }
+        }
+        ("GET", ["export"]) => Response::archive(server.export_documents(None).await),
+        ("GET", ["export", doc_id]) => {
+            let Some(doc_id) = parse_id(doc_id) else {
+                return Response::error(400, "invalid document id");
+            };
+            Response::archive(server.export_documents(Some(&[doc_id])).await)
+        }
+        (_, ["metrics"] | ["rooms"] | ["rooms", _] | ["export"] | ["export", _])
+        | (_, ["rooms", _, "snapshot" | "close"] | ["rooms", _, "peers", _, "evict"]) => {
+            Response::error(405, format!("{method} not allowed on {path}"))
+        }
+        _ => Response::error(404, format!("no route for {path}")),
+    }
+}
+
+fn parse_id(s: &str) -> Option<Uuid> {
+    Uuid::parse_str(s).ok()
+}
+
+/// Render server statistics in the Prometheus text format.
+pub fn render_metrics(stats: &ServerStats, rooms: &[RoomInfo]) -> String {
+    let peers: usize = rooms.iter().map(|room| room.peers.len()).sum();
+    let metrics: [(&str, &str, &str, u64); 34] = [
+        ("connections_total", "counter", "WebSocket connections accepted", stats.total_connections),
+        ("connections_active", "gauge", "Open WebSocket connections", stats.active_connections),
+        ("rooms_active", "gauge", "Open document rooms", stats.active_rooms as u64),
+        ("peers_active", "gauge", "Peers joined to an open room", peers as u64),
+        ("messages_total", "counter", "Messages received from peers", stats.total_messages),
+        ("received_bytes_total", "counter", "Bytes of messages received from peers", stats.total_bytes),
+        ("persistence_queue_depth", "gauge", "Commands waiting for the persistence task", stats.persistence_queue),
+        ("persisted_deltas_total", "counter", "Deltas written to storage", stats.persisted_deltas),
+        ("persisted_snapshots_total", "counter", "Snapshots written to storage", stats.persisted_snapshots),
+        ("scheduled_snapshots_total", "counter", "Snapshots of open rooms taken by the scheduler", stats.scheduled_snapshots),
+        ("compacted_deltas_total", "counter", "Deltas deleted by snapshot compaction", stats.compacted_deltas),
+        ("wal_checkpoints_total", "counter", "WAL truncations after snapshots", stats.wal_checkpoints),
+        ("wal_replayed_total", "counter", "WAL entries restored on startup", stats.wal_replayed),
+        ("storage_bytes", "gauge", "Size of the document store on disk", stats.storage_bytes),
+        ("lag_resyncs_total", "counter", "Peers resynced after falling behind their room", stats.lag_resyncs),
+        ("rejected_joins_total", "counter", "Joins refused by the authenticator", stats.rejected_joins),
+        ("rejected_full_rooms_total", "counter", "Joins refused because the room was full", stats.rejected_full_rooms),
+        ("rejected_writes_total", "counter", "Writes refused for lack of permission", stats.rejected_writes),
+        ("failed_writes_total", "counter", "Writes refused because their WAL append failed", stats.failed_writes),
+        ("evicted_peers_total", "counter", "Peers disconnected by an administrator", stats.evicted_peers),
+        ("heartbeat_timeouts_total", "counter", "Connections dropped after missing heartbeats", stats.heartbeat_timeouts),
+        ("throttled_deltas_total", "counter", "Deltas rejected by a peer's rate limit", stats.throttled_deltas),
+        ("throttled_history_total", "counter", "History requests rejected by a peer's rate limit", stats.throttled_history),
+        ("coalesced_awareness_total", "counter", "Awareness updates superseded while rate limited", stats.coalesced_awareness),
+        ("oversized_messages_total", "counter", "Messages rejected for exceeding the size limit", stats.oversized_messages),
+        ("unknown_messages_total", "counter", "Messages of a newer protocol's types, skipped", stats.unknown_messages),
+        ("legacy_joins_total", "counter", "Joins from peers speaking protocol version 1", stats.legacy_joins),
+        ("compressed_messages_total", "counter", "Messages sent to peers compressed", stats.compressed_messages),
+        ("compression_saved_bytes_total", "counter", "Bytes compression kept off the wire", stats.compression_saved_bytes),
+        ("held_leases", "gauge", "Documents this node holds the storage lease of", stats.held_leases),
+        ("relay_sent_total", "counter", "Room messages relayed to other cluster nodes", stats.relay_sent),
+        ("relay_received_total", "counter", "Room messages relayed from other cluster nodes", stats.relay_received),
+        ("recovery_diagnostics_total", "counter", "Invariant violations found in recovered documents", stats.recovery_diagnostics),
+        ("recovery_repairs_total", "counter", "Violations fixed by recovery-time repair", stats.recovery_repairs),
+    ];
+
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics {
+        let _ = writeln!(out, "# HELP logos_collab_{name} {help}");
+        let _ = writeln!(out, "# TYPE logos_collab_{name} {kind}");
+        let _ = writeln!(out, "logos_collab_{name} {value}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::PeerInfo;
+
+    #[test]
+    fn test_render_metrics() {
+        let stats = ServerStats {
+            total_connections: 5,
+            total_bytes: 1234,
+            persistence_queue: 3,
+            ..ServerStats::default()
+        };
+        let rooms = vec![RoomInfo {
+            doc_id: Uuid::new_v4(),
+            peers: vec![PeerInfo::new("Alice"), PeerInfo::new("Bob")],
+            deltas_since_snapshot: 0,
+        }];
+
+        let text = render_metrics(&stats, &rooms);
+        assert!(text.contains("# TYPE logos_collab_connections_total counter\nlogos_collab_connections_total 5\n"));
+        assert!(text.contains("logos_collab_received_bytes_total 1234\n"));
+        assert!(text.contains("logos_collab_persistence_queue_depth 3\n"));
+        assert!(text.contains("logos_collab_peers_active 2\n"));
+        // Every sample has HELP and TYPE lines
+        let samples = text.lines().filter(|l| !l.starts_with('#')).count();
+        assert_eq!(text.lines().count(), samples * 3);
+    }
+
+    #[tokio::test]
+    async fn test_route_errors() {
+        let server = SyncServer::with_defaults();
+        let doc_id = Uuid::new_v4();
+
+        assert_eq!(route(&server, "GET", "/nope").await.status, 404);
+        assert_eq!(route(&server, "DELETE", "/metrics").await.status, 405);
+        assert_eq!(route(&server, "GET", "/rooms/close").await.status, 400);
+        assert_eq!(route(&server, "POST", &format!("/rooms/{doc_id}/close")).await.status, 404);
+        assert_eq!(route(&server, "GET", &format!("/rooms/{doc_id}/snapshot")).await.status, 405);
+
+        let rooms = route(&server, "GET", "/rooms?pretty").await;
+        assert_eq!((rooms.status, rooms.body.as_slice()), (200, b"[]".as_slice()));
+        let snapshot = route(&server, "POST", &format!("/rooms/{doc_id}/snapshot")).await;
+        assert_eq!(snapshot.status, 404, "no storage, no snapshots");
+
+        assert_eq!(route(&server, "DELETE", "/export").await.status, 405);
+        assert_eq!(route(&server, "GET", "/export/nope").await.status, 400);
+        assert_eq!(route(&server, "GET", &format!("/export/{doc_id}")).await.status, 404);
+        let export = route(&server, "GET", "/export").await;
+        assert_eq!((export.status, export.content_type), (200, "application/octet-stream"));
+        assert!(Archive::decode(&export.body).unwrap().documents().is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+//! Join authentication for the sync server.
+//!
+//! A joining peer sends a bearer token with its `PeerJoined` message. The
+//! server hands it to the configured [`Authenticator`] together with the
+//! requested document; an error becomes a typed `Rejected` message followed
+//! by disconnect.
+//!
+//! [`HmacAuthenticator`] implements signed claim tokens:
+//! ```text
+//! <hex(bincode(AuthClaims))>.<hex(HMAC-SHA256(key, claims bytes))>
+//! ```
+//! Asymmetric schemes (e.g. Ed25519, where the server holds only a public
+//! key) plug in through the same trait.
+//!
+//! Reference: RFC 2104 (HMAC), FIPS 180-4 (SHA-256)
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Claims carried by a join token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthClaims {
+    /// Authenticated user; must match the joining `peer_id`
+    pub user_id: Uuid,
+    /// Document the token grants access to
+    pub doc_id: Uuid,
+    /// Expiry as seconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+impl AuthClaims {
+    /// Claims valid for `ttl_secs` from now.
+    pub fn new(user_id: Uuid, doc_id: Uuid, ttl_secs: u64) -> Self {
+        Self {
+            user_id,
+            doc_id,
+            expires_at: unix_now().saturating_add(ttl_secs),
+        }
+    }
+
+    /// Whether the claims have expired at `now` (Unix seconds).
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Why a join was refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthError {
+    /// The join carried no token
+    MissingToken,
+    /// The token could not be parsed
+    Malformed,
+    /// The signature does not match the claims
+    InvalidSignature,
+    /// The token expired at the given Unix time
+    Expired { expires_at: u64 },
+    /// The token was issued for another document
+    WrongDocument { token_doc_id: Uuid },
+    /// The joining peer is not the user named in the token
+    PeerMismatch { user_id: Uuid },
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingToken => write!(f, "Missing auth token"),
+            Self::Malformed => write!(f, "Malformed auth token"),
+            Self::InvalidSignature => write!(f, "Invalid token signature"),
+            Self::Expired { expires_at } => write!(f, "Token expired at {expires_at}"),
+            Self::WrongDocument { token_doc_id } => {
+                write!(f, "Token was issued for document {token_doc_id}")
+            }
+            Self::PeerMismatch { user_id } => {
+                write!(f, "Token was issued to user {user_id}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// Validates join tokens.
+///
+/// Implementations check the token's integrity, expiry and that it grants
+/// access to `doc_id`. The server separately checks that the returned
+/// `user_id` matches the joining peer.
+pub trait Authenticator: Send + Sync + std::fmt::Debug {
+    fn authenticate(&self, token: Option<&str>, doc_id: Uuid) -> Result<AuthClaims, AuthError>;
+}
+
+/// HMAC-SHA256 signed claim tokens with a shared secret.
+#[derive(Clone)]
+pub struct HmacAuthenticator {
+    key: Vec<u8>,
+}
+
+impl HmacAuthenticator {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    /// Sign `claims` into a bearer token.
+    pub fn issue(&self, claims: &AuthClaims) -> String {
+        let body = bincode::serde::encode_to_vec(claims, bincode::config::standard())
+            .unwrap_or_default();
+        let mac = hmac_sha256(&self.key, &body);
+        format!("{}.{}", to_hex(&body), to_hex(&mac))
+    }
+
+    /// Check the signature and decode the claims, without expiry or
+    /// document checks.
+    pub fn verify(&self, token: &str) -> Result<AuthClaims, AuthError> {
+        let (body, signature) = token.split_once('.').ok_or(AuthError::Malformed)?;
+        let body = from_hex(body).ok_or(AuthError::Malformed)?;
+        let signature = from_hex(signature).ok_or(AuthError::Malformed)?;
+        // Constant-time comparison
+        mac(&self.key, &body)
+            .verify_slice(&signature)
+            .map_err(|_| AuthError::InvalidSignature)?;
+        let (claims, _) = bincode::serde::decode_from_slice(&body, bincode::config::standard())
+            .map_err(|_| AuthError::Malformed)?;
+        Ok(claims)
+    }
+}
+
+/// Never prints the key.
+impl std::fmt::Debug for HmacAuthenticator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacAuthenticator").finish_non_exhaustive()
+    }
+}
+
+impl Authenticator for HmacAuthenticator {
+    fn authenticate(&self, token: Option<&str>, doc_id: Uuid) -> Result<AuthClaims, AuthError> {
+        let claims = self.verify(token.ok_or(AuthError::MissingToken)?)?;
+        if claims.is_expired_at(unix_now()) {
+            return Err(AuthError::Expired { expires_at: claims.expires_at });
+        }
+        if claims.doc_id != doc_id {
+            return Err(AuthError::WrongDocument { token_doc_id: claims.doc_id });
+        }
+        Ok(claims)
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// ─── Encoding ───────────────────────────────────────────────────────────────
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decode lowercase or uppercase hex; any other character, including a
+/// sign, rejects the whole string.
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    fn nibble(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| Some(nibble(pair[0])? << 4 | nibble(pair[1])?))
+        .collect()
+}
+
+// ─── HMAC-SHA256 ────────────────────────────────────────────────────────────
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub(crate) fn mac(key: &[u8], message: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(message);
+    mac
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    mac(key, message).finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(from_hex(&to_hex(&bytes)), Some(bytes.to_vec()));
+        assert_eq!(from_hex("ABcd"), Some(vec![0xab, 0xcd]));
+    }
+
+    #[test]
+    fn test_hex_rejects_non_digits() {
+        assert_eq!(from_hex("+f"), None);
+        assert_eq!(from_hex("-1"), None);
+        assert_eq!(from_hex(" f"), None);
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("é0"), None);
+    }
+
+    #[test]
+    fn test_hmac_rfc4231() {
+        // Test case 2
+        assert_eq!(
+            to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Test case 6: key longer than the block size
+        assert_eq!(
+            to_hex(&hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_token_roundtrip() {
+        let auth = HmacAuthenticator::new("secret");
+        let claims = AuthClaims::new(Uuid::new_v4(), Uuid::new_v4(), 60);
+        let token = auth.issue(&claims);
+        assert_eq!(auth.authenticate(Some(&token), claims.doc_id), Ok(claims));
+    }
+
+    #[test]
+    fn test_token_rejections() {
+        let auth = HmacAuthenticator::new("secret");
+        let doc_id = Uuid::new_v4();
+        let claims = AuthClaims::new(Uuid::new_v4(), doc_id, 60);
+        let token = auth.issue(&claims);
+
+        assert_eq!(auth.authenticate(None, doc_id), Err(AuthError::MissingToken));
+        assert_eq!(auth.authenticate(Some("nope"), doc_id), Err(AuthError::Malformed));
+        assert_eq!(
+            HmacAuthenticator::new("other").authenticate(Some(&token), doc_id),
+            Err(AuthError::InvalidSignature)
+        );
+        assert_eq!(
+            auth.authenticate(Some(&token), Uuid::nil()),
+            Err(AuthError::WrongDocument { token_doc_id: doc_id })
+        );
+
+        let expired = AuthClaims { expires_at: 1, ..claims.clone() };
+        assert_eq!(
+            auth.authenticate(Some(&auth.issue(&expired)), doc_id),
+            Err(AuthError::Expired { expires_at: 1 })
+        );
+
+        // Tampered claims keep the old signature
+        let forged = AuthClaims { user_id: Uuid::new_v4(), ..claims };
+        let forged_body = auth.issue(&forged);
+        let tampered = format!(
+            "{}.{}",
+            forged_body.split_once('.').unwrap().0,
+            token.split_once('.').unwrap().1
+        );
+        assert_eq!(auth.authenticate(Some(&tampered), doc_id), Err(AuthError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_signature_with_sign_prefix_is_malformed() {
+        let auth = HmacAuthenticator::new("secret");
+        let claims = AuthClaims::new(Uuid::new_v4(), Uuid::new_v4(), 60);
+        let token = auth.issue(&claims);
+        let (body, signature) = token.split_once('.').unwrap();
+        // "+a" would parse as 0x0a with `from_str_radix`
+        let signed = format!("{body}.+{}", &signature[1..]);
+        assert_eq!(auth.verify(&signed), Err(AuthError::Malformed));
+    }
+
+    #[test]
+    fn test_debug_hides_key() {
+        let auth = HmacAuthenticator::new("hunter2");
+        assert!(!format!("{auth:?}").contains("hunter2"));
+    }
+}
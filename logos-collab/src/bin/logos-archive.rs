@@ -0,0 +1,178 @@
+//! Export, import and verify document archives without a running server.
+//!
+//! ```text
+//! logos-archive export <store> <archive> [--backend rocksdb|sqlite] [--doc <id>]... [--assets <dir>]
+//! logos-archive import <store> <archive> [--backend rocksdb|sqlite] [--ids keep|rename|fresh] [--assets <dir>]
+//! logos-archive verify <archive>
+//! ```
+//!
+//! `--assets <dir>` maps assets to `<dir>/<doc_id>/<name>`: export attaches
+//! the files found there, import writes them under the new ids. Stores
+//! must not be open in a server; take backups of running servers from the
+//! admin endpoint's `GET /export`.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use logos_collab::storage::archive::{self, Archive, IdPolicy};
+use logos_collab::storage::{BackendKind, DocumentStore, StoreConfig};
+use uuid::Uuid;
+
+const USAGE: &str = "usage:
+  logos-archive export <store> <archive> [--backend rocksdb|sqlite] [--doc <id>]... [--assets <dir>]
+  logos-archive import <store> <archive> [--backend rocksdb|sqlite] [--ids keep|rename|fresh] [--assets <dir>]
+  logos-archive verify <archive>";
+
+type Error = Box<dyn std::error::Error>;
+
+/// Parsed command line.
+struct Args {
+    command: String,
+    paths: Vec<PathBuf>,
+    backend: BackendKind,
+    docs: Vec<Uuid>,
+    ids: IdPolicy,
+    assets: Option<PathBuf>,
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = args.next().ok_or("missing command")?;
+    let mut parsed = Args {
+        command,
+        paths: Vec::new(),
+        backend: BackendKind::default(),
+        docs: Vec::new(),
+        ids: IdPolicy::default(),
+        assets: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--backend" => {
+                parsed.backend = match value()?.as_str() {
+                    "rocksdb" => BackendKind::RocksDb,
+                    "sqlite" => BackendKind::Sqlite,
+                    other => return Err(format!("unknown backend {other}")),
+                }
+            }
+            "--doc" => {
+                let id = value()?;
+                parsed.docs.push(Uuid::parse_str(&id).map_err(|_| format!("invalid document id {id}"))?);
+            }
+            "--ids" => {
+                parsed.ids = match value()?.as_str() {
+                    "keep" => IdPolicy::Keep,
+                    "rename" => IdPolicy::RenameOnClash,
+                    "fresh" => IdPolicy::Fresh,
+                    other => return Err(format!("unknown id policy {other}")),
+                }
+            }
+            "--assets" => parsed.assets = Some(PathBuf::from(value()?)),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {flag}")),
+            _ => parsed.paths.push(PathBuf::from(arg)),
+        }
+    }
+    let expected = if parsed.command == "verify" { 1 } else { 2 };
+    if parsed.paths.len() != expected {
+        return Err(format!("{} takes {expected} path(s)", parsed.command));
+    }
+    Ok(parsed)
+}
+
+fn open_store(path: &Path, backend: BackendKind) -> Result<DocumentStore, Error> {
+    Ok(DocumentStore::open(StoreConfig {
+        path: path.to_path_buf(),
+        backend,
+        ..StoreConfig::default()
+    })?)
+}
+
+fn export(args: Args) -> Result<(), Error> {
+    let store = open_store(&args.paths[0], args.backend)?;
+    let mut archive = if args.docs.is_empty() {
+        archive::export_all(&store)?
+    } else {
+        archive::export(&store, &args.docs)?
+    };
+    if let Some(dir) = &args.assets {
+        for doc_id in archive.documents().to_vec() {
+            let Ok(entries) = std::fs::read_dir(dir.join(doc_id.to_string())) else {
+                continue;
+            };
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    archive.add_asset(doc_id, &name, std::fs::read(entry.path())?)?;
+                }
+            }
+        }
+    }
+    archive.write_to(&args.paths[1])?;
+    let manifest = archive.manifest();
+    println!(
+        "exported {} documents ({} files) to {}",
+        manifest.documents.len(),
+        manifest.files.len(),
+        args.paths[1].display()
+    );
+    Ok(())
+}
+
+fn import(args: Args) -> Result<(), Error> {
+    let archive = Archive::read_from(&args.paths[1])?;
+    let store = open_store(&args.paths[0], args.backend)?;
+    for doc in archive::import(&store, &archive, args.ids)? {
+        if let Some(dir) = &args.assets {
+            let assets = archive.assets(doc.original_id);
+            if !assets.is_empty() {
+                let doc_dir = dir.join(doc.doc_id.to_string());
+                std::fs::create_dir_all(&doc_dir)?;
+                for (name, data) in assets {
+                    std::fs::write(doc_dir.join(name), data)?;
+                }
+            }
+        }
+        println!("{} -> {} ({} versions)", doc.original_id, doc.doc_id, doc.versions);
+    }
+    store.sync()?;
+    Ok(())
+}
+
+fn verify(args: Args) -> Result<(), Error> {
+    let manifest = Archive::read_from(&args.paths[0])?.manifest();
+    for doc_id in &manifest.documents {
+        let prefix = format!("{doc_id}/");
+        let files = manifest.files.iter().filter(|f| f.path.starts_with(&prefix));
+        let (count, bytes) = files.fold((0, 0), |(count, bytes), f| (count + 1, bytes + f.size));
+        println!("{doc_id}: {count} files, {bytes} bytes");
+    }
+    println!("ok: {} documents, {} files verified", manifest.documents.len(), manifest.files.len());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let result = match args.command.as_str() {
+        "export" => export(args),
+        "import" => import(args),
+        "verify" => verify(args),
+        other => {
+            eprintln!("unknown command {other}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
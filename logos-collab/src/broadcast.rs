@@ -22,6 +22,31 @@ pub struct BroadcastStats {
     pub active_peers: usize,
 }
 
+/// One room message as fanned out to every receiver.
+///
+/// Encoded (and, when the group compresses, LZ4-wrapped) once at broadcast
+/// time; each connection picks the encoding its peer agreed to.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Peer the message came from, so receivers can skip their own.
+    /// Nil for raw frames.
+    pub sender: Uuid,
+    /// Plain wire encoding
+    pub plain: Arc<Vec<u8>>,
+    /// Compressed wire encoding, when it came out smaller than `plain`
+    pub compressed: Option<Arc<Vec<u8>>>,
+}
+
+impl Frame {
+    /// The encoding to send to a peer, compressed only if it `accepts_compression`.
+    pub fn for_peer(&self, accepts_compression: bool) -> &Arc<Vec<u8>> {
+        match &self.compressed {
+            Some(compressed) if accepts_compression => compressed,
+            _ => &self.plain,
+        }
+    }
+}
+
 /// Atomic broadcast stats — lock-free on the hot path.
 ///
 /// Stats are tracked via atomics so that broadcast_raw() and broadcast()
@@ -46,7 +71,10 @@ impl AtomicBroadcastStats {
 /// When a peer sends a delta, it's fanned out to N-1 other peers.
 pub struct BroadcastGroup {
     /// Broadcast channel sender (cloned per-room)
-    sender: broadcast::Sender<Arc<Vec<u8>>>,
+    sender: broadcast::Sender<Frame>,
+
+    /// Compress messages above this many bytes (None = never)
+    compress_above: Option<usize>,
 
     /// Connected peers in this room
     peers: Arc<RwLock<HashMap<Uuid, PeerInfo>>>,
@@ -67,16 +95,24 @@ impl BroadcastGroup {
         let (sender, _) = broadcast::channel(capacity);
         Self {
             sender,
+            compress_above: None,
             peers: Arc::new(RwLock::new(HashMap::new())),
             capacity,
             atomic_stats: Arc::new(AtomicBroadcastStats::new()),
         }
     }
 
+    /// Also compress messages larger than `threshold` bytes, so that
+    /// receivers can forward whichever encoding their peer agreed to.
+    pub fn with_compression(mut self, threshold: Option<usize>) -> Self {
+        self.compress_above = threshold;
+        self
+    }
+
     /// Add a peer to this broadcast group.
     ///
     /// Returns a receiver for this peer to consume messages.
-    pub async fn add_peer(&self, info: PeerInfo) -> broadcast::Receiver<Arc<Vec<u8>>> {
+    pub async fn add_peer(&self, info: PeerInfo) -> broadcast::Receiver<Frame> {
         let mut peers = self.peers.write().await;
         peers.insert(info.peer_id, info);
         self.sender.subscribe()
@@ -90,14 +126,21 @@ impl BroadcastGroup {
 
     /// Broadcast a message to all peers except the sender.
     ///
-    /// The message is pre-encoded to avoid redundant serialization.
+    /// The message is encoded and compressed once, not per receiver.
     /// Returns the number of receivers that received the message.
     /// Stats are tracked via atomics — no lock acquired on hot path.
     pub fn broadcast(&self, msg: &SyncMessage) -> Result<usize, crate::protocol::ProtocolError> {
-        let encoded = msg.encode()?;
-        let arc_bytes = Arc::new(encoded);
+        let plain = Arc::new(msg.encode()?);
+        let compressed = match self.compress_above {
+            Some(threshold) if plain.len() > threshold => {
+                let compressed = msg.compress(plain.to_vec(), Some(threshold))?;
+                (compressed.len() < plain.len()).then(|| Arc::new(compressed))
+            }
+            _ => None,
+        };
+        let frame = Frame { sender: msg.peer_id, plain, compressed };
 
-        let receiver_count = self.sender.send(arc_bytes).unwrap_or(0);
+        let receiver_count = self.sender.send(frame).unwrap_or(0);
 
         // Lock-free stats update
         self.atomic_stats.messages_sent.fetch_add(1, Ordering::Relaxed);
@@ -107,8 +150,10 @@ impl BroadcastGroup {
 
     /// Broadcast pre-encoded bytes directly (zero-copy fast path).
     /// Fully lock-free: tokio broadcast::send + atomic stats.
+    /// Raw frames are never compressed and reach every receiver.
     pub fn broadcast_raw(&self, encoded: Arc<Vec<u8>>) -> usize {
-        let count = self.sender.send(encoded).unwrap_or(0);
+        let frame = Frame { sender: Uuid::nil(), plain: encoded, compressed: None };
+        let count = self.sender.send(frame).unwrap_or(0);
         self.atomic_stats.messages_sent.fetch_add(1, Ordering::Relaxed);
         count
     }
@@ -144,7 +189,7 @@ impl BroadcastGroup {
     }
 
     /// Subscribe to this broadcast group (raw receiver).
-    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Vec<u8>>> {
+    pub fn subscribe(&self) -> broadcast::Receiver<Frame> {
         self.sender.subscribe()
     }
 }
@@ -268,7 +313,39 @@ mod tests {
         assert_eq!(count, 1);
 
         let received = rx.recv().await.unwrap();
-        assert_eq!(*received, vec![10, 20, 30]);
+        assert_eq!(*received.plain, vec![10, 20, 30]);
+        assert!(received.compressed.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_compresses_once() {
+        let group = BroadcastGroup::new(16).with_compression(Some(64));
+        let peer = PeerInfo::new("Alice");
+        let mut rx = group.add_peer(peer.clone()).await;
+
+        let msg = SyncMessage::delta(peer.peer_id, Uuid::new_v4(), 1, vec![7; 4096]);
+        group.broadcast(&msg).unwrap();
+        group.broadcast(&SyncMessage::ping(peer.peer_id)).unwrap();
+
+        let frame = rx.recv().await.unwrap();
+        assert_eq!(frame.sender, peer.peer_id);
+        let compressed = frame.compressed.as_ref().expect("large message is compressed");
+        assert!(compressed.len() < frame.plain.len());
+        assert_eq!(SyncMessage::decode(frame.for_peer(true)).unwrap().payload, msg.payload);
+        assert_eq!(SyncMessage::decode(frame.for_peer(false)).unwrap().payload, msg.payload);
+
+        // Small messages go out plain either way
+        let frame = rx.recv().await.unwrap();
+        assert!(frame.compressed.is_none());
+        assert!(Arc::ptr_eq(frame.for_peer(true), &frame.plain));
+
+        // Without a threshold nothing is compressed, however large
+        let plain_group = BroadcastGroup::new(16);
+        let mut rx = plain_group.add_peer(peer).await;
+        plain_group.broadcast(&msg).unwrap();
+        let frame = rx.recv().await.unwrap();
+        assert!(frame.compressed.is_none());
+        assert!(Arc::ptr_eq(frame.for_peer(true), frame.for_peer(false)));
     }
 
     #[tokio::test]
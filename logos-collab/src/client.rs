@@ -1,21 +1,36 @@
 //! WebSocket sync client for connecting to the collaboration server.
 //!
 //! Provides:
-//! - Connection lifecycle (connect, disconnect, reconnect)
+//! - Connection lifecycle (connect, disconnect, reconnect with backoff)
 //! - Delta send/receive with automatic Yrs integration
+//! - Protocol version and capability negotiation ([`Hello`]) on every connect
+//! - LZ4 compression of large messages, where the server supports it
+//! - Two-way state vector handshake (SyncStep1/SyncStep2) on every connect
 //! - Awareness (cursor/selection) updates
 //! - Offline queue for disconnected edits
 //!
 //! Reference: Kleppmann, Chapter 5 — Replication
 
 use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock, Mutex};
-use futures_util::StreamExt;
+use tokio::task::JoinHandle;
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
+use yrs::updates::encoder::Encode;
 
+use crate::history::HistoryMessage;
 use crate::presence::AwarenessMessage;
-use crate::protocol::{AwarenessState, PeerInfo, ProtocolError, SyncMessage};
+use crate::protocol::{
+    AwarenessState, Capabilities, Hello, PeerInfo, ProtocolError, Rejection, SyncMessage,
+    DEFAULT_COMPRESSION_THRESHOLD,
+};
+use crate::storage::wal::{WalEntry, WalEntryType, WalError};
 
 /// Client connection state.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,8 +46,13 @@ pub enum ConnectionState {
 pub enum SyncEvent {
     /// Connection established
     Connected,
-    /// Connection lost
+    /// Connection lost and not being retried (any longer)
     Disconnected,
+    /// Connection lost; waiting `delay` before reconnect attempt `attempt`
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
     /// Received a CRDT delta from a remote peer
     RemoteDelta {
         peer_id: Uuid,
@@ -53,17 +73,41 @@ pub enum SyncEvent {
     PeerJoined(PeerInfo),
     /// A peer left the document
     PeerLeft(Uuid),
-    /// Initial state sync received
+    /// The server's answer to our SyncStep1: a Yrs v1 update holding
+    /// everything the local state vector was missing
     StateSynced(Vec<u8>),
+    /// The server sent its state vector and wants the updates it lacks;
+    /// answer with [`SyncClient::send_sync_step2`]
+    SyncRequested(Vec<u8>),
+    /// The server refused a request; after a final one it disconnects
+    Rejected(Rejection),
+    /// The server's reply to a [`SyncClient::request_history`] request
+    History(HistoryMessage),
+    /// The server answered our hello with the protocol version and
+    /// capabilities both sides support. Servers from before the handshake
+    /// never answer; the connection then stays at [`Hello::legacy`].
+    Negotiated(Hello),
 }
 
 /// Offline queue for edits made while disconnected.
 ///
-/// Queued deltas are replayed on reconnection.
+/// Queued deltas are replayed on reconnection and dropped once the
+/// server has accepted them.
 /// Target: 1000 queued ops replay in <50ms.
+///
+/// [`OfflineQueue::open`] backs the queue with an append-only file of
+/// checksummed [`WalEntry`] records, so queued edits survive a crash or
+/// restart. Once a persistent queue reaches its merge threshold it is
+/// collapsed into a single Yrs update.
 pub struct OfflineQueue {
     queue: VecDeque<QueuedDelta>,
     max_size: usize,
+    /// Merge into one update when this many deltas are queued
+    merge_threshold: Option<usize>,
+    /// On-disk copy of the queue, if persistent
+    file: Option<QueueFile>,
+    /// Leading deltas handed out by the last `replay`, awaiting `acknowledge`
+    in_flight: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -75,35 +119,158 @@ struct QueuedDelta {
 }
 
 impl OfflineQueue {
+    /// Default merge threshold of persistent queues.
+    pub const DEFAULT_MERGE_THRESHOLD: usize = 256;
+
     /// Create a new offline queue with max capacity.
     pub fn new(max_size: usize) -> Self {
         Self {
             queue: VecDeque::with_capacity(max_size.min(1024)),
             max_size,
+            merge_threshold: None,
+            file: None,
+            in_flight: 0,
+        }
+    }
+
+    /// Open a persistent queue for `doc_id` at `path`, loading any deltas
+    /// left from a previous run.
+    ///
+    /// A torn or corrupt tail (e.g. from a crash mid-append) is cut off;
+    /// records for other documents are skipped.
+    pub fn open(path: impl AsRef<Path>, doc_id: Uuid, max_size: usize) -> Result<Self, WalError> {
+        let (file, entries) = QueueFile::open(path.as_ref(), doc_id)?;
+        let mut queue = Self::new(max_size);
+        queue.merge_threshold = Some(Self::DEFAULT_MERGE_THRESHOLD);
+        queue.queue.extend(entries.into_iter().map(|e| QueuedDelta {
+            clock: e.sequence,
+            payload: e.payload,
+            timestamp: std::time::Instant::now(),
+        }));
+        queue.file = Some(file);
+        if queue.merge_threshold.is_some_and(|t| queue.queue.len() >= t) {
+            queue.merge();
         }
+        Ok(queue)
+    }
+
+    /// Merge into a single Yrs update once `threshold` deltas are queued
+    /// (`None` never merges).
+    pub fn with_merge_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.merge_threshold = threshold;
+        self
+    }
+
+    /// Whether the queue is backed by a file.
+    pub fn is_persistent(&self) -> bool {
+        self.file.is_some()
     }
 
     /// Queue a delta for later replay.
+    ///
+    /// Returns false if the queue is full or the delta could not be
+    /// written to disk.
     pub fn enqueue(&mut self, clock: u64, payload: Vec<u8>) -> bool {
         if self.queue.len() >= self.max_size {
             return false; // Queue full
         }
+        if let Some(ref mut file) = self.file {
+            if let Err(e) = file.append(clock, &payload) {
+                log::error!("Offline queue write failed: {e}");
+                return false;
+            }
+        }
         self.queue.push_back(QueuedDelta {
             clock,
             payload,
             timestamp: std::time::Instant::now(),
         });
+        // Only on reaching the threshold, so an unmergeable queue is not
+        // retried on every enqueue
+        if self.merge_threshold == Some(self.queue.len()) {
+            self.merge();
+        }
+        true
+    }
+
+    /// Collapse all queued deltas into one Yrs update with the latest clock.
+    ///
+    /// Returns false, leaving the queue as is, if the payloads are not
+    /// mergeable Yrs updates or the file could not be rewritten.
+    pub fn merge(&mut self) -> bool {
+        if self.queue.len() < 2 {
+            return true;
+        }
+        let merged = match yrs::merge_updates_v1(self.queue.iter().map(|d| d.payload.as_slice())) {
+            Ok(merged) => merged,
+            Err(e) => {
+                log::warn!("Offline queue not mergeable: {e}");
+                return false;
+            }
+        };
+        let clock = self.queue.iter().map(|d| d.clock).max().unwrap_or(0);
+        if let Some(ref mut file) = self.file {
+            if let Err(e) = file.rewrite([(clock, merged.as_slice())]) {
+                log::error!("Offline queue rewrite failed: {e}");
+                return false;
+            }
+        }
+        log::debug!("Merged {} queued deltas into one update", self.queue.len());
+        // The merged update holds unreplayed deltas too
+        self.in_flight = 0;
+        self.queue.clear();
+        self.queue.push_back(QueuedDelta {
+            clock,
+            payload: merged,
+            timestamp: std::time::Instant::now(),
+        });
         true
     }
 
     /// Drain all queued deltas for replay.
     pub fn drain(&mut self) -> Vec<(u64, Vec<u8>)> {
+        self.truncate_file();
+        self.in_flight = 0;
         self.queue
             .drain(..)
             .map(|d| (d.clock, d.payload))
             .collect()
     }
 
+    /// Copies of all queued deltas for replay.
+    ///
+    /// Unlike [`drain`](Self::drain), they stay queued, in memory and on
+    /// disk, until [`acknowledge`](Self::acknowledge) confirms the server
+    /// has them. Replaying twice is harmless: Yrs updates are idempotent.
+    pub fn replay(&mut self) -> Vec<(u64, Vec<u8>)> {
+        self.in_flight = self.queue.len();
+        self.queue.iter().map(|d| (d.clock, d.payload.clone())).collect()
+    }
+
+    /// Drop the deltas handed out by the last [`replay`](Self::replay),
+    /// keeping any queued since.
+    pub fn acknowledge(&mut self) {
+        let acked = std::mem::take(&mut self.in_flight).min(self.queue.len());
+        if acked == 0 {
+            return;
+        }
+        if let Some(ref mut file) = self.file {
+            let rest = self.queue.iter().skip(acked).map(|d| (d.clock, d.payload.as_slice()));
+            if let Err(e) = file.rewrite(rest) {
+                log::error!("Offline queue rewrite failed: {e}");
+                return;
+            }
+        }
+        self.queue.drain(..acked);
+    }
+
+    /// Keep the deltas handed out by the last [`replay`](Self::replay)
+    /// queued, because the server refused some of them. They are replayed
+    /// again on the next connection.
+    pub fn requeue(&mut self) {
+        self.in_flight = 0;
+    }
+
     /// Number of queued deltas.
     pub fn len(&self) -> usize {
         self.queue.len()
@@ -116,6 +283,8 @@ impl OfflineQueue {
 
     /// Clear all queued deltas.
     pub fn clear(&mut self) {
+        self.truncate_file();
+        self.in_flight = 0;
         self.queue.clear();
     }
 
@@ -123,12 +292,176 @@ impl OfflineQueue {
     pub fn total_bytes(&self) -> usize {
         self.queue.iter().map(|d| d.payload.len()).sum()
     }
+
+    fn truncate_file(&mut self) {
+        if let Some(ref mut file) = self.file {
+            if let Err(e) = file.truncate() {
+                log::error!("Offline queue truncate failed: {e}");
+            }
+        }
+    }
+}
+
+/// Append-only file of length-prefixed [`WalEntry`] records.
+struct QueueFile {
+    path: PathBuf,
+    file: File,
+    doc_id: Uuid,
+}
+
+impl QueueFile {
+    fn open(path: &Path, doc_id: Uuid) -> Result<(Self, Vec<WalEntry>), WalError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(io_error)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(io_error)?;
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while let Some((entry, len)) = Self::read_record(&bytes[offset..]) {
+            offset += 4 + len;
+            if entry.doc_id == doc_id {
+                entries.push(entry);
+            } else {
+                log::warn!("Skipping queued delta for doc {} in {}", entry.doc_id, path.display());
+            }
+        }
+        if offset < bytes.len() {
+            log::warn!(
+                "Dropping {} bytes of torn offline queue tail in {}",
+                bytes.len() - offset,
+                path.display()
+            );
+            file.set_len(offset as u64).map_err(io_error)?;
+        }
+
+        let queue_file = Self {
+            path: path.to_path_buf(),
+            file,
+            doc_id,
+        };
+        Ok((queue_file, entries))
+    }
+
+    /// Decode one record; `None` at the end or at a torn/corrupt record.
+    fn read_record(bytes: &[u8]) -> Option<(WalEntry, usize)> {
+        let len = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+        let entry = WalEntry::decode(bytes.get(4..4 + len)?).ok()?;
+        entry.verify().then_some((entry, len))
+    }
+
+    fn record(&self, clock: u64, payload: &[u8]) -> Result<Vec<u8>, WalError> {
+        let entry = WalEntry::new(clock, WalEntryType::Delta, self.doc_id, payload.to_vec());
+        let encoded = entry.encode()?;
+        let mut record = Vec::with_capacity(4 + encoded.len());
+        record.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        record.extend_from_slice(&encoded);
+        Ok(record)
+    }
+
+    fn append(&mut self, clock: u64, payload: &[u8]) -> Result<(), WalError> {
+        let record = self.record(clock, payload)?;
+        self.file.write_all(&record).map_err(io_error)?;
+        self.file.sync_data().map_err(io_error)
+    }
+
+    /// Atomically replace the file with `records`.
+    fn rewrite<'a>(&mut self, records: impl IntoIterator<Item = (u64, &'a [u8])>) -> Result<(), WalError> {
+        let mut bytes = Vec::new();
+        for (clock, payload) in records {
+            bytes.extend_from_slice(&self.record(clock, payload)?);
+        }
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp).map_err(io_error)?;
+        file.write_all(&bytes).map_err(io_error)?;
+        file.sync_data().map_err(io_error)?;
+        std::fs::rename(&tmp, &self.path).map_err(io_error)?;
+        self.file = OpenOptions::new().append(true).open(&self.path).map_err(io_error)?;
+        Ok(())
+    }
+
+    fn truncate(&mut self) -> Result<(), WalError> {
+        self.file.set_len(0).map_err(io_error)?;
+        self.file.sync_data().map_err(io_error)
+    }
 }
 
+fn io_error(e: std::io::Error) -> WalError {
+    WalError::Io(e.to_string())
+}
+
+/// Reconnect policy: exponential backoff with jitter.
+///
+/// Attempt `n` waits `initial_delay * multiplier^(n-1)`, capped at
+/// `max_delay`, minus a random fraction of up to `jitter` so peers dropped
+/// together do not all return at the same instant.
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound on any single delay
+    pub max_delay: Duration,
+    /// Growth factor between attempts
+    pub multiplier: f64,
+    /// Largest fraction of a delay removed at random (0.0–1.0)
+    pub jitter: f64,
+    /// Give up after this many failed attempts; `None` retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: Some(10),
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// Never reconnect: a lost connection ends in `Disconnected`.
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: Some(0),
+            ..Self::default()
+        }
+    }
+
+    /// Delay before reconnect attempt `attempt` (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(64) as i32;
+        let backoff = self.initial_delay.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        // min/max drop a NaN operand, so this is always within [0, max]
+        let capped = backoff.min(self.max_delay.as_secs_f64()).max(0.0);
+        let jitter = if self.jitter.is_finite() { self.jitter.clamp(0.0, 1.0) } else { 0.0 };
+        // A max_delay beyond f64's exact range may round past Duration::MAX
+        Duration::try_from_secs_f64(capped * (1.0 - jitter * random_unit())).unwrap_or(self.max_delay)
+    }
+}
+
+/// Uniform value in `[0, 1)` from std's randomly keyed hasher.
+fn random_unit() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let bits = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+type WsReader = futures_util::stream::SplitStream<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+>;
+
 /// The sync client.
 ///
 /// Manages a WebSocket connection to the collaboration server,
 /// handles delta sync, awareness updates, and offline queueing.
+/// Lost connections are re-established per [`ReconnectConfig`].
 pub struct SyncClient {
     /// Our peer identity
     peer_info: PeerInfo,
@@ -145,8 +478,8 @@ pub struct SyncClient {
     /// Offline queue for disconnected edits
     offline_queue: Arc<Mutex<OfflineQueue>>,
 
-    /// Channel to send messages to the WebSocket writer task
-    outgoing_tx: Option<mpsc::Sender<Vec<u8>>>,
+    /// Channel to the current connection's writer task
+    outgoing_tx: Arc<RwLock<Option<mpsc::Sender<Vec<u8>>>>>,
 
     /// Event receiver for the application
     event_rx: Option<mpsc::Receiver<SyncEvent>>,
@@ -156,6 +489,24 @@ pub struct SyncClient {
 
     /// Server URL
     server_url: String,
+
+    /// Bearer token sent with the join
+    auth_token: Option<String>,
+
+    /// Capabilities offered in our hello
+    capabilities: Capabilities,
+
+    /// What the server agreed to for the current connection
+    protocol: Arc<RwLock<Hello>>,
+
+    /// Encoded state vector of the local doc, sent as SyncStep1 on connect
+    state_vector: Arc<RwLock<Vec<u8>>>,
+
+    /// Backoff policy for lost connections
+    reconnect: ReconnectConfig,
+
+    /// Reader/reconnect task of the current session
+    session_task: Option<JoinHandle<()>>,
 }
 
 impl SyncClient {
@@ -168,13 +519,55 @@ impl SyncClient {
             state: Arc::new(RwLock::new(ConnectionState::Disconnected)),
             clock: Arc::new(RwLock::new(0)),
             offline_queue: Arc::new(Mutex::new(OfflineQueue::new(10_000))),
-            outgoing_tx: None,
+            outgoing_tx: Arc::new(RwLock::new(None)),
             event_rx: Some(event_rx),
             event_tx,
             server_url: server_url.into(),
+            auth_token: None,
+            capabilities: Capabilities::COMMENTS | Capabilities::COMPRESSION,
+            protocol: Arc::new(RwLock::new(Hello::legacy())),
+            state_vector: Arc::new(RwLock::new(yrs::StateVector::default().encode_v1())),
+            reconnect: ReconnectConfig::default(),
+            session_task: None,
         }
     }
 
+    /// Send `token` with the join so authenticating servers accept it.
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Offer `capabilities` in the hello instead of the default
+    /// ([`Capabilities::COMMENTS`] and [`Capabilities::COMPRESSION`]).
+    /// [`Capabilities::AUTH`] is added whenever a token is set.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Use `queue` for offline edits, e.g. a persistent one from
+    /// [`OfflineQueue::open`]. Anything already in it is replayed on connect
+    /// and kept until the server has it.
+    pub fn with_offline_queue(mut self, queue: OfflineQueue) -> Self {
+        self.offline_queue = Arc::new(Mutex::new(queue));
+        self
+    }
+
+    /// Replace the reconnect policy; see [`ReconnectConfig::disabled`].
+    pub fn with_reconnect(mut self, config: ReconnectConfig) -> Self {
+        self.reconnect = config;
+        self
+    }
+
+    /// Record the local doc's encoded state vector.
+    ///
+    /// It is sent as SyncStep1 on the next connect so the server replies
+    /// with only the missing updates. Defaults to the empty state vector.
+    pub async fn set_state_vector(&self, state_vector: Vec<u8>) {
+        *self.state_vector.write().await = state_vector;
+    }
+
     /// Take the event receiver (can only be called once).
     pub fn take_event_rx(&mut self) -> Option<mpsc::Receiver<SyncEvent>> {
         self.event_rx.take()
@@ -183,182 +576,113 @@ impl SyncClient {
     /// Connect to the server.
     ///
     /// Spawns background tasks for reading/writing WebSocket messages.
+    /// Only this first attempt's error is returned; later losses are
+    /// retried in the background and reported as events.
     pub async fn connect(&mut self) -> Result<(), ProtocolError> {
+        if let Some(task) = self.session_task.take() {
+            task.abort();
+        }
         *self.state.write().await = ConnectionState::Connecting;
 
-        let url = format!("{}/{}", self.server_url, self.doc_id);
-        let ws_result = tokio_tungstenite::connect_async(&url).await;
-
-        match ws_result {
-            Ok((ws_stream, _)) => {
-                let (ws_writer, mut ws_reader) = futures_util::StreamExt::split(ws_stream);
-
-                // Outgoing message channel
-                let (out_tx, mut out_rx) = mpsc::channel::<Vec<u8>>(256);
-                self.outgoing_tx = Some(out_tx);
-
-                // Writer task: forward outgoing channel to WebSocket
-                let ws_writer = Arc::new(tokio::sync::Mutex::new(ws_writer));
-                let writer = ws_writer.clone();
-                tokio::spawn(async move {
-                    while let Some(data) = out_rx.recv().await {
-                        let mut w = writer.lock().await;
-                        use futures_util::SinkExt;
-                        if w.send(tokio_tungstenite::tungstenite::Message::Binary(data.into()))
-                            .await
-                            .is_err()
-                        {
-                            break;
-                        }
-                    }
-                });
-
-                // Send PeerJoined message
-                let join_msg = SyncMessage::peer_joined(
-                    self.peer_info.peer_id,
-                    self.doc_id,
-                    &self.peer_info,
-                );
-                if let Ok(encoded) = join_msg.encode() {
-                    if let Some(ref tx) = self.outgoing_tx {
-                        let _ = tx.send(encoded).await;
-                    }
-                }
-
-                *self.state.write().await = ConnectionState::Connected;
-                let _ = self.event_tx.send(SyncEvent::Connected).await;
-
-                // Replay offline queue
-                {
-                    let mut queue = self.offline_queue.lock().await;
-                    let queued = queue.drain();
-                    if !queued.is_empty() {
-                        log::info!("Replaying {} queued deltas", queued.len());
-                        for (clock, payload) in queued {
-                            let msg = SyncMessage::delta(
-                                self.peer_info.peer_id,
-                                self.doc_id,
-                                clock,
-                                payload,
-                            );
-                            if let Ok(encoded) = msg.encode() {
-                                if let Some(ref tx) = self.outgoing_tx {
-                                    let _ = tx.send(encoded).await;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Reader task: process incoming WebSocket messages
-                let event_tx = self.event_tx.clone();
-                let state = self.state.clone();
-                let peer_id = self.peer_info.peer_id;
-                tokio::spawn(async move {
-                    while let Some(msg) = ws_reader.next().await {
-                        match msg {
-                            Ok(tokio_tungstenite::tungstenite::Message::Binary(data)) => {
-                                let bytes: Vec<u8> = data.into();
-                                if let Ok(sync_msg) = SyncMessage::decode(&bytes) {
-                                    // Skip our own messages
-                                    if sync_msg.peer_id == peer_id {
-                                        continue;
-                                    }
-
-                                    let event = match sync_msg.msg_type {
-                                        crate::protocol::MessageType::Delta => {
-                                            Some(SyncEvent::RemoteDelta {
-                                                peer_id: sync_msg.peer_id,
-                                                clock: sync_msg.clock,
-                                                update: sync_msg.payload,
-                                            })
-                                        }
-                                        crate::protocol::MessageType::SyncStep2 => {
-                                            Some(SyncEvent::StateSynced(sync_msg.payload))
-                                        }
-                                        crate::protocol::MessageType::Awareness => {
-                                            // Try new presence format first, fall back to legacy
-                                            if let Ok(presence_msg) = crate::presence::AwarenessMessage::decode(&sync_msg.payload) {
-                                                Some(SyncEvent::PresenceUpdate {
-                                                    peer_id: sync_msg.peer_id,
-                                                    message: presence_msg,
-                                                })
-                                            } else if let Ok(awareness_state) = sync_msg.awareness_state() {
-                                                Some(SyncEvent::RemoteAwareness {
-                                                    peer_id: sync_msg.peer_id,
-                                                    state: awareness_state,
-                                                })
-                                            } else {
-                                                None
-                                            }
-                                        }
-                                        crate::protocol::MessageType::PeerJoined => {
-                                            if let Ok(info) = sync_msg.peer_info() {
-                                                Some(SyncEvent::PeerJoined(info))
-                                            } else {
-                                                None
-                                            }
-                                        }
-                                        crate::protocol::MessageType::PeerLeft => {
-                                            Some(SyncEvent::PeerLeft(sync_msg.peer_id))
-                                        }
-                                        _ => None,
-                                    };
-
-                                    if let Some(evt) = event {
-                                        let _ = event_tx.send(evt).await;
-                                    }
-                                }
-                            }
-                            Ok(tokio_tungstenite::tungstenite::Message::Close(_)) | Err(_) => {
-                                break;
-                            }
-                            _ => {}
-                        }
-                    }
-
-                    // Connection lost
-                    *state.write().await = ConnectionState::Disconnected;
-                    let _ = event_tx.send(SyncEvent::Disconnected).await;
-                });
-
+        let session = self.session();
+        match session.open().await {
+            Ok(reader) => {
+                self.session_task = Some(tokio::spawn(session.run(reader)));
                 Ok(())
             }
-            Err(_e) => {
+            Err(e) => {
                 *self.state.write().await = ConnectionState::Disconnected;
-                Err(ProtocolError::ConnectionClosed)
+                Err(e)
             }
         }
     }
 
+    /// Close the connection and stop reconnecting.
+    pub async fn disconnect(&mut self) {
+        if let Some(task) = self.session_task.take() {
+            task.abort();
+        }
+        let was_connected = {
+            let mut state = self.state.write().await;
+            std::mem::replace(&mut *state, ConnectionState::Disconnected) != ConnectionState::Disconnected
+        };
+        // Dropping the last sender lets the writer close the socket
+        *self.outgoing_tx.write().await = None;
+        if was_connected {
+            let _ = self.event_tx.send(SyncEvent::Disconnected).await;
+        }
+    }
+
+    fn session(&self) -> Session {
+        Session {
+            peer_info: self.peer_info.clone(),
+            doc_id: self.doc_id,
+            url: format!("{}/{}", self.server_url, self.doc_id),
+            auth_token: self.auth_token.clone(),
+            capabilities: self.capabilities,
+            protocol: self.protocol.clone(),
+            reconnect: self.reconnect.clone(),
+            state: self.state.clone(),
+            offline_queue: self.offline_queue.clone(),
+            outgoing_tx: self.outgoing_tx.clone(),
+            event_tx: self.event_tx.clone(),
+            state_vector: self.state_vector.clone(),
+        }
+    }
+
+    /// Serialize `msg`, compressed if the server agreed to it.
+    async fn encode(&self, msg: &SyncMessage) -> Result<Vec<u8>, ProtocolError> {
+        let agreed = self.protocol.read().await.capabilities;
+        msg.encode_with(agreed.contains(Capabilities::COMPRESSION).then_some(DEFAULT_COMPRESSION_THRESHOLD))
+    }
+
+    /// Hand `encoded` to the connection's writer task; fails if there is
+    /// no connection or its writer has gone away.
+    async fn send_encoded(&self, encoded: Vec<u8>) -> Result<(), ProtocolError> {
+        let tx = self.outgoing_tx.read().await.clone();
+        tx.ok_or(ProtocolError::ConnectionClosed)?
+            .send(encoded)
+            .await
+            .map_err(|_| ProtocolError::ConnectionClosed)
+    }
+
     /// Send a CRDT delta to the server.
     ///
-    /// If disconnected, queues the delta for later replay.
-    pub async fn send_delta(&self, yrs_update: Vec<u8>) -> Result<(), ProtocolError> {
+    /// If disconnected, or the connection drops while sending, queues the
+    /// delta for later replay.
+    pub async fn send_delta(&self, mut yrs_update: Vec<u8>) -> Result<(), ProtocolError> {
         let mut clock = self.clock.write().await;
         *clock += 1;
         let current_clock = *clock;
 
         let state = *self.state.read().await;
-        if state != ConnectionState::Connected {
-            // Queue for offline replay
-            let mut queue = self.offline_queue.lock().await;
-            if !queue.enqueue(current_clock, yrs_update) {
-                return Err(ProtocolError::ConnectionClosed);
+        if state == ConnectionState::Connected {
+            let msg = SyncMessage::delta(self.peer_info.peer_id, self.doc_id, current_clock, yrs_update);
+            if self.send_encoded(self.encode(&msg).await?).await.is_ok() {
+                return Ok(());
             }
-            return Ok(());
+            yrs_update = msg.payload;
         }
 
-        let msg = SyncMessage::delta(self.peer_info.peer_id, self.doc_id, current_clock, yrs_update);
-        let encoded = msg.encode()?;
+        // Queue for offline replay
+        let mut queue = self.offline_queue.lock().await;
+        if !queue.enqueue(current_clock, yrs_update) {
+            return Err(ProtocolError::ConnectionClosed);
+        }
+        Ok(())
+    }
 
-        if let Some(ref tx) = self.outgoing_tx {
-            tx.send(encoded)
-                .await
-                .map_err(|_| ProtocolError::ConnectionClosed)?;
+    /// Answer the server's [`SyncEvent::SyncRequested`] with the local
+    /// updates it is missing (`encode_diff_v1` against its state vector).
+    ///
+    /// The server applies it under the same permission checks as a delta.
+    pub async fn send_sync_step2(&self, yrs_update: Vec<u8>) -> Result<(), ProtocolError> {
+        if *self.state.read().await != ConnectionState::Connected {
+            return Err(ProtocolError::ConnectionClosed);
         }
 
-        Ok(())
+        let msg = SyncMessage::sync_step2(self.peer_info.peer_id, self.doc_id, yrs_update);
+        self.send_encoded(self.encode(&msg).await?).await
     }
 
     /// Send an awareness update (cursor position, selection).
@@ -370,29 +694,25 @@ impl SyncClient {
 
         let clock = *self.clock.read().await;
         let msg = SyncMessage::awareness(self.peer_info.peer_id, self.doc_id, clock, awareness_state);
-        let encoded = msg.encode()?;
+        self.send_encoded(msg.encode()?).await
+    }
 
-        if let Some(ref tx) = self.outgoing_tx {
-            tx.send(encoded)
-                .await
-                .map_err(|_| ProtocolError::ConnectionClosed)?;
+    /// Send a version history request: list versions, fetch a snapshot,
+    /// or restore the document. Replies arrive as [`SyncEvent::History`];
+    /// a restore also arrives as a remote delta.
+    pub async fn request_history(&self, request: &HistoryMessage) -> Result<(), ProtocolError> {
+        if *self.state.read().await != ConnectionState::Connected {
+            return Err(ProtocolError::ConnectionClosed);
         }
 
-        Ok(())
+        let msg = SyncMessage::history(self.peer_info.peer_id, self.doc_id, request);
+        self.send_encoded(self.encode(&msg).await?).await
     }
 
     /// Send a ping to the server.
     pub async fn send_ping(&self) -> Result<(), ProtocolError> {
         let msg = SyncMessage::ping(self.peer_info.peer_id);
-        let encoded = msg.encode()?;
-
-        if let Some(ref tx) = self.outgoing_tx {
-            tx.send(encoded)
-                .await
-                .map_err(|_| ProtocolError::ConnectionClosed)?;
-        }
-
-        Ok(())
+        self.send_encoded(msg.encode()?).await
     }
 
     /// Get the current connection state.
@@ -400,6 +720,12 @@ impl SyncClient {
         *self.state.read().await
     }
 
+    /// Protocol version and capabilities negotiated for the current
+    /// connection; [`Hello::legacy`] until the server answers our hello.
+    pub async fn protocol(&self) -> Hello {
+        *self.protocol.read().await
+    }
+
     /// Get our peer info.
     pub fn peer_info(&self) -> &PeerInfo {
         &self.peer_info
@@ -445,15 +771,283 @@ impl SyncClient {
             clock,
             payload,
         };
-        let encoded = sync_msg.encode()?;
+        self.send_encoded(sync_msg.encode()?).await
+    }
+}
 
-        if let Some(ref tx) = self.outgoing_tx {
-            tx.send(encoded)
+impl Drop for SyncClient {
+    fn drop(&mut self) {
+        if let Some(task) = self.session_task.take() {
+            task.abort();
+        }
+        if let Ok(mut tx) = self.outgoing_tx.try_write() {
+            tx.take();
+        }
+    }
+}
+
+/// Connection state shared between the client handle and its session task.
+struct Session {
+    peer_info: PeerInfo,
+    doc_id: Uuid,
+    url: String,
+    auth_token: Option<String>,
+    capabilities: Capabilities,
+    protocol: Arc<RwLock<Hello>>,
+    reconnect: ReconnectConfig,
+    state: Arc<RwLock<ConnectionState>>,
+    offline_queue: Arc<Mutex<OfflineQueue>>,
+    outgoing_tx: Arc<RwLock<Option<mpsc::Sender<Vec<u8>>>>>,
+    event_tx: mpsc::Sender<SyncEvent>,
+    state_vector: Arc<RwLock<Vec<u8>>>,
+}
+
+impl Session {
+    /// Open a socket, say hello, join, start the handshake and replay the
+    /// offline queue.
+    async fn open(&self) -> Result<WsReader, ProtocolError> {
+        let (ws_stream, _) = tokio_tungstenite::connect_async(&self.url)
+            .await
+            .map_err(|_| ProtocolError::ConnectionClosed)?;
+        let (mut ws_writer, ws_reader) = ws_stream.split();
+
+        // Writer task: forward outgoing channel to WebSocket, close when
+        // the last sender is gone
+        let (out_tx, mut out_rx) = mpsc::channel::<Vec<u8>>(256);
+        tokio::spawn(async move {
+            while let Some(data) = out_rx.recv().await {
+                if ws_writer.send(Message::Binary(data.into())).await.is_err() {
+                    break;
+                }
+            }
+            let _ = ws_writer.close().await;
+        });
+
+        // Offer our version and capabilities; servers from before the
+        // handshake skip the unknown message type and never answer
+        let mut capabilities = self.capabilities;
+        if self.auth_token.is_some() {
+            capabilities |= Capabilities::AUTH;
+        }
+        *self.protocol.write().await = Hello::legacy();
+        let hello = SyncMessage::hello(self.peer_info.peer_id, self.doc_id, &Hello::new(capabilities));
+
+        // Send PeerJoined message
+        let join_msg = match &self.auth_token {
+            Some(token) => SyncMessage::peer_joined_with_token(
+                self.peer_info.peer_id,
+                self.doc_id,
+                &self.peer_info,
+                token,
+            ),
+            None => SyncMessage::peer_joined(
+                self.peer_info.peer_id,
+                self.doc_id,
+                &self.peer_info,
+            ),
+        };
+        for msg in [hello, join_msg] {
+            out_tx
+                .send(msg.encode()?)
                 .await
                 .map_err(|_| ProtocolError::ConnectionClosed)?;
         }
 
-        Ok(())
+        // Replay offline queue. The deltas stay queued until the server's
+        // SyncStep1 shows it has processed everything sent before our own,
+        // and then only if it refused none of them
+        {
+            let mut queue = self.offline_queue.lock().await;
+            let queued = queue.replay();
+            if !queued.is_empty() {
+                log::info!("Replaying {} queued deltas", queued.len());
+                for (clock, payload) in queued {
+                    let msg = SyncMessage::delta(self.peer_info.peer_id, self.doc_id, clock, payload);
+                    out_tx
+                        .send(msg.encode()?)
+                        .await
+                        .map_err(|_| ProtocolError::ConnectionClosed)?;
+                }
+            }
+        }
+
+        // Ask for what we are missing; the server answers with
+        // SyncStep2 and its own SyncStep1
+        let step1 = SyncMessage::sync_step1(
+            self.peer_info.peer_id,
+            self.doc_id,
+            self.state_vector.read().await.clone(),
+        );
+        out_tx
+            .send(step1.encode()?)
+            .await
+            .map_err(|_| ProtocolError::ConnectionClosed)?;
+
+        *self.outgoing_tx.write().await = Some(out_tx.clone());
+        *self.state.write().await = ConnectionState::Connected;
+        let _ = self.event_tx.send(SyncEvent::Connected).await;
+
+        Ok(ws_reader)
+    }
+
+    /// Read until the connection is lost for good, reconnecting per policy.
+    async fn run(self, mut reader: WsReader) {
+        loop {
+            let refused = self.read(&mut reader).await;
+            // Leave `Connected` before dropping the writer, so edits made
+            // in between are queued rather than sent nowhere
+            *self.state.write().await = match refused {
+                true => ConnectionState::Disconnected,
+                false => ConnectionState::Reconnecting,
+            };
+            *self.outgoing_tx.write().await = None;
+            let next = if refused { None } else { self.reconnect().await };
+            match next {
+                Some(r) => reader = r,
+                None => {
+                    // Connection lost
+                    *self.state.write().await = ConnectionState::Disconnected;
+                    let _ = self.event_tx.send(SyncEvent::Disconnected).await;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Back off and retry until a connection opens or attempts run out.
+    async fn reconnect(&self) -> Option<WsReader> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            if self.reconnect.max_attempts.is_some_and(|max| attempt > max) {
+                log::warn!("Giving up on doc {} after {} reconnect attempts", self.doc_id, attempt - 1);
+                return None;
+            }
+            let delay = self.reconnect.delay(attempt);
+            *self.state.write().await = ConnectionState::Reconnecting;
+            let _ = self.event_tx.send(SyncEvent::Reconnecting { attempt, delay }).await;
+            tokio::time::sleep(delay).await;
+
+            match self.open().await {
+                Ok(reader) => {
+                    log::info!("Reconnected to doc {} on attempt {attempt}", self.doc_id);
+                    return Some(reader);
+                }
+                Err(e) => log::debug!("Reconnect attempt {attempt} to doc {}: {e}", self.doc_id),
+            }
+        }
+    }
+
+    /// Forward incoming messages as events until the socket closes.
+    ///
+    /// Returns whether the server refused the connection for good (see
+    /// [`Rejection::is_final`]), which is not retried.
+    async fn read(&self, ws_reader: &mut WsReader) -> bool {
+        let mut refused = false;
+        // Whether the server refused a delta before answering our
+        // SyncStep1, i.e. one of the replayed offline deltas
+        let mut replay_refused = false;
+        while let Some(msg) = ws_reader.next().await {
+            match msg {
+                Ok(Message::Binary(data)) => {
+                    let bytes: Vec<u8> = data.into();
+                    // Undecodable messages are skipped, including types
+                    // from a newer protocol version
+                    if let Ok(sync_msg) = SyncMessage::decode(&bytes) {
+                        // Skip our own messages
+                        if sync_msg.peer_id == self.peer_info.peer_id {
+                            continue;
+                        }
+
+                        let event = match sync_msg.msg_type {
+                            crate::protocol::MessageType::Delta => {
+                                Some(SyncEvent::RemoteDelta {
+                                    peer_id: sync_msg.peer_id,
+                                    clock: sync_msg.clock,
+                                    update: sync_msg.payload,
+                                })
+                            }
+                            crate::protocol::MessageType::SyncStep1 => {
+                                // Sent after processing everything we sent
+                                // before our SyncStep1, replayed deltas included
+                                let mut queue = self.offline_queue.lock().await;
+                                if std::mem::take(&mut replay_refused) {
+                                    log::warn!("Server refused replayed deltas; keeping them queued");
+                                    queue.requeue();
+                                } else {
+                                    queue.acknowledge();
+                                }
+                                Some(SyncEvent::SyncRequested(sync_msg.payload))
+                            }
+                            crate::protocol::MessageType::SyncStep2 => {
+                                Some(SyncEvent::StateSynced(sync_msg.payload))
+                            }
+                            crate::protocol::MessageType::Awareness => {
+                                // Try new presence format first, fall back to legacy
+                                if let Ok(presence_msg) = crate::presence::AwarenessMessage::decode(&sync_msg.payload) {
+                                    Some(SyncEvent::PresenceUpdate {
+                                        peer_id: sync_msg.peer_id,
+                                        message: presence_msg,
+                                    })
+                                } else if let Ok(awareness_state) = sync_msg.awareness_state() {
+                                    Some(SyncEvent::RemoteAwareness {
+                                        peer_id: sync_msg.peer_id,
+                                        state: awareness_state,
+                                    })
+                                } else {
+                                    None
+                                }
+                            }
+                            crate::protocol::MessageType::PeerJoined => {
+                                if let Ok(info) = sync_msg.peer_info() {
+                                    Some(SyncEvent::PeerJoined(info))
+                                } else {
+                                    None
+                                }
+                            }
+                            crate::protocol::MessageType::PeerLeft => {
+                                Some(SyncEvent::PeerLeft(sync_msg.peer_id))
+                            }
+                            crate::protocol::MessageType::Rejected => {
+                                let rejection = sync_msg.rejection().ok();
+                                refused |= rejection.as_ref().is_some_and(Rejection::is_final);
+                                replay_refused |= matches!(
+                                    rejection,
+                                    Some(
+                                        Rejection::StorageFailed { msg_type: crate::protocol::MessageType::Delta, .. }
+                                            | Rejection::RateLimited { msg_type: crate::protocol::MessageType::Delta, .. }
+                                    )
+                                );
+                                rejection.map(SyncEvent::Rejected)
+                            }
+                            crate::protocol::MessageType::History => {
+                                sync_msg.history_message().ok().map(SyncEvent::History)
+                            }
+                            crate::protocol::MessageType::Hello => match sync_msg.hello_payload() {
+                                Ok(agreed) => {
+                                    *self.protocol.write().await = agreed;
+                                    Some(SyncEvent::Negotiated(agreed))
+                                }
+                                Err(e) => {
+                                    log::warn!("Undecodable hello from server: {e}");
+                                    None
+                                }
+                            },
+                            _ => None,
+                        };
+
+                        if let Some(evt) = event {
+                            let _ = self.event_tx.send(evt).await;
+                        }
+                    }
+                }
+                Ok(Message::Close(_)) | Err(_) => {
+                    break;
+                }
+                _ => {}
+            }
+        }
+        refused
     }
 }
 
@@ -498,6 +1092,24 @@ mod tests {
         assert_eq!(client.clock().await, 2);
     }
 
+    #[tokio::test]
+    async fn test_send_delta_without_writer_queues() {
+        let info = PeerInfo::new("TestUser");
+        let client = SyncClient::new(info, Uuid::new_v4(), "ws://localhost:9090");
+
+        // Writer already gone while the state still reads connected
+        *client.state.write().await = ConnectionState::Connected;
+        client.send_delta(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(client.offline_queue_len().await, 1);
+
+        // Writer task exited
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        *client.outgoing_tx.write().await = Some(tx);
+        client.send_delta(vec![4, 5, 6]).await.unwrap();
+        assert_eq!(client.offline_queue_len().await, 2);
+    }
+
     #[tokio::test]
     async fn test_send_awareness_offline_noop() {
         let info = PeerInfo::new("TestUser");
@@ -539,6 +1151,171 @@ mod tests {
         assert_eq!(queue.len(), 3);
     }
 
+    /// Successive text inserts from one doc, each as its own update.
+    fn text_updates(parts: &[&str]) -> (Vec<Vec<u8>>, String) {
+        use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+        let doc = yrs::Doc::new();
+        let updates = parts
+            .iter()
+            .map(|part| {
+                let mut txn = doc.transact_mut();
+                let text = txn.get_or_insert_text("content");
+                let len = text.len(&txn);
+                text.insert(&mut txn, len, part);
+                txn.encode_update_v1()
+            })
+            .collect();
+        let txn = doc.transact();
+        let content = txn.get_text("content").unwrap().get_string(&txn);
+        (updates, content)
+    }
+
+    #[test]
+    fn test_persistent_queue_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+        let doc_id = Uuid::new_v4();
+
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        assert!(queue.is_persistent());
+        queue.enqueue(1, vec![1, 2, 3]);
+        queue.enqueue(2, vec![4, 5]);
+        drop(queue);
+
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        assert_eq!(queue.drain(), vec![(1, vec![1, 2, 3]), (2, vec![4, 5])]);
+        drop(queue);
+
+        // Drained deltas are gone from disk too
+        assert!(OfflineQueue::open(&path, doc_id, 100).unwrap().is_empty());
+        // ...and other documents never see them
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        queue.enqueue(3, vec![6]);
+        drop(queue);
+        assert!(OfflineQueue::open(&path, Uuid::new_v4(), 100).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replayed_deltas_kept_until_acknowledged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+        let doc_id = Uuid::new_v4();
+
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        queue.enqueue(1, vec![1, 2, 3]);
+        queue.enqueue(2, vec![4, 5]);
+        assert_eq!(queue.replay(), vec![(1, vec![1, 2, 3]), (2, vec![4, 5])]);
+        drop(queue);
+
+        // Lost before the server confirmed: everything is replayed again
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        assert_eq!(queue.replay().len(), 2);
+        queue.enqueue(3, vec![6]);
+
+        // Only what was replayed is dropped, on disk too
+        queue.acknowledge();
+        assert_eq!(queue.len(), 1);
+        drop(queue);
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        assert_eq!(queue.replay(), vec![(3, vec![6])]);
+
+        // Refused by the server: kept, and only dropped after a replay
+        // the server accepted
+        queue.replay();
+        queue.requeue();
+        queue.acknowledge();
+        assert_eq!(queue.len(), 1);
+        drop(queue);
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        assert_eq!(queue.replay(), vec![(3, vec![6])]);
+
+        // A second acknowledgement without a replay is a no-op
+        queue.acknowledge();
+        queue.acknowledge();
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_merge_cancels_pending_acknowledgement() {
+        let (updates, _) = text_updates(&["a", "b", "c"]);
+        let mut queue = OfflineQueue::new(100);
+        queue.enqueue(1, updates[0].clone());
+        queue.replay();
+        queue.enqueue(2, updates[1].clone());
+        assert!(queue.merge());
+
+        // The merged update holds the unreplayed delta
+        queue.acknowledge();
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_persistent_queue_cuts_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+        let doc_id = Uuid::new_v4();
+
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        queue.enqueue(1, vec![1]);
+        queue.enqueue(2, vec![2]);
+        drop(queue);
+        // A crash mid-append leaves half a record behind
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[200, 0, 0, 0, 9, 9]).unwrap();
+        drop(file);
+
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap();
+        assert_eq!(queue.len(), 2);
+        queue.enqueue(3, vec![3]);
+        drop(queue);
+        assert_eq!(OfflineQueue::open(&path, doc_id, 100).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_queue_merges_into_single_update() {
+        use yrs::updates::decoder::Decode;
+        use yrs::{GetString, ReadTxn, Transact};
+
+        let (updates, content) = text_updates(&["a", "b", "c", "d", "e"]);
+        let mut queue = OfflineQueue::new(100).with_merge_threshold(Some(4));
+        for (clock, update) in updates.iter().enumerate() {
+            assert!(queue.enqueue(clock as u64 + 1, update.clone()));
+        }
+        // Four merged into one, then the fifth appended
+        assert_eq!(queue.len(), 2);
+
+        let doc = yrs::Doc::new();
+        let drained = queue.drain();
+        assert_eq!(drained[0].0, 4, "merged delta keeps the latest clock");
+        for (_, update) in drained {
+            doc.transact_mut().apply_update(yrs::Update::decode_v1(&update).unwrap()).unwrap();
+        }
+        let txn = doc.transact();
+        assert_eq!(txn.get_text("content").unwrap().get_string(&txn), content);
+    }
+
+    #[test]
+    fn test_persistent_merge_is_written_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+        let doc_id = Uuid::new_v4();
+        let (updates, _) = text_updates(&["one ", "two ", "three"]);
+
+        let mut queue = OfflineQueue::open(&path, doc_id, 100).unwrap().with_merge_threshold(Some(3));
+        for (clock, update) in updates.into_iter().enumerate() {
+            queue.enqueue(clock as u64, update);
+        }
+        assert_eq!(queue.len(), 1);
+        drop(queue);
+        assert_eq!(OfflineQueue::open(&path, doc_id, 100).unwrap().len(), 1);
+
+        // Garbage is left alone rather than lost
+        let mut queue = OfflineQueue::new(100).with_merge_threshold(Some(2));
+        queue.enqueue(1, vec![0xff, 0xff]);
+        queue.enqueue(2, vec![0xff]);
+        assert_eq!(queue.len(), 2);
+    }
+
     #[test]
     fn test_offline_queue_clear() {
         let mut queue = OfflineQueue::new(100);
@@ -564,4 +1341,50 @@ mod tests {
         // Second take should return None
         assert!(client.take_event_rx().is_none());
     }
+
+    #[test]
+    fn test_reconnect_backoff_grows_and_caps() {
+        let config = ReconnectConfig {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_attempts: Some(10),
+        };
+        assert_eq!(config.delay(1), Duration::from_millis(100));
+        assert_eq!(config.delay(2), Duration::from_millis(200));
+        assert_eq!(config.delay(4), Duration::from_millis(800));
+        assert_eq!(config.delay(5), Duration::from_secs(1));
+        assert_eq!(config.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_reconnect_jitter_stays_in_bounds() {
+        let config = ReconnectConfig {
+            jitter: 0.5,
+            ..ReconnectConfig::default()
+        };
+        let full = Duration::from_millis(400);
+        let delays: Vec<Duration> = (0..50).map(|_| config.delay(3)).collect();
+        assert!(delays.iter().all(|d| *d <= full && *d >= full / 2), "{delays:?}");
+        assert!(delays.iter().any(|d| *d != delays[0]), "jitter should vary delays");
+        assert_eq!(ReconnectConfig::disabled().max_attempts, Some(0));
+    }
+
+    #[test]
+    fn test_reconnect_delay_survives_extreme_config() {
+        let unbounded = ReconnectConfig {
+            max_delay: Duration::MAX,
+            multiplier: f64::MAX,
+            jitter: 0.0,
+            ..ReconnectConfig::default()
+        };
+        assert_eq!(unbounded.delay(1), Duration::from_millis(100));
+        assert_eq!(unbounded.delay(u32::MAX), Duration::MAX);
+
+        let nan_jitter = ReconnectConfig { jitter: f64::NAN, ..ReconnectConfig::default() };
+        assert_eq!(nan_jitter.delay(2), Duration::from_millis(200));
+        let nan_growth = ReconnectConfig { multiplier: f64::NAN, jitter: 0.0, ..ReconnectConfig::default() };
+        assert_eq!(nan_growth.delay(3), Duration::from_millis(100));
+    }
 }
@@ -0,0 +1,749 @@
+//! Multi-node clustering: relaying room traffic between server nodes.
+//!
+//! Architecture:
+//! ```text
+//! Client A ──► Node 1 ──┐                 ┌── Node 2 ◄── Client B
+//!              (room)   │   RoomRelay     │   (room)
+//!                       └──── deltas ─────┘
+//!                             awareness
+//!                             sync steps
+//!                                 │
+//!                          StorageLease: one node
+//!                          persists each document
+//! ```
+//!
+//! Every node keeps its own copy of an open room and applies what its own
+//! peers send, as a single server does; a [`RoomRelay`] carries deltas,
+//! awareness and joins to the other nodes, which apply and fan them out to
+//! their peers. A node opening a room asks the cluster for the state it is
+//! missing with a `SyncStep1`, answered by every node that has the room open
+//! (or persists it).
+//!
+//! Persistence follows a [`StorageLease`]: the node holding a document's
+//! lease writes every delta of it, its own peers' and relayed ones, to its
+//! storage and is the only one taking snapshots. The first node to open or
+//! write a document takes its lease and renews it while running. A node
+//! taking over an expired lease snapshots its copy of the room at once, so
+//! its storage holds the whole document from then on.
+//!
+//! Relays are at most once. A delta accepted by a node that does not hold
+//! the lease is durable once the holder has logged it, and version history
+//! is read from a node's own storage, complete only on the holder.
+//!
+//! Reference: Kleppmann — Designing Data-Intensive Applications, Chapters 5 & 8
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use hmac::Mac;
+
+use crate::auth;
+use crate::protocol::{ProtocolError, SyncMessage, MAX_DECOMPRESSED_BYTES};
+
+/// A room message travelling between nodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayEnvelope {
+    /// Node the message comes from
+    pub origin: Uuid,
+    /// Node the message is meant for (None = every other node)
+    pub target: Option<Uuid>,
+    pub message: SyncMessage,
+}
+
+impl RelayEnvelope {
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .map_err(|e| ProtocolError::SerializationError(e.to_string()))
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+            .map(|(envelope, _)| envelope)
+            .map_err(|e| ProtocolError::DeserializationError(e.to_string()))
+    }
+
+    /// Whether a node with id `node` should handle this envelope.
+    pub fn is_for(&self, node: Uuid) -> bool {
+        self.origin != node && self.target.is_none_or(|target| target == node)
+    }
+}
+
+/// Carries room traffic between the nodes of a cluster.
+///
+/// Publishing never blocks; messages to unreachable nodes are dropped.
+pub trait RoomRelay: Send + Sync + std::fmt::Debug {
+    /// This node's id, unique in the cluster.
+    fn node_id(&self) -> Uuid;
+
+    /// Send an envelope to its target, or to every other node.
+    fn publish(&self, envelope: RelayEnvelope);
+
+    /// Envelopes from other nodes meant for this one. Handed out once, to
+    /// the server the relay belongs to.
+    fn take_incoming(&self) -> Option<mpsc::UnboundedReceiver<RelayEnvelope>>;
+}
+
+/// Decides which node persists a document.
+pub trait StorageLease: Send + Sync + std::fmt::Debug {
+    /// Take the lease on `doc_id` for `node` if it is free or expired, or
+    /// renew it if `node` holds it already, until `ttl` from now. Returns
+    /// whether `node` holds the lease.
+    fn acquire(&self, doc_id: Uuid, node: Uuid, ttl: Duration) -> bool;
+
+    /// The node holding an unexpired lease on `doc_id`.
+    fn holder(&self, doc_id: Uuid) -> Option<Uuid>;
+
+    /// Give the lease on `doc_id` up, if `node` holds it.
+    fn release(&self, doc_id: Uuid, node: Uuid);
+}
+
+/// Leases kept in memory, shared by the nodes of one process.
+#[derive(Debug, Default)]
+pub struct MemoryLeases {
+    /// doc_id → (holder, expiry)
+    leases: Mutex<HashMap<Uuid, (Uuid, Instant)>>,
+}
+
+impl MemoryLeases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageLease for MemoryLeases {
+    fn acquire(&self, doc_id: Uuid, node: Uuid, ttl: Duration) -> bool {
+        let now = Instant::now();
+        let mut leases = self.leases.lock().unwrap();
+        match leases.get(&doc_id) {
+            Some(&(holder, expires)) if holder != node && expires > now => false,
+            _ => {
+                leases.insert(doc_id, (node, now + ttl));
+                true
+            }
+        }
+    }
+
+    fn holder(&self, doc_id: Uuid) -> Option<Uuid> {
+        let leases = self.leases.lock().unwrap();
+        leases
+            .get(&doc_id)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(holder, _)| *holder)
+    }
+
+    fn release(&self, doc_id: Uuid, node: Uuid) {
+        let mut leases = self.leases.lock().unwrap();
+        if leases.get(&doc_id).is_some_and(|(holder, _)| *holder == node) {
+            leases.remove(&doc_id);
+        }
+    }
+}
+
+/// Cluster membership of a server node.
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    /// How this node reaches the others
+    pub relay: Arc<dyn RoomRelay>,
+    /// Shared by every node of the cluster
+    pub leases: Arc<dyn StorageLease>,
+    /// How long a lease lasts unless renewed; renewed every third of it
+    pub lease_ttl_secs: u64,
+}
+
+impl ClusterConfig {
+    pub fn new(relay: Arc<dyn RoomRelay>, leases: Arc<dyn StorageLease>) -> Self {
+        Self { relay, leases, lease_ttl_secs: 15 }
+    }
+}
+
+/// The in-process hub of a [`LoopbackRelay`] cluster.
+#[derive(Debug, Clone, Default)]
+pub struct LoopbackHub {
+    nodes: Arc<Mutex<HashMap<Uuid, mpsc::UnboundedSender<RelayEnvelope>>>>,
+}
+
+impl LoopbackHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node to the cluster.
+    pub fn join(&self) -> LoopbackRelay {
+        let node_id = Uuid::new_v4();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.nodes.lock().unwrap().insert(node_id, tx);
+        LoopbackRelay {
+            node_id,
+            hub: self.clone(),
+            incoming: Mutex::new(Some(rx)),
+        }
+    }
+
+    /// Nodes currently in the cluster.
+    pub fn node_count(&self) -> usize {
+        self.nodes.lock().unwrap().len()
+    }
+}
+
+/// Relay between nodes in the same process, through a [`LoopbackHub`].
+#[derive(Debug)]
+pub struct LoopbackRelay {
+    node_id: Uuid,
+    hub: LoopbackHub,
+    incoming: Mutex<Option<mpsc::UnboundedReceiver<RelayEnvelope>>>,
+}
+
+impl RoomRelay for LoopbackRelay {
+    fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    fn publish(&self, envelope: RelayEnvelope) {
+        let nodes = self.hub.nodes.lock().unwrap();
+        for (node, tx) in nodes.iter() {
+            if envelope.is_for(*node) {
+                let _ = tx.send(envelope.clone());
+            }
+        }
+    }
+
+    fn take_incoming(&self) -> Option<mpsc::UnboundedReceiver<RelayEnvelope>> {
+        self.incoming.lock().unwrap().take()
+    }
+}
+
+impl Drop for LoopbackRelay {
+    fn drop(&mut self) {
+        self.hub.nodes.lock().unwrap().remove(&self.node_id);
+    }
+}
+
+/// Largest envelope accepted from another node.
+const MAX_RELAY_FRAME: usize = MAX_DECOMPRESSED_BYTES;
+
+/// How long a new connection may take to send its node id (and prove it
+/// knows the mesh secret).
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outgoing frames to one connected node.
+type FrameSender = mpsc::UnboundedSender<Arc<Vec<u8>>>;
+
+/// Relay over TCP, with a connection to every other node.
+///
+/// Nodes exchange their ids when connecting, then length-prefixed (u32 LE)
+/// bincode envelopes. Each pair of nodes needs one connection, made by
+/// either side with [`connect`](Self::connect); a node that goes away is
+/// forgotten until connected to again.
+///
+/// Envelopes are applied to rooms as they come, so a node must only talk
+/// to nodes it trusts. Meshes bound with [`bind_with_secret`](Self::bind_with_secret)
+/// check that the other side knows the same secret before taking anything
+/// from it:
+///
+/// ```text
+/// A ── id_A, nonce_A ──────────────► B
+/// A ◄────────────── id_B, nonce_B ── B
+/// A ── HMAC(secret, id_A ‖ nonce_B) ► B   each side checks the other's
+/// A ◄ HMAC(secret, id_B ‖ nonce_A) ── B   and hangs up on a mismatch
+/// ```
+///
+/// Traffic is neither encrypted nor signed after the handshake. Nodes
+/// bound with [`bind`](Self::bind) accept any connection, and are meant for
+/// tests and private networks only.
+#[derive(Debug)]
+pub struct TcpMeshRelay {
+    node_id: Uuid,
+    local_addr: SocketAddr,
+    /// Shared secret other nodes must prove they know, if any
+    secret: Option<Arc<[u8]>>,
+    peers: Arc<Mutex<HashMap<Uuid, FrameSender>>>,
+    incoming_tx: mpsc::UnboundedSender<RelayEnvelope>,
+    incoming: Mutex<Option<mpsc::UnboundedReceiver<RelayEnvelope>>>,
+    /// Accept loop and connection tasks, aborted on drop
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl TcpMeshRelay {
+    /// Listen for other nodes on `addr`, accepting any that connect.
+    pub async fn bind(addr: &str) -> std::io::Result<Self> {
+        Self::listen(addr, None).await
+    }
+
+    /// Listen for other nodes on `addr`, connecting only with nodes bound
+    /// with the same `secret`.
+    pub async fn bind_with_secret(addr: &str, secret: impl Into<Vec<u8>>) -> std::io::Result<Self> {
+        Self::listen(addr, Some(secret.into().into())).await
+    }
+
+    async fn listen(addr: &str, secret: Option<Arc<[u8]>>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let relay = Self {
+            node_id: Uuid::new_v4(),
+            local_addr: listener.local_addr()?,
+            secret,
+            peers: Arc::new(Mutex::new(HashMap::new())),
+            incoming_tx,
+            incoming: Mutex::new(Some(incoming_rx)),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let (node_id, secret, peers, incoming_tx, tasks) = (
+            relay.node_id,
+            relay.secret.clone(),
+            relay.peers.clone(),
+            relay.incoming_tx.clone(),
+            relay.tasks.clone(),
+        );
+        let accept = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, addr)) => {
+                        // Handshakes run on their own so a silent
+                        // connection cannot hold up the next one
+                        let handshake = tokio::spawn({
+                            let (secret, peers, incoming_tx, tasks) =
+                                (secret.clone(), peers.clone(), incoming_tx.clone(), tasks.clone());
+                            async move {
+                                let attached =
+                                    Self::attach(stream, node_id, secret.as_deref(), &peers, &incoming_tx, &tasks);
+                                if let Err(e) = attached.await {
+                                    log::warn!("Relay connection from {addr}: {e}");
+                                }
+                            }
+                        });
+                        tasks.lock().unwrap().push(handshake);
+                    }
+                    Err(e) => log::error!("Relay accept: {e}"),
+                }
+            }
+        });
+        relay.tasks.lock().unwrap().push(accept);
+        Ok(relay)
+    }
+
+    /// Address other nodes connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Connect to the node listening on `addr`, returning its id.
+    pub async fn connect(&self, addr: impl tokio::net::ToSocketAddrs) -> std::io::Result<Uuid> {
+        let stream = TcpStream::connect(addr).await?;
+        Self::attach(stream, self.node_id, self.secret.as_deref(), &self.peers, &self.incoming_tx, &self.tasks).await
+    }
+
+    /// Nodes currently connected.
+    pub fn peers(&self) -> Vec<Uuid> {
+        self.peers.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Exchange node ids over a new connection, and with a `secret` check
+    /// the other side knows it, giving up after [`HANDSHAKE_TIMEOUT`]; then
+    /// run its reader and writer until either side hangs up.
+    async fn attach(
+        mut stream: TcpStream,
+        node_id: Uuid,
+        secret: Option<&[u8]>,
+        peers: &Arc<Mutex<HashMap<Uuid, FrameSender>>>,
+        incoming_tx: &mpsc::UnboundedSender<RelayEnvelope>,
+        tasks: &Mutex<Vec<JoinHandle<()>>>,
+    ) -> std::io::Result<Uuid> {
+        stream.set_nodelay(true)?;
+        let handshake = async {
+            let mut remote = [0u8; 16];
+            stream.write_all(node_id.as_bytes()).await?;
+            let Some(secret) = secret else {
+                stream.read_exact(&mut remote).await?;
+                return Ok(Uuid::from_bytes(remote));
+            };
+
+            let nonce = Uuid::new_v4();
+            let mut remote_nonce = [0u8; 16];
+            stream.write_all(nonce.as_bytes()).await?;
+            stream.read_exact(&mut remote).await?;
+            stream.read_exact(&mut remote_nonce).await?;
+            // A proof names its sender, so one cannot be reflected back
+            // at the node it was asked of
+            if remote == *node_id.as_bytes() {
+                return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "relay node claims our id"));
+            }
+            let proof = auth::hmac_sha256(secret, &[node_id.as_bytes().as_slice(), &remote_nonce].concat());
+            stream.write_all(&proof).await?;
+            let mut remote_proof = [0u8; 32];
+            stream.read_exact(&mut remote_proof).await?;
+            // Constant-time comparison
+            auth::mac(secret, &[remote.as_slice(), nonce.as_bytes()].concat())
+                .verify_slice(&remote_proof)
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::PermissionDenied, "relay secret mismatch"))?;
+            Ok(Uuid::from_bytes(remote))
+        };
+        let remote = tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "relay handshake timed out"))??;
+
+        let (mut reader, mut writer) = stream.into_split();
+        let (tx, mut rx) = mpsc::unbounded_channel::<Arc<Vec<u8>>>();
+        // A second connection to the same node takes over sending
+        peers.lock().unwrap().insert(remote, tx.clone());
+        log::info!("Relay node {node_id} connected to {remote}");
+
+        let write = tokio::spawn(async move {
+            while let Some(frame) = rx.recv().await {
+                let sent = async {
+                    writer.write_u32_le(frame.len() as u32).await?;
+                    writer.write_all(&frame).await
+                };
+                if let Err(e) = sent.await {
+                    log::warn!("Relay to {remote}: {e}");
+                    break;
+                }
+            }
+        });
+
+        let (peers, incoming_tx) = (peers.clone(), incoming_tx.clone());
+        let read = tokio::spawn(async move {
+            while let Ok(len) = reader.read_u32_le().await {
+                let len = len as usize;
+                if len > MAX_RELAY_FRAME {
+                    log::warn!("Relay frame of {len} bytes from {remote}, disconnecting");
+                    break;
+                }
+                let mut frame = vec![0u8; len];
+                if reader.read_exact(&mut frame).await.is_err() {
+                    break;
+                }
+                match RelayEnvelope::decode(&frame) {
+                    Ok(envelope) if envelope.is_for(node_id) => {
+                        let _ = incoming_tx.send(envelope);
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Undecodable relay frame from {remote}: {e}"),
+                }
+            }
+            let mut peers = peers.lock().unwrap();
+            if peers.get(&remote).is_some_and(|current| current.same_channel(&tx)) {
+                peers.remove(&remote);
+            }
+            log::info!("Relay node {node_id} lost {remote}");
+        });
+
+        let mut tasks = tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        tasks.extend([write, read]);
+        Ok(remote)
+    }
+}
+
+impl RoomRelay for TcpMeshRelay {
+    fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    fn publish(&self, envelope: RelayEnvelope) {
+        let frame = match envelope.encode() {
+            Ok(frame) => Arc::new(frame),
+            Err(e) => {
+                log::error!("Encoding relay envelope: {e}");
+                return;
+            }
+        };
+        let peers = self.peers.lock().unwrap();
+        for (node, tx) in peers.iter() {
+            if envelope.is_for(*node) {
+                let _ = tx.send(frame.clone());
+            }
+        }
+    }
+
+    fn take_incoming(&self) -> Option<mpsc::UnboundedReceiver<RelayEnvelope>> {
+        self.incoming.lock().unwrap().take()
+    }
+}
+
+impl Drop for TcpMeshRelay {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// Whether a node persists a document, as found by [`ClusterNode::claim`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Claim {
+    /// The node held the lease already
+    Held,
+    /// The node just took the lease over
+    Acquired,
+    /// Another node holds the lease
+    Elsewhere,
+}
+
+impl Claim {
+    pub(crate) fn persists(self) -> bool {
+        self != Claim::Elsewhere
+    }
+}
+
+/// A server's side of its cluster: relaying, leases held and counters.
+#[derive(Debug)]
+pub(crate) struct ClusterNode {
+    config: ClusterConfig,
+    /// Documents this node holds the lease of
+    held: Mutex<HashSet<Uuid>>,
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl ClusterNode {
+    pub(crate) fn new(config: ClusterConfig) -> Self {
+        Self {
+            config,
+            held: Mutex::new(HashSet::new()),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn node_id(&self) -> Uuid {
+        self.config.relay.node_id()
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.lease_ttl_secs.max(1))
+    }
+
+    /// How often held leases are renewed.
+    pub(crate) fn renew_period(&self) -> Duration {
+        self.ttl() / 3
+    }
+
+    pub(crate) fn take_incoming(&self) -> Option<mpsc::UnboundedReceiver<RelayEnvelope>> {
+        self.config.relay.take_incoming()
+    }
+
+    /// Send `message` to `target`, or to every other node.
+    pub(crate) fn publish(&self, target: Option<Uuid>, message: SyncMessage) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.config.relay.publish(RelayEnvelope { origin: self.node_id(), target, message });
+    }
+
+    pub(crate) fn count_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether this node persists `doc_id`, taking the lease if nobody
+    /// holds it.
+    pub(crate) fn claim(&self, doc_id: Uuid) -> Claim {
+        let mut held = self.held.lock().unwrap();
+        if self.config.leases.acquire(doc_id, self.node_id(), self.ttl()) {
+            match held.insert(doc_id) {
+                true => {
+                    log::info!("Node {} took the storage lease of doc {doc_id}", self.node_id());
+                    Claim::Acquired
+                }
+                false => Claim::Held,
+            }
+        } else {
+            if held.remove(&doc_id) {
+                log::warn!("Node {} lost the storage lease of doc {doc_id}", self.node_id());
+            }
+            Claim::Elsewhere
+        }
+    }
+
+    /// Whether this node held the lease of `doc_id` when last renewed.
+    pub(crate) fn holds(&self, doc_id: Uuid) -> bool {
+        self.held.lock().unwrap().contains(&doc_id)
+    }
+
+    /// Renew every lease held, forgetting those lost.
+    pub(crate) fn renew(&self) {
+        let docs: Vec<Uuid> = self.held.lock().unwrap().iter().copied().collect();
+        for doc_id in docs {
+            self.claim(doc_id);
+        }
+    }
+
+    /// Give every lease up, so other nodes can take over at once.
+    pub(crate) fn release_all(&self) {
+        for doc_id in self.held.lock().unwrap().drain() {
+            self.config.leases.release(doc_id, self.node_id());
+        }
+    }
+
+    /// (held leases, envelopes sent, envelopes received)
+    pub(crate) fn counters(&self) -> (u64, u64, u64) {
+        (
+            self.held.lock().unwrap().len() as u64,
+            self.sent.load(Ordering::Relaxed),
+            self.received.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    fn delta(doc_id: Uuid) -> SyncMessage {
+        SyncMessage::delta(Uuid::new_v4(), doc_id, 1, vec![1, 2, 3])
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let envelope = RelayEnvelope {
+            origin: Uuid::new_v4(),
+            target: Some(Uuid::new_v4()),
+            message: delta(Uuid::new_v4()),
+        };
+        let decoded = RelayEnvelope::decode(&envelope.encode().unwrap()).unwrap();
+        assert_eq!(decoded.origin, envelope.origin);
+        assert_eq!(decoded.target, envelope.target);
+        assert_eq!(decoded.message.payload, vec![1, 2, 3]);
+        assert!(!envelope.is_for(envelope.origin));
+        assert!(!envelope.is_for(Uuid::new_v4()));
+        assert!(envelope.is_for(envelope.target.unwrap()));
+    }
+
+    #[test]
+    fn test_memory_leases() {
+        let leases = MemoryLeases::new();
+        let (doc, a, b) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(leases.holder(doc), None);
+        assert!(leases.acquire(doc, a, ttl));
+        assert!(!leases.acquire(doc, b, ttl), "held by a");
+        assert!(leases.acquire(doc, a, ttl), "renewal");
+        assert_eq!(leases.holder(doc), Some(a));
+
+        leases.release(doc, b);
+        assert_eq!(leases.holder(doc), Some(a), "only the holder releases");
+        leases.release(doc, a);
+        assert!(leases.acquire(doc, b, ttl));
+
+        // Expired leases are free for the taking
+        assert!(leases.acquire(doc, b, Duration::ZERO));
+        assert_eq!(leases.holder(doc), None);
+        assert!(leases.acquire(doc, a, ttl));
+    }
+
+    #[test]
+    fn test_cluster_node_claims() {
+        let hub = LoopbackHub::new();
+        let leases: Arc<dyn StorageLease> = Arc::new(MemoryLeases::new());
+        let a = ClusterNode::new(ClusterConfig::new(Arc::new(hub.join()), leases.clone()));
+        let b = ClusterNode::new(ClusterConfig::new(Arc::new(hub.join()), leases.clone()));
+        let doc = Uuid::new_v4();
+
+        assert_eq!(a.claim(doc), Claim::Acquired);
+        assert_eq!(a.claim(doc), Claim::Held);
+        assert_eq!(b.claim(doc), Claim::Elsewhere);
+        assert!(a.holds(doc) && !b.holds(doc));
+        assert_eq!(a.counters().0, 1);
+
+        a.release_all();
+        assert_eq!(leases.holder(doc), None);
+        assert_eq!(b.claim(doc), Claim::Acquired);
+        assert_eq!(a.claim(doc), Claim::Elsewhere);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_relay() {
+        let hub = LoopbackHub::new();
+        let (a, b, c) = (hub.join(), hub.join(), hub.join());
+        let (mut a_rx, mut b_rx, mut c_rx) =
+            (a.take_incoming().unwrap(), b.take_incoming().unwrap(), c.take_incoming().unwrap());
+        assert!(a.take_incoming().is_none());
+
+        let doc = Uuid::new_v4();
+        a.publish(RelayEnvelope { origin: a.node_id(), target: None, message: delta(doc) });
+        assert_eq!(b_rx.recv().await.unwrap().origin, a.node_id());
+        assert_eq!(c_rx.recv().await.unwrap().origin, a.node_id());
+        assert!(a_rx.try_recv().is_err(), "no echo");
+
+        b.publish(RelayEnvelope { origin: b.node_id(), target: Some(c.node_id()), message: delta(doc) });
+        assert_eq!(c_rx.recv().await.unwrap().origin, b.node_id());
+        assert!(a_rx.try_recv().is_err(), "targeted");
+
+        drop(c);
+        assert_eq!(hub.node_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_mesh_relay() {
+        let a = TcpMeshRelay::bind("127.0.0.1:0").await.unwrap();
+        let b = TcpMeshRelay::bind("127.0.0.1:0").await.unwrap();
+        let c = TcpMeshRelay::bind("127.0.0.1:0").await.unwrap();
+        assert_eq!(a.connect(b.local_addr()).await.unwrap(), b.node_id());
+        assert_eq!(a.connect(c.local_addr()).await.unwrap(), c.node_id());
+        assert_eq!(b.connect(c.local_addr()).await.unwrap(), c.node_id());
+        let (mut b_rx, mut c_rx) = (b.take_incoming().unwrap(), c.take_incoming().unwrap());
+        let mut a_rx = a.take_incoming().unwrap();
+
+        let doc = Uuid::new_v4();
+        a.publish(RelayEnvelope { origin: a.node_id(), target: None, message: delta(doc) });
+        let wait = Duration::from_secs(2);
+        let got = timeout(wait, b_rx.recv()).await.unwrap().unwrap();
+        assert_eq!((got.origin, got.message.doc_id), (a.node_id(), doc));
+        assert_eq!(timeout(wait, c_rx.recv()).await.unwrap().unwrap().origin, a.node_id());
+
+        // The accepting side of a connection sends too
+        c.publish(RelayEnvelope { origin: c.node_id(), target: Some(a.node_id()), message: delta(doc) });
+        assert_eq!(timeout(wait, a_rx.recv()).await.unwrap().unwrap().origin, c.node_id());
+        assert!(timeout(Duration::from_millis(100), b_rx.recv()).await.is_err(), "targeted");
+
+        drop(c);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(a.peers(), vec![b.node_id()]);
+    }
+
+    #[tokio::test]
+    async fn test_silent_connection_does_not_block_accept() {
+        let a = TcpMeshRelay::bind("127.0.0.1:0").await.unwrap();
+        let b = TcpMeshRelay::bind("127.0.0.1:0").await.unwrap();
+        // Connects but never sends its node id
+        let _silent = TcpStream::connect(a.local_addr()).await.unwrap();
+
+        let connected = timeout(Duration::from_secs(2), b.connect(a.local_addr())).await;
+        assert_eq!(connected.unwrap().unwrap(), a.node_id());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_mesh_relay_checks_secret() {
+        let a = TcpMeshRelay::bind_with_secret("127.0.0.1:0", "mesh").await.unwrap();
+        let b = TcpMeshRelay::bind_with_secret("127.0.0.1:0", "mesh").await.unwrap();
+        let intruder = TcpMeshRelay::bind_with_secret("127.0.0.1:0", "guess").await.unwrap();
+        let open = TcpMeshRelay::bind("127.0.0.1:0").await.unwrap();
+        let mut a_rx = a.take_incoming().unwrap();
+
+        let wait = Duration::from_secs(2);
+        assert_eq!(timeout(wait, b.connect(a.local_addr())).await.unwrap().unwrap(), a.node_id());
+        let refused = timeout(wait, intruder.connect(a.local_addr())).await.unwrap();
+        assert_eq!(refused.unwrap_err().kind(), std::io::ErrorKind::PermissionDenied);
+        // A node without the secret takes the id exchange for the whole
+        // handshake, but never gets a proof through
+        let _ = timeout(wait, open.connect(a.local_addr())).await.unwrap();
+
+        let doc = Uuid::new_v4();
+        intruder.publish(RelayEnvelope { origin: intruder.node_id(), target: None, message: delta(doc) });
+        open.publish(RelayEnvelope { origin: open.node_id(), target: None, message: delta(doc) });
+        b.publish(RelayEnvelope { origin: b.node_id(), target: None, message: delta(doc) });
+        assert_eq!(timeout(wait, a_rx.recv()).await.unwrap().unwrap().origin, b.node_id());
+        assert!(timeout(Duration::from_millis(100), a_rx.recv()).await.is_err(), "only the mesh is heard");
+        assert_eq!(a.peers(), vec![b.node_id()]);
+    }
+}
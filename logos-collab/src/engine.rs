@@ -0,0 +1,423 @@
+//! High-level collaboration engine that owns the Yrs document.
+//!
+//! [`SyncClient`] only moves opaque bytes. [`CollaborationEngine`] wraps it
+//! around a `yrs::Doc` so applications just edit the document:
+//!
+//! ```text
+//! local txn ──observe_update_v1──► driver ──Delta──────────► server
+//! server ──Delta / SyncStep2────► driver ──apply (remote)──► Doc
+//!                                    │
+//!                                    └──► EngineEvent stream
+//! ```
+//!
+//! The client reconnects on its own, retrying forever by default. Every
+//! connect runs the state vector handshake, so edits made while offline
+//! reach the server as one diff instead of a replay of every queued delta.
+//!
+//! Reference: Kleppmann, Chapter 5 — Replication
+
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Origin, ReadTxn, Transact};
+
+use crate::client::{ReconnectConfig, SyncClient, SyncEvent};
+use crate::history::HistoryMessage;
+use crate::presence::AwarenessMessage;
+use crate::protocol::{AwarenessState, PeerInfo, ProtocolError, Rejection};
+
+/// Transaction origin of updates applied from the server.
+///
+/// Local observers can compare `txn.origin()` against it to tell remote
+/// changes from their own.
+pub const REMOTE_ORIGIN: &str = "logos-collab/remote";
+
+/// Where a document change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOrigin {
+    /// A transaction committed on the local doc
+    Local,
+    /// A delta broadcast by another peer
+    Remote(Uuid),
+    /// Missing updates received during the connect handshake
+    Sync,
+}
+
+/// One committed change to the engine's document.
+#[derive(Debug, Clone)]
+pub struct DocChange {
+    pub origin: ChangeOrigin,
+    /// The change as a Yrs v1 update
+    pub update: Vec<u8>,
+}
+
+/// Events emitted by the collaboration engine.
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    /// Connection (re-)established
+    Connected,
+    /// Connection lost; waiting `delay` before reconnect attempt `attempt`
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    /// Connection lost for good (reconnect attempts exhausted or refused)
+    Disconnected,
+    /// The document changed
+    Changed(DocChange),
+    /// The connect handshake finished applying the server's state
+    Synced,
+    /// A peer joined the document
+    PeerJoined(PeerInfo),
+    /// A peer left the document
+    PeerLeft(Uuid),
+    /// Presence message (cursor/selection/join/leave)
+    Presence {
+        peer_id: Uuid,
+        message: AwarenessMessage,
+    },
+    /// Legacy awareness state
+    Awareness {
+        peer_id: Uuid,
+        state: AwarenessState,
+    },
+    /// The server refused a request; final rejections are not retried
+    Rejected(Rejection),
+    /// Reply to [`CollaborationEngine::request_history`]
+    History(HistoryMessage),
+}
+
+/// Requests from the engine handle to its driver task.
+enum Command {
+    Presence(AwarenessMessage),
+    History(HistoryMessage),
+}
+
+/// A [`SyncClient`] that owns its document.
+///
+/// Edit [`doc`](Self::doc) with ordinary transactions; they are sent
+/// automatically. Remote updates are applied with [`REMOTE_ORIGIN`].
+pub struct CollaborationEngine {
+    doc: yrs::Doc,
+    peer_info: PeerInfo,
+    doc_id: Uuid,
+    server_url: String,
+    auth_token: Option<String>,
+    reconnect: ReconnectConfig,
+    event_tx: mpsc::Sender<EngineEvent>,
+    event_rx: Option<mpsc::Receiver<EngineEvent>>,
+    command_tx: Option<mpsc::Sender<Command>>,
+    driver: Option<JoinHandle<()>>,
+}
+
+impl CollaborationEngine {
+    /// Create an engine around a new, empty document.
+    pub fn new(peer_info: PeerInfo, doc_id: Uuid, server_url: impl Into<String>) -> Self {
+        let (event_tx, event_rx) = mpsc::channel(256);
+        Self {
+            doc: yrs::Doc::new(),
+            peer_info,
+            doc_id,
+            server_url: server_url.into(),
+            auth_token: None,
+            reconnect: ReconnectConfig {
+                max_attempts: None,
+                ..ReconnectConfig::default()
+            },
+            event_tx,
+            event_rx: Some(event_rx),
+            command_tx: None,
+            driver: None,
+        }
+    }
+
+    /// Sync an existing document, e.g. one restored from local storage.
+    pub fn with_doc(mut self, doc: yrs::Doc) -> Self {
+        self.doc = doc;
+        self
+    }
+
+    /// Send `token` with the join so authenticating servers accept it.
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Replace the reconnect policy (default: backoff, retry forever).
+    pub fn with_reconnect(mut self, config: ReconnectConfig) -> Self {
+        self.reconnect = config;
+        self
+    }
+
+    /// The shared document. Transactions on it are synced automatically.
+    pub fn doc(&self) -> &yrs::Doc {
+        &self.doc
+    }
+
+    pub fn peer_info(&self) -> &PeerInfo {
+        &self.peer_info
+    }
+
+    pub fn doc_id(&self) -> Uuid {
+        self.doc_id
+    }
+
+    /// Take the event receiver (can only be called once).
+    pub fn take_event_rx(&mut self) -> Option<mpsc::Receiver<EngineEvent>> {
+        self.event_rx.take()
+    }
+
+    /// Connect and start syncing.
+    ///
+    /// The first attempt's error is returned; after that the client
+    /// reconnects on its own per the [`ReconnectConfig`].
+    pub async fn connect(&mut self) -> Result<(), ProtocolError> {
+        if self.driver.is_some() {
+            return Ok(());
+        }
+
+        let mut client = SyncClient::new(self.peer_info.clone(), self.doc_id, self.server_url.clone())
+            .with_reconnect(self.reconnect.clone());
+        if let Some(ref token) = self.auth_token {
+            client = client.with_auth_token(token.clone());
+        }
+        let client_events = client.take_event_rx().ok_or(ProtocolError::ConnectionClosed)?;
+
+        // Observe before reading the state vector so no commit is missed
+        let (local_tx, local_rx) = mpsc::unbounded_channel();
+        self.doc
+            .observe_update_v1_with(self.observer_key(), move |txn, event| {
+                if txn.origin() != Some(&Origin::from(REMOTE_ORIGIN)) {
+                    let _ = local_tx.send(event.update.clone());
+                }
+            })
+            .map_err(|e| {
+                log::warn!("Cannot observe doc {}: {e}", self.doc_id);
+                ProtocolError::ConnectionClosed
+            })?;
+
+        let sv = self.doc.transact().state_vector().encode_v1();
+        client.set_state_vector(sv).await;
+        if let Err(e) = client.connect().await {
+            let _ = self.doc.unobserve_update_v1(self.observer_key());
+            return Err(e);
+        }
+
+        let (command_tx, command_rx) = mpsc::channel(64);
+        self.command_tx = Some(command_tx);
+        let driver = Driver {
+            client,
+            client_events,
+            local_rx,
+            command_rx,
+            doc: self.doc.clone(),
+            events: self.event_tx.clone(),
+        };
+        self.driver = Some(tokio::spawn(driver.run()));
+        Ok(())
+    }
+
+    /// Send a presence message (cursor, selection) to the room.
+    ///
+    /// Dropped while disconnected, like [`SyncClient::send_presence`].
+    pub async fn send_presence(&self, msg: AwarenessMessage) -> Result<(), ProtocolError> {
+        match self.command_tx {
+            Some(ref tx) => tx
+                .send(Command::Presence(msg))
+                .await
+                .map_err(|_| ProtocolError::ConnectionClosed),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a version history request (see [`SyncClient::request_history`]).
+    ///
+    /// A restore comes back as a remote change to the document; a request
+    /// that cannot be sent is answered with `HistoryMessage::Failed`.
+    pub async fn request_history(&self, request: HistoryMessage) -> Result<(), ProtocolError> {
+        match self.command_tx {
+            Some(ref tx) => tx
+                .send(Command::History(request))
+                .await
+                .map_err(|_| ProtocolError::ConnectionClosed),
+            None => Err(ProtocolError::ConnectionClosed),
+        }
+    }
+
+    fn observer_key(&self) -> Origin {
+        Origin::from(self.peer_info.peer_id.as_bytes().as_slice())
+    }
+}
+
+impl Drop for CollaborationEngine {
+    fn drop(&mut self) {
+        if let Some(driver) = self.driver.take() {
+            driver.abort();
+            let _ = self.doc.unobserve_update_v1(self.observer_key());
+        }
+    }
+}
+
+/// Background task owning the [`SyncClient`].
+struct Driver {
+    client: SyncClient,
+    client_events: mpsc::Receiver<SyncEvent>,
+    local_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    command_rx: mpsc::Receiver<Command>,
+    doc: yrs::Doc,
+    events: mpsc::Sender<EngineEvent>,
+}
+
+impl Driver {
+    async fn run(mut self) {
+        // Local updates are only sent once Connected is seen; anything
+        // committed before that is covered by the handshake's SyncStep2.
+        let mut connected = false;
+        loop {
+            tokio::select! {
+                event = self.client_events.recv() => {
+                    let Some(event) = event else { break };
+                    match event {
+                        SyncEvent::Connected => {
+                            connected = true;
+                            self.emit(EngineEvent::Connected).await;
+                        }
+                        SyncEvent::Reconnecting { attempt, delay } => {
+                            connected = false;
+                            // Keep the next SyncStep1 current so the server
+                            // sends only what is still missing
+                            let sv = self.doc.transact().state_vector().encode_v1();
+                            self.client.set_state_vector(sv).await;
+                            self.emit(EngineEvent::Reconnecting { attempt, delay }).await;
+                        }
+                        SyncEvent::Disconnected => {
+                            connected = false;
+                            self.emit(EngineEvent::Disconnected).await;
+                        }
+                        SyncEvent::RemoteDelta { peer_id, update, .. } => {
+                            self.apply_remote(ChangeOrigin::Remote(peer_id), update).await;
+                        }
+                        SyncEvent::StateSynced(update) => {
+                            self.apply_remote(ChangeOrigin::Sync, update).await;
+                            self.emit(EngineEvent::Synced).await;
+                        }
+                        SyncEvent::SyncRequested(sv) => {
+                            // Everything the server lacks, offline edits included
+                            let diff = match yrs::StateVector::decode_v1(&sv) {
+                                Ok(sv) => self.doc.transact().encode_diff_v1(&sv),
+                                Err(e) => {
+                                    log::warn!("Undecodable server state vector: {e}");
+                                    continue;
+                                }
+                            };
+                            let _ = self.client.send_sync_step2(diff).await;
+                        }
+                        SyncEvent::PeerJoined(info) => self.emit(EngineEvent::PeerJoined(info)).await,
+                        SyncEvent::PeerLeft(peer_id) => self.emit(EngineEvent::PeerLeft(peer_id)).await,
+                        SyncEvent::PresenceUpdate { peer_id, message } => {
+                            self.emit(EngineEvent::Presence { peer_id, message }).await;
+                        }
+                        SyncEvent::RemoteAwareness { peer_id, state } => {
+                            self.emit(EngineEvent::Awareness { peer_id, state }).await;
+                        }
+                        SyncEvent::Rejected(rejection) => {
+                            self.emit(EngineEvent::Rejected(rejection)).await;
+                        }
+                        SyncEvent::History(reply) => self.emit(EngineEvent::History(reply)).await,
+                        SyncEvent::Negotiated(_) => {}
+                    }
+                }
+                Some(update) = self.local_rx.recv() => {
+                    if connected {
+                        let _ = self.client.send_delta(update.clone()).await;
+                    }
+                    self.emit(EngineEvent::Changed(DocChange { origin: ChangeOrigin::Local, update })).await;
+                }
+                Some(command) = self.command_rx.recv() => match command {
+                    Command::Presence(msg) => {
+                        let _ = self.client.send_presence(&msg).await;
+                    }
+                    Command::History(request) => {
+                        if let Err(e) = self.client.request_history(&request).await {
+                            self.emit(EngineEvent::History(HistoryMessage::Failed(e.to_string()))).await;
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    async fn apply_remote(&self, origin: ChangeOrigin, update: Vec<u8>) {
+        if self.apply(origin, &update) {
+            self.emit(EngineEvent::Changed(DocChange { origin, update })).await;
+        }
+    }
+
+    /// Apply `update` under [`REMOTE_ORIGIN`]; false if it was empty or bad.
+    fn apply(&self, origin: ChangeOrigin, update: &[u8]) -> bool {
+        let decoded = match yrs::Update::decode_v1(update) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                log::warn!("Undecodable update from {origin:?}: {e}");
+                return false;
+            }
+        };
+        if decoded.is_empty() {
+            return false;
+        }
+        let mut txn = self.doc.transact_mut_with(REMOTE_ORIGIN);
+        match txn.apply_update(decoded) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Failed to apply update from {origin:?}: {e}");
+                false
+            }
+        }
+    }
+
+    async fn emit(&self, event: EngineEvent) {
+        let _ = self.events.send(event).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yrs::{GetString, Text, WriteTxn};
+
+    #[test]
+    fn test_engine_creation() {
+        let info = PeerInfo::new("Alice");
+        let doc_id = Uuid::new_v4();
+        let mut engine = CollaborationEngine::new(info.clone(), doc_id, "ws://localhost:9090");
+        assert_eq!(engine.peer_info().peer_id, info.peer_id);
+        assert_eq!(engine.doc_id(), doc_id);
+        assert!(engine.take_event_rx().is_some());
+        assert!(engine.take_event_rx().is_none());
+    }
+
+    #[test]
+    fn test_with_doc_keeps_content() {
+        let doc = yrs::Doc::new();
+        {
+            let mut txn = doc.transact_mut();
+            txn.get_or_insert_text("content").insert(&mut txn, 0, "restored");
+        }
+        let engine = CollaborationEngine::new(PeerInfo::new("Bob"), Uuid::new_v4(), "ws://localhost:9090")
+            .with_doc(doc);
+        let txn = engine.doc().transact();
+        assert_eq!(txn.get_text("content").unwrap().get_string(&txn), "restored");
+    }
+
+    #[tokio::test]
+    async fn test_connect_failure_is_reported() {
+        let mut engine = CollaborationEngine::new(PeerInfo::new("Carol"), Uuid::new_v4(), "ws://127.0.0.1:1");
+        assert!(engine.connect().await.is_err());
+        // A failed first attempt leaves no driver behind and may be retried
+        assert!(engine.driver.is_none());
+        assert!(engine.connect().await.is_err());
+    }
+}
@@ -0,0 +1,535 @@
+//! Version history and point-in-time restore.
+//!
+//! The store records every delta with its author and time in a history
+//! that snapshot compaction leaves alone ([`DocumentStore::list_versions`]);
+//! only the retention policy squashes old versions together
+//! ([`DocumentStore::prune_history`]).
+//! This module rebuilds a document as it was at a version and computes the
+//! change that brings the live document back to it:
+//!
+//! ```text
+//! history ≤ V ──► scratch doc ──► history > V (tracked by an UndoManager)
+//!                                      │ undo everything
+//!                                      ▼
+//!                      forward update = what the undo wrote
+//! ```
+//!
+//! A restore is an ordinary forward change: it deletes what was added
+//! after V and re-inserts what was removed, so no version is rewritten and
+//! peers apply it like any other delta. Content older than the recorded
+//! history is never touched.
+//!
+//! Named checkpoints ([`DocumentStore::create_checkpoint`]) mark versions
+//! worth keeping. [`fork`] copies a document at a checkpoint into a new
+//! document that remembers its origin, and [`merge`] brings the branch back
+//! with an ordinary CRDT merge, reporting the layers both sides changed:
+//!
+//! ```text
+//! main   ──●───────●────────●──── merge ──►
+//!      "v2 handoff" \                ▲
+//! branch             ●──●──●─────────┘
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use yrs::undo::{Options as UndoOptions, UndoManager};
+use yrs::updates::decoder::Decode;
+use yrs::{Doc, Map, Origin, ReadTxn, Transact, WriteTxn};
+
+use crate::storage::{Checkpoint, DocumentStore, ForkOrigin, StoreError, VersionInfo};
+
+/// Origin of the replayed changes a restore undoes.
+const REPLAY_ORIGIN: &str = "logos-collab/history";
+
+/// Name of the Yrs root map holding the document's layers.
+const LAYERS_ROOT: &str = "layers";
+
+/// A point in a document's history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VersionRef {
+    /// A version as listed by [`DocumentStore::list_versions`], or the
+    /// latest one stored before it if it was pruned or never recorded
+    Version(u64),
+    /// The latest version stored at or before this time (ms since epoch)
+    At(u64),
+}
+
+impl VersionRef {
+    /// Resolve to a version of `doc_id` that is in its history.
+    pub fn resolve(self, store: &DocumentStore, doc_id: Uuid) -> Result<u64, StoreError> {
+        match self {
+            Self::Version(version) => store
+                .list_versions(doc_id)?
+                .iter()
+                .take_while(|info| info.version <= version)
+                .last()
+                .map(|info| info.version)
+                .ok_or(StoreError::VersionNotFound { doc_id, version }),
+            Self::At(timestamp_ms) => store
+                .version_at(doc_id, timestamp_ms)?
+                .ok_or(StoreError::VersionNotFound { doc_id, version: 0 }),
+        }
+    }
+}
+
+/// History requests and replies, carried by `MessageType::History`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryMessage {
+    /// Request the document's version list
+    ListVersions,
+    /// Reply to `ListVersions`, oldest first
+    Versions(Vec<VersionInfo>),
+    /// Request a read-only copy of the document at a version
+    GetSnapshot(VersionRef),
+    /// Reply to `GetSnapshot`: the full Yrs state at `version`
+    Snapshot { version: u64, state: Vec<u8> },
+    /// Restore the document to a version as a new change (editors only)
+    Restore(VersionRef),
+    /// Reply to `Restore`; the change itself arrives as a delta
+    Restored { version: u64 },
+    /// Name the latest version (editors only)
+    CreateCheckpoint(String),
+    /// Reply to `CreateCheckpoint`
+    CheckpointCreated(Checkpoint),
+    /// Request the document's checkpoints
+    ListCheckpoints,
+    /// Reply to `ListCheckpoints`, in creation order
+    Checkpoints(Vec<Checkpoint>),
+    /// Fork the document at a named checkpoint into a new document
+    Fork(String),
+    /// Reply to `Fork`: join `doc_id` to edit the branch
+    Forked { doc_id: Uuid, origin: ForkOrigin },
+    /// Merge a branch of this document back into it (editors only)
+    MergeBranch(Uuid),
+    /// Reply to `MergeBranch`; the merged changes arrive as a delta
+    Merged(MergeReport),
+    /// The request could not be served
+    Failed(String),
+}
+
+impl HistoryMessage {
+    /// Whether serving this request changes a document, which takes edit
+    /// rights on it.
+    pub fn is_write(&self) -> bool {
+        matches!(self, Self::Restore(_) | Self::CreateCheckpoint(_) | Self::MergeBranch(_))
+    }
+}
+
+/// What a branch merge touched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// The merged branch
+    pub branch: Uuid,
+    /// The document it was forked from and merged into
+    pub target: Uuid,
+    /// Fork point in the target's history
+    pub base_version: u64,
+    /// Layer ids the branch changed since the fork
+    pub branch_layers: Vec<String>,
+    /// Layer ids the target changed since the fork
+    pub target_layers: Vec<String>,
+    /// Layer ids changed on both sides. The merge keeps one of the
+    /// concurrent values of each, so these want a look.
+    pub conflicts: Vec<String>,
+}
+
+/// A computed branch merge, ready to apply to the target.
+#[derive(Debug, Clone)]
+pub struct BranchMerge {
+    /// The branch's changes the target lacks, `None` if it has them all
+    pub update: Option<Vec<u8>>,
+    pub report: MergeReport,
+}
+
+/// Full Yrs state of `doc_id` as it was at `version`.
+pub fn materialize(store: &DocumentStore, doc_id: Uuid, version: u64) -> Result<Vec<u8>, StoreError> {
+    let history = store.load_history(doc_id, version)?;
+    if history.is_empty() {
+        return Err(StoreError::VersionNotFound { doc_id, version });
+    }
+    let doc = Doc::new();
+    replay(&doc, doc_id, &history, None);
+    let txn = doc.transact();
+    Ok(txn.encode_state_as_update_v1(&yrs::StateVector::default()))
+}
+
+/// The update that restores `doc_id` to its state at `version`, or `None`
+/// if nothing was recorded after it.
+///
+/// Applied to the live document it undoes every later version while
+/// keeping them in the history.
+pub fn restore_update(
+    store: &DocumentStore,
+    doc_id: Uuid,
+    version: u64,
+) -> Result<Option<Vec<u8>>, StoreError> {
+    let history = store.load_history(doc_id, u64::MAX)?;
+    let split = history.partition_point(|(info, _)| info.version <= version);
+    if split == 0 {
+        return Err(StoreError::VersionNotFound { doc_id, version });
+    }
+    if split == history.len() {
+        return Ok(None);
+    }
+
+    // Undo needs every root in scope before the tracked changes arrive
+    let probe = Doc::new();
+    replay(&probe, doc_id, &history, None);
+    let roots: Vec<String> = probe.transact().root_refs().map(|(name, _)| name.to_string()).collect();
+
+    // Keep deleted content around so the undo can bring it back
+    let scratch = Doc::with_options(yrs::Options {
+        skip_gc: true,
+        ..yrs::Options::default()
+    });
+    let branches: Vec<_> = {
+        let mut txn = scratch.transact_mut();
+        roots.iter().map(|name| txn.get_or_insert_map(name.as_str())).collect()
+    };
+    replay(&scratch, doc_id, &history[..split], None);
+
+    let mut undo: UndoManager<()> = UndoManager::with_options(
+        &scratch,
+        UndoOptions {
+            capture_timeout_millis: 0,
+            ..UndoOptions::default()
+        },
+    );
+    for branch in &branches {
+        undo.expand_scope(branch);
+    }
+    undo.include_origin(Origin::from(REPLAY_ORIGIN));
+    replay(&scratch, doc_id, &history[split..], Some(REPLAY_ORIGIN));
+
+    let before = scratch.transact().state_vector();
+    while undo.undo_blocking() {}
+    let txn = scratch.transact();
+    Ok(Some(txn.encode_state_as_update_v1(&before)))
+}
+
+/// Fork `doc_id` at its checkpoint `name` into the new document
+/// `branch_id`. The branch starts with a single version holding the
+/// checkpoint's state, by `author`, and records where it came from.
+pub fn fork(
+    store: &DocumentStore,
+    doc_id: Uuid,
+    name: &str,
+    branch_id: Uuid,
+    author: Option<Uuid>,
+) -> Result<ForkOrigin, StoreError> {
+    if store.document_exists(branch_id)? {
+        return Err(StoreError::DocumentExists(branch_id));
+    }
+    let checkpoint = store.checkpoint(doc_id, name)?;
+    let state = materialize(store, doc_id, checkpoint.version)?;
+    let origin = ForkOrigin {
+        doc_id,
+        checkpoint: checkpoint.name,
+        version: checkpoint.version,
+    };
+    store.set_fork_origin(branch_id, origin.clone())?;
+    store.store_delta_by(branch_id, store.allocate_version(), author, &state)?;
+    Ok(origin)
+}
+
+/// Compute the merge of branch `branch_id` back into the document it was
+/// forked from.
+///
+/// The update is the branch's changes the target lacks; applying it is an
+/// ordinary CRDT merge, so nothing on either side is lost and concurrent
+/// edits to the same layer resolve as they would between live peers. The
+/// report lists the layers each side changed since the fork, compared
+/// entry by entry in the layers map.
+pub fn merge(store: &DocumentStore, branch_id: Uuid) -> Result<BranchMerge, StoreError> {
+    let origin = store
+        .load_metadata(branch_id)?
+        .forked_from
+        .ok_or(StoreError::NotABranch(branch_id))?;
+
+    let base = Doc::new();
+    apply(&base, origin.doc_id, origin.version, &materialize(store, origin.doc_id, origin.version)?, None);
+    let target = current(store, origin.doc_id)?;
+    let branch = current(store, branch_id)?;
+
+    let branch_layers = touched_layers(&base, &branch);
+    let target_layers = touched_layers(&base, &target);
+    let conflicts = branch_layers.intersection(&target_layers).cloned().collect();
+
+    // A diff always carries the branch's whole delete set, so whether it
+    // brings anything new shows only by applying it
+    let before = target.transact().snapshot();
+    let update = branch.transact().encode_diff_v1(&before.state_map);
+    apply(&target, origin.doc_id, 0, &update, None);
+    let update = (target.transact().snapshot() != before).then_some(update);
+
+    Ok(BranchMerge {
+        update,
+        report: MergeReport {
+            branch: branch_id,
+            target: origin.doc_id,
+            base_version: origin.version,
+            branch_layers: branch_layers.into_iter().collect(),
+            target_layers: target_layers.into_iter().collect(),
+            conflicts,
+        },
+    })
+}
+
+/// The stored state of `doc_id`: its snapshot, then the deltas after it.
+fn current(store: &DocumentStore, doc_id: Uuid) -> Result<Doc, StoreError> {
+    let doc = Doc::new();
+    match store.load_snapshot(doc_id) {
+        Ok(snapshot) => apply(&doc, doc_id, 0, &snapshot, None),
+        Err(StoreError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    for (version, delta) in store.load_all_deltas(doc_id)? {
+        apply(&doc, doc_id, version, &delta, None);
+    }
+    Ok(doc)
+}
+
+/// Keys of the layers map whose entries differ between `base` and `side`.
+fn touched_layers(base: &Doc, side: &Doc) -> BTreeSet<String> {
+    let before = layer_entries(base);
+    let after = layer_entries(side);
+    before
+        .keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect()
+}
+
+fn layer_entries(doc: &Doc) -> BTreeMap<String, String> {
+    let txn = doc.transact();
+    let Some(layers) = txn.get_map(LAYERS_ROOT) else {
+        return BTreeMap::new();
+    };
+    layers
+        .iter(&txn)
+        .map(|(key, value)| (key.to_string(), value.to_string(&txn)))
+        .collect()
+}
+
+/// Apply history entries to `doc` in order, one transaction each.
+fn replay(doc: &Doc, doc_id: Uuid, history: &[(VersionInfo, Vec<u8>)], origin: Option<&str>) {
+    for (info, delta) in history {
+        apply(doc, doc_id, info.version, delta, origin);
+    }
+}
+
+/// Apply one stored update, logging and skipping it if it is corrupt.
+fn apply(doc: &Doc, doc_id: Uuid, version: u64, delta: &[u8], origin: Option<&str>) {
+    let update = match yrs::Update::decode_v1(delta) {
+        Ok(update) => update,
+        Err(e) => {
+            log::warn!("Skipping undecodable version {version} of doc {doc_id}: {e}");
+            return;
+        }
+    };
+    let mut txn = match origin {
+        Some(origin) => doc.transact_mut_with(origin),
+        None => doc.transact_mut(),
+    };
+    if let Err(e) = txn.apply_update(update) {
+        log::warn!("Skipping version {version} of doc {doc_id}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StoreConfig;
+    use yrs::{GetString, Map, Text};
+
+    fn open_store(name: &str) -> (DocumentStore, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("logos_test_history_{name}_{}", Uuid::new_v4()));
+        (DocumentStore::open(StoreConfig::for_testing(&path)).unwrap(), path)
+    }
+
+    /// Run `edit` on `doc` and store the resulting update as `version`.
+    fn commit(store: &DocumentStore, doc_id: Uuid, doc: &Doc, version: u64, edit: impl FnOnce(&mut yrs::TransactionMut)) {
+        let before = doc.transact().state_vector();
+        edit(&mut doc.transact_mut());
+        let update = doc.transact().encode_state_as_update_v1(&before);
+        store.store_delta_by(doc_id, version, Some(Uuid::nil()), &update).unwrap();
+    }
+
+    fn load(state: &[u8]) -> Doc {
+        let doc = Doc::new();
+        doc.transact_mut().apply_update(yrs::Update::decode_v1(state).unwrap()).unwrap();
+        doc
+    }
+
+    fn text(doc: &Doc) -> String {
+        let txn = doc.transact();
+        txn.get_text("content").map(|t| t.get_string(&txn)).unwrap_or_default()
+    }
+
+    /// v1 "hello", v2 layer a, v3 " world", v4 delete "hello", v5 new root
+    fn edited_doc(store: &DocumentStore, doc_id: Uuid) -> Doc {
+        let doc = Doc::new();
+        commit(store, doc_id, &doc, 1, |txn| txn.get_or_insert_text("content").insert(txn, 0, "hello"));
+        commit(store, doc_id, &doc, 2, |txn| {
+            txn.get_or_insert_map("layers").insert(txn, "a", 1);
+        });
+        commit(store, doc_id, &doc, 3, |txn| txn.get_or_insert_text("content").insert(txn, 5, " world"));
+        commit(store, doc_id, &doc, 4, |txn| {
+            txn.get_or_insert_text("content").remove_range(txn, 0, 5);
+            txn.get_or_insert_map("layers").insert(txn, "b", 2);
+        });
+        commit(store, doc_id, &doc, 5, |txn| {
+            txn.get_or_insert_map("extra").insert(txn, "x", true);
+        });
+        doc
+    }
+
+    #[test]
+    fn test_materialize_at_each_version() {
+        let (store, path) = open_store("materialize");
+        let doc_id = Uuid::new_v4();
+        edited_doc(&store, doc_id);
+
+        assert_eq!(text(&load(&materialize(&store, doc_id, 1).unwrap())), "hello");
+        assert_eq!(text(&load(&materialize(&store, doc_id, 3).unwrap())), "hello world");
+        assert_eq!(text(&load(&materialize(&store, doc_id, 4).unwrap())), " world");
+        assert!(matches!(
+            materialize(&store, doc_id, 0),
+            Err(StoreError::VersionNotFound { version: 0, .. })
+        ));
+
+        // Compaction drops deltas but not history
+        store.compact_deltas(doc_id, 5).unwrap();
+        assert_eq!(text(&load(&materialize(&store, doc_id, 3).unwrap())), "hello world");
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_restore_is_a_forward_change() {
+        let (store, path) = open_store("restore");
+        let doc_id = Uuid::new_v4();
+        let live = edited_doc(&store, doc_id);
+
+        let update = restore_update(&store, doc_id, 3).unwrap().unwrap();
+        live.transact_mut().apply_update(yrs::Update::decode_v1(&update).unwrap()).unwrap();
+
+        let txn = live.transact();
+        assert_eq!(txn.get_text("content").unwrap().get_string(&txn), "hello world");
+        let layers = txn.get_map("layers").unwrap();
+        assert_eq!(layers.len(&txn), 1);
+        assert!(layers.get(&txn, "a").is_some());
+        assert_eq!(txn.get_map("extra").unwrap().len(&txn), 0);
+        drop(txn);
+
+        // Restoring the latest version is a no-op; unknown versions fail
+        assert!(restore_update(&store, doc_id, 5).unwrap().is_none());
+        assert!(restore_update(&store, Uuid::new_v4(), 1).is_err());
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_fork_and_merge_report_layers_both_touched() {
+        let (store, path) = open_store("fork");
+        let main_id = Uuid::new_v4();
+        let main = Doc::new();
+        commit(&store, main_id, &main, 1, |txn| {
+            let layers = txn.get_or_insert_map("layers");
+            for id in ["a", "b", "c"] {
+                layers.insert(txn, id, format!("{{{id}}}"));
+            }
+        });
+        store.create_checkpoint(main_id, "v1", 1, None).unwrap();
+
+        let branch_id = Uuid::new_v4();
+        let origin = fork(&store, main_id, "v1", branch_id, None).unwrap();
+        assert_eq!((origin.doc_id, origin.version), (main_id, 1));
+        assert!(matches!(
+            fork(&store, main_id, "v1", branch_id, None),
+            Err(StoreError::DocumentExists(_))
+        ));
+
+        // Both sides move on: the branch edits a and adds d, main edits a
+        // and removes c
+        let branch = current(&store, branch_id).unwrap();
+        commit(&store, branch_id, &branch, 10, |txn| {
+            let layers = txn.get_or_insert_map("layers");
+            layers.insert(txn, "a", "{a: branch}");
+            layers.insert(txn, "d", "{d}");
+        });
+        commit(&store, main_id, &main, 11, |txn| {
+            let layers = txn.get_or_insert_map("layers");
+            layers.insert(txn, "a", "{a: main}");
+            layers.remove(txn, "c");
+        });
+
+        let merged = merge(&store, branch_id).unwrap();
+        assert_eq!(merged.report.target, main_id);
+        assert_eq!(merged.report.branch_layers, vec!["a", "d"]);
+        assert_eq!(merged.report.target_layers, vec!["a", "c"]);
+        assert_eq!(merged.report.conflicts, vec!["a"]);
+
+        let update = merged.update.unwrap();
+        main.transact_mut().apply_update(yrs::Update::decode_v1(&update).unwrap()).unwrap();
+        let entries = layer_entries(&main);
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["a", "b", "d"]);
+
+        // Once merged, there is nothing left to bring over
+        store.store_delta(main_id, 12, &update).unwrap();
+        assert!(merge(&store, branch_id).unwrap().update.is_none());
+        assert!(matches!(merge(&store, main_id), Err(StoreError::NotABranch(_))));
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_version_ref_resolves_timestamps() {
+        let (store, path) = open_store("resolve");
+        let doc_id = Uuid::new_v4();
+        edited_doc(&store, doc_id);
+
+        let versions = store.list_versions(doc_id).unwrap();
+        assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert!(versions.iter().all(|v| v.author == Some(Uuid::nil())));
+
+        let last = versions.last().unwrap().timestamp_ms;
+        assert_eq!(VersionRef::At(last).resolve(&store, doc_id).unwrap(), 5);
+        assert_eq!(VersionRef::Version(2).resolve(&store, doc_id).unwrap(), 2);
+        assert!(VersionRef::At(0).resolve(&store, doc_id).is_err());
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_version_ref_resolves_to_stored_versions() {
+        let (store, path) = open_store("resolve-versions");
+        let doc_id = Uuid::new_v4();
+        let doc = Doc::new();
+        commit(&store, doc_id, &doc, 2, |txn| txn.get_or_insert_text("content").insert(txn, 0, "a"));
+        commit(&store, doc_id, &doc, 5, |txn| txn.get_or_insert_text("content").insert(txn, 1, "b"));
+
+        // Gaps and versions past the head fall back to the one before
+        assert_eq!(VersionRef::Version(2).resolve(&store, doc_id).unwrap(), 2);
+        assert_eq!(VersionRef::Version(4).resolve(&store, doc_id).unwrap(), 2);
+        assert_eq!(VersionRef::Version(99).resolve(&store, doc_id).unwrap(), 5);
+        assert!(matches!(
+            VersionRef::Version(1).resolve(&store, doc_id),
+            Err(StoreError::VersionNotFound { version: 1, .. })
+        ));
+        assert!(matches!(
+            VersionRef::Version(3).resolve(&store, Uuid::new_v4()),
+            Err(StoreError::VersionNotFound { version: 3, .. })
+        ));
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
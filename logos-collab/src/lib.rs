@@ -24,10 +24,17 @@
 //!
 //! ## Modules
 //!
-//! - [`protocol`] — Binary wire protocol (bincode-encoded SyncMessage)
+//! - [`protocol`] — Binary wire protocol (bincode-encoded SyncMessage), version negotiation
 //! - [`broadcast`] — Room-based fan-out with backpressure
 //! - [`server`] — WebSocket sync server
 //! - [`client`] — WebSocket sync client with offline queue
+//! - [`engine`] — Client that owns its Yrs document and syncs it itself
+//! - [`auth`] — Join authentication (signed bearer tokens)
+//! - [`permissions`] — Per-document roles enforced on writes
+//! - [`history`] — Version browsing, point-in-time restore, checkpoints and branches
+//! - [`admin`] — HTTP admin endpoint: Prometheus metrics, room listing, evictions
+//! - [`ratelimit`] — Per-peer token buckets for deltas, awareness and history
+//! - [`cluster`] — Relaying rooms between server nodes, storage leases
 //!
 //! ## Performance Targets
 //!
@@ -42,22 +49,41 @@ pub mod protocol;
 pub mod broadcast;
 pub mod server;
 pub mod client;
+pub mod engine;
 pub mod presence;
 pub mod storage;
+pub mod auth;
+pub mod permissions;
+pub mod history;
+pub mod admin;
+pub mod ratelimit;
+pub mod cluster;
 
 // Re-exports for convenience
 pub use protocol::{
-    AwarenessState, MessageType, PeerInfo, ProtocolError, SyncMessage,
+    AwarenessState, Capabilities, Hello, MessageType, PeerInfo, ProtocolError, Rejection, SyncMessage,
+    PROTOCOL_VERSION,
 };
-pub use broadcast::{BroadcastGroup, BroadcastStats, RoomManager};
+pub use auth::{AuthClaims, AuthError, Authenticator, HmacAuthenticator};
+pub use permissions::{Permissions, Role};
+pub use history::{HistoryMessage, MergeReport, VersionRef};
+pub use broadcast::{BroadcastGroup, BroadcastStats, Frame, RoomManager};
 pub use presence::{
     AwarenessMessage, CursorColor, CursorInstance, CursorRenderData,
     PresenceRoom, RemoteCursorState, Vec2, build_cursor_instances,
 };
-pub use server::{ServerConfig, ServerStats, SyncServer};
-pub use client::{ConnectionState, OfflineQueue, SyncClient, SyncEvent};
+pub use server::{RecoveryValidation, RoomInfo, ServerConfig, ServerStats, SyncServer};
+pub use admin::AdminServer;
+pub use ratelimit::{RateLimit, RateLimits};
+pub use cluster::{
+    ClusterConfig, LoopbackHub, LoopbackRelay, MemoryLeases, RelayEnvelope, RoomRelay, StorageLease, TcpMeshRelay,
+};
+pub use client::{ConnectionState, OfflineQueue, ReconnectConfig, SyncClient, SyncEvent};
+pub use engine::{ChangeOrigin, CollaborationEngine, DocChange, EngineEvent};
 pub use storage::{
-    DocumentStore, StoreConfig, StoreError, DocumentMetadata,
+    DocumentStore, StoreConfig, StoreError, DocumentMetadata, VersionInfo, Checkpoint, ForkOrigin,
+    StorageBackend, BackendKind, RocksBackend, SqliteBackend, MemoryBackend,
+    Archive, ArchiveError, IdPolicy, ImportedDocument,
     DeltaLog, CompressedDelta, DeltaStats,
     WriteAheadLog, WalEntry, WalConfig, WalError,
 };
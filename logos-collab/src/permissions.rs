@@ -0,0 +1,353 @@
+//! Per-document roles and write checks for the sync server.
+//!
+//! ```text
+//! Role       Delta/SyncStep2  Comments   SyncStep1   Awareness   History
+//! ───────────────────────────────────────────────────────────────────────────
+//! Owner      ✓                ✓          ✓           ✓           ✓
+//! Editor     ✓                ✓          ✓           ✓           ✓
+//! Commenter  comments only    ✓          ✓           ✓           browse only
+//! Viewer     ✗                ✗          ✓           ✓           browse only
+//! ```
+//!
+//! A client's SyncStep2 carries the updates it made offline, so it is
+//! checked exactly like a delta. Restoring a version, naming a checkpoint
+//! and merging a branch change the document, so they need edit rights;
+//! listing, fetching snapshots and forking only read it (whoever forks
+//! owns the new branch).
+//!
+//! Roles are looked up on every write, so [`Permissions::set_role`] takes
+//! effect on live connections without a rejoin. Users without a grant get
+//! the server's default role.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::{BranchID, Out, ReadTxn, Transact, WriteTxn};
+
+/// Name of the Yrs root map holding document comments.
+pub const COMMENTS_ROOT: &str = "comments";
+
+/// Access level of a user on one document, ordered from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Role {
+    /// Read-only sync plus awareness
+    Viewer,
+    /// May change the comments root only
+    Commenter,
+    /// May change anything
+    Editor,
+    /// Editor rights; the document's owner
+    Owner,
+}
+
+impl Role {
+    /// Whether this role may apply arbitrary deltas.
+    pub fn can_edit(self) -> bool {
+        self >= Role::Editor
+    }
+
+    /// Whether this role may change comments.
+    pub fn can_comment(self) -> bool {
+        self >= Role::Commenter
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Viewer => write!(f, "viewer"),
+            Self::Commenter => write!(f, "commenter"),
+            Self::Editor => write!(f, "editor"),
+            Self::Owner => write!(f, "owner"),
+        }
+    }
+}
+
+/// Role grants per document: doc_id → user_id → role.
+#[derive(Debug)]
+pub struct Permissions {
+    grants: RwLock<HashMap<Uuid, HashMap<Uuid, Role>>>,
+    default_role: Role,
+}
+
+impl Permissions {
+    /// `default_role` applies to users without an explicit grant.
+    pub fn new(default_role: Role) -> Self {
+        Self {
+            grants: RwLock::new(HashMap::new()),
+            default_role,
+        }
+    }
+
+    /// Effective role of `user_id` on `doc_id`.
+    pub fn role(&self, doc_id: Uuid, user_id: Uuid) -> Role {
+        self.grants
+            .read()
+            .ok()
+            .and_then(|g| g.get(&doc_id)?.get(&user_id).copied())
+            .unwrap_or(self.default_role)
+    }
+
+    /// Grant `role` to `user_id` on `doc_id`, returning the previous grant.
+    pub fn set_role(&self, doc_id: Uuid, user_id: Uuid, role: Role) -> Option<Role> {
+        let mut grants = self.grants.write().unwrap_or_else(|e| e.into_inner());
+        grants.entry(doc_id).or_default().insert(user_id, role)
+    }
+
+    /// Remove an explicit grant; the user falls back to the default role.
+    pub fn revoke(&self, doc_id: Uuid, user_id: Uuid) -> Option<Role> {
+        let mut grants = self.grants.write().unwrap_or_else(|e| e.into_inner());
+        let doc = grants.get_mut(&doc_id)?;
+        let previous = doc.remove(&user_id);
+        if doc.is_empty() {
+            grants.remove(&doc_id);
+        }
+        previous
+    }
+
+    /// Explicit grants on a document.
+    pub fn grants(&self, doc_id: Uuid) -> HashMap<Uuid, Role> {
+        self.grants
+            .read()
+            .ok()
+            .and_then(|g| g.get(&doc_id).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn default_role(&self) -> Role {
+        self.default_role
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::new(Role::Editor)
+    }
+}
+
+/// Whether a peer with `role` may apply `update` to `doc`.
+pub fn may_apply(role: Role, doc: &yrs::Doc, update: &[u8]) -> bool {
+    role.can_edit() || (role.can_comment() && is_comment_only(doc, update))
+}
+
+/// Whether `update` only changes the [`COMMENTS_ROOT`] map of `doc`, or
+/// types nested inside it.
+///
+/// The update is dry-run against a scratch copy of `doc`, so the check costs
+/// a full state copy; it is only used for commenter writes. Updates that do
+/// not decode, or that create new root types, are not comment-only.
+pub fn is_comment_only(doc: &yrs::Doc, update: &[u8]) -> bool {
+    let Ok(update) = yrs::Update::decode_v1(update) else {
+        return false;
+    };
+    let state = doc.transact().encode_state_as_update_v1(&yrs::StateVector::default());
+    let Ok(state) = yrs::Update::decode_v1(&state) else {
+        return false;
+    };
+
+    let scratch = yrs::Doc::new();
+    let mut txn = scratch.transact_mut();
+    if txn.apply_update(state).is_err() {
+        return false;
+    }
+    // Roots restored from an update have no type yet and would not report
+    // changes; typing them as maps makes every change visible below.
+    let roots: Vec<String> = txn.root_refs().map(|(name, _)| name.to_string()).collect();
+    let untyped: Vec<String> = txn
+        .root_refs()
+        .filter(|(_, value)| matches!(value, Out::UndefinedRef(_)))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    for name in untyped {
+        txn.get_or_insert_map(name);
+    }
+    txn.commit();
+    drop(txn);
+
+    // Deep observers on the other roots fire for changes to the roots
+    // themselves and to any type nested below them, however deep.
+    let touched = Arc::new(AtomicBool::new(false));
+    let _subscriptions: Vec<_> = {
+        let txn = scratch.transact();
+        roots
+            .iter()
+            .filter(|name| *name != COMMENTS_ROOT)
+            .filter_map(|name| BranchID::get_root(&txn, name.as_str()))
+            .map(|branch| {
+                let touched = touched.clone();
+                branch.observe_deep(move |_, _| touched.store(true, Ordering::Relaxed))
+            })
+            .collect()
+    };
+
+    let mut txn = scratch.transact_mut();
+    if txn.apply_update(update).is_err() {
+        return false;
+    }
+    let new_root = txn
+        .root_refs()
+        .any(|(name, _)| name != COMMENTS_ROOT && !roots.iter().any(|r| r == name));
+    txn.commit();
+    drop(txn);
+    !new_root && !touched.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yrs::{GetString, Map, MapPrelim, MapRef, Text};
+
+    /// Apply `edit` to a peer synced with `doc` and return the delta.
+    fn delta(doc: &yrs::Doc, edit: impl FnOnce(&mut yrs::TransactionMut)) -> Vec<u8> {
+        let peer = yrs::Doc::new();
+        let state = doc.transact().encode_state_as_update_v1(&yrs::StateVector::default());
+        peer.transact_mut().apply_update(yrs::Update::decode_v1(&state).unwrap()).unwrap();
+        let before = peer.transact().state_vector();
+        {
+            let mut txn = peer.transact_mut();
+            edit(&mut txn);
+        }
+        let update = peer.transact().encode_state_as_update_v1(&before);
+        update
+    }
+
+    /// The map stored under `key` in the root map `root`.
+    fn nested_map(txn: &mut yrs::TransactionMut, root: &str, key: &str) -> MapRef {
+        match txn.get_or_insert_map(root).get(txn, key) {
+            Some(Out::YMap(map)) => map,
+            other => panic!("{root}.{key} is not a map: {other:?}"),
+        }
+    }
+
+    /// A server-side doc: roots arrive through updates, untyped.
+    fn server_doc() -> yrs::Doc {
+        let author = yrs::Doc::new();
+        {
+            let mut txn = author.transact_mut();
+            let layers = txn.get_or_insert_map("layers");
+            layers.insert(&mut txn, "a", "{}");
+            layers.insert(&mut txn, "nested", MapPrelim::default());
+            let comments = txn.get_or_insert_map(COMMENTS_ROOT);
+            comments.insert(&mut txn, "c1", "first");
+            comments.insert(&mut txn, "thread", MapPrelim::default());
+            txn.get_or_insert_text("title").insert(&mut txn, 0, "Doc");
+        }
+        let doc = yrs::Doc::new();
+        let state = author.transact().encode_state_as_update_v1(&yrs::StateVector::default());
+        doc.transact_mut().apply_update(yrs::Update::decode_v1(&state).unwrap()).unwrap();
+        doc
+    }
+
+    #[test]
+    fn test_role_ordering() {
+        assert!(Role::Owner.can_edit());
+        assert!(Role::Editor.can_edit());
+        assert!(!Role::Commenter.can_edit());
+        assert!(Role::Commenter.can_comment());
+        assert!(!Role::Viewer.can_comment());
+        assert_eq!(Role::Commenter.to_string(), "commenter");
+    }
+
+    #[test]
+    fn test_grants_and_default() {
+        let perms = Permissions::new(Role::Viewer);
+        let (doc, user) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(perms.role(doc, user), Role::Viewer);
+
+        assert_eq!(perms.set_role(doc, user, Role::Editor), None);
+        assert_eq!(perms.role(doc, user), Role::Editor);
+        assert_eq!(perms.role(Uuid::new_v4(), user), Role::Viewer, "grants are per document");
+        assert_eq!(perms.grants(doc).len(), 1);
+
+        assert_eq!(perms.revoke(doc, user), Some(Role::Editor));
+        assert_eq!(perms.role(doc, user), Role::Viewer);
+        assert!(perms.grants(doc).is_empty());
+    }
+
+    #[test]
+    fn test_comment_changes_allowed() {
+        let doc = server_doc();
+        let add = delta(&doc, |txn| {
+            txn.get_or_insert_map(COMMENTS_ROOT).insert(txn, "c2", "second");
+        });
+        assert!(is_comment_only(&doc, &add));
+
+        let remove = delta(&doc, |txn| {
+            txn.get_or_insert_map(COMMENTS_ROOT).remove(txn, "c1");
+        });
+        assert!(is_comment_only(&doc, &remove));
+
+        let reply = delta(&doc, |txn| {
+            let thread = nested_map(txn, COMMENTS_ROOT, "thread");
+            thread.insert(txn, "r1", "reply");
+        });
+        assert!(is_comment_only(&doc, &reply));
+
+        // The check does not touch the authoritative doc
+        assert_eq!(
+            doc.transact().root_refs().count(),
+            3,
+            "dry run must leave the source document alone"
+        );
+    }
+
+    #[test]
+    fn test_other_changes_rejected() {
+        let doc = server_doc();
+        let layer = delta(&doc, |txn| {
+            txn.get_or_insert_map("layers").insert(txn, "b", "{}");
+        });
+        assert!(!is_comment_only(&doc, &layer));
+
+        let delete = delta(&doc, |txn| {
+            txn.get_or_insert_map("layers").remove(txn, "a");
+        });
+        assert!(!is_comment_only(&doc, &delete));
+
+        let nested = delta(&doc, |txn| {
+            let nested = nested_map(txn, "layers", "nested");
+            let deeper = nested.insert(txn, "deeper", MapPrelim::default());
+            deeper.insert(txn, "fill", "#f00");
+        });
+        assert!(!is_comment_only(&doc, &nested));
+
+        let deep_edit = delta(&doc, |txn| {
+            let nested = nested_map(txn, "layers", "nested");
+            nested.insert(txn, "x", 10);
+        });
+        assert!(!is_comment_only(&doc, &deep_edit));
+
+        let text = delta(&doc, |txn| {
+            let title = txn.get_or_insert_text("title");
+            title.insert(txn, 3, "!");
+            assert_eq!(title.get_string(txn), "Doc!");
+        });
+        assert!(!is_comment_only(&doc, &text));
+
+        let new_root = delta(&doc, |txn| {
+            txn.get_or_insert_map("sneaky").insert(txn, "k", "v");
+        });
+        assert!(!is_comment_only(&doc, &new_root));
+
+        let mixed = delta(&doc, |txn| {
+            txn.get_or_insert_map(COMMENTS_ROOT).insert(txn, "c3", "ok");
+            txn.get_or_insert_map("layers").insert(txn, "c", "{}");
+        });
+        assert!(!is_comment_only(&doc, &mixed));
+
+        assert!(!is_comment_only(&doc, b"junk"));
+    }
+
+    #[test]
+    fn test_comments_root_may_be_created() {
+        let doc = yrs::Doc::new();
+        let first = delta(&doc, |txn| {
+            txn.get_or_insert_map(COMMENTS_ROOT).insert(txn, "c1", "hello");
+        });
+        assert!(is_comment_only(&doc, &first));
+    }
+}
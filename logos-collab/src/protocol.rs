@@ -10,10 +10,37 @@
 //!
 //! Performance target: serialization < 500ns for typical delta.
 //! Reference: Patterson & Hennessy, Section 5.7 — Data Compression
+//!
+//! ## Versioning
+//!
+//! The header itself is frozen: it has no version field, and changing it
+//! would break every deployed peer. Instead a client opens with a
+//! [`Hello`] naming its [`PROTOCOL_VERSION`] and [`Capabilities`]; the
+//! server answers with what both sides support. Peers that send no hello
+//! speak version 1 with no capabilities, and are never sent anything
+//! version 1 cannot decode.
+//!
+//! To stay forward-compatible:
+//! - new message types are appended to [`MessageType`]; decoding one this
+//!   build does not know fails with [`ProtocolError::UnknownMessageType`],
+//!   which receivers skip instead of treating the peer as broken
+//! - new [`Hello`] fields are appended; older decoders ignore the tail
+//!
+//! ## Compression
+//!
+//! Between peers that agreed on [`Capabilities::COMPRESSION`], an encoded
+//! message larger than a threshold may be sent LZ4-compressed inside a
+//! `Compressed` message with the same header. [`SyncMessage::decode`]
+//! unwraps it, so receivers never see the wrapper.
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::auth::AuthError;
+use crate::history::HistoryMessage;
+use crate::permissions::Role;
+use crate::storage::delta::{lz4_compress, lz4_decompress};
+
 /// Message types for the sync protocol.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
@@ -34,6 +61,195 @@ pub enum MessageType {
     Ping = 7,
     /// Heartbeat pong
     Pong = 8,
+    /// Server refused a request (payload: [`Rejection`])
+    Rejected = 9,
+    /// Version history request or reply (payload: [`HistoryMessage`])
+    History = 10,
+    /// Protocol version and capabilities (payload: [`Hello`])
+    Hello = 11,
+    /// Another encoded message, LZ4-compressed (payload: size-prepended
+    /// LZ4 block); only sent to peers that agreed on compression
+    Compressed = 12,
+}
+
+/// Protocol version spoken by this build. Version 1 is the protocol
+/// before the hello handshake.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Encoded size above which messages are compressed by default. Smaller
+/// messages gain too little to pay for the compression.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Largest size a compressed message may expand to in
+/// [`SyncMessage::decode`]: the WebSocket layer's default message limit.
+pub const MAX_DECOMPRESSED_BYTES: usize = 64 << 20;
+
+/// Optional protocol features, as a bit set.
+///
+/// Each side advertises what it supports; only features both sides
+/// advertise are used. Unknown bits from newer peers are ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Payload compression
+    pub const COMPRESSION: Self = Self(1 << 0);
+    /// Bearer tokens on join
+    pub const AUTH: Self = Self(1 << 1);
+    /// Comment-only edits under the commenter role
+    pub const COMMENTS: Self = Self(1 << 2);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether every flag of `other` is set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Flags set in both.
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl std::ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl std::fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [(Self::COMPRESSION, "compression"), (Self::AUTH, "auth"), (Self::COMMENTS, "comments")];
+        let mut set = names.iter().filter(|(flag, _)| self.contains(*flag)).map(|(_, name)| *name);
+        match set.next() {
+            Some(first) => {
+                write!(f, "{first}")?;
+                set.try_for_each(|name| write!(f, ", {name}"))
+            }
+            None => write!(f, "none"),
+        }
+    }
+}
+
+/// Payload of a `Hello` message.
+///
+/// The client sends the highest version it speaks and its capabilities;
+/// the server replies with the [negotiated](Self::negotiate) result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u16,
+    pub capabilities: Capabilities,
+}
+
+impl Hello {
+    /// This build's version with `capabilities`.
+    pub fn new(capabilities: Capabilities) -> Self {
+        Self { version: PROTOCOL_VERSION, capabilities }
+    }
+
+    /// What a peer that sent no hello speaks.
+    pub fn legacy() -> Self {
+        Self { version: 1, capabilities: Capabilities::empty() }
+    }
+
+    /// The version and capabilities both sides support.
+    pub fn negotiate(&self, remote: &Hello) -> Hello {
+        Hello {
+            version: self.version.min(remote.version),
+            capabilities: self.capabilities.intersection(remote.capabilities),
+        }
+    }
+}
+
+/// Typed reason carried by a `Rejected` message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rejection {
+    /// The join failed authentication; the server disconnects afterwards
+    Unauthorized(AuthError),
+    /// The sender's role does not allow this message; it was not applied
+    Forbidden {
+        role: Role,
+        msg_type: MessageType,
+        /// Clock of the refused message
+        clock: u64,
+    },
+    /// An administrator removed the peer from the room; the server
+    /// disconnects afterwards
+    Evicted,
+    /// An administrator closed the room; the server disconnects afterwards
+    RoomClosed,
+    /// The room already has `max_peers` peers; the server disconnects
+    /// afterwards
+    RoomFull { max_peers: usize },
+    /// The sender's rate limit for this message type is spent; the message
+    /// was not applied
+    RateLimited {
+        msg_type: MessageType,
+        /// Clock of the refused message
+        clock: u64,
+    },
+    /// The message exceeded the server's size limit and was not decoded
+    TooLarge { size: usize, max: usize },
+    /// The server could not make the update durable; it was neither
+    /// applied nor broadcast, and may be resent
+    StorageFailed {
+        msg_type: MessageType,
+        /// Clock of the refused message
+        clock: u64,
+    },
+}
+
+impl Rejection {
+    /// Whether the server disconnects after this rejection. Clients do not
+    /// reconnect after one: an immediate retry would be refused the same
+    /// way, so retrying a full room later is left to the application.
+    pub fn is_final(&self) -> bool {
+        !matches!(
+            self,
+            Self::Forbidden { .. } | Self::RateLimited { .. } | Self::TooLarge { .. } | Self::StorageFailed { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unauthorized(e) => write!(f, "Unauthorized: {e}"),
+            Self::Forbidden { role, msg_type, clock } => {
+                write!(f, "Forbidden: {role} may not send {msg_type:?} (clock {clock})")
+            }
+            Self::Evicted => write!(f, "Evicted by an administrator"),
+            Self::RoomClosed => write!(f, "Room closed by an administrator"),
+            Self::RoomFull { max_peers } => write!(f, "Room full ({max_peers} peers)"),
+            Self::RateLimited { msg_type, clock } => {
+                write!(f, "Rate limited: too many {msg_type:?} messages (clock {clock})")
+            }
+            Self::TooLarge { size, max } => write!(f, "Message too large: {size} bytes (max {max})"),
+            Self::StorageFailed { msg_type, clock } => {
+                write!(f, "Storage failed: {msg_type:?} not applied (clock {clock})")
+            }
+        }
+    }
 }
 
 /// Peer identity with display metadata.
@@ -177,6 +393,57 @@ impl SyncMessage {
         }
     }
 
+    /// Create a join request carrying a bearer token.
+    ///
+    /// The token is appended after the `PeerInfo`, so [`Self::peer_info`]
+    /// still decodes the payload.
+    pub fn peer_joined_with_token(peer_id: Uuid, doc_id: Uuid, info: &PeerInfo, token: &str) -> Self {
+        let mut msg = Self::peer_joined(peer_id, doc_id, info);
+        if let Ok(bytes) = bincode::serde::encode_to_vec(token, bincode::config::standard()) {
+            msg.payload.extend_from_slice(&bytes);
+        }
+        msg
+    }
+
+    /// Create a rejection sent by the server.
+    pub fn rejected(doc_id: Uuid, rejection: &Rejection) -> Self {
+        let payload = bincode::serde::encode_to_vec(rejection, bincode::config::standard())
+            .unwrap_or_default();
+        Self {
+            msg_type: MessageType::Rejected,
+            peer_id: Uuid::nil(),
+            doc_id,
+            clock: 0,
+            payload,
+        }
+    }
+
+    /// Create a version history request or reply.
+    pub fn history(peer_id: Uuid, doc_id: Uuid, message: &HistoryMessage) -> Self {
+        let payload = bincode::serde::encode_to_vec(message, bincode::config::standard())
+            .unwrap_or_default();
+        Self {
+            msg_type: MessageType::History,
+            peer_id,
+            doc_id,
+            clock: 0,
+            payload,
+        }
+    }
+
+    /// Create a protocol hello.
+    pub fn hello(peer_id: Uuid, doc_id: Uuid, hello: &Hello) -> Self {
+        let payload = bincode::serde::encode_to_vec(hello, bincode::config::standard())
+            .unwrap_or_default();
+        Self {
+            msg_type: MessageType::Hello,
+            peer_id,
+            doc_id,
+            clock: 0,
+            payload,
+        }
+    }
+
     /// Create a peer left notification.
     pub fn peer_left(peer_id: Uuid, doc_id: Uuid) -> Self {
         Self {
@@ -217,12 +484,86 @@ impl SyncMessage {
             .map_err(|e| ProtocolError::SerializationError(e.to_string()))
     }
 
-    /// Deserialize from binary wire format.
+    /// Serialize, compressed if the encoding is larger than `threshold`
+    /// bytes and compression shrinks it. `None` never compresses; only pass
+    /// a threshold for peers that agreed on [`Capabilities::COMPRESSION`].
+    pub fn encode_with(&self, threshold: Option<usize>) -> Result<Vec<u8>, ProtocolError> {
+        self.compress(self.encode()?, threshold)
+    }
+
+    /// [`Self::encode_with`] for an existing encoding of this message.
+    pub fn compress(&self, encoded: Vec<u8>, threshold: Option<usize>) -> Result<Vec<u8>, ProtocolError> {
+        if threshold.is_none_or(|t| encoded.len() <= t) {
+            return Ok(encoded);
+        }
+        let wrapper = Self {
+            msg_type: MessageType::Compressed,
+            peer_id: self.peer_id,
+            doc_id: self.doc_id,
+            clock: self.clock,
+            payload: lz4_compress(&encoded),
+        };
+        let compressed = wrapper.encode()?;
+        Ok(if compressed.len() < encoded.len() { compressed } else { encoded })
+    }
+
+    /// Deserialize from binary wire format, unwrapping compressed messages.
+    ///
+    /// A well-formed message of a type this build does not know fails with
+    /// [`ProtocolError::UnknownMessageType`].
     #[inline(always)]
     pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
-        let (msg, _) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())
-            .map_err(|e| ProtocolError::DeserializationError(e.to_string()))?;
-        Ok(msg)
+        Self::decode_limited(bytes, MAX_DECOMPRESSED_BYTES)
+    }
+
+    /// [`Self::decode`], refusing compressed messages that would expand to
+    /// more than `max_bytes` before decompressing them.
+    pub fn decode_limited(bytes: &[u8], max_bytes: usize) -> Result<Self, ProtocolError> {
+        let msg = Self::decode_plain(bytes)?;
+        if msg.msg_type != MessageType::Compressed {
+            return Ok(msg);
+        }
+        // lz4_compress prepends the uncompressed size as a little-endian u32
+        let size = match msg.payload.first_chunk::<4>() {
+            Some(prefix) => u32::from_le_bytes(*prefix) as usize,
+            None => return Err(ProtocolError::DeserializationError("truncated compressed message".into())),
+        };
+        if size > max_bytes {
+            return Err(ProtocolError::TooLarge { size, max: max_bytes });
+        }
+        let inner = lz4_decompress(&msg.payload).map_err(|e| ProtocolError::DeserializationError(e.to_string()))?;
+        let inner = Self::decode_plain(&inner)?;
+        if inner.msg_type == MessageType::Compressed {
+            return Err(ProtocolError::DeserializationError("nested compressed message".into()));
+        }
+        Ok(inner)
+    }
+
+    #[inline(always)]
+    fn decode_plain(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        match bincode::serde::decode_from_slice(bytes, bincode::config::standard()) {
+            Ok((msg, _)) => Ok(msg),
+            Err(e) => Err(Self::unknown_type(bytes)
+                .unwrap_or_else(|| ProtocolError::DeserializationError(e.to_string()))),
+        }
+    }
+
+    /// The error for a message that only failed to decode because of its
+    /// type, if that is the case.
+    #[cold]
+    fn unknown_type(bytes: &[u8]) -> Option<ProtocolError> {
+        // Same layout with the type as its raw variant index
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct RawMessage {
+            msg_type: u32,
+            peer_id: Uuid,
+            doc_id: Uuid,
+            clock: u64,
+            payload: Vec<u8>,
+        }
+        let (raw, _): (RawMessage, _) = bincode::serde::decode_from_slice(bytes, bincode::config::standard()).ok()?;
+        Some(ProtocolError::UnknownMessageType(raw.msg_type))
     }
 
     /// Parse awareness payload.
@@ -244,6 +585,47 @@ impl SyncMessage {
             .map_err(|e| ProtocolError::DeserializationError(e.to_string()))?;
         Ok(info)
     }
+
+    /// Bearer token of a join request, `None` if it carries none.
+    pub fn join_token(&self) -> Option<String> {
+        if self.msg_type != MessageType::PeerJoined {
+            return None;
+        }
+        let config = bincode::config::standard();
+        let (_, used): (PeerInfo, _) = bincode::serde::decode_from_slice(&self.payload, config).ok()?;
+        let (token, _) = bincode::serde::decode_from_slice(&self.payload[used..], config).ok()?;
+        Some(token)
+    }
+
+    /// Parse rejection payload.
+    pub fn rejection(&self) -> Result<Rejection, ProtocolError> {
+        if self.msg_type != MessageType::Rejected {
+            return Err(ProtocolError::InvalidMessageType);
+        }
+        let (rejection, _) = bincode::serde::decode_from_slice(&self.payload, bincode::config::standard())
+            .map_err(|e| ProtocolError::DeserializationError(e.to_string()))?;
+        Ok(rejection)
+    }
+
+    /// Parse hello payload.
+    pub fn hello_payload(&self) -> Result<Hello, ProtocolError> {
+        if self.msg_type != MessageType::Hello {
+            return Err(ProtocolError::InvalidMessageType);
+        }
+        let (hello, _) = bincode::serde::decode_from_slice(&self.payload, bincode::config::standard())
+            .map_err(|e| ProtocolError::DeserializationError(e.to_string()))?;
+        Ok(hello)
+    }
+
+    /// Parse version history payload.
+    pub fn history_message(&self) -> Result<HistoryMessage, ProtocolError> {
+        if self.msg_type != MessageType::History {
+            return Err(ProtocolError::InvalidMessageType);
+        }
+        let (message, _) = bincode::serde::decode_from_slice(&self.payload, bincode::config::standard())
+            .map_err(|e| ProtocolError::DeserializationError(e.to_string()))?;
+        Ok(message)
+    }
 }
 
 /// Protocol errors.
@@ -252,6 +634,10 @@ pub enum ProtocolError {
     SerializationError(String),
     DeserializationError(String),
     InvalidMessageType,
+    /// A message type from a newer protocol version (raw variant index)
+    UnknownMessageType(u32),
+    /// A compressed message would expand beyond the decoder's limit
+    TooLarge { size: usize, max: usize },
     ConnectionClosed,
     Timeout,
 }
@@ -262,6 +648,8 @@ impl std::fmt::Display for ProtocolError {
             Self::SerializationError(e) => write!(f, "Serialization error: {e}"),
             Self::DeserializationError(e) => write!(f, "Deserialization error: {e}"),
             Self::InvalidMessageType => write!(f, "Invalid message type"),
+            Self::UnknownMessageType(t) => write!(f, "Unknown message type {t}"),
+            Self::TooLarge { size, max } => write!(f, "Message too large: {size} bytes (max {max})"),
             Self::ConnectionClosed => write!(f, "Connection closed"),
             Self::Timeout => write!(f, "Connection timeout"),
         }
@@ -357,6 +745,59 @@ mod tests {
         assert_eq!(parsed.peer_id, info.peer_id);
     }
 
+    #[test]
+    fn test_join_token_roundtrip() {
+        let info = PeerInfo::new("Alice");
+        let doc = Uuid::new_v4();
+
+        let plain = SyncMessage::peer_joined(info.peer_id, doc, &info);
+        assert_eq!(plain.join_token(), None);
+
+        let msg = SyncMessage::peer_joined_with_token(info.peer_id, doc, &info, "abc.def");
+        let decoded = SyncMessage::decode(&msg.encode().unwrap()).unwrap();
+        assert_eq!(decoded.peer_info().unwrap(), info);
+        assert_eq!(decoded.join_token().as_deref(), Some("abc.def"));
+    }
+
+    #[test]
+    fn test_rejected_roundtrip() {
+        let doc = Uuid::new_v4();
+        let rejection = Rejection::Unauthorized(AuthError::Expired { expires_at: 7 });
+
+        let msg = SyncMessage::rejected(doc, &rejection);
+        let decoded = SyncMessage::decode(&msg.encode().unwrap()).unwrap();
+
+        assert_eq!(decoded.msg_type, MessageType::Rejected);
+        assert_eq!(decoded.rejection().unwrap(), rejection);
+        assert!(SyncMessage::ping(doc).rejection().is_err());
+        assert!(rejection.is_final());
+
+        let closed = SyncMessage::rejected(doc, &Rejection::RoomClosed);
+        let decoded = SyncMessage::decode(&closed.encode().unwrap()).unwrap();
+        assert_eq!(decoded.rejection().unwrap(), Rejection::RoomClosed);
+        assert!(Rejection::Evicted.is_final());
+        let forbidden = Rejection::Forbidden { role: Role::Viewer, msg_type: MessageType::Delta, clock: 3 };
+        assert!(!forbidden.is_final());
+        assert!(!Rejection::RateLimited { msg_type: MessageType::Delta, clock: 4 }.is_final());
+        assert!(!Rejection::TooLarge { size: 10, max: 5 }.is_final());
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        use crate::history::VersionRef;
+
+        let peer = Uuid::new_v4();
+        let doc = Uuid::new_v4();
+        let request = HistoryMessage::Restore(VersionRef::At(1_700_000_000_000));
+
+        let msg = SyncMessage::history(peer, doc, &request);
+        let decoded = SyncMessage::decode(&msg.encode().unwrap()).unwrap();
+
+        assert_eq!(decoded.msg_type, MessageType::History);
+        assert_eq!(decoded.history_message().unwrap(), request);
+        assert!(SyncMessage::ping(peer).history_message().is_err());
+    }
+
     #[test]
     fn test_peer_left_roundtrip() {
         let peer = Uuid::new_v4();
@@ -450,6 +891,139 @@ mod tests {
         assert_eq!(MessageType::PeerLeft as u8, 6);
         assert_eq!(MessageType::Ping as u8, 7);
         assert_eq!(MessageType::Pong as u8, 8);
+        assert_eq!(MessageType::Rejected as u8, 9);
+        assert_eq!(MessageType::History as u8, 10);
+        assert_eq!(MessageType::Hello as u8, 11);
+        assert_eq!(MessageType::Compressed as u8, 12);
+    }
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let peer = Uuid::new_v4();
+        let doc = Uuid::new_v4();
+        let large = SyncMessage::sync_step2(peer, doc, b"paste ".repeat(2000));
+        let plain = large.encode().unwrap();
+
+        let compressed = large.encode_with(Some(DEFAULT_COMPRESSION_THRESHOLD)).unwrap();
+        assert!(compressed.len() < plain.len() / 10, "{} of {} bytes", compressed.len(), plain.len());
+        let decoded = SyncMessage::decode(&compressed).unwrap();
+        assert_eq!(decoded.msg_type, MessageType::SyncStep2);
+        assert_eq!((decoded.peer_id, decoded.doc_id), (peer, doc));
+        assert_eq!(decoded.payload, large.payload);
+
+        // Small, unnegotiated and incompressible messages go out as they are
+        let small = SyncMessage::delta(peer, doc, 1, vec![1; 64]);
+        assert_eq!(small.encode_with(Some(DEFAULT_COMPRESSION_THRESHOLD)).unwrap(), small.encode().unwrap());
+        assert_eq!(large.encode_with(None).unwrap(), plain);
+        let mut x = 0x9E37_79B9_7F4A_7C15u64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        let noisy = SyncMessage::delta(peer, doc, 2, noise);
+        assert!(noisy.encode_with(Some(0)).unwrap() == noisy.encode().unwrap(), "incompressible: sent as is");
+    }
+
+    #[test]
+    fn test_compressed_decode_limited() {
+        let msg = SyncMessage::sync_step2(Uuid::new_v4(), Uuid::new_v4(), vec![0; 100_000]);
+        let compressed = msg.encode_with(Some(0)).unwrap();
+        assert!(compressed.len() < 1000);
+        assert!(SyncMessage::decode_limited(&compressed, 200_000).is_ok());
+        assert!(matches!(
+            SyncMessage::decode_limited(&compressed, 50_000),
+            Err(ProtocolError::TooLarge { max: 50_000, .. })
+        ));
+
+        // A size prefix claiming 4 GiB is refused without allocating it
+        let bomb = SyncMessage {
+            msg_type: MessageType::Compressed,
+            peer_id: Uuid::nil(),
+            doc_id: Uuid::nil(),
+            clock: 0,
+            payload: vec![0xFF, 0xFF, 0xFF, 0xFF, 0x00],
+        };
+        let bytes = bomb.encode().unwrap();
+        assert!(matches!(SyncMessage::decode_limited(&bytes, 1 << 20), Err(ProtocolError::TooLarge { .. })));
+        assert!(matches!(SyncMessage::decode(&bytes), Err(ProtocolError::TooLarge { .. })));
+
+        // Compression does not nest
+        let nested = SyncMessage { payload: lz4_compress(&compressed), ..bomb };
+        assert!(SyncMessage::decode(&nested.encode().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_hello_roundtrip_and_negotiation() {
+        let client = Hello::new(Capabilities::COMPRESSION | Capabilities::COMMENTS);
+        let msg = SyncMessage::hello(Uuid::new_v4(), Uuid::new_v4(), &client);
+        let decoded = SyncMessage::decode(&msg.encode().unwrap()).unwrap();
+        assert_eq!(decoded.msg_type, MessageType::Hello);
+        assert_eq!(decoded.hello_payload().unwrap(), client);
+        assert!(SyncMessage::ping(Uuid::nil()).hello_payload().is_err());
+
+        // A newer server with an extra flag
+        let server = Hello {
+            version: PROTOCOL_VERSION + 1,
+            capabilities: Capabilities::COMMENTS | Capabilities::AUTH | Capabilities::from_bits(1 << 31),
+        };
+        let agreed = server.negotiate(&client);
+        assert_eq!(agreed, client.negotiate(&server));
+        assert_eq!(agreed.version, PROTOCOL_VERSION);
+        assert_eq!(agreed.capabilities, Capabilities::COMMENTS);
+        assert_eq!(agreed.capabilities.to_string(), "comments");
+        assert_eq!(Hello::legacy().negotiate(&server).capabilities, Capabilities::empty());
+        assert_eq!(Capabilities::empty().to_string(), "none");
+    }
+
+    #[test]
+    fn test_hello_ignores_appended_fields() {
+        // A newer peer's hello with a field this build does not know
+        #[derive(Serialize)]
+        struct FutureHello {
+            version: u16,
+            capabilities: u32,
+            max_batch: u64,
+        }
+        let future = FutureHello { version: 9, capabilities: Capabilities::AUTH.bits(), max_batch: 64 };
+        let mut msg = SyncMessage::hello(Uuid::nil(), Uuid::nil(), &Hello::legacy());
+        msg.payload = bincode::serde::encode_to_vec(&future, bincode::config::standard()).unwrap();
+
+        let hello = SyncMessage::decode(&msg.encode().unwrap()).unwrap().hello_payload().unwrap();
+        assert_eq!(hello, Hello { version: 9, capabilities: Capabilities::AUTH });
+    }
+
+    #[test]
+    fn test_decode_unknown_message_type() {
+        // A well-formed message of a type added after this build
+        #[derive(Serialize)]
+        struct FutureMessage {
+            msg_type: u32,
+            peer_id: Uuid,
+            doc_id: Uuid,
+            clock: u64,
+            payload: Vec<u8>,
+        }
+        let future = FutureMessage {
+            msg_type: 42,
+            peer_id: Uuid::new_v4(),
+            doc_id: Uuid::new_v4(),
+            clock: 1,
+            payload: vec![1, 2],
+        };
+        let bytes = bincode::serde::encode_to_vec(&future, bincode::config::standard()).unwrap();
+        assert!(matches!(SyncMessage::decode(&bytes), Err(ProtocolError::UnknownMessageType(42))));
+
+        // Known types keep their wire encoding: the variant index
+        let delta = SyncMessage::delta(future.peer_id, future.doc_id, 1, vec![1, 2]).encode().unwrap();
+        let as_future = FutureMessage { msg_type: 2, ..future };
+        assert_eq!(delta, bincode::serde::encode_to_vec(&as_future, bincode::config::standard()).unwrap());
+
+        // Garbage is still garbage
+        assert!(matches!(SyncMessage::decode(&[0xFF, 0xFE]), Err(ProtocolError::DeserializationError(_))));
     }
 
     #[test]
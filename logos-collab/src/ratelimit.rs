@@ -0,0 +1,197 @@
+//! Per-peer token-bucket rate limits.
+//!
+//! Each connection gets a [`PeerLimiter`] holding one bucket per limited
+//! message type. A bucket holds up to `burst` tokens and refills at
+//! `per_second`; every message takes one token. What happens to a message
+//! that finds its bucket empty is up to the server: deltas and history
+//! requests are rejected, cursor and selection updates are coalesced.
+//!
+//! Reference: Tanenbaum — Computer Networks, Section 5.4 (Token Bucket)
+
+use std::time::{Duration, Instant};
+
+use crate::protocol::MessageType;
+
+/// Sustained rate and burst size of one bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Tokens added per second
+    pub per_second: f64,
+    /// Bucket size: messages accepted back to back from a full bucket
+    pub burst: f64,
+}
+
+impl RateLimit {
+    pub fn new(per_second: f64, burst: f64) -> Self {
+        Self { per_second, burst }
+    }
+}
+
+/// Limits applied to every peer (None = unlimited).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    /// Document updates: deltas and SyncStep2 messages alike
+    pub delta: Option<RateLimit>,
+    /// Cursor and selection updates; joins and leaves are never limited
+    pub awareness: Option<RateLimit>,
+    /// Version history requests
+    pub history: Option<RateLimit>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            delta: Some(RateLimit::new(200.0, 1000.0)),
+            awareness: Some(RateLimit::new(30.0, 60.0)),
+            history: Some(RateLimit::new(5.0, 20.0)),
+        }
+    }
+}
+
+impl RateLimits {
+    /// No limits at all.
+    pub fn unlimited() -> Self {
+        Self { delta: None, awareness: None, history: None }
+    }
+}
+
+/// A single token bucket.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket.
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        Self { limit, tokens: limit.burst, refilled_at: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst);
+        self.refilled_at = now;
+    }
+
+    /// Take a token if one is available.
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// When the next token becomes available (`now` if one already is),
+    /// or `None` if the bucket is empty and never refills.
+    pub fn next_token_at(&mut self, now: Instant) -> Option<Instant> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Some(now)
+        } else if self.limit.per_second > 0.0 {
+            Some(now + Duration::from_secs_f64((1.0 - self.tokens) / self.limit.per_second))
+        } else {
+            None
+        }
+    }
+}
+
+/// The buckets of one peer.
+#[derive(Debug, Clone)]
+pub struct PeerLimiter {
+    delta: Option<TokenBucket>,
+    awareness: Option<TokenBucket>,
+    history: Option<TokenBucket>,
+}
+
+impl PeerLimiter {
+    pub fn new(limits: &RateLimits, now: Instant) -> Self {
+        let bucket = |limit: Option<RateLimit>| limit.map(|l| TokenBucket::new(l, now));
+        Self {
+            delta: bucket(limits.delta),
+            awareness: bucket(limits.awareness),
+            history: bucket(limits.history),
+        }
+    }
+
+    fn bucket(&mut self, msg_type: MessageType) -> Option<&mut TokenBucket> {
+        match msg_type {
+            // A SyncStep2 is applied exactly like a delta
+            MessageType::Delta | MessageType::SyncStep2 => self.delta.as_mut(),
+            MessageType::Awareness => self.awareness.as_mut(),
+            MessageType::History => self.history.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// Take a token for a message of `msg_type`. Unlimited types always pass.
+    pub fn try_take(&mut self, msg_type: MessageType, now: Instant) -> bool {
+        self.bucket(msg_type).is_none_or(|bucket| bucket.try_take(now))
+    }
+
+    /// When a message of `msg_type` will next be let through, if ever.
+    pub fn next_token_at(&mut self, msg_type: MessageType, now: Instant) -> Option<Instant> {
+        self.bucket(msg_type).map_or(Some(now), |bucket| bucket.next_token_at(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_burst_then_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(10.0, 3.0), start);
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start), "burst spent");
+
+        // 10 per second: one token every 100ms
+        assert_eq!(bucket.next_token_at(start), Some(start + Duration::from_millis(100)));
+        assert!(!bucket.try_take(start + Duration::from_millis(50)));
+        assert!(bucket.try_take(start + Duration::from_millis(100)));
+
+        // Refill never exceeds the burst
+        let later = start + Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take(later)));
+        assert!(!bucket.try_take(later));
+    }
+
+    #[test]
+    fn test_limiter_per_message_type() {
+        let now = Instant::now();
+        let limits = RateLimits {
+            delta: Some(RateLimit::new(1.0, 1.0)),
+            awareness: None,
+            history: Some(RateLimit::new(1.0, 2.0)),
+        };
+        let mut limiter = PeerLimiter::new(&limits, now);
+
+        assert!(limiter.try_take(MessageType::Delta, now));
+        assert!(!limiter.try_take(MessageType::Delta, now));
+        assert!(!limiter.try_take(MessageType::SyncStep2, now), "shares the delta bucket");
+        // Separate buckets: deltas running dry leaves history untouched
+        assert!(limiter.try_take(MessageType::History, now));
+        assert!(limiter.try_take(MessageType::History, now));
+        assert!(!limiter.try_take(MessageType::History, now));
+        // Unlimited types
+        assert!((0..1000).all(|_| limiter.try_take(MessageType::Awareness, now)));
+        assert!(limiter.try_take(MessageType::SyncStep1, now));
+        assert_eq!(limiter.next_token_at(MessageType::Awareness, now), Some(now));
+    }
+
+    #[test]
+    fn test_bucket_without_refill() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(0.0, 1.0), now);
+        assert_eq!(bucket.next_token_at(now), Some(now));
+        assert!(bucket.try_take(now));
+        assert_eq!(bucket.next_token_at(now + Duration::from_secs(3600)), None);
+    }
+}
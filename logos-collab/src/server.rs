@@ -5,7 +5,7 @@
 //! Client A ──┐
 //!             ├── Room (doc_id) ── Yrs Doc ── BroadcastGroup
 //! Client B ──┘                        │
-//!                                     ├── DocumentStore (RocksDB)
+//!                                     ├── DocumentStore (RocksDB/SQLite/memory)
 //!                                     │       │
 //!                                     │       ├── Snapshots (LZ4)
 //!                                     │       ├── Deltas (LZ4)
@@ -20,29 +20,47 @@
 //! - A Yrs `Doc` for authoritative state
 //! - A `BroadcastGroup` for fan-out to connected peers
 //! - Peer presence tracking
-//! - Persistent storage via DocumentStore (RocksDB)
+//! - Persistent storage via DocumentStore over a pluggable backend
+//! - In a cluster, relaying to the other nodes' copies (see [`crate::cluster`])
 //!
 //! Reference: Kleppmann — Designing Data-Intensive Applications, Chapters 3 & 8
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::MissedTickBehavior;
 use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use tokio_tungstenite::tungstenite::Message;
+use serde::Serialize;
 use uuid::Uuid;
 use yrs::ReadTxn;
 use yrs::updates::decoder::Decode;
 use yrs::updates::encoder::Encode;
 
-use crate::broadcast::{BroadcastGroup, RoomManager};
+use crate::auth::{AuthError, Authenticator};
+use crate::broadcast::{BroadcastGroup, Frame, RoomManager};
+use crate::cluster::{Claim, ClusterConfig, ClusterNode, RelayEnvelope};
+use crate::history::{self, HistoryMessage, MergeReport, VersionRef};
+use crate::permissions::{self, Permissions, Role};
 use crate::presence::AwarenessMessage;
-use crate::protocol::{MessageType, PeerInfo, SyncMessage};
-use crate::storage::{DocumentStore, StoreConfig};
+use crate::protocol::{
+    Capabilities, Hello, MessageType, PeerInfo, ProtocolError, Rejection, SyncMessage,
+    DEFAULT_COMPRESSION_THRESHOLD,
+};
+use crate::ratelimit::{PeerLimiter, RateLimits};
+use crate::storage::archive;
+use crate::storage::{
+    Archive, ArchiveError, BackendKind, Checkpoint, DocumentStore, ForkOrigin, IdPolicy, ImportedDocument,
+    StoreConfig, StoreError, VersionInfo, WalConfig,
+};
 
 /// Server configuration.
 #[derive(Debug, Clone)]
@@ -53,10 +71,54 @@ pub struct ServerConfig {
     pub max_peers_per_room: usize,
     /// Broadcast channel capacity per room
     pub broadcast_capacity: usize,
-    /// Heartbeat interval in seconds
+    /// Heartbeat interval in seconds (0 = no heartbeats)
     pub heartbeat_interval_secs: u64,
+    /// Drop a peer after this many heartbeats pass without hearing from it
+    pub heartbeat_max_missed: u32,
+    /// Per-peer token buckets for deltas, awareness and history requests
+    pub rate_limits: RateLimits,
+    /// Largest message accepted, checked before decoding and again before
+    /// decompressing. Frames over twice this are cut off by the WebSocket
+    /// layer, closing the connection.
+    pub max_message_bytes: usize,
+    /// Compress messages above this many bytes for peers that support it
+    /// (None = never, and compression is not offered)
+    pub compression_threshold: Option<usize>,
     /// Persistence storage path (None = in-memory only)
     pub storage_path: Option<PathBuf>,
+    /// Engine behind `storage_path` (default: RocksDB). `BackendKind::Memory`
+    /// still needs `storage_path` set to enable persistence, but ignores it.
+    pub storage_backend: BackendKind,
+    /// Invariant checking applied to documents restored by `recover()`
+    pub recovery_validation: RecoveryValidation,
+    /// Join token validation (None = joins are not authenticated)
+    pub authenticator: Option<Arc<dyn Authenticator>>,
+    /// Role of users without an explicit per-document grant
+    pub default_role: Role,
+    /// How often WAL appends are fsynced, in milliseconds
+    pub wal_sync_interval_ms: u64,
+    /// Snapshot an open room after this many deltas (None = no count trigger)
+    pub snapshot_every_deltas: Option<u64>,
+    /// Snapshot an open room with new deltas this often (None = no timer)
+    pub snapshot_interval_secs: Option<u64>,
+    /// Squash version history older than this into a single version when
+    /// a snapshot is saved (None = keep every version)
+    pub history_retention_secs: Option<u64>,
+    /// Other nodes serving the same documents (None = a single server)
+    pub cluster: Option<ClusterConfig>,
+}
+
+/// What `SyncServer::recover` does with structural invariant violations
+/// (see [`logos_core::validate`]) in restored documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecoveryValidation {
+    /// Skip validation entirely
+    Off,
+    /// Log diagnostics but leave the document untouched
+    #[default]
+    Report,
+    /// Repair what can be fixed and persist the repair as a delta
+    Repair,
 }
 
 impl Default for ServerConfig {
@@ -66,7 +128,20 @@ impl Default for ServerConfig {
             max_peers_per_room: 100,
             broadcast_capacity: 256,
             heartbeat_interval_secs: 30,
+            heartbeat_max_missed: 3,
+            rate_limits: RateLimits::default(),
+            max_message_bytes: 16 * 1024 * 1024,
+            compression_threshold: Some(DEFAULT_COMPRESSION_THRESHOLD),
             storage_path: None,
+            storage_backend: BackendKind::default(),
+            recovery_validation: RecoveryValidation::default(),
+            authenticator: None,
+            default_role: Role::Editor,
+            wal_sync_interval_ms: WalConfig::default().sync_interval_ms,
+            snapshot_every_deltas: Some(1000),
+            snapshot_interval_secs: Some(300),
+            history_retention_secs: Some(30 * 24 * 3600),
+            cluster: None,
         }
     }
 }
@@ -82,6 +157,76 @@ pub struct ServerStats {
     pub persisted_deltas: u64,
     pub persisted_snapshots: u64,
     pub storage_bytes: u64,
+    /// Invariant violations found in recovered documents
+    pub recovery_diagnostics: u64,
+    /// Violations fixed by recovery-time repair
+    pub recovery_repairs: u64,
+    /// Joins refused by the authenticator
+    pub rejected_joins: u64,
+    /// Joins refused because the room had `max_peers_per_room` peers
+    pub rejected_full_rooms: u64,
+    /// Writes refused for lack of permission
+    pub rejected_writes: u64,
+    /// Writes refused because their WAL append failed
+    pub failed_writes: u64,
+    /// WAL entries restored into the delta log on startup
+    pub wal_replayed: u64,
+    /// WAL truncations after snapshots
+    pub wal_checkpoints: u64,
+    /// Snapshots of open rooms taken by the scheduler
+    pub scheduled_snapshots: u64,
+    /// Peers resynced after falling behind their room's broadcast channel
+    pub lag_resyncs: u64,
+    /// Deltas deleted by snapshot compaction
+    pub compacted_deltas: u64,
+    /// Commands waiting for the background persistence task
+    pub persistence_queue: u64,
+    /// Peers disconnected by an administrator, alone or with their room
+    pub evicted_peers: u64,
+    /// Connections dropped after missing `heartbeat_max_missed` heartbeats
+    pub heartbeat_timeouts: u64,
+    /// Deltas and SyncStep2 messages rejected by a peer's rate limit
+    pub throttled_deltas: u64,
+    /// History requests rejected by a peer's rate limit
+    pub throttled_history: u64,
+    /// Awareness updates superseded by a newer one while rate limited
+    pub coalesced_awareness: u64,
+    /// Messages over `max_message_bytes`, rejected undecoded
+    pub oversized_messages: u64,
+    /// Messages of a type from a newer protocol version, skipped
+    pub unknown_messages: u64,
+    /// Joins from peers that sent no hello (protocol version 1)
+    pub legacy_joins: u64,
+    /// Messages sent to peers compressed
+    pub compressed_messages: u64,
+    /// Bytes compression kept off the wire
+    pub compression_saved_bytes: u64,
+    /// Documents this node holds the storage lease of
+    pub held_leases: u64,
+    /// Room messages relayed to other cluster nodes
+    pub relay_sent: u64,
+    /// Room messages relayed from other cluster nodes
+    pub relay_received: u64,
+}
+
+/// An open room as listed by [`SyncServer::list_rooms`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomInfo {
+    pub doc_id: Uuid,
+    /// Connected peers, in no particular order
+    pub peers: Vec<PeerInfo>,
+    /// Deltas accepted since the room was last snapshotted
+    pub deltas_since_snapshot: u64,
+}
+
+/// Admin request to disconnect a peer (or every peer) from a room.
+#[derive(Clone)]
+struct Eviction {
+    doc_id: Uuid,
+    /// The room instance meant, so peers of a room reopened since are spared
+    room: Arc<BroadcastGroup>,
+    /// `None` closes the whole room
+    peer_id: Option<Uuid>,
 }
 
 /// Commands sent to the background persistence task.
@@ -95,33 +240,114 @@ enum PersistenceCommand {
     StoreDelta {
         doc_id: Uuid,
         version: u64,
+        /// Peer whose change this is, recorded in the version history
+        author: Option<Uuid>,
         payload: Vec<u8>,
     },
-    /// Save a full snapshot (on room close)
+    /// Save a full snapshot (on room close or by the scheduler)
     SaveSnapshot {
         doc_id: Uuid,
         snapshot: Vec<u8>,
-        compact_version: u64,
+        /// Deltas up to this version are covered by the snapshot
+        compact_version: Option<u64>,
+        /// Told whether the snapshot and its WAL checkpoint were stored
+        done: Option<oneshot::Sender<Result<(), StoreError>>>,
     },
+    /// Reply once every command queued before this one has been handled
+    Flush(oneshot::Sender<()>),
     /// Shutdown the persistence task
     Shutdown,
 }
 
+/// Sending half of the persistence channel, counting the commands the
+/// persistence task has not picked up yet.
+#[derive(Clone)]
+struct PersistenceQueue {
+    tx: mpsc::UnboundedSender<PersistenceCommand>,
+    depth: Arc<AtomicU64>,
+}
+
+impl PersistenceQueue {
+    fn send(&self, cmd: PersistenceCommand) -> Result<(), mpsc::error::SendError<PersistenceCommand>> {
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(cmd).inspect_err(|_| {
+            self.depth.fetch_sub(1, Ordering::Relaxed);
+        })
+    }
+
+    /// Commands queued and not yet received by the persistence task.
+    fn depth(&self) -> u64 {
+        self.depth.load(Ordering::Relaxed)
+    }
+}
+
 /// Document room: Yrs Doc + broadcast group.
 struct DocumentRoom {
     /// Authoritative Yrs document
     doc: yrs::Doc,
     /// Broadcast group for fan-out
     broadcast: Arc<BroadcastGroup>,
+    /// Deltas accepted since the last snapshot was queued
+    deltas_since_snapshot: u64,
+    /// When the last snapshot was queued (or the room opened)
+    last_snapshot: Instant,
 }
 
 impl DocumentRoom {
-    fn new(broadcast_capacity: usize) -> Self {
+    fn new(config: &ServerConfig) -> Self {
         Self {
             doc: yrs::Doc::new(),
-            broadcast: Arc::new(BroadcastGroup::new(broadcast_capacity)),
+            broadcast: Arc::new(
+                BroadcastGroup::new(config.broadcast_capacity).with_compression(config.compression_threshold),
+            ),
+            deltas_since_snapshot: 0,
+            last_snapshot: Instant::now(),
         }
     }
+
+    /// Whether the scheduler should snapshot this room now.
+    fn snapshot_due(&self, config: &ServerConfig) -> bool {
+        self.deltas_since_snapshot > 0
+            && (config.snapshot_every_deltas.is_some_and(|n| self.deltas_since_snapshot >= n)
+                || config.snapshot_interval_secs.is_some_and(|secs| {
+                    self.last_snapshot.elapsed() >= Duration::from_secs(secs)
+                }))
+    }
+
+    /// Queue a full snapshot, compacting the deltas it covers.
+    ///
+    /// Called with the rooms lock held, so every delta below the WAL's next
+    /// sequence has been applied to `doc` already.
+    fn queue_snapshot(
+        &mut self,
+        doc_id: Uuid,
+        store: &DocumentStore,
+        ptx: &PersistenceQueue,
+    ) {
+        self.queue_snapshot_reporting(doc_id, store, ptx, None);
+    }
+
+    /// [`Self::queue_snapshot`], telling `done` how storing it went.
+    fn queue_snapshot_reporting(
+        &mut self,
+        doc_id: Uuid,
+        store: &DocumentStore,
+        ptx: &PersistenceQueue,
+        done: Option<oneshot::Sender<Result<(), StoreError>>>,
+    ) {
+        let snapshot = {
+            let txn = yrs::Transact::transact(&self.doc);
+            txn.encode_state_as_update_v1(&yrs::StateVector::default())
+        };
+        let _ = ptx.send(PersistenceCommand::SaveSnapshot {
+            doc_id,
+            snapshot,
+            compact_version: store.wal_sequence().checked_sub(1),
+            done,
+        });
+        self.deltas_since_snapshot = 0;
+        self.last_snapshot = Instant::now();
+    }
 }
 
 /// The sync server.
@@ -133,29 +359,41 @@ pub struct SyncServer {
     room_manager: Arc<RoomManager>,
     /// Server-wide statistics
     stats: Arc<RwLock<ServerStats>>,
+    /// Per-document role grants
+    permissions: Arc<Permissions>,
     /// Persistent document store (optional)
     store: Option<Arc<DocumentStore>>,
-    /// Global delta version counter for persistence
-    delta_version: Arc<AtomicU64>,
     /// Atomic counters for persistence stats (lock-free on hot path)
     persisted_deltas_counter: Arc<AtomicU64>,
     persisted_snapshots_counter: Arc<AtomicU64>,
+    wal_checkpoints_counter: Arc<AtomicU64>,
+    compacted_deltas_counter: Arc<AtomicU64>,
+    scheduled_snapshots_counter: Arc<AtomicU64>,
+    /// Wakes the snapshot scheduler when a room crosses its delta threshold
+    snapshot_due: Arc<Notify>,
+    /// Admin evictions, watched by every connection
+    evictions: tokio::sync::broadcast::Sender<Eviction>,
     /// Channel to send persistence commands to background task.
     /// Dropping this sender causes the background task to exit.
-    persistence_tx: Option<mpsc::UnboundedSender<PersistenceCommand>>,
+    persistence_tx: Option<PersistenceQueue>,
     /// Handle to the background persistence task (for join on drop)
     persistence_handle: Option<tokio::task::JoinHandle<()>>,
+    /// Handle to the snapshot scheduler task (aborted on drop)
+    scheduler_handle: Option<tokio::task::JoinHandle<()>>,
+    /// This node's side of its cluster
+    cluster: Option<Arc<ClusterNode>>,
+    /// Handle to the task handling relayed messages (aborted on drop)
+    relay_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl SyncServer {
     /// Create a new sync server with the given configuration.
     pub fn new(config: ServerConfig) -> Self {
-        let room_manager = Arc::new(RoomManager::new(config.broadcast_capacity));
-
         // Open persistent storage if configured
         let store = config.storage_path.as_ref().map(|path| {
             let store_config = StoreConfig {
                 path: path.clone(),
+                backend: config.storage_backend.clone(),
                 ..StoreConfig::default()
             };
             Arc::new(
@@ -163,45 +401,134 @@ impl SyncServer {
                     .expect("Failed to open document store"),
             )
         });
+        Self::with_store(config, store)
+    }
 
-        let delta_version = Arc::new(AtomicU64::new(
-            store.as_ref().map_or(0, |s| s.wal_sequence()),
-        ));
+    /// Create a sync server on an already opened store, ignoring
+    /// `config.storage_path`.
+    pub(crate) fn with_store(config: ServerConfig, store: Option<Arc<DocumentStore>>) -> Self {
+        let room_manager = Arc::new(RoomManager::new(config.broadcast_capacity));
+        let permissions = Arc::new(Permissions::new(config.default_role));
 
         let persisted_deltas_counter = Arc::new(AtomicU64::new(0));
         let persisted_snapshots_counter = Arc::new(AtomicU64::new(0));
+        let wal_checkpoints_counter = Arc::new(AtomicU64::new(0));
+        let compacted_deltas_counter = Arc::new(AtomicU64::new(0));
+        let scheduled_snapshots_counter = Arc::new(AtomicU64::new(0));
 
         // Spawn background persistence task if storage is configured
         let (persistence_tx, persistence_handle) = if let Some(ref s) = store {
             let (tx, mut rx) = mpsc::unbounded_channel::<PersistenceCommand>();
+            let depth = Arc::new(AtomicU64::new(0));
+            let queued = depth.clone();
             let store_clone = s.clone();
             let deltas_counter = persisted_deltas_counter.clone();
             let snapshots_counter = persisted_snapshots_counter.clone();
+            let checkpoints_counter = wal_checkpoints_counter.clone();
+            let compacted_counter = compacted_deltas_counter.clone();
+            let sync_interval = Duration::from_millis(config.wal_sync_interval_ms.max(1));
+            let retention = config.history_retention_secs.map(Duration::from_secs);
 
             let handle = tokio::spawn(async move {
-                while let Some(cmd) = rx.recv().await {
+                let mut fsync = tokio::time::interval(sync_interval);
+                fsync.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                // WAL appends made since the last fsync
+                let mut unsynced = false;
+                // Highest WAL sequence stored as a delta, with every earlier
+                // one; deltas arrive in sequence order. Cleared for good by a
+                // failed write so the WAL keeps whatever was not stored.
+                let mut stored_through: Option<u64> = None;
+                let mut intact = true;
+
+                loop {
+                    let cmd = tokio::select! {
+                        cmd = rx.recv() => match cmd {
+                            Some(cmd) => {
+                                queued.fetch_sub(1, Ordering::Relaxed);
+                                cmd
+                            }
+                            None => break,
+                        },
+                        _ = fsync.tick() => {
+                            if std::mem::take(&mut unsynced) {
+                                if let Err(e) = store_clone.sync_wal() {
+                                    log::error!("Background WAL fsync: {e}");
+                                }
+                            }
+                            continue;
+                        }
+                    };
+
                     match cmd {
-                        PersistenceCommand::StoreDelta { doc_id, version, payload } => {
-                            match store_clone.store_delta(doc_id, version, &payload) {
+                        PersistenceCommand::StoreDelta { doc_id, version, author, payload } => {
+                            unsynced = true;
+                            match store_clone.store_delta_by(doc_id, version, author, &payload) {
                                 Ok(_) => {
                                     deltas_counter.fetch_add(1, Ordering::Relaxed);
+                                    if intact {
+                                        stored_through = Some(version);
+                                    }
                                 }
                                 Err(e) => {
+                                    intact = false;
+                                    stored_through = None;
                                     log::error!("Background persist delta for doc {doc_id}: {e}");
                                 }
                             }
                         }
-                        PersistenceCommand::SaveSnapshot { doc_id, snapshot, compact_version } => {
+                        PersistenceCommand::SaveSnapshot { doc_id, snapshot, compact_version, done } => {
                             match store_clone.save_snapshot(doc_id, &snapshot) {
                                 Ok(_) => {
-                                    let _ = store_clone.compact_deltas(doc_id, compact_version);
+                                    if let Some(version) = compact_version {
+                                        if let Ok(removed) = store_clone.compact_deltas(doc_id, version) {
+                                            compacted_counter.fetch_add(removed, Ordering::Relaxed);
+                                        }
+                                    }
+                                    if let Some(retention) = retention {
+                                        let cutoff = SystemTime::now()
+                                            .checked_sub(retention)
+                                            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                                            .map_or(0, |d| d.as_millis() as u64);
+                                        match store_clone.prune_history(doc_id, cutoff) {
+                                            Ok(0) => {}
+                                            Ok(pruned) => log::info!("Squashed {pruned} old versions of doc {doc_id}"),
+                                            Err(e) => log::error!("History pruning for doc {doc_id}: {e}"),
+                                        }
+                                    }
                                     snapshots_counter.fetch_add(1, Ordering::Relaxed);
                                     log::info!("Background persisted snapshot for doc {doc_id}");
                                 }
                                 Err(e) => {
                                     log::error!("Background persist snapshot for doc {doc_id}: {e}");
+                                    if let Some(done) = done {
+                                        let _ = done.send(Err(e));
+                                    }
+                                    continue;
+                                }
+                            }
+
+                            // Checkpoint: once the stored deltas are durable,
+                            // the WAL entries behind them are redundant
+                            let mut checkpointed = Ok(());
+                            if let Some(seq) = stored_through.take() {
+                                match store_clone.sync_wal().and_then(|_| store_clone.wal_truncate(seq)) {
+                                    Ok(removed) => {
+                                        unsynced = false;
+                                        checkpoints_counter.fetch_add(1, Ordering::Relaxed);
+                                        log::debug!("WAL checkpoint at {seq}: {removed} entries truncated");
+                                    }
+                                    Err(e) => {
+                                        log::error!("WAL checkpoint at {seq}: {e}");
+                                        checkpointed = Err(e);
+                                    }
                                 }
                             }
+                            if let Some(done) = done {
+                                let _ = done.send(checkpointed);
+                            }
+                        }
+                        PersistenceCommand::Flush(done) => {
+                            let _ = done.send(());
                         }
                         PersistenceCommand::Shutdown => {
                             log::info!("Persistence task shutting down");
@@ -211,22 +538,234 @@ impl SyncServer {
                 }
             });
 
-            (Some(tx), Some(handle))
+            (Some(PersistenceQueue { tx, depth }), Some(handle))
         } else {
             (None, None)
         };
 
+        let rooms = Arc::new(RwLock::new(HashMap::new()));
+        let snapshot_due = Arc::new(Notify::new());
+        let scheduler_handle = match (&store, &persistence_tx) {
+            (Some(s), Some(ptx))
+                if config.snapshot_every_deltas.is_some() || config.snapshot_interval_secs.is_some() =>
+            {
+                Some(tokio::spawn(Self::run_snapshot_scheduler(
+                    config.clone(),
+                    rooms.clone(),
+                    s.clone(),
+                    ptx.clone(),
+                    snapshot_due.clone(),
+                    scheduled_snapshots_counter.clone(),
+                )))
+            }
+            _ => None,
+        };
+
+        let cluster = config.cluster.clone().map(|c| Arc::new(ClusterNode::new(c)));
+        let relay_handle = cluster.as_ref().map(|node| {
+            tokio::spawn(Self::run_relay(
+                config.clone(),
+                rooms.clone(),
+                store.clone(),
+                persistence_tx.clone(),
+                snapshot_due.clone(),
+                node.clone(),
+            ))
+        });
+
         Self {
             config,
-            rooms: Arc::new(RwLock::new(HashMap::new())),
+            rooms,
             room_manager,
             stats: Arc::new(RwLock::new(ServerStats::default())),
+            permissions,
             store,
-            delta_version,
             persisted_deltas_counter,
             persisted_snapshots_counter,
+            wal_checkpoints_counter,
+            compacted_deltas_counter,
+            scheduled_snapshots_counter,
+            snapshot_due,
+            evictions: tokio::sync::broadcast::channel(64).0,
             persistence_tx,
             persistence_handle,
+            scheduler_handle,
+            cluster,
+            relay_handle,
+        }
+    }
+
+    /// Background task snapshotting open rooms (see [`DocumentRoom::snapshot_due`]).
+    ///
+    /// Rooms are otherwise only snapshotted when their last peer leaves, so
+    /// a document that is always open would grow its delta log forever.
+    /// Wakes when the hot path reports a room over its delta threshold, and
+    /// on a timer a quarter of `snapshot_interval_secs` for the age check.
+    async fn run_snapshot_scheduler(
+        config: ServerConfig,
+        rooms: Arc<RwLock<HashMap<Uuid, DocumentRoom>>>,
+        store: Arc<DocumentStore>,
+        ptx: PersistenceQueue,
+        snapshot_due: Arc<Notify>,
+        scheduled: Arc<AtomicU64>,
+    ) {
+        let period = config
+            .snapshot_interval_secs
+            .map_or(Duration::from_secs(60), |secs| Duration::from_secs(secs) / 4)
+            .max(Duration::from_millis(10));
+        let mut timer = tokio::time::interval(period);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = snapshot_due.notified() => {}
+                _ = timer.tick() => {}
+            }
+
+            let mut rooms_w = rooms.write().await;
+            for (doc_id, room) in rooms_w.iter_mut() {
+                if room.snapshot_due(&config) {
+                    room.queue_snapshot(*doc_id, &store, &ptx);
+                    scheduled.fetch_add(1, Ordering::Relaxed);
+                    log::debug!("Scheduled snapshot for doc {doc_id}");
+                }
+            }
+        }
+    }
+
+    /// Background task applying what other cluster nodes relay to the local
+    /// rooms, and renewing this node's storage leases.
+    async fn run_relay(
+        config: ServerConfig,
+        rooms: Arc<RwLock<HashMap<Uuid, DocumentRoom>>>,
+        store: Option<Arc<DocumentStore>>,
+        ptx: Option<PersistenceQueue>,
+        snapshot_due: Arc<Notify>,
+        cluster: Arc<ClusterNode>,
+    ) {
+        let mut incoming = cluster.take_incoming();
+        if incoming.is_none() {
+            log::error!("Relay of node {} is in use by another server", cluster.node_id());
+        }
+        let mut renew = tokio::time::interval(cluster.renew_period());
+        renew.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                envelope = async {
+                    match incoming.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => match envelope {
+                    Some(envelope) => {
+                        cluster.count_received();
+                        Self::handle_relayed(
+                            &config, &rooms, store.as_ref(), ptx.as_ref(), &snapshot_due, &cluster, envelope,
+                        ).await;
+                    }
+                    None => incoming = None,
+                },
+                _ = renew.tick() => cluster.renew(),
+            }
+        }
+    }
+
+    /// Apply a message from another node to the local room, as if one of
+    /// the room's own peers had sent it.
+    async fn handle_relayed(
+        config: &ServerConfig,
+        rooms: &RwLock<HashMap<Uuid, DocumentRoom>>,
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        snapshot_due: &Notify,
+        cluster: &ClusterNode,
+        envelope: RelayEnvelope,
+    ) {
+        let msg = envelope.message;
+        let did = msg.doc_id;
+        match msg.msg_type {
+            MessageType::Delta | MessageType::SyncStep2 => {
+                match yrs::Update::decode_v1(&msg.payload) {
+                    Ok(update) if !update.is_empty() => {}
+                    Ok(_) => return,
+                    Err(e) => {
+                        log::warn!("Undecodable update for doc {did} relayed by node {}: {e}", envelope.origin);
+                        return;
+                    }
+                }
+                let broadcast = {
+                    let mut rooms_w = rooms.write().await;
+                    let mut room = rooms_w.get_mut(&did);
+                    // The lease holder logs it even with the room closed
+                    // here, like a server-made change
+                    let logged = match Self::persist_delta(
+                        room.as_deref_mut(), store, ptx, Some(cluster), did, Some(msg.peer_id).filter(|p| !p.is_nil()), &msg.payload,
+                    ) {
+                        Ok(logged) => logged,
+                        Err(e) => {
+                            log::error!("WAL append for doc {did} relayed by node {}: {e}", envelope.origin);
+                            return;
+                        }
+                    };
+                    room.map(|room| {
+                        if logged {
+                            room.deltas_since_snapshot += 1;
+                            if room.snapshot_due(config) {
+                                snapshot_due.notify_one();
+                            }
+                        }
+                        room.broadcast.clone()
+                    })
+                };
+                if let Some(bc) = broadcast {
+                    let _ = bc.broadcast(&SyncMessage::delta(msg.peer_id, did, msg.clock, msg.payload));
+                }
+            }
+
+            MessageType::SyncStep1 => {
+                // A node opening the room: answer with what it is missing,
+                // from the open room or, on the lease holder, from storage
+                let Ok(remote_sv) = yrs::StateVector::decode_v1(&msg.payload) else {
+                    return;
+                };
+                let open = {
+                    let rooms_r = rooms.read().await;
+                    rooms_r
+                        .get(&did)
+                        .map(|room| yrs::Transact::transact(&room.doc).encode_diff_v1(&remote_sv))
+                };
+                let diff = match open {
+                    Some(diff) => Some(diff),
+                    None if cluster.holds(did) => match Self::flushed_store(store, ptx, did).await {
+                        Ok((store, _)) => {
+                            let doc = yrs::Doc::new();
+                            match Self::load_persisted(store, did, &doc) {
+                                Ok(Some(_)) => Some(yrs::Transact::transact(&doc).encode_diff_v1(&remote_sv)),
+                                Ok(None) => None,
+                                Err(e) => {
+                                    log::error!("Load persisted doc {did} for node {}: {e}", envelope.origin);
+                                    None
+                                }
+                            }
+                        }
+                        Err(_) => None,
+                    },
+                    None => None,
+                };
+                if let Some(diff) = diff {
+                    cluster.publish(Some(envelope.origin), SyncMessage::sync_step2(Uuid::nil(), did, diff));
+                }
+            }
+
+            MessageType::Awareness | MessageType::PeerJoined | MessageType::PeerLeft => {
+                let broadcast = rooms.read().await.get(&did).map(|room| room.broadcast.clone());
+                if let Some(bc) = broadcast {
+                    let _ = bc.broadcast(&msg);
+                }
+            }
+
+            other => log::debug!("Ignoring {other:?} relayed by node {}", envelope.origin),
         }
     }
 
@@ -248,37 +787,432 @@ impl SyncServer {
     /// Recover persisted documents from storage on startup.
     ///
     /// Loads all previously persisted documents into rooms so they are
-    /// immediately available when peers reconnect.
+    /// immediately available when peers reconnect. Each document is rebuilt
+    /// from its last snapshot plus the deltas written after it, so edits
+    /// made since the last room close survive a crash. WAL entries that
+    /// never reached the delta log are restored into it first.
     pub async fn recover(&self) -> Result<usize, Box<dyn std::error::Error>> {
         let store = match &self.store {
             Some(s) => s,
             None => return Ok(0),
         };
 
+        let replayed = Self::replay_wal(store)?;
+        if replayed > 0 {
+            self.stats.write().await.wal_replayed += replayed as u64;
+        }
+
         let doc_ids = store.list_documents()?;
         let mut recovered = 0;
 
         for doc_id in &doc_ids {
-            if let Ok(snapshot) = store.load_snapshot(*doc_id) {
-                let mut rooms_w = self.rooms.write().await;
-                let room = rooms_w
-                    .entry(*doc_id)
-                    .or_insert_with(|| DocumentRoom::new(self.config.broadcast_capacity));
-
-                // Apply the persisted snapshot to the Yrs doc
-                if let Ok(update) = yrs::Update::decode_v1(&snapshot) {
-                    let mut txn = yrs::Transact::transact_mut(&room.doc);
-                    let _ = txn.apply_update(update);
-                }
-                recovered += 1;
-                log::info!("Recovered document {doc_id} from storage");
-            }
+            let room = DocumentRoom::new(&self.config);
+            let Some(replayed) = Self::load_persisted(store, *doc_id, &room.doc)? else {
+                continue;
+            };
+
+            let mut rooms_w = self.rooms.write().await;
+            let room = rooms_w.entry(*doc_id).or_insert(room);
+            room.deltas_since_snapshot += replayed as u64;
+            self.validate_recovered(*doc_id, &room.doc).await;
+            recovered += 1;
+            log::info!("Recovered document {doc_id} from storage ({replayed} deltas replayed)");
         }
 
         log::info!("Recovery complete: {recovered}/{} documents restored", doc_ids.len());
         Ok(recovered)
     }
 
+    /// Store WAL entries missing from the delta log, then truncate the WAL.
+    ///
+    /// Deltas are versioned by their WAL sequence, so an entry whose delta
+    /// was already written is recognised and skipped. Returns the number of
+    /// deltas restored.
+    fn replay_wal(store: &DocumentStore) -> Result<usize, StoreError> {
+        let entries = store.wal_read_since(0)?;
+        let Some(&(last, _, _)) = entries.last() else {
+            return Ok(0);
+        };
+
+        let mut replayed = 0;
+        for (seq, doc_id, payload) in &entries {
+            if !store.has_delta(*doc_id, *seq)? {
+                store.store_delta(*doc_id, *seq, payload)?;
+                replayed += 1;
+            }
+        }
+        store.sync_wal()?;
+        store.wal_truncate(last)?;
+        log::info!("WAL replay: {replayed}/{} entries restored", entries.len());
+        Ok(replayed)
+    }
+
+    /// Make an accepted delta durable before it is broadcast: append it to
+    /// the WAL, then queue it for the delta log under its WAL sequence.
+    /// The WAL is fsynced by the persistence task on its interval.
+    ///
+    /// The author is only kept in the version history; a delta restored
+    /// from the WAL after a crash has none.
+    fn log_delta(
+        store: &DocumentStore,
+        ptx: &PersistenceQueue,
+        doc_id: Uuid,
+        author: Option<Uuid>,
+        payload: &[u8],
+    ) -> Result<(), StoreError> {
+        let version = store.wal_append(doc_id, payload)?;
+        let _ = ptx.send(PersistenceCommand::StoreDelta {
+            doc_id,
+            version,
+            author,
+            payload: payload.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Log an accepted delta when this node persists `doc_id`, then apply
+    /// it to `room` (if open here).
+    ///
+    /// A node persists a document always on a single server, and in a
+    /// cluster while it holds the document's storage lease. A node taking
+    /// the lease over snapshots its copy of the room right away, so its
+    /// storage holds the whole document. Returns whether the delta was
+    /// logged; if the WAL append fails the delta is not applied, and must
+    /// not be broadcast either.
+    fn persist_delta(
+        room: Option<&mut DocumentRoom>,
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        cluster: Option<&ClusterNode>,
+        doc_id: Uuid,
+        author: Option<Uuid>,
+        payload: &[u8],
+    ) -> Result<bool, StoreError> {
+        // Only a node with the room open may take the lease over
+        let persisting = match (store, ptx) {
+            (Some(store), Some(ptx)) => match (cluster, &room) {
+                (None, _) => Some((store, ptx, Claim::Held)),
+                (Some(cluster), Some(_)) => Some((store, ptx, cluster.claim(doc_id))),
+                (Some(cluster), None) if cluster.holds(doc_id) => Some((store, ptx, Claim::Held)),
+                (Some(_), None) => None,
+            },
+            _ => None,
+        }
+        .filter(|(_, _, claim)| claim.persists());
+
+        if let Some((store, ptx, _)) = persisting {
+            Self::log_delta(store, ptx, doc_id, author, payload)?;
+        }
+        let Some(room) = room else {
+            return Ok(persisting.is_some());
+        };
+        if let Ok(update) = yrs::Update::decode_v1(payload) {
+            let mut txn = yrs::Transact::transact_mut(&room.doc);
+            let _ = txn.apply_update(update);
+        }
+        // After applying, so the snapshot covers the delta just logged
+        if let Some((store, ptx, Claim::Acquired)) = persisting {
+            room.queue_snapshot(doc_id, store, ptx);
+        }
+        Ok(persisting.is_some())
+    }
+
+    /// The store and persistence queue, once every delta queued so far has
+    /// been written, so history reads see the latest edits. Servers without
+    /// storage keep no history.
+    async fn flushed_store<'a>(
+        store: Option<&'a Arc<DocumentStore>>,
+        ptx: Option<&'a PersistenceQueue>,
+        doc_id: Uuid,
+    ) -> Result<(&'a DocumentStore, &'a PersistenceQueue), StoreError> {
+        let (Some(store), Some(ptx)) = (store, ptx) else {
+            return Err(StoreError::NotFound(doc_id));
+        };
+        let (done, flushed) = oneshot::channel();
+        if ptx.send(PersistenceCommand::Flush(done)).is_ok() {
+            let _ = flushed.await;
+        }
+        Ok((store, ptx))
+    }
+
+    /// Answer a history request from a peer (or the server API).
+    #[allow(clippy::too_many_arguments)]
+    async fn serve_history(
+        config: &ServerConfig,
+        rooms: &RwLock<HashMap<Uuid, DocumentRoom>>,
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        snapshot_due: &Notify,
+        cluster: Option<&ClusterNode>,
+        doc_id: Uuid,
+        author: Option<Uuid>,
+        request: HistoryMessage,
+    ) -> HistoryMessage {
+        let reply = match request {
+            HistoryMessage::ListVersions => Self::versions(store, ptx, doc_id)
+                .await
+                .map(HistoryMessage::Versions),
+            HistoryMessage::GetSnapshot(at) => Self::snapshot_at(store, ptx, doc_id, at)
+                .await
+                .map(|(version, state)| HistoryMessage::Snapshot { version, state }),
+            HistoryMessage::Restore(at) => Self::restore(config, rooms, store, ptx, snapshot_due, cluster, doc_id, at, author)
+                .await
+                .map(|version| HistoryMessage::Restored { version }),
+            HistoryMessage::CreateCheckpoint(name) => Self::checkpoint_head(store, ptx, doc_id, &name, author)
+                .await
+                .map(HistoryMessage::CheckpointCreated),
+            HistoryMessage::ListCheckpoints => match Self::flushed_store(store, ptx, doc_id).await {
+                Ok((store, _)) => store.list_checkpoints(doc_id).map(HistoryMessage::Checkpoints),
+                Err(e) => Err(e),
+            },
+            HistoryMessage::Fork(name) => Self::fork(store, ptx, doc_id, &name, author)
+                .await
+                .map(|(doc_id, origin)| HistoryMessage::Forked { doc_id, origin }),
+            HistoryMessage::MergeBranch(branch_id) => {
+                // Branches are merged from the room of the document they
+                // were forked from
+                let forked_from = store
+                    .and_then(|s| s.load_metadata(branch_id).ok())
+                    .and_then(|meta| meta.forked_from);
+                if forked_from.is_some_and(|origin| origin.doc_id == doc_id) {
+                    Self::merge(config, rooms, store, ptx, snapshot_due, cluster, branch_id, author)
+                        .await
+                        .map(HistoryMessage::Merged)
+                } else {
+                    Err(StoreError::NotABranch(branch_id))
+                }
+            }
+            other => return HistoryMessage::Failed(format!("not a request: {other:?}")),
+        };
+        reply.unwrap_or_else(|e| HistoryMessage::Failed(e.to_string()))
+    }
+
+    /// Every recorded version of `doc_id`, oldest first.
+    async fn versions(
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        doc_id: Uuid,
+    ) -> Result<Vec<VersionInfo>, StoreError> {
+        let (store, _) = Self::flushed_store(store, ptx, doc_id).await?;
+        store.list_versions(doc_id)
+    }
+
+    /// The version `at` resolves to and the full Yrs state of `doc_id` then.
+    async fn snapshot_at(
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        doc_id: Uuid,
+        at: VersionRef,
+    ) -> Result<(u64, Vec<u8>), StoreError> {
+        let (store, _) = Self::flushed_store(store, ptx, doc_id).await?;
+        let version = at.resolve(store, doc_id)?;
+        Ok((version, history::materialize(store, doc_id, version)?))
+    }
+
+    /// Bring `doc_id` back to its state at `at` with a new change by
+    /// `author`. The change is applied to the open room, logged like any
+    /// delta and broadcast as coming from the server, so the requesting
+    /// peer receives it too. Returns the version restored.
+    #[allow(clippy::too_many_arguments)]
+    async fn restore(
+        config: &ServerConfig,
+        rooms: &RwLock<HashMap<Uuid, DocumentRoom>>,
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        snapshot_due: &Notify,
+        cluster: Option<&ClusterNode>,
+        doc_id: Uuid,
+        at: VersionRef,
+        author: Option<Uuid>,
+    ) -> Result<u64, StoreError> {
+        let (flushed, _) = Self::flushed_store(store, ptx, doc_id).await?;
+        let version = at.resolve(flushed, doc_id)?;
+        let Some(update) = history::restore_update(flushed, doc_id, version)? else {
+            return Ok(version);
+        };
+
+        Self::commit(config, rooms, store, ptx, snapshot_due, cluster, doc_id, author, update).await?;
+        log::info!("Doc {doc_id} restored to version {version} by {author:?}");
+        Ok(version)
+    }
+
+    /// Name the latest version of `doc_id`.
+    async fn checkpoint_head(
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        doc_id: Uuid,
+        name: &str,
+        author: Option<Uuid>,
+    ) -> Result<Checkpoint, StoreError> {
+        let (store, _) = Self::flushed_store(store, ptx, doc_id).await?;
+        let head = store
+            .list_versions(doc_id)?
+            .last()
+            .map(|info| info.version)
+            .ok_or(StoreError::VersionNotFound { doc_id, version: 0 })?;
+        let checkpoint = store.create_checkpoint(doc_id, name, head, author)?;
+        log::info!("Doc {doc_id}: checkpoint {name:?} at version {head}");
+        Ok(checkpoint)
+    }
+
+    /// Fork `doc_id` at checkpoint `name` into a new document.
+    async fn fork(
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        doc_id: Uuid,
+        name: &str,
+        author: Option<Uuid>,
+    ) -> Result<(Uuid, ForkOrigin), StoreError> {
+        let (store, _) = Self::flushed_store(store, ptx, doc_id).await?;
+        let branch_id = Uuid::new_v4();
+        let origin = history::fork(store, doc_id, name, branch_id, author)?;
+        log::info!("Doc {doc_id} forked at {name:?} into {branch_id}");
+        Ok((branch_id, origin))
+    }
+
+    /// Merge branch `branch_id` back into the document it was forked from,
+    /// as a change by `author`.
+    #[allow(clippy::too_many_arguments)]
+    async fn merge(
+        config: &ServerConfig,
+        rooms: &RwLock<HashMap<Uuid, DocumentRoom>>,
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        snapshot_due: &Notify,
+        cluster: Option<&ClusterNode>,
+        branch_id: Uuid,
+        author: Option<Uuid>,
+    ) -> Result<MergeReport, StoreError> {
+        let (flushed, _) = Self::flushed_store(store, ptx, branch_id).await?;
+        let merged = history::merge(flushed, branch_id)?;
+        let report = merged.report;
+        if let Some(update) = merged.update {
+            Self::commit(config, rooms, store, ptx, snapshot_due, cluster, report.target, author, update).await?;
+        }
+        log::info!(
+            "Branch {branch_id} merged into {}: {} layers touched on both sides",
+            report.target,
+            report.conflicts.len()
+        );
+        Ok(report)
+    }
+
+    /// Apply a server-made change to `doc_id`: to the log like any delta,
+    /// then the open room, then broadcast as coming from the server so the
+    /// peer that asked for it receives it too, on every node of a cluster.
+    /// A closed room picks the change up from the log when it reopens.
+    #[allow(clippy::too_many_arguments)]
+    async fn commit(
+        config: &ServerConfig,
+        rooms: &RwLock<HashMap<Uuid, DocumentRoom>>,
+        store: Option<&Arc<DocumentStore>>,
+        ptx: Option<&PersistenceQueue>,
+        snapshot_due: &Notify,
+        cluster: Option<&ClusterNode>,
+        doc_id: Uuid,
+        author: Option<Uuid>,
+        update: Vec<u8>,
+    ) -> Result<(), StoreError> {
+        let broadcast = {
+            let mut rooms_w = rooms.write().await;
+            let mut room = rooms_w.get_mut(&doc_id);
+            let logged = Self::persist_delta(room.as_deref_mut(), store, ptx, cluster, doc_id, author, &update)?;
+            room.map(|room| {
+                if logged {
+                    room.deltas_since_snapshot += 1;
+                    if room.snapshot_due(config) {
+                        snapshot_due.notify_one();
+                    }
+                }
+                room.broadcast.clone()
+            })
+        };
+        let delta = SyncMessage::delta(Uuid::nil(), doc_id, 0, update);
+        if let Some(bc) = broadcast {
+            let _ = bc.broadcast(&delta);
+        }
+        if let Some(cluster) = cluster {
+            cluster.publish(None, delta);
+        }
+        Ok(())
+    }
+
+    /// Apply the persisted state of `doc_id` to `doc`: the snapshot, if any,
+    /// then every stored delta in version order.
+    ///
+    /// Snapshot compaction moves the log past the deltas a snapshot covers,
+    /// so the remaining ones are exactly those written after it. A crash
+    /// between the two writes can leave covered deltas in the log; Yrs
+    /// ignores updates it has already integrated, so replaying them is
+    /// harmless.
+    ///
+    /// Returns the number of deltas replayed, or `None` if nothing is stored.
+    fn load_persisted(
+        store: &DocumentStore,
+        doc_id: Uuid,
+        doc: &yrs::Doc,
+    ) -> Result<Option<usize>, StoreError> {
+        let snapshot = match store.load_snapshot(doc_id) {
+            Ok(snapshot) => Some(snapshot),
+            Err(StoreError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let deltas = store.load_deltas_since(doc_id, 0)?;
+        if snapshot.is_none() && deltas.is_empty() {
+            return Ok(None);
+        }
+
+        let mut txn = yrs::Transact::transact_mut(doc);
+        for payload in snapshot.iter().chain(deltas.iter().map(|(_, delta)| delta)) {
+            match yrs::Update::decode_v1(payload) {
+                Ok(update) => {
+                    if let Err(e) = txn.apply_update(update) {
+                        log::warn!("Skipping persisted update for doc {doc_id}: {e}");
+                    }
+                }
+                Err(e) => log::warn!("Skipping undecodable update for doc {doc_id}: {e}"),
+            }
+        }
+        Ok(Some(deltas.len()))
+    }
+
+    /// Run the configured invariant check on a freshly recovered document.
+    async fn validate_recovered(&self, doc_id: Uuid, doc: &yrs::Doc) {
+        let (found, fixed) = match self.config.recovery_validation {
+            RecoveryValidation::Off => return,
+            RecoveryValidation::Report => {
+                let diagnostics = logos_core::validate::validate_doc(doc);
+                for d in &diagnostics {
+                    log::warn!("Recovered document {doc_id}: {d}");
+                }
+                (diagnostics.len(), 0)
+            }
+            RecoveryValidation::Repair => {
+                let (report, update) = logos_core::validate::repair_doc(doc);
+                for d in &report.fixed {
+                    log::warn!("Recovered document {doc_id}: repaired {d}");
+                }
+                for d in &report.remaining {
+                    log::error!("Recovered document {doc_id}: unrepaired {d}");
+                }
+                if !update.is_empty() {
+                    if let (Some(store), Some(ptx)) = (&self.store, &self.persistence_tx) {
+                        if let Err(e) = Self::log_delta(store, ptx, doc_id, None, &update) {
+                            log::error!("WAL append of repair for doc {doc_id}: {e}");
+                        }
+                    }
+                }
+                (report.fixed.len() + report.remaining.len(), report.fixed.len())
+            }
+        };
+
+        if found > 0 {
+            let mut stats = self.stats.write().await;
+            stats.recovery_diagnostics += found as u64;
+            stats.recovery_repairs += fixed as u64;
+        }
+    }
+
     /// Start listening for WebSocket connections.
     ///
     /// This runs the server event loop. Call from an async runtime.
@@ -300,15 +1234,18 @@ impl SyncServer {
             let stats = self.stats.clone();
             let config = self.config.clone();
             let room_manager = self.room_manager.clone();
+            let permissions = self.permissions.clone();
             let store = self.store.clone();
-            let delta_version = self.delta_version.clone();
             let persistence_tx = self.persistence_tx.clone();
+            let snapshot_due = self.snapshot_due.clone();
+            let evictions = self.evictions.subscribe();
+            let cluster = self.cluster.clone();
 
             tokio::spawn(async move {
                 if let Err(e) =
                     Self::handle_connection(
                         stream, addr, rooms, stats, config, room_manager,
-                        store, delta_version, persistence_tx,
+                        permissions, store, persistence_tx, snapshot_due, evictions, cluster,
                     ).await
                 {
                     log::error!("Connection error from {addr}: {e}");
@@ -325,11 +1262,18 @@ impl SyncServer {
         stats: Arc<RwLock<ServerStats>>,
         config: ServerConfig,
         _room_manager: Arc<RoomManager>,
+        permissions: Arc<Permissions>,
         store: Option<Arc<DocumentStore>>,
-        delta_version: Arc<AtomicU64>,
-        persistence_tx: Option<mpsc::UnboundedSender<PersistenceCommand>>,
+        persistence_tx: Option<PersistenceQueue>,
+        snapshot_due: Arc<Notify>,
+        evictions: tokio::sync::broadcast::Receiver<Eviction>,
+        cluster: Option<Arc<ClusterNode>>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let ws_stream = tokio_tungstenite::accept_async(stream).await?;
+        let hard_limit = config.max_message_bytes.saturating_mul(2);
+        let ws_config = WebSocketConfig::default()
+            .max_message_size(Some(hard_limit))
+            .max_frame_size(Some(hard_limit));
+        let ws_stream = tokio_tungstenite::accept_async_with_config(stream, Some(ws_config)).await?;
         let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
         log::info!("WebSocket connection established from {addr}");
@@ -343,17 +1287,48 @@ impl SyncServer {
         // State for this connection
         let mut peer_id: Option<Uuid> = None;
         let mut doc_id: Option<Uuid> = None;
-        let mut broadcast_rx: Option<tokio::sync::broadcast::Receiver<Arc<Vec<u8>>>> = None;
+        let mut broadcast_rx: Option<tokio::sync::broadcast::Receiver<Frame>> = None;
+        let mut joined_room: Option<Arc<BroadcastGroup>> = None;
+        // Lower bound of the peer's document state: the server's state vector
+        // when the peer was last sent a full diff. Lag resyncs diff from here.
+        let mut peer_sv = yrs::StateVector::default();
+        // Dropped if the server goes away first
+        let mut evictions = Some(evictions);
+        // WebSocket pings; every peer answers them without help from the
+        // application, and any frame received counts as a sign of life
+        let heartbeat_period = Duration::from_secs(config.heartbeat_interval_secs);
+        let mut heartbeat = (!heartbeat_period.is_zero()).then(|| {
+            tokio::time::interval_at(tokio::time::Instant::now() + heartbeat_period, heartbeat_period)
+        });
+        let mut missed_heartbeats = 0;
+        let mut limiter = PeerLimiter::new(&config.rate_limits, Instant::now());
+        // Latest rate-limited awareness update per slot (see `awareness_slot`),
+        // sent once the peer's awareness bucket refills
+        let mut held_awareness: BTreeMap<u8, SyncMessage> = BTreeMap::new();
+        // What the peer speaks; peers that send no hello predate it
+        let mut protocol = Hello::legacy();
+        // Compression threshold for messages to this peer, once agreed on
+        let mut compress_above: Option<usize> = None;
 
         // Process incoming messages
         loop {
+            let awareness_due = match held_awareness.is_empty() {
+                true => None,
+                false => limiter.next_token_at(MessageType::Awareness, Instant::now()),
+            };
+
             tokio::select! {
                 // Incoming WebSocket message
                 msg = ws_receiver.next() => {
+                    missed_heartbeats = 0;
                     match msg {
                         Some(Ok(Message::Binary(data))) => {
                             let bytes: Vec<u8> = data.into();
-                            match SyncMessage::decode(&bytes) {
+                            let decoded = match bytes.len() > config.max_message_bytes {
+                                true => Err(ProtocolError::TooLarge { size: bytes.len(), max: config.max_message_bytes }),
+                                false => SyncMessage::decode_limited(&bytes, config.max_message_bytes),
+                            };
+                            match decoded {
                                 Ok(sync_msg) => {
                                     {
                                         let mut s = stats.write().await;
@@ -361,46 +1336,150 @@ impl SyncServer {
                                         s.total_bytes += bytes.len() as u64;
                                     }
 
+                                    // Rate limits: updates and history requests over
+                                    // the limit are refused, awareness is held back
+                                    let now = Instant::now();
+                                    match sync_msg.msg_type {
+                                        MessageType::Delta | MessageType::SyncStep2 | MessageType::History
+                                            if !limiter.try_take(sync_msg.msg_type, now) =>
+                                        {
+                                            log::debug!("Throttled {:?} from {addr}", sync_msg.msg_type);
+                                            {
+                                                let mut s = stats.write().await;
+                                                if sync_msg.msg_type != MessageType::History {
+                                                    s.throttled_deltas += 1;
+                                                } else {
+                                                    s.throttled_history += 1;
+                                                }
+                                            }
+                                            let reject = SyncMessage::rejected(
+                                                sync_msg.doc_id,
+                                                &Rejection::RateLimited {
+                                                    msg_type: sync_msg.msg_type,
+                                                    clock: sync_msg.clock,
+                                                },
+                                            );
+                                            ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                            continue;
+                                        }
+                                        MessageType::Awareness => {
+                                            if let Some(slot) = Self::awareness_slot(&sync_msg.payload) {
+                                                if !limiter.try_take(MessageType::Awareness, now) {
+                                                    if held_awareness.insert(slot, sync_msg).is_some() {
+                                                        stats.write().await.coalesced_awareness += 1;
+                                                    }
+                                                    continue;
+                                                }
+                                                // Newer than anything held back
+                                                if held_awareness.remove(&slot).is_some() {
+                                                    stats.write().await.coalesced_awareness += 1;
+                                                }
+                                            }
+                                        }
+                                        _ => {}
+                                    }
+
                                     match sync_msg.msg_type {
                                         MessageType::PeerJoined => {
-                                            // First message: peer joins a document room
-                                            peer_id = Some(sync_msg.peer_id);
-                                            doc_id = Some(sync_msg.doc_id);
+                                            // Authenticate before touching any room state
+                                            if let Err(e) = Self::authenticate_join(&config, &sync_msg) {
+                                                log::warn!(
+                                                    "Rejected join from {addr} for doc {}: {e}",
+                                                    sync_msg.doc_id
+                                                );
+                                                stats.write().await.rejected_joins += 1;
+                                                let reject = SyncMessage::rejected(
+                                                    sync_msg.doc_id,
+                                                    &Rejection::Unauthorized(e),
+                                                );
+                                                ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                                ws_sender.send(Message::Close(None)).await?;
+                                                break;
+                                            }
+
+                                            // A second join moves the connection: leave the
+                                            // current room first, so it stops being counted
+                                            // there. Its subscription is replaced below, or
+                                            // dropped with the connection if the join fails
+                                            if let (Some(pid), Some(did)) = (peer_id.take(), doc_id.take()) {
+                                                Self::leave_room(&rooms, &stats, &store, &persistence_tx, &cluster, pid, did)
+                                                    .await;
+                                                held_awareness.clear();
+                                                peer_sv = yrs::StateVector::default();
+                                            }
 
                                             let info = sync_msg.peer_info().unwrap_or_else(|_| {
                                                 PeerInfo::with_id(sync_msg.peer_id, "Anonymous")
                                             });
 
-                                            // Get or create room
+                                            // Refuse the join if the room is at capacity
                                             let mut rooms_w = rooms.write().await;
+                                            let peers = match rooms_w.get(&sync_msg.doc_id) {
+                                                Some(room) => room.broadcast.peer_count().await,
+                                                None => 0,
+                                            };
+                                            if peers >= config.max_peers_per_room {
+                                                drop(rooms_w);
+                                                log::warn!(
+                                                    "Rejected join from {addr} for doc {}: room full ({peers} peers)",
+                                                    sync_msg.doc_id
+                                                );
+                                                stats.write().await.rejected_full_rooms += 1;
+                                                let reject = SyncMessage::rejected(
+                                                    sync_msg.doc_id,
+                                                    &Rejection::RoomFull { max_peers: config.max_peers_per_room },
+                                                );
+                                                ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                                ws_sender.send(Message::Close(None)).await?;
+                                                break;
+                                            }
+
+                                            // First message: peer joins a document room
+                                            peer_id = Some(sync_msg.peer_id);
+                                            doc_id = Some(sync_msg.doc_id);
+
+                                            // Get or create room
                                             let is_new_room = !rooms_w.contains_key(&sync_msg.doc_id);
                                             let room = rooms_w
                                                 .entry(sync_msg.doc_id)
-                                                .or_insert_with(|| DocumentRoom::new(config.broadcast_capacity));
+                                                .or_insert_with(|| DocumentRoom::new(&config));
 
-                                            // Load persisted snapshot into new room
+                                            // Load persisted snapshot + later deltas into new room
                                             if is_new_room {
                                                 if let Some(ref s) = store {
-                                                    if let Ok(snapshot) = s.load_snapshot(sync_msg.doc_id) {
-                                                        if let Ok(update) = yrs::Update::decode_v1(&snapshot) {
-                                                            let mut txn = yrs::Transact::transact_mut(&room.doc);
-                                                            let _ = txn.apply_update(update);
-                                                            log::info!("Loaded persisted snapshot for doc {}", sync_msg.doc_id);
+                                                    match Self::load_persisted(s, sync_msg.doc_id, &room.doc) {
+                                                        Ok(Some(replayed)) => {
+                                                            room.deltas_since_snapshot = replayed as u64;
+                                                            log::info!(
+                                                                "Loaded persisted doc {} ({replayed} deltas replayed)",
+                                                                sync_msg.doc_id
+                                                            );
                                                         }
+                                                        Ok(None) => {}
+                                                        Err(e) => log::error!(
+                                                            "Load persisted doc {}: {e}", sync_msg.doc_id
+                                                        ),
                                                     }
                                                 }
+                                                // Take the lease if nobody holds it, and ask
+                                                // the other nodes for what this copy lacks
+                                                if let Some(cluster) = &cluster {
+                                                    cluster.claim(sync_msg.doc_id);
+                                                    let sv = yrs::Transact::transact(&room.doc).state_vector();
+                                                    cluster.publish(
+                                                        None,
+                                                        SyncMessage::sync_step1(Uuid::nil(), sync_msg.doc_id, sv.encode_v1()),
+                                                    );
+                                                }
                                             }
 
                                             // Add peer to broadcast group
                                             let rx = room.broadcast.add_peer(info.clone()).await;
                                             broadcast_rx = Some(rx);
+                                            joined_room = Some(room.broadcast.clone());
 
-                                            // Send current state (SyncStep2)
-                                            // Scope the transaction so it's dropped before await
-                                            let sv = {
-                                                let txn = yrs::Transact::transact(&room.doc);
-                                                txn.state_vector().encode_v1()
-                                            };
+                                            // State is exchanged once the client sends its
+                                            // SyncStep1; see the handler below
 
                                             // Broadcast peer joined to others
                                             let join_msg = SyncMessage::peer_joined(
@@ -412,59 +1491,154 @@ impl SyncServer {
                                             let room_count = rooms_w.len();
                                             drop(rooms_w); // Release lock before await
 
-                                            let state_msg = SyncMessage::sync_step2(
-                                                Uuid::nil(),
-                                                sync_msg.doc_id,
-                                                sv,
-                                            );
-                                            let encoded = state_msg.encode()?;
-                                            ws_sender.send(Message::Binary(encoded.into())).await?;
-
                                             let _ = broadcast_clone.broadcast(&join_msg);
+                                            if let Some(cluster) = &cluster {
+                                                cluster.publish(None, join_msg);
+                                            }
 
                                             {
                                                 let mut s = stats.write().await;
                                                 s.active_rooms = room_count;
                                             }
 
+                                            if protocol.version == Hello::legacy().version {
+                                                stats.write().await.legacy_joins += 1;
+                                            }
                                             log::info!(
-                                                "Peer {} ({}) joined doc {}",
+                                                "Peer {} ({}) joined doc {} (protocol v{}, capabilities: {})",
                                                 info.name,
                                                 info.peer_id,
-                                                sync_msg.doc_id
+                                                sync_msg.doc_id,
+                                                protocol.version,
+                                                protocol.capabilities
                                             );
                                         }
 
-                                        MessageType::Delta => {
-                                            // Apply delta to server's Yrs doc, then broadcast
-                                            if let Some(did) = doc_id {
+                                        MessageType::Hello => match sync_msg.hello_payload() {
+                                            Ok(hello) => {
+                                                protocol = Self::server_hello(&config).negotiate(&hello);
+                                                compress_above = config
+                                                    .compression_threshold
+                                                    .filter(|_| protocol.capabilities.contains(Capabilities::COMPRESSION));
+                                                log::debug!(
+                                                    "Peer {} from {addr} offers v{}, agreed on v{} ({})",
+                                                    sync_msg.peer_id,
+                                                    hello.version,
+                                                    protocol.version,
+                                                    protocol.capabilities
+                                                );
+                                                let reply = SyncMessage::hello(Uuid::nil(), sync_msg.doc_id, &protocol);
+                                                ws_sender.send(Message::Binary(reply.encode()?.into())).await?;
+                                            }
+                                            Err(e) => log::warn!("Undecodable hello from {addr}: {e}"),
+                                        },
+
+                                        MessageType::Delta | MessageType::SyncStep2 => {
+                                            // Apply delta to server's Yrs doc, then broadcast.
+                                            // A SyncStep2 is the client's answer to our SyncStep1:
+                                            // the updates we were missing, checked like any delta.
+                                            let is_empty_step2 = sync_msg.msg_type == MessageType::SyncStep2
+                                                && yrs::Update::decode_v1(&sync_msg.payload)
+                                                    .is_ok_and(|u| u.is_empty());
+                                            if is_empty_step2 {
+                                                continue;
+                                            }
+                                            if let (Some(did), Some(pid)) = (doc_id, peer_id) {
+                                                // Looked up per write so role changes apply live
+                                                let role = permissions.role(did, pid);
+                                                let mut forbidden = false;
+                                                let mut failed = false;
                                                 let broadcast_clone = {
                                                     let mut rooms_w = rooms.write().await;
                                                     if let Some(room) = rooms_w.get_mut(&did) {
-                                                        // Apply to authoritative doc (sync, no await)
-                                                        if let Ok(update) = yrs::Update::decode_v1(&sync_msg.payload) {
-                                                            let mut txn = yrs::Transact::transact_mut(&room.doc);
-                                                            let _ = txn.apply_update(update);
+                                                        forbidden = !permissions::may_apply(role, &room.doc, &sync_msg.payload);
+                                                        if forbidden {
+                                                            None
+                                                        } else {
+                                                            // WAL first, then the authoritative doc, under
+                                                            // the lock so sequence numbers follow apply order
+                                                            match Self::persist_delta(
+                                                                Some(&mut *room),
+                                                                store.as_ref(),
+                                                                persistence_tx.as_ref(),
+                                                                cluster.as_deref(),
+                                                                did,
+                                                                Some(pid),
+                                                                &sync_msg.payload,
+                                                            ) {
+                                                                Ok(logged) => {
+                                                                    if logged {
+                                                                        room.deltas_since_snapshot += 1;
+                                                                        if room.snapshot_due(&config) {
+                                                                            snapshot_due.notify_one();
+                                                                        }
+                                                                    }
+                                                                    Some(room.broadcast.clone())
+                                                                }
+                                                                Err(e) => {
+                                                                    log::error!("WAL append for doc {did} from {pid}: {e}");
+                                                                    failed = true;
+                                                                    None
+                                                                }
+                                                            }
                                                         }
-                                                        Some(room.broadcast.clone())
                                                     } else {
                                                         None
                                                     }
                                                 };
 
-                                                // Queue delta for background persistence (LOCK-FREE, ~59ns)
-                                                if let Some(ref ptx) = persistence_tx {
-                                                    let version = delta_version.fetch_add(1, Ordering::SeqCst);
-                                                    let _ = ptx.send(PersistenceCommand::StoreDelta {
-                                                        doc_id: did,
-                                                        version,
-                                                        payload: sync_msg.payload.clone(),
-                                                    });
+                                                if failed {
+                                                    // Not durable, so neither applied nor broadcast
+                                                    stats.write().await.failed_writes += 1;
+                                                    let reject = SyncMessage::rejected(
+                                                        did,
+                                                        &Rejection::StorageFailed {
+                                                            msg_type: sync_msg.msg_type,
+                                                            clock: sync_msg.clock,
+                                                        },
+                                                    );
+                                                    ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                                    continue;
                                                 }
 
-                                                // Broadcast outside of lock (LOCK-FREE)
+                                                if forbidden {
+                                                    log::warn!(
+                                                        "Rejected {:?} from {pid} ({role}) on doc {did}",
+                                                        sync_msg.msg_type
+                                                    );
+                                                    stats.write().await.rejected_writes += 1;
+                                                    let reject = SyncMessage::rejected(
+                                                        did,
+                                                        &Rejection::Forbidden {
+                                                            role,
+                                                            msg_type: sync_msg.msg_type,
+                                                            clock: sync_msg.clock,
+                                                        },
+                                                    );
+                                                    ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                                    continue;
+                                                }
+
+                                                // Broadcast outside of lock (LOCK-FREE); peers
+                                                // receive sync answers as ordinary deltas
                                                 if let Some(bc) = broadcast_clone {
-                                                    let _ = bc.broadcast(&sync_msg);
+                                                    if sync_msg.msg_type == MessageType::SyncStep2 {
+                                                        let delta = SyncMessage::delta(
+                                                            pid,
+                                                            did,
+                                                            sync_msg.clock,
+                                                            sync_msg.payload.clone(),
+                                                        );
+                                                        let _ = bc.broadcast(&delta);
+                                                        if let Some(cluster) = &cluster {
+                                                            cluster.publish(None, delta);
+                                                        }
+                                                    } else {
+                                                        let _ = bc.broadcast(&sync_msg);
+                                                        if let Some(cluster) = &cluster {
+                                                            cluster.publish(None, sync_msg);
+                                                        }
+                                                    }
                                                 }
                                             }
                                         }
@@ -477,7 +1651,7 @@ impl SyncServer {
                                                     if let Some(room) = rooms_r.get(&did) {
                                                         let txn = yrs::Transact::transact(&room.doc);
                                                         if let Ok(remote_sv) = yrs::StateVector::decode_v1(&sync_msg.payload) {
-                                                            Some(txn.encode_diff_v1(&remote_sv))
+                                                            Some((txn.encode_diff_v1(&remote_sv), txn.state_vector()))
                                                         } else {
                                                             None
                                                         }
@@ -485,14 +1659,25 @@ impl SyncServer {
                                                         None
                                                     }
                                                 };
-                                                if let Some(diff) = diff_result {
+                                                if let Some((diff, sv)) = diff_result {
+                                                    // Reply with what the client is missing, then
+                                                    // ask for what we are missing in return
                                                     let response = SyncMessage::sync_step2(
                                                         Uuid::nil(),
                                                         did,
                                                         diff,
                                                     );
-                                                    let encoded = response.encode()?;
-                                                    ws_sender.send(Message::Binary(encoded.into())).await?;
+                                                    let request = SyncMessage::sync_step1(
+                                                        Uuid::nil(),
+                                                        did,
+                                                        sv.encode_v1(),
+                                                    );
+                                                    peer_sv = sv;
+                                                    let response = Self::compress_for_peer(
+                                                        &stats, &response, response.encode()?, compress_above,
+                                                    ).await?;
+                                                    ws_sender.send(Message::Binary(response.into())).await?;
+                                                    ws_sender.send(Message::Binary(request.encode()?.into())).await?;
                                                 }
                                             }
                                         }
@@ -524,10 +1709,61 @@ impl SyncServer {
                                                 };
                                                 if let Some(bc) = broadcast_clone {
                                                     let _ = bc.broadcast(&sync_msg);
+                                                    if let Some(cluster) = &cluster {
+                                                        cluster.publish(None, sync_msg);
+                                                    }
                                                 }
                                             }
                                         }
 
+                                        MessageType::History => {
+                                            if let (Some(did), Some(pid)) = (doc_id, peer_id) {
+                                                let request = match sync_msg.history_message() {
+                                                    Ok(request) => request,
+                                                    Err(e) => {
+                                                        log::warn!("Bad history request from {pid}: {e}");
+                                                        continue;
+                                                    }
+                                                };
+                                                // Browsing and forking only read this document
+                                                let role = permissions.role(did, pid);
+                                                if request.is_write() && !role.can_edit() {
+                                                    log::warn!("Rejected {request:?} from {pid} ({role}) on doc {did}");
+                                                    stats.write().await.rejected_writes += 1;
+                                                    let reject = SyncMessage::rejected(
+                                                        did,
+                                                        &Rejection::Forbidden {
+                                                            role,
+                                                            msg_type: MessageType::History,
+                                                            clock: sync_msg.clock,
+                                                        },
+                                                    );
+                                                    ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                                    continue;
+                                                }
+                                                let reply = Self::serve_history(
+                                                    &config,
+                                                    &rooms,
+                                                    store.as_ref(),
+                                                    persistence_tx.as_ref(),
+                                                    &snapshot_due,
+                                                    cluster.as_deref(),
+                                                    did,
+                                                    Some(pid),
+                                                    request,
+                                                )
+                                                .await;
+                                                if let HistoryMessage::Forked { doc_id: branch_id, .. } = &reply {
+                                                    permissions.set_role(*branch_id, pid, Role::Owner);
+                                                }
+                                                let reply = SyncMessage::history(Uuid::nil(), did, &reply);
+                                                let reply = Self::compress_for_peer(
+                                                    &stats, &reply, reply.encode()?, compress_above,
+                                                ).await?;
+                                                ws_sender.send(Message::Binary(reply.into())).await?;
+                                            }
+                                        }
+
                                         MessageType::Ping => {
                                             // Respond with pong
                                             if let Some(pid) = peer_id {
@@ -542,6 +1778,20 @@ impl SyncServer {
                                         }
                                     }
                                 }
+                                Err(ProtocolError::TooLarge { size, max }) => {
+                                    log::warn!("Rejected {size} byte message from {addr}");
+                                    stats.write().await.oversized_messages += 1;
+                                    let reject = SyncMessage::rejected(
+                                        doc_id.unwrap_or_default(),
+                                        &Rejection::TooLarge { size, max },
+                                    );
+                                    ws_sender.send(Message::Binary(reject.encode()?.into())).await?;
+                                }
+                                Err(ProtocolError::UnknownMessageType(t)) => {
+                                    // A newer peer; nobody here could make sense of it
+                                    log::debug!("Skipped message of unknown type {t} from {addr}");
+                                    stats.write().await.unknown_messages += 1;
+                                }
                                 Err(e) => {
                                     log::warn!("Failed to decode message from {addr}: {e}");
                                 }
@@ -576,59 +1826,228 @@ impl SyncServer {
                     }
                 } => {
                     match msg {
-                        Ok(data) => {
+                        Ok(frame) => {
                             // Don't echo back to sender
-                            if let Ok(sync_msg) = SyncMessage::decode(&data) {
-                                if Some(sync_msg.peer_id) == peer_id {
-                                    continue; // Skip own messages
-                                }
+                            if Some(frame.sender) == peer_id {
+                                continue; // Skip own messages
                             }
-                            ws_sender.send(Message::Binary(data.to_vec().into())).await?;
+                            // The room compressed once; each peer gets the
+                            // encoding it agreed to
+                            let outgoing = frame.for_peer(compress_above.is_some());
+                            if !Arc::ptr_eq(outgoing, &frame.plain) {
+                                let mut s = stats.write().await;
+                                s.compressed_messages += 1;
+                                s.compression_saved_bytes += (frame.plain.len() - outgoing.len()) as u64;
+                            }
+                            ws_sender.send(Message::Binary(outgoing.to_vec().into())).await?;
                         }
                         Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                            log::warn!("Peer {peer_id:?} lagged by {n} messages");
+                            // The skipped messages are gone from the channel;
+                            // send everything since the peer's last full sync
+                            // instead. Deltas still queued behind the gap
+                            // arrive again afterwards, which Yrs ignores.
+                            log::warn!("Peer {peer_id:?} lagged by {n} messages, resyncing");
+                            stats.write().await.lag_resyncs += 1;
+                            if let Some(did) = doc_id {
+                                let resync = {
+                                    let rooms_r = rooms.read().await;
+                                    rooms_r.get(&did).map(|room| {
+                                        let txn = yrs::Transact::transact(&room.doc);
+                                        (txn.encode_diff_v1(&peer_sv), txn.state_vector())
+                                    })
+                                };
+                                if let Some((diff, sv)) = resync {
+                                    peer_sv = sv;
+                                    let response = SyncMessage::sync_step2(Uuid::nil(), did, diff);
+                                    let response = Self::compress_for_peer(
+                                        &stats, &response, response.encode()?, compress_above,
+                                    ).await?;
+                                    ws_sender.send(Message::Binary(response.into())).await?;
+                                }
+                            }
                         }
                         Err(_) => break,
                     }
                 }
+
+                // Held-back awareness, once the peer's bucket has a token
+                _ = async {
+                    match awareness_due {
+                        Some(at) => tokio::time::sleep_until(at.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if limiter.try_take(MessageType::Awareness, Instant::now()) {
+                        if let Some((_, held)) = held_awareness.pop_first() {
+                            let broadcast = match doc_id {
+                                Some(did) => rooms.read().await.get(&did).map(|r| r.broadcast.clone()),
+                                None => None,
+                            };
+                            if let Some(bc) = broadcast {
+                                let _ = bc.broadcast(&held);
+                                if let Some(cluster) = &cluster {
+                                    cluster.publish(None, held);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Heartbeat: ping, or give up on a peer gone quiet
+                _ = async {
+                    match heartbeat.as_mut() {
+                        Some(timer) => timer.tick().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if missed_heartbeats >= config.heartbeat_max_missed {
+                        log::warn!("Peer {peer_id:?} from {addr} missed {missed_heartbeats} heartbeats, dropping");
+                        stats.write().await.heartbeat_timeouts += 1;
+                        break;
+                    }
+                    missed_heartbeats += 1;
+                    // A peer that stopped reading fills the socket buffer; give
+                    // up on the ping rather than wait on it forever
+                    let ping = ws_sender.send(Message::Ping(Vec::new().into()));
+                    if !matches!(tokio::time::timeout(heartbeat_period, ping).await, Ok(Ok(()))) {
+                        log::warn!("Heartbeat to peer {peer_id:?} from {addr} not sent, dropping");
+                        stats.write().await.heartbeat_timeouts += 1;
+                        break;
+                    }
+                }
+
+                // Admin eviction of this peer or its whole room
+                eviction = async {
+                    match evictions.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    match eviction {
+                        Ok(eviction)
+                            if joined_room.as_ref().is_some_and(|room| Arc::ptr_eq(room, &eviction.room))
+                                && eviction.peer_id.is_none_or(|p| peer_id == Some(p)) =>
+                        {
+                            let rejection = match eviction.peer_id {
+                                Some(_) => Rejection::Evicted,
+                                None => Rejection::RoomClosed,
+                            };
+                            log::info!("Disconnecting peer {peer_id:?} from doc {}: {rejection}", eviction.doc_id);
+                            stats.write().await.evicted_peers += 1;
+                            // Best effort: the peer is removed from the room either way
+                            let reject = SyncMessage::rejected(eviction.doc_id, &rejection);
+                            let _ = ws_sender.send(Message::Binary(reject.encode()?.into())).await;
+                            let _ = ws_sender.send(Message::Close(None)).await;
+                            break;
+                        }
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => evictions = None,
+                    }
+                }
             }
         }
 
         // Cleanup: remove peer from room
         if let (Some(pid), Some(did)) = (peer_id, doc_id) {
-            let mut rooms_w = rooms.write().await;
-            if let Some(room) = rooms_w.get_mut(&did) {
-                room.broadcast.remove_peer(&pid).await;
-
-                // Broadcast peer left (lock-free)
-                let leave_msg = SyncMessage::peer_left(pid, did);
-                let _ = room.broadcast.broadcast(&leave_msg);
-
-                // Remove empty rooms — queue snapshot for background persistence
-                if room.broadcast.peer_count().await == 0 {
-                    if let Some(ref ptx) = persistence_tx {
-                        let snapshot = {
-                            let txn = yrs::Transact::transact(&room.doc);
-                            txn.encode_state_as_update_v1(&yrs::StateVector::default())
-                        };
-                        let current_version = delta_version.load(Ordering::SeqCst);
-                        let _ = ptx.send(PersistenceCommand::SaveSnapshot {
-                            doc_id: did,
-                            snapshot,
-                            compact_version: current_version,
-                        });
-                    }
+            Self::leave_room(&rooms, &stats, &store, &persistence_tx, &cluster, pid, did).await;
+        }
+        stats.write().await.active_connections -= 1;
+
+        Ok(())
+    }
+
+    /// Take peer `pid` out of room `did`: tell the others it left, and drop
+    /// the room, snapshotting it first, once nobody is left in it.
+    async fn leave_room(
+        rooms: &RwLock<HashMap<Uuid, DocumentRoom>>,
+        stats: &RwLock<ServerStats>,
+        store: &Option<Arc<DocumentStore>>,
+        persistence_tx: &Option<PersistenceQueue>,
+        cluster: &Option<Arc<ClusterNode>>,
+        pid: Uuid,
+        did: Uuid,
+    ) {
+        let mut rooms_w = rooms.write().await;
+        if let Some(room) = rooms_w.get_mut(&did) {
+            room.broadcast.remove_peer(&pid).await;
+
+            // Broadcast peer left (lock-free)
+            let leave_msg = SyncMessage::peer_left(pid, did);
+            let _ = room.broadcast.broadcast(&leave_msg);
+            if let Some(cluster) = cluster {
+                cluster.publish(None, leave_msg);
+            }
 
-                    rooms_w.remove(&did);
-                    log::info!("Room {did} removed (empty)");
+            // Remove empty rooms — queue snapshot for background persistence,
+            // on the lease holder in a cluster
+            if room.broadcast.peer_count().await == 0 {
+                if let (Some(s), Some(ptx)) = (store, persistence_tx) {
+                    if cluster.as_ref().is_none_or(|c| c.holds(did)) {
+                        room.queue_snapshot(did, s, ptx);
+                    }
                 }
+
+                rooms_w.remove(&did);
+                log::info!("Room {did} removed (empty)");
             }
+        }
+
+        stats.write().await.active_rooms = rooms_w.len();
+    }
+
+    /// Which rate-limited awareness slot a payload belongs to: a newer
+    /// message replaces a held-back one in the same slot. `None` for joins
+    /// and leaves, which are never limited.
+    fn awareness_slot(payload: &[u8]) -> Option<u8> {
+        match AwarenessMessage::decode(payload) {
+            Ok(AwarenessMessage::Join { .. } | AwarenessMessage::Leave { .. }) => None,
+            Ok(AwarenessMessage::Cursor { .. }) => Some(1),
+            Ok(AwarenessMessage::Selection { .. }) => Some(2),
+            // Legacy `AwarenessState`
+            Err(_) => Some(0),
+        }
+    }
 
+    /// The server's side of the hello: its version and capabilities.
+    fn server_hello(config: &ServerConfig) -> Hello {
+        let mut capabilities = Capabilities::COMMENTS;
+        if config.authenticator.is_some() {
+            capabilities |= Capabilities::AUTH;
+        }
+        if config.compression_threshold.is_some() {
+            capabilities |= Capabilities::COMPRESSION;
+        }
+        Hello::new(capabilities)
+    }
+
+    /// Prepare `msg`, already serialized as `encoded`, for a peer: compressed
+    /// above `compress_above`, counting what that saved.
+    async fn compress_for_peer(
+        stats: &RwLock<ServerStats>,
+        msg: &SyncMessage,
+        encoded: Vec<u8>,
+        compress_above: Option<usize>,
+    ) -> Result<Vec<u8>, ProtocolError> {
+        let plain_len = encoded.len();
+        let out = msg.compress(encoded, compress_above)?;
+        if out.len() < plain_len {
             let mut s = stats.write().await;
-            s.active_connections -= 1;
-            s.active_rooms = rooms_w.len();
+            s.compressed_messages += 1;
+            s.compression_saved_bytes += (plain_len - out.len()) as u64;
         }
+        Ok(out)
+    }
 
+    /// Validate a join against the configured authenticator. The token must
+    /// grant the requested document to the joining peer.
+    fn authenticate_join(config: &ServerConfig, msg: &SyncMessage) -> Result<(), AuthError> {
+        let Some(auth) = &config.authenticator else {
+            return Ok(());
+        };
+        let claims = auth.authenticate(msg.join_token().as_deref(), msg.doc_id)?;
+        if claims.user_id != msg.peer_id {
+            return Err(AuthError::PeerMismatch { user_id: claims.user_id });
+        }
         Ok(())
     }
 
@@ -639,9 +2058,95 @@ impl SyncServer {
         let mut s = self.stats.read().await.clone();
         s.persisted_deltas = self.persisted_deltas_counter.load(Ordering::Relaxed);
         s.persisted_snapshots = self.persisted_snapshots_counter.load(Ordering::Relaxed);
+        s.wal_checkpoints = self.wal_checkpoints_counter.load(Ordering::Relaxed);
+        s.compacted_deltas = self.compacted_deltas_counter.load(Ordering::Relaxed);
+        s.scheduled_snapshots = self.scheduled_snapshots_counter.load(Ordering::Relaxed);
+        s.persistence_queue = self.persistence_tx.as_ref().map_or(0, PersistenceQueue::depth);
+        if let Some(cluster) = &self.cluster {
+            (s.held_leases, s.relay_sent, s.relay_received) = cluster.counters();
+        }
+        if let Some(store) = &self.store {
+            match store.disk_usage() {
+                Ok(bytes) => s.storage_bytes = bytes,
+                Err(e) => log::warn!("Measuring storage size: {e}"),
+            }
+        }
         s
     }
 
+    /// Every open room with its connected peers, ordered by document id.
+    pub async fn list_rooms(&self) -> Vec<RoomInfo> {
+        let open: Vec<_> = self
+            .rooms
+            .read()
+            .await
+            .iter()
+            .map(|(doc_id, room)| (*doc_id, room.broadcast.clone(), room.deltas_since_snapshot))
+            .collect();
+        let mut rooms = Vec::with_capacity(open.len());
+        for (doc_id, broadcast, deltas_since_snapshot) in open {
+            rooms.push(RoomInfo { doc_id, peers: broadcast.peers().await, deltas_since_snapshot });
+        }
+        rooms.sort_by_key(|room| room.doc_id);
+        rooms
+    }
+
+    /// Snapshot the open room of `doc_id` now, compacting its deltas, and
+    /// wait until the snapshot has been written. Fails with
+    /// [`StoreError::NotFound`] if the room is not open or there is no
+    /// storage, and with the storage error if the snapshot or its WAL
+    /// checkpoint could not be written.
+    pub async fn snapshot_room(&self, doc_id: Uuid) -> Result<(), StoreError> {
+        let (Some(store), Some(ptx)) = (&self.store, &self.persistence_tx) else {
+            return Err(StoreError::NotFound(doc_id));
+        };
+        let (done, stored) = oneshot::channel();
+        self.rooms
+            .write()
+            .await
+            .get_mut(&doc_id)
+            .ok_or(StoreError::NotFound(doc_id))?
+            .queue_snapshot_reporting(doc_id, store, ptx, Some(done));
+        stored
+            .await
+            .map_err(|_| StoreError::DatabaseError("persistence task stopped".into()))??;
+        log::info!("Room {doc_id} snapshotted on request");
+        Ok(())
+    }
+
+    /// Disconnect `peer_id` from the room of `doc_id`. It is sent a
+    /// [`Rejection::Evicted`] and does not reconnect by itself. Returns
+    /// whether the peer was connected.
+    pub async fn evict_peer(&self, doc_id: Uuid, peer_id: Uuid) -> bool {
+        let broadcast = self.rooms.read().await.get(&doc_id).map(|room| room.broadcast.clone());
+        let Some(broadcast) = broadcast else {
+            return false;
+        };
+        if !broadcast.has_peer(&peer_id).await {
+            return false;
+        }
+        log::info!("Evicting peer {peer_id} from doc {doc_id}");
+        let _ = self.evictions.send(Eviction { doc_id, room: broadcast, peer_id: Some(peer_id) });
+        true
+    }
+
+    /// Close the room of `doc_id`: snapshot it, drop it and disconnect its
+    /// peers with [`Rejection::RoomClosed`]. The document stays in storage
+    /// and reopens on the next join. Returns whether the room was open.
+    pub async fn close_room(&self, doc_id: Uuid) -> bool {
+        let mut rooms_w = self.rooms.write().await;
+        let Some(mut room) = rooms_w.remove(&doc_id) else {
+            return false;
+        };
+        if let (Some(s), Some(ptx)) = (&self.store, &self.persistence_tx) {
+            room.queue_snapshot(doc_id, s, ptx);
+        }
+        let _ = self.evictions.send(Eviction { doc_id, room: room.broadcast.clone(), peer_id: None });
+        self.stats.write().await.active_rooms = rooms_w.len();
+        log::info!("Room {doc_id} closed on request");
+        true
+    }
+
     /// Get the configured bind address.
     pub fn bind_addr(&self) -> &str {
         &self.config.bind_addr
@@ -652,10 +2157,132 @@ impl SyncServer {
         &self.room_manager
     }
 
+    /// Get the per-document role grants.
+    pub fn permissions(&self) -> &Arc<Permissions> {
+        &self.permissions
+    }
+
+    /// Grant `role` to `user_id` on `doc_id`. Takes effect on the user's
+    /// live connections from their next write.
+    pub fn set_role(&self, doc_id: Uuid, user_id: Uuid, role: Role) -> Option<Role> {
+        log::info!("User {user_id} is now {role} on doc {doc_id}");
+        self.permissions.set_role(doc_id, user_id, role)
+    }
+
     /// Get the persistent store (if configured).
     pub fn store(&self) -> Option<&Arc<DocumentStore>> {
         self.store.as_ref()
     }
+
+    /// Every recorded version of `doc_id`, oldest first.
+    pub async fn list_versions(&self, doc_id: Uuid) -> Result<Vec<VersionInfo>, StoreError> {
+        Self::versions(self.store.as_ref(), self.persistence_tx.as_ref(), doc_id).await
+    }
+
+    /// Read-only copy of `doc_id` as it was at `at`: the version it resolved
+    /// to and the full Yrs state.
+    pub async fn version_snapshot(&self, doc_id: Uuid, at: VersionRef) -> Result<(u64, Vec<u8>), StoreError> {
+        Self::snapshot_at(self.store.as_ref(), self.persistence_tx.as_ref(), doc_id, at).await
+    }
+
+    /// Name the latest version of `doc_id` as a checkpoint.
+    pub async fn create_checkpoint(
+        &self,
+        doc_id: Uuid,
+        name: &str,
+        author: Option<Uuid>,
+    ) -> Result<Checkpoint, StoreError> {
+        Self::checkpoint_head(self.store.as_ref(), self.persistence_tx.as_ref(), doc_id, name, author).await
+    }
+
+    /// Fork `doc_id` at checkpoint `name` into a new document, returning
+    /// its id. `author` becomes the branch's owner.
+    pub async fn fork_document(
+        &self,
+        doc_id: Uuid,
+        name: &str,
+        author: Option<Uuid>,
+    ) -> Result<Uuid, StoreError> {
+        let (branch_id, _) =
+            Self::fork(self.store.as_ref(), self.persistence_tx.as_ref(), doc_id, name, author).await?;
+        if let Some(author) = author {
+            self.permissions.set_role(branch_id, author, Role::Owner);
+        }
+        Ok(branch_id)
+    }
+
+    /// Merge branch `branch_id` back into the document it was forked from.
+    /// Connected peers of that document receive the merge as a delta.
+    pub async fn merge_branch(&self, branch_id: Uuid, author: Option<Uuid>) -> Result<MergeReport, StoreError> {
+        Self::merge(
+            &self.config,
+            &self.rooms,
+            self.store.as_ref(),
+            self.persistence_tx.as_ref(),
+            &self.snapshot_due,
+            self.cluster.as_deref(),
+            branch_id,
+            author,
+        )
+        .await
+    }
+
+    /// Restore `doc_id` to its state at `at` as a new change; later versions
+    /// stay in the history. Connected peers receive it as a delta. Returns
+    /// the version restored.
+    pub async fn restore_version(
+        &self,
+        doc_id: Uuid,
+        at: VersionRef,
+        author: Option<Uuid>,
+    ) -> Result<u64, StoreError> {
+        Self::restore(
+            &self.config,
+            &self.rooms,
+            self.store.as_ref(),
+            self.persistence_tx.as_ref(),
+            &self.snapshot_due,
+            self.cluster.as_deref(),
+            doc_id,
+            at,
+            author,
+        )
+        .await
+    }
+
+    /// Export `doc_ids`, or every stored document if `None`, once every
+    /// delta queued so far has been written. Servers without storage
+    /// export nothing.
+    pub async fn export_documents(&self, doc_ids: Option<&[Uuid]>) -> Result<Archive, ArchiveError> {
+        let (Some(store), Some(ptx)) = (&self.store, &self.persistence_tx) else {
+            return match doc_ids.and_then(|ids| ids.first()) {
+                Some(&doc_id) => Err(StoreError::NotFound(doc_id).into()),
+                None => Ok(Archive::new()),
+            };
+        };
+        let (store, _) = Self::flushed_store(Some(store), Some(ptx), Uuid::nil()).await?;
+        let archive = match doc_ids {
+            Some(doc_ids) => archive::export(store, doc_ids)?,
+            None => archive::export_all(store)?,
+        };
+        log::info!("Exported {} documents", archive.documents().len());
+        Ok(archive)
+    }
+
+    /// Import `archive` into the store. Open rooms count as taken ids, and
+    /// imported documents open from storage on their first join.
+    pub async fn import_archive(&self, archive: &Archive, ids: IdPolicy) -> Result<Vec<ImportedDocument>, ArchiveError> {
+        let (Some(store), Some(ptx)) = (&self.store, &self.persistence_tx) else {
+            return Err(StoreError::NotFound(archive.documents().first().copied().unwrap_or_default()).into());
+        };
+        let (store, _) = Self::flushed_store(Some(store), Some(ptx), Uuid::nil()).await?;
+        let open = self.rooms.read().await.keys().copied().collect();
+        let imported = archive::import_avoiding(store, archive, ids, &open)?;
+        for doc in &imported {
+            log::info!("Imported doc {} as {}", doc.original_id, doc.doc_id);
+        }
+        Ok(imported)
+    }
 }
 
 /// Drop sends Shutdown to the persistence task and aborts it
@@ -668,13 +2295,23 @@ impl Drop for SyncServer {
         if let Some(handle) = self.persistence_handle.take() {
             handle.abort();
         }
+        if let Some(handle) = self.scheduler_handle.take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.relay_handle.take() {
+            handle.abort();
+        }
+        // Let other nodes take this one's documents over at once
+        if let Some(cluster) = &self.cluster {
+            cluster.release_all();
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use yrs::{WriteTxn, GetString, Text};
+    use yrs::{WriteTxn, GetString, Text, Transact};
 
     #[test]
     fn test_server_config_default() {
@@ -683,7 +2320,15 @@ mod tests {
         assert_eq!(config.max_peers_per_room, 100);
         assert_eq!(config.broadcast_capacity, 256);
         assert_eq!(config.heartbeat_interval_secs, 30);
+        assert_eq!(config.heartbeat_max_missed, 3);
+        assert_eq!(config.compression_threshold, Some(DEFAULT_COMPRESSION_THRESHOLD));
         assert!(config.storage_path.is_none());
+        assert_eq!(config.recovery_validation, RecoveryValidation::Report);
+        assert_eq!(config.wal_sync_interval_ms, 1000);
+        assert_eq!(config.snapshot_every_deltas, Some(1000));
+        assert_eq!(config.snapshot_interval_secs, Some(300));
+        assert_eq!(config.history_retention_secs, Some(30 * 24 * 3600));
+        assert_eq!(config.default_role, Role::Editor);
     }
 
     #[test]
@@ -701,6 +2346,7 @@ mod tests {
             broadcast_capacity: 512,
             heartbeat_interval_secs: 15,
             storage_path: None,
+            ..ServerConfig::default()
         };
         let server = SyncServer::new(config);
         assert_eq!(server.bind_addr(), "0.0.0.0:8080");
@@ -776,9 +2422,258 @@ mod tests {
         assert_eq!(text.get_string(&txn), "Hello, persistence!");
     }
 
+    #[tokio::test]
+    async fn test_server_recovery_replays_deltas_after_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db");
+        let (with_snapshot, deltas_only) = (Uuid::new_v4(), Uuid::new_v4());
+
+        {
+            let store = DocumentStore::open(StoreConfig::for_testing(&db_path)).unwrap();
+            let doc = yrs::Doc::new();
+            let append = |s: &str| {
+                let before = yrs::Transact::transact(&doc).state_vector();
+                {
+                    let mut txn = yrs::Transact::transact_mut(&doc);
+                    let text = txn.get_or_insert_text("test");
+                    let len = text.len(&txn);
+                    text.insert(&mut txn, len, s);
+                }
+                yrs::Transact::transact(&doc).encode_state_as_update_v1(&before)
+            };
+
+            store.save_snapshot(with_snapshot, &append("snap")).unwrap();
+            store.store_delta(with_snapshot, 0, &append("+a")).unwrap();
+            store.store_delta(with_snapshot, 1, &append("+b")).unwrap();
+
+            // Crashed before its room ever closed: no snapshot at all
+            let other = yrs::Doc::new();
+            let first = {
+                let mut txn = yrs::Transact::transact_mut(&other);
+                txn.get_or_insert_text("test").insert(&mut txn, 0, "unsnapshotted");
+                txn.encode_update_v1()
+            };
+            store.store_delta(deltas_only, 2, &first).unwrap();
+        }
+
+        let server = SyncServer::with_storage("127.0.0.1:0", &db_path);
+        assert_eq!(server.recover().await.unwrap(), 2);
+        assert_eq!(server.store().unwrap().wal_sequence(), 3, "versions must not be reused");
+
+        let rooms = server.rooms.read().await;
+        let text_of = |id: Uuid| {
+            let txn = yrs::Transact::transact(&rooms[&id].doc);
+            txn.get_text("test").unwrap().get_string(&txn)
+        };
+        assert_eq!(text_of(with_snapshot), "snap+a+b");
+        assert_eq!(text_of(deltas_only), "unsnapshotted");
+    }
+
+    #[tokio::test]
+    async fn test_server_recovery_repairs_invariants() {
+        use yrs::Map;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db");
+        let doc_id = Uuid::new_v4();
+        let layer_id = Uuid::new_v4();
+
+        // Persist a layer whose parent does not exist
+        {
+            let store = DocumentStore::open(StoreConfig {
+                path: db_path.clone(),
+                ..StoreConfig::default()
+            })
+            .unwrap();
+            let doc = yrs::Doc::new();
+            {
+                let mut txn = yrs::Transact::transact_mut(&doc);
+                let layers = txn.get_or_insert_map("layers");
+                let json = format!(
+                    r#"{{"Rect":{{"id":"{layer_id}","parent_id":"{}","bounds":{{"x":0,"y":0,"width":1,"height":1}}}}}}"#,
+                    Uuid::new_v4()
+                );
+                layers.insert(&mut txn, layer_id.to_string(), json);
+            }
+            let snapshot = yrs::Transact::transact(&doc)
+                .encode_state_as_update_v1(&yrs::StateVector::default());
+            store.save_snapshot(doc_id, &snapshot).unwrap();
+        }
+
+        let server = SyncServer::new(ServerConfig {
+            bind_addr: "127.0.0.1:0".into(),
+            storage_path: Some(db_path),
+            recovery_validation: RecoveryValidation::Repair,
+            ..ServerConfig::default()
+        });
+        assert_eq!(server.recover().await.unwrap(), 1);
+
+        let stats = server.stats().await;
+        assert_eq!(stats.recovery_diagnostics, 1);
+        assert_eq!(stats.recovery_repairs, 1);
+
+        let rooms = server.rooms.read().await;
+        let room = rooms.get(&doc_id).unwrap();
+        assert!(logos_core::validate::validate_doc(&room.doc).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_document_room_snapshot_due() {
+        let config = ServerConfig {
+            snapshot_every_deltas: Some(3),
+            snapshot_interval_secs: Some(60),
+            ..ServerConfig::default()
+        };
+        let mut room = DocumentRoom::new(&config);
+        assert!(!room.snapshot_due(&config), "nothing to snapshot");
+        room.deltas_since_snapshot = 2;
+        assert!(!room.snapshot_due(&config));
+        room.deltas_since_snapshot = 3;
+        assert!(room.snapshot_due(&config));
+
+        room.deltas_since_snapshot = 1;
+        room.last_snapshot = Instant::now() - Duration::from_secs(61);
+        assert!(room.snapshot_due(&config), "old changes are snapshotted");
+
+        let never = ServerConfig {
+            snapshot_every_deltas: None,
+            snapshot_interval_secs: None,
+            ..ServerConfig::default()
+        };
+        room.deltas_since_snapshot = 10_000;
+        assert!(!room.snapshot_due(&never));
+    }
+
+    /// Memory storage that refuses every WAL append and/or snapshot write.
+    #[derive(Default)]
+    struct Broken {
+        inner: crate::storage::MemoryBackend,
+        wal: bool,
+        snapshots: bool,
+    }
+
+    impl crate::storage::StorageBackend for Broken {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+        fn put_snapshot(&self, doc_id: Uuid, snapshot: &[u8], metadata: &[u8]) -> Result<(), StoreError> {
+            if self.snapshots {
+                return Err(StoreError::DatabaseError("disk full".into()));
+            }
+            self.inner.put_snapshot(doc_id, snapshot, metadata)
+        }
+        fn get_snapshot(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+            self.inner.get_snapshot(doc_id)
+        }
+        fn put_delta(
+            &self,
+            doc_id: Uuid,
+            version: u64,
+            delta: Option<&[u8]>,
+            history: Option<&[u8]>,
+            metadata: &[u8],
+        ) -> Result<(), StoreError> {
+            self.inner.put_delta(doc_id, version, delta, history, metadata)
+        }
+        fn get_delta(&self, doc_id: Uuid, version: u64) -> Result<Option<Vec<u8>>, StoreError> {
+            self.inner.get_delta(doc_id, version)
+        }
+        fn scan_deltas(&self, doc_id: Uuid, from_version: u64) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+            self.inner.scan_deltas(doc_id, from_version)
+        }
+        fn delete_deltas(&self, doc_id: Uuid, up_to_version: u64) -> Result<u64, StoreError> {
+            self.inner.delete_deltas(doc_id, up_to_version)
+        }
+        fn scan_history(&self, doc_id: Uuid, up_to_version: u64) -> Result<Vec<Vec<u8>>, StoreError> {
+            self.inner.scan_history(doc_id, up_to_version)
+        }
+        fn put_metadata(&self, doc_id: Uuid, metadata: &[u8]) -> Result<(), StoreError> {
+            self.inner.put_metadata(doc_id, metadata)
+        }
+        fn get_metadata(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+            self.inner.get_metadata(doc_id)
+        }
+        fn list_documents(&self) -> Result<Vec<Uuid>, StoreError> {
+            self.inner.list_documents()
+        }
+        fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError> {
+            self.inner.delete_document(doc_id)
+        }
+        fn wal_put(&self, seq: u64, doc_id: Uuid, delta: &[u8]) -> Result<(), StoreError> {
+            if self.wal {
+                return Err(StoreError::DatabaseError("disk full".into()));
+            }
+            self.inner.wal_put(seq, doc_id, delta)
+        }
+        fn wal_scan(&self, since_seq: u64) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError> {
+            self.inner.wal_scan(since_seq)
+        }
+        fn wal_delete(&self, up_to_seq: u64) -> Result<u64, StoreError> {
+            self.inner.wal_delete(up_to_seq)
+        }
+        fn wal_last(&self) -> Result<Option<u64>, StoreError> {
+            self.inner.wal_last()
+        }
+        fn flush(&self) -> Result<(), StoreError> {
+            self.inner.flush()
+        }
+        fn flush_wal(&self) -> Result<(), StoreError> {
+            self.inner.flush_wal()
+        }
+        fn path(&self) -> Option<&std::path::Path> {
+            None
+        }
+        fn disk_usage(&self) -> Result<u64, StoreError> {
+            self.inner.disk_usage()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unlogged_delta_is_not_applied() {
+        let store = Arc::new(DocumentStore::with_backend(Broken { wal: true, ..Default::default() }).unwrap());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let ptx = PersistenceQueue { tx, depth: Arc::new(AtomicU64::new(0)) };
+        let mut room = DocumentRoom::new(&ServerConfig::default());
+
+        let source = yrs::Doc::new();
+        let text = source.get_or_insert_text("t");
+        text.insert(&mut yrs::Transact::transact_mut(&source), 0, "lost");
+        let update = source.transact().encode_state_as_update_v1(&yrs::StateVector::default());
+
+        let result = SyncServer::persist_delta(
+            Some(&mut room),
+            Some(&store),
+            Some(&ptx),
+            None,
+            Uuid::new_v4(),
+            None,
+            &update,
+        );
+        assert!(result.is_err());
+        assert_eq!(room.doc.get_or_insert_text("t").get_string(&room.doc.transact()), "");
+        assert!(rx.try_recv().is_err(), "nothing queued for storage");
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_room_reports_storage_failure() {
+        let store = DocumentStore::with_backend(Broken { snapshots: true, ..Default::default() }).unwrap();
+        let server = SyncServer::with_store(ServerConfig::default(), Some(Arc::new(store)));
+        let doc_id = Uuid::new_v4();
+        server.rooms.write().await.insert(doc_id, DocumentRoom::new(&server.config));
+
+        let err = server.snapshot_room(doc_id).await.unwrap_err();
+        assert!(matches!(err, StoreError::DatabaseError(_)), "{err}");
+
+        let path = format!("/rooms/{doc_id}/snapshot");
+        assert_eq!(crate::admin::route(&server, "POST", &path).await.status, 500);
+        let missing = format!("/rooms/{}/snapshot", Uuid::new_v4());
+        assert_eq!(crate::admin::route(&server, "POST", &missing).await.status, 404);
+    }
+
     #[tokio::test]
     async fn test_document_room_creation() {
-        let room = DocumentRoom::new(64);
+        let config = ServerConfig { broadcast_capacity: 64, ..ServerConfig::default() };
+        let room = DocumentRoom::new(&config);
         assert_eq!(room.broadcast.peer_count().await, 0);
         assert_eq!(room.broadcast.capacity(), 64);
     }
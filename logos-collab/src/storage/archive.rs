@@ -0,0 +1,642 @@
+//! Portable document archives for backup, export and restore.
+//!
+//! An archive holds each document as a set of files plus a manifest
+//! listing every file's size and [`checksum`], the fold
+//! [`WalEntry::verify`](super::WalEntry::verify) uses on WAL payloads:
+//!
+//! ```text
+//! <doc_id>/metadata.json        DocumentMetadata
+//! <doc_id>/snapshot             Yrs state (absent if never snapshotted)
+//! <doc_id>/history.json         VersionInfo of every history entry
+//! <doc_id>/history/<version>    the delta each history entry recorded
+//! <doc_id>/deltas/<version>     deltas not yet compacted into the snapshot
+//! <doc_id>/assets/<name>        files attached with Archive::add_asset
+//! ```
+//!
+//! Encoded, an archive is `LOGOSARC` followed by the LZ4-compressed bincode
+//! of the manifest and files. [`Archive::decode`] checks every file against
+//! the manifest, so nothing is imported from a damaged archive.
+//!
+//! [`import`] never overwrites what the target store holds: documents keep
+//! their ids only as the [`IdPolicy`] allows, and every delta gets a new
+//! version from the target's counter. Checkpoints, the current version and
+//! branch origins inside the archive are remapped to match.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::store::{DocumentMetadata, DocumentStore, StoreError, VersionInfo};
+use super::wal::checksum;
+
+/// Leading bytes of an encoded archive.
+const MAGIC: &[u8; 8] = b"LOGOSARC";
+
+/// Archive layout version written by [`Archive::encode`].
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// One file listed in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path inside the archive, e.g. `<doc_id>/snapshot`
+    pub path: String,
+    /// File size in bytes
+    pub size: u64,
+    /// [`checksum`] of the file contents
+    pub checksum: u32,
+}
+
+/// Table of contents of an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Archive layout version
+    pub format: u32,
+    /// When the archive was created (milliseconds since epoch)
+    pub created_at_ms: u64,
+    /// Archived documents, in export order
+    pub documents: Vec<Uuid>,
+    /// Every file, sorted by path
+    pub files: Vec<ManifestEntry>,
+}
+
+/// Manifest and files as encoded after the magic bytes.
+#[derive(Serialize, Deserialize)]
+struct Encoded {
+    manifest: Manifest,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// Documents exported from a [`DocumentStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    created_at_ms: u64,
+    documents: Vec<Uuid>,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+/// How [`import`] picks the ids of imported documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdPolicy {
+    /// Keep every archived id; fail with [`StoreError::DocumentExists`]
+    /// before writing anything if one is taken
+    Keep,
+    /// Keep archived ids that are free, give the others new ones
+    #[default]
+    RenameOnClash,
+    /// Give every document a new id, e.g. to copy documents within a store
+    Fresh,
+}
+
+/// Where an archived document ended up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedDocument {
+    /// Id in the archive; its assets are under this id
+    pub original_id: Uuid,
+    /// Id in the target store
+    pub doc_id: Uuid,
+    /// History and delta log entries written
+    pub versions: usize,
+}
+
+/// Archive errors.
+#[derive(Debug, Clone)]
+pub enum ArchiveError {
+    /// Reading from or writing to the store failed
+    Store(StoreError),
+    /// Reading or writing the archive file failed
+    Io(String),
+    /// Not an archive, or a malformed one
+    Format(String),
+    /// Archive written by a newer, unknown layout
+    UnsupportedFormat(u32),
+    /// A file's contents do not match its manifest entry
+    ChecksumMismatch { path: String },
+    /// A file the manifest or a document needs is missing, or one the
+    /// manifest does not list is present
+    MissingFile(String),
+    /// Empty name or one containing a path separator
+    InvalidName(String),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Store(e) => write!(f, "{e}"),
+            Self::Io(e) => write!(f, "Archive I/O error: {e}"),
+            Self::Format(e) => write!(f, "Malformed archive: {e}"),
+            Self::UnsupportedFormat(v) => write!(f, "Unsupported archive format {v} (expected {ARCHIVE_FORMAT})"),
+            Self::ChecksumMismatch { path } => write!(f, "Checksum mismatch for {path}"),
+            Self::MissingFile(path) => write!(f, "Archive file missing or unlisted: {path}"),
+            Self::InvalidName(name) => write!(f, "Invalid archive name: {name:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<StoreError> for ArchiveError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Archive {
+    /// An empty archive stamped with the current time.
+    pub fn new() -> Self {
+        Self {
+            created_at_ms: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            documents: Vec::new(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Archived documents, in export order.
+    pub fn documents(&self) -> &[Uuid] {
+        &self.documents
+    }
+
+    /// Manifest describing the archive's current contents.
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            format: ARCHIVE_FORMAT,
+            created_at_ms: self.created_at_ms,
+            documents: self.documents.clone(),
+            files: self
+                .files
+                .iter()
+                .map(|(path, data)| ManifestEntry {
+                    path: path.clone(),
+                    size: data.len() as u64,
+                    checksum: checksum(data),
+                })
+                .collect(),
+        }
+    }
+
+    /// Attach a file to an archived document, replacing one of the same
+    /// name. The store keeps no assets, so callers add them after export.
+    pub fn add_asset(&mut self, doc_id: Uuid, name: &str, data: Vec<u8>) -> Result<(), ArchiveError> {
+        if !self.documents.contains(&doc_id) {
+            return Err(StoreError::NotFound(doc_id).into());
+        }
+        check_name(name)?;
+        self.files.insert(format!("{doc_id}/assets/{name}"), data);
+        Ok(())
+    }
+
+    /// Assets of an archived document as `(name, contents)`, by name.
+    pub fn assets(&self, doc_id: Uuid) -> Vec<(&str, &[u8])> {
+        let prefix = format!("{doc_id}/assets/");
+        self.files
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(path, data)| (&path[prefix.len()..], data.as_slice()))
+            .collect()
+    }
+
+    /// Encode for writing to disk or sending over the wire.
+    pub fn encode(&self) -> Result<Vec<u8>, ArchiveError> {
+        encode_parts(self.manifest(), &self.files)
+    }
+
+    /// Decode an archive, verifying every file against the manifest.
+    pub fn decode(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        let body = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or_else(|| ArchiveError::Format("missing LOGOSARC header".into()))?;
+        let body = lz4_flex::decompress_size_prepended(body)
+            .map_err(|e| ArchiveError::Format(e.to_string()))?;
+        let (encoded, _): (Encoded, _) = bincode::serde::decode_from_slice(&body, bincode::config::standard())
+            .map_err(|e| ArchiveError::Format(e.to_string()))?;
+        let Encoded { manifest, files } = encoded;
+        if manifest.format != ARCHIVE_FORMAT {
+            return Err(ArchiveError::UnsupportedFormat(manifest.format));
+        }
+
+        let mut contents: BTreeMap<String, Vec<u8>> = files.into_iter().collect();
+        let mut verified = BTreeMap::new();
+        for entry in &manifest.files {
+            entry.path.split('/').try_for_each(check_name)?;
+            let data = contents
+                .remove(&entry.path)
+                .ok_or_else(|| ArchiveError::MissingFile(entry.path.clone()))?;
+            if data.len() as u64 != entry.size || checksum(&data) != entry.checksum {
+                return Err(ArchiveError::ChecksumMismatch { path: entry.path.clone() });
+            }
+            verified.insert(entry.path.clone(), data);
+        }
+        if let Some(path) = contents.into_keys().next() {
+            return Err(ArchiveError::MissingFile(path));
+        }
+        for doc_id in &manifest.documents {
+            let path = format!("{doc_id}/metadata.json");
+            if !verified.contains_key(&path) {
+                return Err(ArchiveError::MissingFile(path));
+            }
+        }
+
+        Ok(Self {
+            created_at_ms: manifest.created_at_ms,
+            documents: manifest.documents,
+            files: verified,
+        })
+    }
+
+    /// Encode and write to `path`.
+    pub fn write_to(&self, path: &Path) -> Result<(), ArchiveError> {
+        Ok(std::fs::write(path, self.encode()?)?)
+    }
+
+    /// Read and decode the archive at `path`.
+    pub fn read_from(path: &Path) -> Result<Self, ArchiveError> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    fn put(&mut self, path: String, data: Vec<u8>) {
+        self.files.insert(path, data);
+    }
+
+    fn file(&self, path: &str) -> Result<&[u8], ArchiveError> {
+        self.files
+            .get(path)
+            .map(Vec::as_slice)
+            .ok_or_else(|| ArchiveError::MissingFile(path.to_string()))
+    }
+
+    fn json<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, ArchiveError> {
+        serde_json::from_slice(self.file(path)?).map_err(|e| ArchiveError::Format(format!("{path}: {e}")))
+    }
+
+    /// Files under `<doc_id>/<dir>/`, keyed by the version in their name.
+    fn versioned(&self, doc_id: Uuid, dir: &str) -> Result<BTreeMap<u64, &[u8]>, ArchiveError> {
+        let prefix = format!("{doc_id}/{dir}/");
+        self.files
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(path, data)| {
+                let version = path[prefix.len()..]
+                    .parse()
+                    .map_err(|_| ArchiveError::Format(format!("{path}: not a version")))?;
+                Ok((version, data.as_slice()))
+            })
+            .collect()
+    }
+}
+
+fn encode_parts(manifest: Manifest, files: &BTreeMap<String, Vec<u8>>) -> Result<Vec<u8>, ArchiveError> {
+    let encoded = Encoded {
+        manifest,
+        files: files.iter().map(|(path, data)| (path.clone(), data.clone())).collect(),
+    };
+    let body = bincode::serde::encode_to_vec(&encoded, bincode::config::standard())
+        .map_err(|e| ArchiveError::Format(e.to_string()))?;
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(lz4_flex::compress_prepend_size(&body));
+    Ok(bytes)
+}
+
+/// Reject names that could escape an asset directory when written out.
+fn check_name(name: &str) -> Result<(), ArchiveError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return Err(ArchiveError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+fn json_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>, ArchiveError> {
+    serde_json::to_vec_pretty(value).map_err(|e| ArchiveError::Format(e.to_string()))
+}
+
+// ─── Export ───────────────────────────────────────────────────────────
+
+/// Export `doc_ids` from `store`.
+///
+/// Safe against concurrent writers: each document is read delta log
+/// first, then history, snapshot and metadata. History is never compacted
+/// and a snapshot covers the deltas compacted before it, so the archive
+/// holds at least the document as it was when its export started.
+pub fn export(store: &DocumentStore, doc_ids: &[Uuid]) -> Result<Archive, ArchiveError> {
+    let mut archive = Archive::new();
+    for &doc_id in doc_ids {
+        if !archive.documents.contains(&doc_id) {
+            export_document(store, doc_id, &mut archive)?;
+        }
+    }
+    Ok(archive)
+}
+
+/// Export every document in `store`.
+pub fn export_all(store: &DocumentStore) -> Result<Archive, ArchiveError> {
+    export(store, &store.list_documents()?)
+}
+
+fn export_document(store: &DocumentStore, doc_id: Uuid, archive: &mut Archive) -> Result<(), ArchiveError> {
+    let deltas = store.load_all_deltas(doc_id)?;
+    let history = store.load_history(doc_id, u64::MAX)?;
+    let snapshot = match store.load_snapshot(doc_id) {
+        Ok(snapshot) => Some(snapshot),
+        Err(StoreError::NotFound(_)) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let metadata = store.load_metadata(doc_id)?;
+
+    archive.put(format!("{doc_id}/metadata.json"), json_bytes(&metadata)?);
+    if let Some(snapshot) = snapshot {
+        archive.put(format!("{doc_id}/snapshot"), snapshot);
+    }
+    let infos: Vec<&VersionInfo> = history.iter().map(|(info, _)| info).collect();
+    archive.put(format!("{doc_id}/history.json"), json_bytes(&infos)?);
+    for (info, delta) in history {
+        archive.put(format!("{doc_id}/history/{}", info.version), delta);
+    }
+    for (version, delta) in deltas {
+        archive.put(format!("{doc_id}/deltas/{version}"), delta);
+    }
+    archive.documents.push(doc_id);
+    Ok(())
+}
+
+// ─── Import ───────────────────────────────────────────────────────────
+
+/// An archived document read back for import.
+struct ArchivedDocument<'a> {
+    doc_id: Uuid,
+    metadata: DocumentMetadata,
+    snapshot: Option<&'a [u8]>,
+    /// By archived version: history info if recorded, the delta, and
+    /// whether it was still in the delta log
+    entries: BTreeMap<u64, (Option<VersionInfo>, &'a [u8], bool)>,
+}
+
+impl Archive {
+    fn document(&self, doc_id: Uuid) -> Result<ArchivedDocument<'_>, ArchiveError> {
+        let metadata = self.json(&format!("{doc_id}/metadata.json"))?;
+        let snapshot = self.files.get(&format!("{doc_id}/snapshot")).map(Vec::as_slice);
+        let infos: Vec<VersionInfo> = self.json(&format!("{doc_id}/history.json"))?;
+        let mut recorded = self.versioned(doc_id, "history")?;
+
+        let mut entries = BTreeMap::new();
+        for info in infos {
+            let delta = recorded
+                .remove(&info.version)
+                .ok_or_else(|| ArchiveError::MissingFile(format!("{doc_id}/history/{}", info.version)))?;
+            entries.insert(info.version, (Some(info), delta, false));
+        }
+        if let Some(version) = recorded.into_keys().next() {
+            return Err(ArchiveError::MissingFile(format!("{doc_id}/history/{version}")));
+        }
+        for (version, delta) in self.versioned(doc_id, "deltas")? {
+            entries
+                .entry(version)
+                .and_modify(|entry: &mut (Option<VersionInfo>, &[u8], bool)| entry.2 = true)
+                .or_insert((None, delta, true));
+        }
+
+        Ok(ArchivedDocument { doc_id, metadata, snapshot, entries })
+    }
+}
+
+/// Import every document of `archive` into `store`, in archive order.
+///
+/// Ids are checked against `store` before anything is written. Each
+/// delta gets a new version from the store's counter, in the original
+/// order, so imports never collide with versions already in the store.
+pub fn import(store: &DocumentStore, archive: &Archive, ids: IdPolicy) -> Result<Vec<ImportedDocument>, ArchiveError> {
+    import_avoiding(store, archive, ids, &HashSet::new())
+}
+
+/// [`import`], also treating the ids in `reserved` as taken (e.g. rooms a
+/// server has open that have not reached the store yet).
+pub(crate) fn import_avoiding(
+    store: &DocumentStore,
+    archive: &Archive,
+    ids: IdPolicy,
+    reserved: &HashSet<Uuid>,
+) -> Result<Vec<ImportedDocument>, ArchiveError> {
+    let docs = archive
+        .documents
+        .iter()
+        .map(|&doc_id| archive.document(doc_id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut new_ids = HashMap::new();
+    for doc in &docs {
+        let taken = reserved.contains(&doc.doc_id) || store.document_exists(doc.doc_id)?;
+        let keep = match ids {
+            IdPolicy::Keep if taken => return Err(StoreError::DocumentExists(doc.doc_id).into()),
+            IdPolicy::Keep => true,
+            IdPolicy::RenameOnClash => !taken,
+            IdPolicy::Fresh => false,
+        };
+        new_ids.insert(doc.doc_id, if keep { doc.doc_id } else { Uuid::new_v4() });
+    }
+
+    let versions: HashMap<Uuid, BTreeMap<u64, u64>> = docs
+        .iter()
+        .map(|doc| {
+            let map = doc.entries.keys().map(|&old| (old, store.allocate_version())).collect();
+            (doc.doc_id, map)
+        })
+        .collect();
+
+    docs.iter().map(|doc| write_document(store, doc, &new_ids, &versions)).collect()
+}
+
+fn write_document(
+    store: &DocumentStore,
+    doc: &ArchivedDocument<'_>,
+    new_ids: &HashMap<Uuid, Uuid>,
+    versions: &HashMap<Uuid, BTreeMap<u64, u64>>,
+) -> Result<ImportedDocument, ArchiveError> {
+    let doc_id = new_ids[&doc.doc_id];
+    let map = &versions[&doc.doc_id];
+
+    let mut meta = doc.metadata.clone();
+    meta.doc_id = doc_id;
+    meta.version = remap(map, meta.version);
+    for checkpoint in &mut meta.checkpoints {
+        checkpoint.version = remap(map, checkpoint.version);
+    }
+    // Branches of documents outside the archive keep pointing at them
+    if let Some(origin) = &mut meta.forked_from {
+        if let Some(origin_map) = versions.get(&origin.doc_id) {
+            origin.version = remap(origin_map, origin.version);
+            origin.doc_id = new_ids[&origin.doc_id];
+        }
+    }
+
+    // The log starts at the first entry still in it, or after them all
+    meta.log_start = match doc.entries.iter().find(|(_, (_, _, in_log))| *in_log) {
+        Some((old, _)) => map[old],
+        None => map.values().next_back().map_or(0, |last| last + 1),
+    };
+
+    for (old, (info, delta, _)) in &doc.entries {
+        store.import_delta(doc_id, map[old], info.clone(), delta, &meta)?;
+    }
+    store.import_snapshot(doc_id, doc.snapshot, meta)?;
+
+    Ok(ImportedDocument {
+        original_id: doc.doc_id,
+        doc_id,
+        versions: doc.entries.len(),
+    })
+}
+
+/// New version of the latest entry at or before archived `version`.
+fn remap(map: &BTreeMap<u64, u64>, version: u64) -> u64 {
+    map.range(..=version).next_back().map_or(0, |(_, &new)| new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{fork, materialize};
+    use crate::storage::MemoryBackend;
+    use yrs::updates::decoder::Decode;
+    use yrs::{Doc, GetString, ReadTxn, Text, Transact, WriteTxn};
+
+    fn memory_store() -> DocumentStore {
+        DocumentStore::with_backend(MemoryBackend::new()).unwrap()
+    }
+
+    /// Store "hello" at v1 and " world" at v2, checkpoint v1, snapshot
+    /// and compact v1 away.
+    fn write_doc(store: &DocumentStore, doc_id: Uuid) {
+        let doc = Doc::new();
+        for (index, (pos, chunk)) in [(0, "hello"), (5, " world")].into_iter().enumerate() {
+            let before = doc.transact().state_vector();
+            {
+                let txn = &mut doc.transact_mut();
+                txn.get_or_insert_text("content").insert(txn, pos, chunk);
+            }
+            let update = doc.transact().encode_state_as_update_v1(&before);
+            let version = store.allocate_version();
+            store.store_delta_by(doc_id, version, Some(Uuid::nil()), &update).unwrap();
+            if index == 0 {
+                store.create_checkpoint(doc_id, "first", version, None).unwrap();
+                let state = doc.transact().encode_state_as_update_v1(&yrs::StateVector::default());
+                store.save_snapshot(doc_id, &state).unwrap();
+                store.compact_deltas(doc_id, version).unwrap();
+            }
+        }
+    }
+
+    fn text_at(store: &DocumentStore, doc_id: Uuid, version: u64) -> String {
+        let doc = Doc::new();
+        let state = materialize(store, doc_id, version).unwrap();
+        doc.transact_mut().apply_update(yrs::Update::decode_v1(&state).unwrap()).unwrap();
+        let txn = doc.transact();
+        txn.get_text("content").unwrap().get_string(&txn)
+    }
+
+    #[test]
+    fn test_roundtrip_into_another_store() {
+        let source = memory_store();
+        let doc_id = Uuid::new_v4();
+        write_doc(&source, doc_id);
+
+        let mut archive = export_all(&source).unwrap();
+        archive.add_asset(doc_id, "logo.png", vec![1, 2, 3]).unwrap();
+        assert!(matches!(archive.add_asset(doc_id, "../x", vec![]), Err(ArchiveError::InvalidName(_))));
+        let archive = Archive::decode(&archive.encode().unwrap()).unwrap();
+        assert_eq!(archive.documents(), &[doc_id]);
+        assert_eq!(archive.assets(doc_id), vec![("logo.png", [1u8, 2, 3].as_slice())]);
+
+        let target = memory_store();
+        let imported = import(&target, &archive, IdPolicy::default()).unwrap();
+        assert_eq!(imported, vec![ImportedDocument { original_id: doc_id, doc_id, versions: 2 }]);
+
+        let versions = target.list_versions(doc_id).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].author, Some(Uuid::nil()));
+        assert_eq!(target.load_all_deltas(doc_id).unwrap().len(), 1);
+        assert_eq!(target.load_snapshot(doc_id).unwrap(), source.load_snapshot(doc_id).unwrap());
+        let checkpoint = target.checkpoint(doc_id, "first").unwrap();
+        assert_eq!(checkpoint.version, versions[0].version);
+        assert_eq!(text_at(&target, doc_id, checkpoint.version), "hello");
+        assert_eq!(text_at(&target, doc_id, versions[1].version), "hello world");
+    }
+
+    #[test]
+    fn test_tampered_file_fails_checksum() {
+        let store = memory_store();
+        let doc_id = Uuid::new_v4();
+        write_doc(&store, doc_id);
+        let mut archive = export(&store, &[doc_id]).unwrap();
+        let manifest = archive.manifest();
+
+        let path = format!("{doc_id}/snapshot");
+        archive.files.get_mut(&path).unwrap()[0] ^= 1;
+        assert!(matches!(
+            Archive::decode(&encode_parts(manifest.clone(), &archive.files).unwrap()),
+            Err(ArchiveError::ChecksumMismatch { path: p }) if p == path
+        ));
+
+        archive.files.remove(&path);
+        assert!(matches!(
+            Archive::decode(&encode_parts(manifest, &archive.files).unwrap()),
+            Err(ArchiveError::MissingFile(p)) if p == path
+        ));
+        assert!(matches!(Archive::decode(b"not an archive"), Err(ArchiveError::Format(_))));
+    }
+
+    #[test]
+    fn test_import_into_same_store_renames() {
+        let store = memory_store();
+        let main_id = Uuid::new_v4();
+        write_doc(&store, main_id);
+        let branch_id = Uuid::new_v4();
+        fork(&store, main_id, "first", branch_id, None).unwrap();
+        let latest = store.list_versions(main_id).unwrap().last().unwrap().version;
+
+        let archive = export_all(&store).unwrap();
+        assert!(matches!(
+            import(&store, &archive, IdPolicy::Keep),
+            Err(ArchiveError::Store(StoreError::DocumentExists(_)))
+        ));
+        let imported = import(&store, &archive, IdPolicy::RenameOnClash).unwrap();
+        let new_id = |old| imported.iter().find(|d| d.original_id == old).unwrap().doc_id;
+        let (new_main, new_branch) = (new_id(main_id), new_id(branch_id));
+        assert_ne!(new_main, main_id);
+        assert_ne!(new_branch, branch_id);
+
+        // Fresh versions, so the originals are untouched
+        let versions = store.list_versions(new_main).unwrap();
+        assert!(versions.iter().all(|info| info.version > latest));
+        assert_eq!(store.list_versions(main_id).unwrap().len(), 2);
+        assert_eq!(text_at(&store, main_id, latest), "hello world");
+
+        // The copied branch points at the copied main
+        let origin = store.load_metadata(new_branch).unwrap().forked_from.unwrap();
+        assert_eq!((origin.doc_id, origin.version), (new_main, versions[0].version));
+        assert_eq!(text_at(&store, new_branch, u64::MAX), "hello");
+    }
+
+    #[test]
+    fn test_export_unknown_document_fails() {
+        let store = memory_store();
+        assert!(matches!(
+            export(&store, &[Uuid::new_v4()]),
+            Err(ArchiveError::Store(StoreError::NotFound(_)))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+//! Storage backend trait behind [`DocumentStore`](super::DocumentStore).
+//!
+//! A backend keeps opaque byte values under three kinds of key:
+//! - `doc_id` — snapshots and metadata
+//! - `(doc_id, version)` — deltas and history records, scanned in version order
+//! - WAL sequence number — `(doc_id, delta)` entries, scanned in sequence order
+//!
+//! Compression, metadata encoding, checkpoints and version allocation all
+//! live in `DocumentStore`, so every backend stores byte-identical values
+//! and a new backend only has to get ordering and atomicity right.
+//!
+//! Implementations:
+//! - [`RocksBackend`](super::RocksBackend) — RocksDB column families (default)
+//! - [`SqliteBackend`](super::SqliteBackend) — one SQLite database file
+//! - [`MemoryBackend`](super::MemoryBackend) — process memory, for tests and embedding
+
+use std::path::Path;
+use uuid::Uuid;
+
+use super::store::StoreError;
+
+/// Key-ordered byte storage for snapshots, deltas, metadata and the WAL.
+///
+/// Methods writing several values must apply them atomically: a reader
+/// never sees a delta without its history record and metadata. Range
+/// bounds are inclusive.
+pub trait StorageBackend: Send + Sync {
+    /// Short engine name for logs, e.g. "rocksdb".
+    fn name(&self) -> &'static str;
+
+    // ─── Snapshots ────────────────────────────────────────────────────
+
+    /// Write a document's snapshot and metadata together.
+    fn put_snapshot(&self, doc_id: Uuid, snapshot: &[u8], metadata: &[u8]) -> Result<(), StoreError>;
+
+    /// Read a document's snapshot.
+    fn get_snapshot(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError>;
+
+    // ─── Deltas & History ─────────────────────────────────────────────
+
+    /// Write a delta and/or its history record under `version`, with the
+    /// document's metadata, together. Imports write deltas older than
+    /// history without a record.
+    fn put_delta(
+        &self,
+        doc_id: Uuid,
+        version: u64,
+        delta: Option<&[u8]>,
+        history: Option<&[u8]>,
+        metadata: &[u8],
+    ) -> Result<(), StoreError>;
+
+    /// Read the delta stored under `version`.
+    fn get_delta(&self, doc_id: Uuid, version: u64) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Deltas from `from_version` on, in version order.
+    fn scan_deltas(&self, doc_id: Uuid, from_version: u64) -> Result<Vec<(u64, Vec<u8>)>, StoreError>;
+
+    /// Delete deltas and their history records up to `up_to_version`,
+    /// returning how many deltas went.
+    fn delete_deltas(&self, doc_id: Uuid, up_to_version: u64) -> Result<u64, StoreError>;
+
+    /// History records up to `up_to_version`, in version order.
+    fn scan_history(&self, doc_id: Uuid, up_to_version: u64) -> Result<Vec<Vec<u8>>, StoreError>;
+
+    // ─── Metadata ─────────────────────────────────────────────────────
+
+    /// Write a document's metadata.
+    fn put_metadata(&self, doc_id: Uuid, metadata: &[u8]) -> Result<(), StoreError>;
+
+    /// Read a document's metadata.
+    fn get_metadata(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Every document with metadata.
+    fn list_documents(&self) -> Result<Vec<Uuid>, StoreError>;
+
+    /// Remove a document's snapshot, deltas, history and metadata together.
+    fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError>;
+
+    // ─── WAL ──────────────────────────────────────────────────────────
+
+    /// Append a WAL entry under `seq`.
+    fn wal_put(&self, seq: u64, doc_id: Uuid, delta: &[u8]) -> Result<(), StoreError>;
+
+    /// WAL entries from `since_seq` on, in sequence order.
+    fn wal_scan(&self, since_seq: u64) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError>;
+
+    /// Delete WAL entries up to `up_to_seq`, returning how many went.
+    fn wal_delete(&self, up_to_seq: u64) -> Result<u64, StoreError>;
+
+    /// Highest sequence number in the WAL.
+    fn wal_last(&self) -> Result<Option<u64>, StoreError>;
+
+    // ─── Durability ───────────────────────────────────────────────────
+
+    /// Flush everything written so far to durable storage.
+    fn flush(&self) -> Result<(), StoreError>;
+
+    /// Make every write so far survive power loss, as cheaply as the
+    /// engine allows.
+    fn flush_wal(&self) -> Result<(), StoreError>;
+
+    /// Where the data lives on disk, if anywhere.
+    fn path(&self) -> Option<&Path>;
+
+    /// Bytes the backend occupies.
+    fn disk_usage(&self) -> Result<u64, StoreError>;
+}
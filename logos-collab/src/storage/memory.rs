@@ -0,0 +1,242 @@
+//! In-memory storage backend.
+//!
+//! Keeps every table in ordered maps behind one mutex, so multi-value
+//! writes are atomic by construction. Nothing survives the process, but
+//! clones share their tables: a server dropped and rebuilt over a clone
+//! recovers what the first one wrote, which is what tests and embedders
+//! without a disk need.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+use super::backend::StorageBackend;
+use super::store::StoreError;
+
+/// Tables, mirroring the RocksDB column families.
+#[derive(Default)]
+struct Tables {
+    documents: HashMap<Uuid, Vec<u8>>,
+    deltas: BTreeMap<(Uuid, u64), Vec<u8>>,
+    history: BTreeMap<(Uuid, u64), Vec<u8>>,
+    metadata: BTreeMap<Uuid, Vec<u8>>,
+    wal: BTreeMap<u64, (Uuid, Vec<u8>)>,
+}
+
+impl Tables {
+    fn size(&self) -> u64 {
+        let keyed = |len: usize| (16 + 8 + len) as u64;
+        self.documents.values().map(|v| keyed(v.len())).sum::<u64>()
+            + self.deltas.values().map(|v| keyed(v.len())).sum::<u64>()
+            + self.history.values().map(|v| keyed(v.len())).sum::<u64>()
+            + self.metadata.values().map(|v| keyed(v.len())).sum::<u64>()
+            + self.wal.values().map(|(_, v)| keyed(v.len())).sum::<u64>()
+    }
+}
+
+/// Storage held in process memory; clones share the same data.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl MemoryBackend {
+    /// Create an empty backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl std::fmt::Debug for MemoryBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tables = self.lock();
+        f.debug_struct("MemoryBackend")
+            .field("documents", &tables.metadata.len())
+            .field("wal_entries", &tables.wal.len())
+            .finish()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn put_snapshot(&self, doc_id: Uuid, snapshot: &[u8], metadata: &[u8]) -> Result<(), StoreError> {
+        let mut tables = self.lock();
+        tables.documents.insert(doc_id, snapshot.to_vec());
+        tables.metadata.insert(doc_id, metadata.to_vec());
+        Ok(())
+    }
+
+    fn get_snapshot(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.lock().documents.get(&doc_id).cloned())
+    }
+
+    fn put_delta(
+        &self,
+        doc_id: Uuid,
+        version: u64,
+        delta: Option<&[u8]>,
+        history: Option<&[u8]>,
+        metadata: &[u8],
+    ) -> Result<(), StoreError> {
+        let mut tables = self.lock();
+        if let Some(delta) = delta {
+            tables.deltas.insert((doc_id, version), delta.to_vec());
+        }
+        if let Some(history) = history {
+            tables.history.insert((doc_id, version), history.to_vec());
+        }
+        tables.metadata.insert(doc_id, metadata.to_vec());
+        Ok(())
+    }
+
+    fn get_delta(&self, doc_id: Uuid, version: u64) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.lock().deltas.get(&(doc_id, version)).cloned())
+    }
+
+    fn scan_deltas(&self, doc_id: Uuid, from_version: u64) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+        Ok(self
+            .lock()
+            .deltas
+            .range((doc_id, from_version)..=(doc_id, u64::MAX))
+            .map(|(&(_, version), delta)| (version, delta.clone()))
+            .collect())
+    }
+
+    fn delete_deltas(&self, doc_id: Uuid, up_to_version: u64) -> Result<u64, StoreError> {
+        let mut tables = self.lock();
+        let range = (doc_id, 0)..=(doc_id, up_to_version);
+        let doomed: Vec<(Uuid, u64)> = tables.deltas.range(range.clone()).map(|(key, _)| *key).collect();
+        let records: Vec<(Uuid, u64)> = tables.history.range(range).map(|(key, _)| *key).collect();
+        for key in &doomed {
+            tables.deltas.remove(key);
+        }
+        for key in &records {
+            tables.history.remove(key);
+        }
+        Ok(doomed.len() as u64)
+    }
+
+    fn scan_history(&self, doc_id: Uuid, up_to_version: u64) -> Result<Vec<Vec<u8>>, StoreError> {
+        Ok(self
+            .lock()
+            .history
+            .range((doc_id, 0)..=(doc_id, up_to_version))
+            .map(|(_, record)| record.clone())
+            .collect())
+    }
+
+    fn put_metadata(&self, doc_id: Uuid, metadata: &[u8]) -> Result<(), StoreError> {
+        self.lock().metadata.insert(doc_id, metadata.to_vec());
+        Ok(())
+    }
+
+    fn get_metadata(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.lock().metadata.get(&doc_id).cloned())
+    }
+
+    fn list_documents(&self) -> Result<Vec<Uuid>, StoreError> {
+        Ok(self.lock().metadata.keys().copied().collect())
+    }
+
+    fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError> {
+        let mut tables = self.lock();
+        tables.documents.remove(&doc_id);
+        tables.metadata.remove(&doc_id);
+        tables.deltas.retain(|(id, _), _| *id != doc_id);
+        tables.history.retain(|(id, _), _| *id != doc_id);
+        Ok(())
+    }
+
+    fn wal_put(&self, seq: u64, doc_id: Uuid, delta: &[u8]) -> Result<(), StoreError> {
+        self.lock().wal.insert(seq, (doc_id, delta.to_vec()));
+        Ok(())
+    }
+
+    fn wal_scan(&self, since_seq: u64) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError> {
+        Ok(self
+            .lock()
+            .wal
+            .range(since_seq..)
+            .map(|(&seq, (doc_id, delta))| (seq, *doc_id, delta.clone()))
+            .collect())
+    }
+
+    fn wal_delete(&self, up_to_seq: u64) -> Result<u64, StoreError> {
+        let mut tables = self.lock();
+        let kept = match up_to_seq.checked_add(1) {
+            Some(next) => tables.wal.split_off(&next),
+            None => BTreeMap::new(),
+        };
+        let removed = tables.wal.len() as u64;
+        tables.wal = kept;
+        Ok(removed)
+    }
+
+    fn wal_last(&self) -> Result<Option<u64>, StoreError> {
+        Ok(self.lock().wal.keys().next_back().copied())
+    }
+
+    fn flush(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    fn flush_wal(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Bytes held in the tables, counting keys.
+    fn disk_usage(&self) -> Result<u64, StoreError> {
+        Ok(self.lock().size())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_stay_within_document() {
+        let backend = MemoryBackend::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        for version in 1..=4 {
+            backend.put_delta(a, version, Some(b"a"), Some(b"ha"), b"ma").unwrap();
+            backend.put_delta(b, version, Some(b"b"), Some(b"hb"), b"mb").unwrap();
+        }
+
+        assert_eq!(backend.scan_deltas(a, 3).unwrap(), vec![(3, b"a".to_vec()), (4, b"a".to_vec())]);
+        assert_eq!(backend.delete_deltas(a, 2).unwrap(), 2);
+        assert_eq!(backend.scan_deltas(b, 0).unwrap().len(), 4);
+        assert_eq!(backend.scan_history(a, u64::MAX).unwrap().len(), 2, "records go with their deltas");
+        assert_eq!(backend.scan_history(b, u64::MAX).unwrap().len(), 4);
+
+        backend.delete_document(a).unwrap();
+        assert!(backend.scan_history(a, u64::MAX).unwrap().is_empty());
+        assert_eq!(backend.list_documents().unwrap(), vec![b]);
+    }
+
+    #[test]
+    fn test_wal_delete_is_inclusive() {
+        let backend = MemoryBackend::new();
+        let doc_id = Uuid::new_v4();
+        for seq in 0..5 {
+            backend.wal_put(seq, doc_id, &[seq as u8]).unwrap();
+        }
+        assert_eq!(backend.wal_delete(2).unwrap(), 3);
+        assert_eq!(backend.wal_scan(0).unwrap()[0].0, 3);
+        assert_eq!(backend.wal_last().unwrap(), Some(4));
+        assert_eq!(backend.wal_delete(u64::MAX).unwrap(), 2);
+        assert_eq!(backend.wal_last().unwrap(), None);
+    }
+}
@@ -4,20 +4,30 @@
 //! ```text
 //! ┌─────────────┐     deltas      ┌──────────────┐
 //! │ SyncServer  │ ──────────────► │ DocumentStore│
-//! │ (in-memory) │                 │ (RocksDB)    │
+//! │ (in-memory) │                 │ (pluggable)  │
 //! └──────┬──────┘                 └──────┬───────┘
-//!        │                               │
-//!        │ on startup                    │ column families
-//!        ▼                               ▼
+//!        │                               │ StorageBackend
+//!        │ on startup                    │ (RocksDB shown;
+//!        ▼                               ▼  SQLite, memory)
 //! ┌─────────────┐     ┌──────────────────────────────────┐
 //! │ Yrs Doc     │     │ CF "documents" — full snapshots   │
 //! │ (restored)  │     │ CF "deltas"    — compressed edits │
 //! └─────────────┘     │ CF "metadata"  — doc metadata     │
 //!                     │ CF "wal"       — write-ahead log  │
+//!                     │ CF "history"   — authored deltas  │
 //!                     └──────────────────────────────────┘
 //! ```
 //!
-//! ## Performance Targets
+//! `DocumentStore` does compression, metadata, history and checkpoints;
+//! the [`StorageBackend`] underneath only stores bytes. RocksDB is the
+//! default, [`SqliteBackend`] keeps a store in one file and
+//! [`MemoryBackend`] keeps it in process memory. Choose with
+//! [`StoreConfig::backend`].
+//!
+//! [`archive`] exports documents to portable, checksummed archives and
+//! imports them into any store without clashing on ids.
+//!
+//! ## Performance Targets (RocksDB)
 //!
 //! | Metric               | Target  | Reference                          |
 //! |----------------------|---------|------------------------------------|
@@ -30,10 +40,23 @@
 //!
 //! Reference: Kleppmann — Designing Data-Intensive Applications, Chapter 3
 
+pub mod backend;
+pub mod store;
 pub mod rocks;
+pub mod sqlite;
+pub mod memory;
 pub mod delta;
 pub mod wal;
+pub mod archive;
 
-pub use rocks::{DocumentStore, StoreConfig, StoreError, DocumentMetadata};
+pub use backend::StorageBackend;
+pub use store::{
+    BackendKind, DocumentStore, StoreConfig, StoreError, DocumentMetadata, VersionInfo, Checkpoint,
+    ForkOrigin,
+};
+pub use rocks::RocksBackend;
+pub use sqlite::SqliteBackend;
+pub use memory::MemoryBackend;
+pub use archive::{Archive, ArchiveError, IdPolicy, ImportedDocument, Manifest, ManifestEntry};
 pub use delta::{DeltaLog, CompressedDelta, DeltaStats};
 pub use wal::{WriteAheadLog, WalEntry, WalConfig, WalError};
@@ -1,10 +1,12 @@
-//! RocksDB-backed persistent document store.
+//! RocksDB storage backend.
 //!
 //! Column families:
 //! - `documents` — Full Yrs document snapshots (LZ4 compressed)
 //! - `deltas`    — Incremental CRDT deltas (LZ4 compressed, keyed by doc_id:version)
-//! - `metadata`  — Document metadata (JSON: created_at, version, size)
+//! - `metadata`  — Document metadata (created_at, version, size, checkpoints)
 //! - `wal`       — Write-ahead log entries (sequential, keyed by sequence number)
+//! - `history`   — Every delta with its author and time (keyed like `deltas`,
+//!   never compacted)
 //!
 //! Performance targets:
 //! - Open (10k docs): <100ms (bloom filters + block cache)
@@ -19,145 +21,21 @@ use rocksdb::{
     BlockBasedOptions, Cache, ColumnFamilyDescriptor, DBCompressionType, DBWithThreadMode,
     IteratorMode, Options, SingleThreaded, WriteBatch, WriteOptions,
 };
-use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::SystemTime;
 use uuid::Uuid;
 
+use super::backend::StorageBackend;
+use super::store::{StoreConfig, StoreError};
+
 /// Column family names.
 const CF_DOCUMENTS: &str = "documents";
 const CF_DELTAS: &str = "deltas";
 const CF_METADATA: &str = "metadata";
 const CF_WAL: &str = "wal";
+const CF_HISTORY: &str = "history";
 
 /// All column family names for initialization.
-const COLUMN_FAMILIES: &[&str] = &[CF_DOCUMENTS, CF_DELTAS, CF_METADATA, CF_WAL];
-
-/// Store configuration.
-#[derive(Debug, Clone)]
-pub struct StoreConfig {
-    /// Database directory path
-    pub path: PathBuf,
-    /// Block cache size in bytes (default: 256MB)
-    pub block_cache_size: usize,
-    /// Bloom filter bits per key (default: 10)
-    pub bloom_filter_bits: i32,
-    /// Enable fsync on every write (default: false — batch fsync instead)
-    pub sync_writes: bool,
-    /// Max open files for RocksDB (default: 512)
-    pub max_open_files: i32,
-    /// Write buffer size per column family (default: 64MB)
-    pub write_buffer_size: usize,
-}
-
-impl Default for StoreConfig {
-    fn default() -> Self {
-        Self {
-            path: PathBuf::from("logos_data"),
-            block_cache_size: 256 * 1024 * 1024, // 256MB
-            bloom_filter_bits: 10,
-            sync_writes: false, // Batch fsync via WAL
-            max_open_files: 512,
-            write_buffer_size: 64 * 1024 * 1024, // 64MB
-        }
-    }
-}
-
-impl StoreConfig {
-    /// Create config for testing (small caches, temp directory).
-    pub fn for_testing(path: impl Into<PathBuf>) -> Self {
-        Self {
-            path: path.into(),
-            block_cache_size: 8 * 1024 * 1024, // 8MB
-            bloom_filter_bits: 10,
-            sync_writes: false,
-            max_open_files: 64,
-            write_buffer_size: 4 * 1024 * 1024, // 4MB
-        }
-    }
-}
-
-/// Document metadata stored alongside snapshots.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DocumentMetadata {
-    /// Document UUID
-    pub doc_id: Uuid,
-    /// Current version (monotonically increasing)
-    pub version: u64,
-    /// Total number of deltas stored
-    pub delta_count: u64,
-    /// Uncompressed snapshot size in bytes
-    pub snapshot_size: u64,
-    /// Compressed snapshot size in bytes
-    pub compressed_size: u64,
-    /// Creation timestamp (seconds since epoch)
-    pub created_at: u64,
-    /// Last modified timestamp (seconds since epoch)
-    pub updated_at: u64,
-}
-
-impl DocumentMetadata {
-    fn new(doc_id: Uuid) -> Self {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        Self {
-            doc_id,
-            version: 0,
-            delta_count: 0,
-            snapshot_size: 0,
-            compressed_size: 0,
-            created_at: now,
-            updated_at: now,
-        }
-    }
-
-    fn encode(&self) -> Result<Vec<u8>, StoreError> {
-        bincode::serde::encode_to_vec(self, bincode::config::standard())
-            .map_err(|e| StoreError::SerializationError(e.to_string()))
-    }
-
-    fn decode(bytes: &[u8]) -> Result<Self, StoreError> {
-        let (meta, _) =
-            bincode::serde::decode_from_slice(bytes, bincode::config::standard())
-                .map_err(|e| StoreError::DeserializationError(e.to_string()))?;
-        Ok(meta)
-    }
-}
-
-/// Storage errors.
-#[derive(Debug, Clone)]
-pub enum StoreError {
-    /// RocksDB internal error
-    DatabaseError(String),
-    /// Document not found
-    NotFound(Uuid),
-    /// Serialization failed
-    SerializationError(String),
-    /// Deserialization failed
-    DeserializationError(String),
-    /// Compression error
-    CompressionError(String),
-    /// I/O error
-    IoError(String),
-}
-
-impl std::fmt::Display for StoreError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            StoreError::DatabaseError(e) => write!(f, "Database error: {e}"),
-            StoreError::NotFound(id) => write!(f, "Document not found: {id}"),
-            StoreError::SerializationError(e) => write!(f, "Serialization error: {e}"),
-            StoreError::DeserializationError(e) => write!(f, "Deserialization error: {e}"),
-            StoreError::CompressionError(e) => write!(f, "Compression error: {e}"),
-            StoreError::IoError(e) => write!(f, "I/O error: {e}"),
-        }
-    }
-}
-
-impl std::error::Error for StoreError {}
+const COLUMN_FAMILIES: &[&str] = &[CF_DOCUMENTS, CF_DELTAS, CF_METADATA, CF_WAL, CF_HISTORY];
 
 impl From<rocksdb::Error> for StoreError {
     fn from(e: rocksdb::Error) -> Self {
@@ -165,28 +43,26 @@ impl From<rocksdb::Error> for StoreError {
     }
 }
 
-/// RocksDB-backed document store.
+/// RocksDB-backed storage.
 ///
-/// Provides durable storage for collaborative documents with:
-/// - LZ4-compressed snapshots and deltas
+/// Provides:
 /// - Bloom filters for fast key lookup
 /// - Block cache for hot document access
 /// - Atomic write batches for consistency
-pub struct DocumentStore {
+pub struct RocksBackend {
     /// RocksDB instance (single-threaded mode — concurrency via tokio)
     db: DBWithThreadMode<SingleThreaded>,
-    /// Store configuration
-    config: StoreConfig,
-    /// Global sequence number for WAL entries
-    sequence: AtomicU64,
+    /// Database directory
+    path: PathBuf,
+    /// Fsync every batch write
+    sync_writes: bool,
 }
 
-impl DocumentStore {
-    /// Open the document store at the configured path.
+impl RocksBackend {
+    /// Open the database at `config.path`.
     ///
     /// Creates the database and column families if they don't exist.
-    /// Target: <100ms for database with 10,000 documents.
-    pub fn open(config: StoreConfig) -> Result<Self, StoreError> {
+    pub fn open(config: &StoreConfig) -> Result<Self, StoreError> {
         let mut db_opts = Options::default();
         db_opts.create_if_missing(true);
         db_opts.create_missing_column_families(true);
@@ -199,7 +75,7 @@ impl DocumentStore {
         let cf_descriptors: Vec<ColumnFamilyDescriptor> = COLUMN_FAMILIES
             .iter()
             .map(|name| {
-                let cf_opts = Self::cf_options(name, &config);
+                let cf_opts = Self::cf_options(name, config);
                 ColumnFamilyDescriptor::new(*name, cf_opts)
             })
             .collect();
@@ -210,14 +86,7 @@ impl DocumentStore {
             cf_descriptors,
         )?;
 
-        // Recover sequence number from WAL
-        let sequence = Self::recover_sequence(&db);
-
-        Ok(Self {
-            db,
-            config,
-            sequence: AtomicU64::new(sequence),
-        })
+        Ok(Self { db, path: config.path.clone(), sync_writes: config.sync_writes })
     }
 
     /// Build column-family-specific options.
@@ -258,160 +127,42 @@ impl DocumentStore {
                 opts.set_max_write_buffer_number(2);
                 opts.set_compression_type(DBCompressionType::None); // WAL needs speed
             }
+            CF_HISTORY => {
+                // Written with every delta, prefix-scanned when browsing
+                opts.set_max_write_buffer_number(4);
+                opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(16));
+            }
             _ => {}
         }
 
         opts
     }
 
-    /// Recover the last sequence number from the WAL column family.
-    fn recover_sequence(db: &DBWithThreadMode<SingleThreaded>) -> u64 {
-        let cf = match db.cf_handle(CF_WAL) {
-            Some(cf) => cf,
-            None => return 0,
-        };
-
-        // Get the last key in WAL CF (highest sequence number)
-        let mut iter = db.iterator_cf(&cf, IteratorMode::End);
-        match iter.next() {
-            Some(Ok((key, _))) => {
-                if key.len() >= 8 {
-                    let mut buf = [0u8; 8];
-                    buf.copy_from_slice(&key[..8]);
-                    u64::from_be_bytes(buf) + 1
-                } else {
-                    0
-                }
-            }
-            _ => 0,
-        }
-    }
-
-    // ─── Document Snapshots ───────────────────────────────────────────
-
-    /// Save a full document snapshot (LZ4 compressed).
-    ///
-    /// Used for periodic compaction and initial persistence.
-    /// The snapshot is the full Yrs document state encoded with `encode_v1`.
-    pub fn save_snapshot(
-        &self,
-        doc_id: Uuid,
-        snapshot: &[u8],
-    ) -> Result<DocumentMetadata, StoreError> {
-        let cf_docs = self.cf(CF_DOCUMENTS)?;
-        let cf_meta = self.cf(CF_METADATA)?;
-
-        // LZ4 compress the snapshot
-        let compressed = lz4_flex::compress_prepend_size(snapshot);
-
-        // Load or create metadata
-        let mut meta = self.load_metadata(doc_id).unwrap_or_else(|_| DocumentMetadata::new(doc_id));
-        meta.snapshot_size = snapshot.len() as u64;
-        meta.compressed_size = compressed.len() as u64;
-        meta.updated_at = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-
-        // Atomic batch write: snapshot + metadata
-        let mut batch = WriteBatch::default();
-        let key = doc_id.as_bytes().to_vec();
-        batch.put_cf(&cf_docs, &key, &compressed);
-        batch.put_cf(&cf_meta, &key, &meta.encode()?);
-
+    /// Write a batch, fsyncing if configured.
+    fn write(&self, batch: WriteBatch) -> Result<(), StoreError> {
         let mut write_opts = WriteOptions::default();
-        write_opts.set_sync(self.config.sync_writes);
+        write_opts.set_sync(self.sync_writes);
         self.db.write_opt(batch, &write_opts)?;
-
-        Ok(meta)
-    }
-
-    /// Load a document snapshot (LZ4 decompressed).
-    ///
-    /// Returns the raw Yrs document state for `apply_update`.
-    /// Target: <1ms for cache-hot document.
-    pub fn load_snapshot(&self, doc_id: Uuid) -> Result<Vec<u8>, StoreError> {
-        let cf = self.cf(CF_DOCUMENTS)?;
-        let key = doc_id.as_bytes().to_vec();
-
-        match self.db.get_cf(&cf, &key)? {
-            Some(compressed) => {
-                lz4_flex::decompress_size_prepended(&compressed)
-                    .map_err(|e| StoreError::CompressionError(e.to_string()))
-            }
-            None => Err(StoreError::NotFound(doc_id)),
-        }
-    }
-
-    /// Check if a document exists.
-    pub fn document_exists(&self, doc_id: Uuid) -> Result<bool, StoreError> {
-        let cf = self.cf(CF_METADATA)?;
-        let key = doc_id.as_bytes().to_vec();
-        Ok(self.db.get_cf(&cf, &key)?.is_some())
-    }
-
-    // ─── Deltas ───────────────────────────────────────────────────────
-
-    /// Store a compressed delta for a document.
-    ///
-    /// Key format: `<doc_id:16 bytes><version:8 bytes big-endian>`
-    /// Value: LZ4-compressed delta payload.
-    /// Target: <50μs per delta write.
-    pub fn store_delta(
-        &self,
-        doc_id: Uuid,
-        version: u64,
-        delta: &[u8],
-    ) -> Result<u64, StoreError> {
-        let cf_deltas = self.cf(CF_DELTAS)?;
-        let cf_meta = self.cf(CF_METADATA)?;
-
-        // LZ4 compress the delta
-        let compressed = lz4_flex::compress_prepend_size(delta);
-        let compressed_len = compressed.len() as u64;
-
-        // Build key: doc_id (16 bytes) + version (8 bytes BE)
-        let key = Self::delta_key(doc_id, version);
-
-        // Update metadata atomically
-        let mut meta = self.load_metadata(doc_id).unwrap_or_else(|_| DocumentMetadata::new(doc_id));
-        meta.version = version;
-        meta.delta_count += 1;
-        meta.updated_at = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-
-        let mut batch = WriteBatch::default();
-        batch.put_cf(&cf_deltas, &key, &compressed);
-        batch.put_cf(&cf_meta, doc_id.as_bytes(), &meta.encode()?);
-
-        let mut write_opts = WriteOptions::default();
-        write_opts.set_sync(self.config.sync_writes);
-        self.db.write_opt(batch, &write_opts)?;
-
-        Ok(compressed_len)
+        Ok(())
     }
 
-    /// Load all deltas for a document since a given version.
-    ///
-    /// Returns deltas in version order, LZ4 decompressed.
-    pub fn load_deltas_since(
+    /// Visit the `(version, value)` pairs of a document in a
+    /// `doc_id:version` keyed column family, from `from` to `to` inclusive.
+    fn scan_versions(
         &self,
+        cf: &rocksdb::ColumnFamily,
         doc_id: Uuid,
-        since_version: u64,
-    ) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
-        let cf = self.cf(CF_DELTAS)?;
-
-        let start_key = Self::delta_key(doc_id, since_version);
-        let end_key = Self::delta_key(doc_id, u64::MAX);
+        from: u64,
+        to: u64,
+        mut visit: impl FnMut(u64, &[u8], &[u8]),
+    ) -> Result<(), StoreError> {
+        let start_key = Self::delta_key(doc_id, from);
+        let end_key = Self::delta_key(doc_id, to);
 
-        let mut deltas = Vec::new();
         let iter = self.db.iterator_cf(
-            &cf,
+            cf,
             IteratorMode::From(&start_key, rocksdb::Direction::Forward),
         );
-
         for item in iter {
             let (key, value) = item.map_err(|e| StoreError::DatabaseError(e.to_string()))?;
 
@@ -426,86 +177,125 @@ impl DocumentStore {
             // Extract version from key
             let mut ver_buf = [0u8; 8];
             ver_buf.copy_from_slice(&key[16..24]);
-            let version = u64::from_be_bytes(ver_buf);
+            visit(u64::from_be_bytes(ver_buf), &key, &value);
+        }
+        Ok(())
+    }
 
-            // Decompress delta
-            let decompressed = lz4_flex::decompress_size_prepended(&value)
-                .map_err(|e| StoreError::CompressionError(e.to_string()))?;
+    /// Get a column family handle.
+    fn cf(&self, name: &str) -> Result<&rocksdb::ColumnFamily, StoreError> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| StoreError::DatabaseError(format!("Column family '{name}' not found")))
+    }
 
-            deltas.push((version, decompressed));
-        }
+    /// Build a delta key: doc_id (16 bytes) + version (8 bytes big-endian).
+    fn delta_key(doc_id: Uuid, version: u64) -> Vec<u8> {
+        let mut key = Vec::with_capacity(24);
+        key.extend_from_slice(doc_id.as_bytes());
+        key.extend_from_slice(&version.to_be_bytes());
+        key
+    }
+}
 
-        Ok(deltas)
+impl StorageBackend for RocksBackend {
+    fn name(&self) -> &'static str {
+        "rocksdb"
     }
 
-    /// Load all deltas for a document.
-    pub fn load_all_deltas(&self, doc_id: Uuid) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
-        self.load_deltas_since(doc_id, 0)
+    // ─── Document Snapshots ───────────────────────────────────────────
+
+    fn put_snapshot(&self, doc_id: Uuid, snapshot: &[u8], metadata: &[u8]) -> Result<(), StoreError> {
+        let mut batch = WriteBatch::default();
+        batch.put_cf(self.cf(CF_DOCUMENTS)?, doc_id.as_bytes(), snapshot);
+        batch.put_cf(self.cf(CF_METADATA)?, doc_id.as_bytes(), metadata);
+        self.write(batch)
     }
 
-    /// Count deltas stored for a document.
-    pub fn delta_count(&self, doc_id: Uuid) -> Result<u64, StoreError> {
-        let meta = self.load_metadata(doc_id)?;
-        Ok(meta.delta_count)
+    fn get_snapshot(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.db.get_cf(self.cf(CF_DOCUMENTS)?, doc_id.as_bytes())?)
     }
 
-    /// Delete all deltas for a document up to a version (after snapshot compaction).
-    pub fn compact_deltas(
+    // ─── Deltas ───────────────────────────────────────────────────────
+
+    /// Key format: `<doc_id:16 bytes><version:8 bytes big-endian>` in both
+    /// `deltas` and `history`.
+    fn put_delta(
         &self,
         doc_id: Uuid,
-        up_to_version: u64,
-    ) -> Result<u64, StoreError> {
-        let cf = self.cf(CF_DELTAS)?;
+        version: u64,
+        delta: Option<&[u8]>,
+        history: Option<&[u8]>,
+        metadata: &[u8],
+    ) -> Result<(), StoreError> {
+        let key = Self::delta_key(doc_id, version);
+        let mut batch = WriteBatch::default();
+        if let Some(delta) = delta {
+            batch.put_cf(self.cf(CF_DELTAS)?, &key, delta);
+        }
+        if let Some(history) = history {
+            batch.put_cf(self.cf(CF_HISTORY)?, &key, history);
+        }
+        batch.put_cf(self.cf(CF_METADATA)?, doc_id.as_bytes(), metadata);
+        self.write(batch)
+    }
 
-        let start_key = Self::delta_key(doc_id, 0);
-        let end_key = Self::delta_key(doc_id, up_to_version + 1);
+    fn get_delta(&self, doc_id: Uuid, version: u64) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.db.get_cf(self.cf(CF_DELTAS)?, Self::delta_key(doc_id, version))?)
+    }
 
-        // Count before deleting
-        let mut count = 0u64;
-        let iter = self.db.iterator_cf(
-            &cf,
-            IteratorMode::From(&start_key, rocksdb::Direction::Forward),
-        );
+    fn scan_deltas(&self, doc_id: Uuid, from_version: u64) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+        let mut deltas = Vec::new();
+        self.scan_versions(self.cf(CF_DELTAS)?, doc_id, from_version, u64::MAX, |version, _, value| {
+            deltas.push((version, value.to_vec()));
+        })?;
+        Ok(deltas)
+    }
 
+    fn delete_deltas(&self, doc_id: Uuid, up_to_version: u64) -> Result<u64, StoreError> {
+        let deltas = self.cf(CF_DELTAS)?;
+        let history = self.cf(CF_HISTORY)?;
+        let mut count = 0u64;
+        let mut records = 0u64;
         let mut batch = WriteBatch::default();
-        for item in iter {
-            let (key, _) = item.map_err(|e| StoreError::DatabaseError(e.to_string()))?;
-            if key.len() < 24 || &key[..16] != doc_id.as_bytes() {
-                break;
-            }
-            if key.as_ref() >= end_key.as_slice() {
-                break;
-            }
-            batch.delete_cf(&cf, &key);
+        self.scan_versions(deltas, doc_id, 0, up_to_version, |_, key, _| {
+            batch.delete_cf(deltas, key);
             count += 1;
-        }
-
-        if count > 0 {
+        })?;
+        self.scan_versions(history, doc_id, 0, up_to_version, |_, key, _| {
+            batch.delete_cf(history, key);
+            records += 1;
+        })?;
+        if count + records > 0 {
             self.db.write(batch)?;
         }
-
         Ok(count)
     }
 
+    fn scan_history(&self, doc_id: Uuid, up_to_version: u64) -> Result<Vec<Vec<u8>>, StoreError> {
+        let mut records = Vec::new();
+        self.scan_versions(self.cf(CF_HISTORY)?, doc_id, 0, up_to_version, |_, _, value| {
+            records.push(value.to_vec());
+        })?;
+        Ok(records)
+    }
+
     // ─── Metadata ─────────────────────────────────────────────────────
 
-    /// Load document metadata.
-    pub fn load_metadata(&self, doc_id: Uuid) -> Result<DocumentMetadata, StoreError> {
-        let cf = self.cf(CF_METADATA)?;
-        let key = doc_id.as_bytes().to_vec();
+    fn put_metadata(&self, doc_id: Uuid, metadata: &[u8]) -> Result<(), StoreError> {
+        self.db.put_cf(self.cf(CF_METADATA)?, doc_id.as_bytes(), metadata)?;
+        Ok(())
+    }
 
-        match self.db.get_cf(&cf, &key)? {
-            Some(bytes) => DocumentMetadata::decode(&bytes),
-            None => Err(StoreError::NotFound(doc_id)),
-        }
+    fn get_metadata(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.db.get_cf(self.cf(CF_METADATA)?, doc_id.as_bytes())?)
     }
 
-    /// List all document IDs in the store.
-    pub fn list_documents(&self) -> Result<Vec<Uuid>, StoreError> {
+    fn list_documents(&self) -> Result<Vec<Uuid>, StoreError> {
         let cf = self.cf(CF_METADATA)?;
         let mut doc_ids = Vec::new();
 
-        let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
+        let iter = self.db.iterator_cf(cf, IteratorMode::Start);
         for item in iter {
             let (key, _) = item.map_err(|e| StoreError::DatabaseError(e.to_string()))?;
             if key.len() == 16 {
@@ -521,35 +311,14 @@ impl DocumentStore {
         Ok(doc_ids)
     }
 
-    /// Delete a document and all its deltas/metadata.
-    pub fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError> {
-        let cf_docs = self.cf(CF_DOCUMENTS)?;
-        let cf_meta = self.cf(CF_METADATA)?;
-
-        let key = doc_id.as_bytes().to_vec();
-
+    fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError> {
         let mut batch = WriteBatch::default();
-        batch.delete_cf(&cf_docs, &key);
-        batch.delete_cf(&cf_meta, &key);
-
-        // Delete all deltas for this doc
-        let cf_deltas = self.cf(CF_DELTAS)?;
-        let start_key = Self::delta_key(doc_id, 0);
-        let end_key = Self::delta_key(doc_id, u64::MAX);
+        batch.delete_cf(self.cf(CF_DOCUMENTS)?, doc_id.as_bytes());
+        batch.delete_cf(self.cf(CF_METADATA)?, doc_id.as_bytes());
 
-        let iter = self.db.iterator_cf(
-            &cf_deltas,
-            IteratorMode::From(&start_key, rocksdb::Direction::Forward),
-        );
-        for item in iter {
-            let (key, _) = item.map_err(|e| StoreError::DatabaseError(e.to_string()))?;
-            if key.len() < 24 || &key[..16] != doc_id.as_bytes() {
-                break;
-            }
-            if key.as_ref() >= end_key.as_slice() {
-                break;
-            }
-            batch.delete_cf(&cf_deltas, &key);
+        // Delete all deltas and history for this doc
+        for cf in [self.cf(CF_DELTAS)?, self.cf(CF_HISTORY)?] {
+            self.scan_versions(cf, doc_id, 0, u64::MAX, |_, key, _| batch.delete_cf(cf, key))?;
         }
 
         self.db.write(batch)?;
@@ -558,17 +327,7 @@ impl DocumentStore {
 
     // ─── WAL Operations ───────────────────────────────────────────────
 
-    /// Append a WAL entry. Returns the sequence number assigned.
-    ///
-    /// Target: <10μs per append (no fsync, buffered).
-    pub fn wal_append(
-        &self,
-        doc_id: Uuid,
-        delta: &[u8],
-    ) -> Result<u64, StoreError> {
-        let cf = self.cf(CF_WAL)?;
-        let seq = self.sequence.fetch_add(1, Ordering::SeqCst);
-
+    fn wal_put(&self, seq: u64, doc_id: Uuid, delta: &[u8]) -> Result<(), StoreError> {
         // Key: sequence number (8 bytes BE) for sequential ordering
         let key = seq.to_be_bytes();
 
@@ -581,24 +340,17 @@ impl DocumentStore {
         write_opts.set_sync(false); // No fsync per-write — batched by caller
         write_opts.disable_wal(false); // Use RocksDB's own WAL for atomicity
 
-        self.db.put_cf_opt(&cf, key, &value, &write_opts)?;
-
-        Ok(seq)
+        self.db.put_cf_opt(self.cf(CF_WAL)?, key, &value, &write_opts)?;
+        Ok(())
     }
 
-    /// Read all WAL entries since a given sequence number.
-    ///
-    /// Used during crash recovery to replay uncommitted deltas.
-    pub fn wal_read_since(
-        &self,
-        since_seq: u64,
-    ) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError> {
+    fn wal_scan(&self, since_seq: u64) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError> {
         let cf = self.cf(CF_WAL)?;
         let start_key = since_seq.to_be_bytes();
 
         let mut entries = Vec::new();
         let iter = self.db.iterator_cf(
-            &cf,
+            cf,
             IteratorMode::From(&start_key, rocksdb::Direction::Forward),
         );
 
@@ -626,14 +378,13 @@ impl DocumentStore {
         Ok(entries)
     }
 
-    /// Truncate WAL entries up to a sequence number (after successful compaction).
-    pub fn wal_truncate(&self, up_to_seq: u64) -> Result<u64, StoreError> {
+    fn wal_delete(&self, up_to_seq: u64) -> Result<u64, StoreError> {
         let cf = self.cf(CF_WAL)?;
 
         let mut count = 0u64;
         let mut batch = WriteBatch::default();
 
-        let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
+        let iter = self.db.iterator_cf(cf, IteratorMode::Start);
         for item in iter {
             let (key, _) = item.map_err(|e| StoreError::DatabaseError(e.to_string()))?;
             if key.len() < 8 {
@@ -648,7 +399,7 @@ impl DocumentStore {
                 break;
             }
 
-            batch.delete_cf(&cf, &key);
+            batch.delete_cf(cf, &key);
             count += 1;
         }
 
@@ -659,37 +410,46 @@ impl DocumentStore {
         Ok(count)
     }
 
-    /// Force fsync on the database (called periodically, e.g., every 1 second).
-    pub fn sync(&self) -> Result<(), StoreError> {
+    fn wal_last(&self) -> Result<Option<u64>, StoreError> {
+        // Get the last key in WAL CF (highest sequence number)
+        let mut iter = self.db.iterator_cf(self.cf(CF_WAL)?, IteratorMode::End);
+        match iter.next() {
+            Some(Ok((key, _))) if key.len() >= 8 => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&key[..8]);
+                Ok(Some(u64::from_be_bytes(buf)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn flush(&self) -> Result<(), StoreError> {
         self.db.flush().map_err(|e| StoreError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
-    /// Get the current WAL sequence number.
-    pub fn wal_sequence(&self) -> u64 {
-        self.sequence.load(Ordering::SeqCst)
+    fn flush_wal(&self) -> Result<(), StoreError> {
+        self.db.flush_wal(true)?;
+        Ok(())
     }
 
-    /// Get the database path.
-    pub fn path(&self) -> &Path {
-        &self.config.path
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
     }
 
-    // ─── Helpers ──────────────────────────────────────────────────────
-
-    /// Get a column family handle.
-    fn cf(&self, name: &str) -> Result<&rocksdb::ColumnFamily, StoreError> {
-        self.db
-            .cf_handle(name)
-            .ok_or_else(|| StoreError::DatabaseError(format!("Column family '{name}' not found")))
-    }
-
-    /// Build a delta key: doc_id (16 bytes) + version (8 bytes big-endian).
-    fn delta_key(doc_id: Uuid, version: u64) -> Vec<u8> {
-        let mut key = Vec::with_capacity(24);
-        key.extend_from_slice(doc_id.as_bytes());
-        key.extend_from_slice(&version.to_be_bytes());
-        key
+    /// Bytes on disk under the database directory: SST files, RocksDB's
+    /// own log and manifests.
+    fn disk_usage(&self) -> Result<u64, StoreError> {
+        fn dir_size(dir: &Path) -> std::io::Result<u64> {
+            let mut total = 0;
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let meta = entry.metadata()?;
+                total += if meta.is_dir() { dir_size(&entry.path())? } else { meta.len() };
+            }
+            Ok(total)
+        }
+        dir_size(&self.path).map_err(|e| StoreError::IoError(e.to_string()))
     }
 }
 
@@ -702,8 +462,10 @@ fn num_cpus() -> i32 {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::storage::{DocumentStore, StoreConfig, StoreError};
     use std::fs;
+    use std::path::{Path, PathBuf};
+    use uuid::Uuid;
 
     /// Create a temp directory for test database.
     fn temp_db_path(name: &str) -> PathBuf {
@@ -721,7 +483,21 @@ mod tests {
         let path = temp_db_path("open_close");
         let config = StoreConfig::for_testing(&path);
         let store = DocumentStore::open(config).unwrap();
-        assert!(store.path().exists());
+        assert!(store.path().unwrap().exists());
+        assert_eq!(store.backend_name(), "rocksdb");
+        drop(store);
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_disk_usage_grows() {
+        let path = temp_db_path("disk_usage");
+        let store = DocumentStore::open(StoreConfig::for_testing(&path)).unwrap();
+        let before = store.disk_usage().unwrap();
+        let doc_id = Uuid::new_v4();
+        store.save_snapshot(doc_id, &vec![7u8; 64 * 1024]).unwrap();
+        store.sync().unwrap();
+        assert!(store.disk_usage().unwrap() > before);
         drop(store);
         cleanup(&path);
     }
@@ -1057,19 +833,38 @@ mod tests {
     }
 
     #[test]
-    fn test_store_config_default() {
-        let config = StoreConfig::default();
-        assert_eq!(config.block_cache_size, 256 * 1024 * 1024);
-        assert_eq!(config.bloom_filter_bits, 10);
-        assert!(!config.sync_writes);
-    }
+    fn test_checkpoints() {
+        let path = temp_db_path("checkpoints");
+        let store = DocumentStore::open(StoreConfig::for_testing(&path)).unwrap();
+        let doc_id = Uuid::new_v4();
 
-    #[test]
-    fn test_store_error_display() {
-        let err = StoreError::NotFound(Uuid::nil());
-        assert!(err.to_string().contains("not found"));
+        assert!(matches!(
+            store.create_checkpoint(doc_id, "draft", 0, None),
+            Err(StoreError::NotFound(_))
+        ));
+        store.store_delta(doc_id, 4, b"delta").unwrap();
+        let author = Some(Uuid::new_v4());
+        let draft = store.create_checkpoint(doc_id, "draft", 4, author).unwrap();
+        assert_eq!((draft.version, draft.author), (4, author));
+        assert!(matches!(
+            store.create_checkpoint(doc_id, "draft", 5, None),
+            Err(StoreError::CheckpointExists { .. })
+        ));
+        store.create_checkpoint(doc_id, "v2 handoff", 5, None).unwrap();
+
+        // Later deltas keep the checkpoints
+        store.store_delta(doc_id, 6, b"delta").unwrap();
+        let names: Vec<String> = store.list_checkpoints(doc_id).unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["draft", "v2 handoff"]);
+        assert_eq!(store.checkpoint(doc_id, "v2 handoff").unwrap().version, 5);
+
+        assert_eq!(store.delete_checkpoint(doc_id, "draft").unwrap(), draft);
+        assert!(matches!(
+            store.checkpoint(doc_id, "draft"),
+            Err(StoreError::CheckpointNotFound { .. })
+        ));
 
-        let err = StoreError::DatabaseError("test".into());
-        assert!(err.to_string().contains("Database error"));
+        drop(store);
+        cleanup(&path);
     }
 }
@@ -0,0 +1,315 @@
+//! SQLite storage backend.
+//!
+//! Everything lives in one database file with a rollback journal, so a
+//! store can be copied, mailed or opened with the `sqlite3` shell while
+//! the server is stopped. Tables mirror the RocksDB column families:
+//! - `documents (doc_id, snapshot)`
+//! - `deltas    (doc_id, version, delta)`
+//! - `history   (doc_id, version, record)`
+//! - `metadata  (doc_id, metadata)`
+//! - `wal       (seq, doc_id, delta)`
+//!
+//! Versions and sequence numbers are stored as SQLite integers (i64).
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+use super::backend::StorageBackend;
+use super::store::{StoreConfig, StoreError};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS documents (
+        doc_id BLOB PRIMARY KEY,
+        snapshot BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS deltas (
+        doc_id BLOB NOT NULL,
+        version INTEGER NOT NULL,
+        delta BLOB NOT NULL,
+        PRIMARY KEY (doc_id, version)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS history (
+        doc_id BLOB NOT NULL,
+        version INTEGER NOT NULL,
+        record BLOB NOT NULL,
+        PRIMARY KEY (doc_id, version)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS metadata (
+        doc_id BLOB PRIMARY KEY,
+        metadata BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS wal (
+        seq INTEGER PRIMARY KEY,
+        doc_id BLOB NOT NULL,
+        delta BLOB NOT NULL
+    );
+";
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::DatabaseError(e.to_string())
+    }
+}
+
+/// Single-file SQLite storage.
+pub struct SqliteBackend {
+    /// Connection; SQLite connections are not `Sync`, so calls take turns
+    conn: Mutex<Connection>,
+    /// Database file
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    /// Open or create the database file at `config.path`.
+    ///
+    /// `config.sync_writes` selects `synchronous = FULL`; otherwise commits
+    /// are fsynced at SQLite's `NORMAL` level.
+    pub fn open(config: &StoreConfig) -> Result<Self, StoreError> {
+        if let Some(parent) = config.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| StoreError::IoError(e.to_string()))?;
+        }
+        let conn = Connection::open(&config.path)?;
+        let synchronous = if config.sync_writes { "FULL" } else { "NORMAL" };
+        conn.execute_batch(&format!("PRAGMA synchronous = {synchronous};"))?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn: Mutex::new(conn), path: config.path.clone() })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Clamp a version or sequence number into SQLite's integer range.
+fn int(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+fn uuid_from(bytes: &[u8]) -> Result<Uuid, StoreError> {
+    Uuid::from_slice(bytes).map_err(|_| StoreError::DeserializationError("Invalid UUID key".into()))
+}
+
+impl StorageBackend for SqliteBackend {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn put_snapshot(&self, doc_id: Uuid, snapshot: &[u8], metadata: &[u8]) -> Result<(), StoreError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO documents (doc_id, snapshot) VALUES (?1, ?2)",
+            params![doc_id.as_bytes(), snapshot],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO metadata (doc_id, metadata) VALUES (?1, ?2)",
+            params![doc_id.as_bytes(), metadata],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_snapshot(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self
+            .lock()
+            .query_row("SELECT snapshot FROM documents WHERE doc_id = ?1", [doc_id.as_bytes()], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn put_delta(
+        &self,
+        doc_id: Uuid,
+        version: u64,
+        delta: Option<&[u8]>,
+        history: Option<&[u8]>,
+        metadata: &[u8],
+    ) -> Result<(), StoreError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        if let Some(delta) = delta {
+            tx.execute(
+                "INSERT OR REPLACE INTO deltas (doc_id, version, delta) VALUES (?1, ?2, ?3)",
+                params![doc_id.as_bytes(), int(version), delta],
+            )?;
+        }
+        if let Some(history) = history {
+            tx.execute(
+                "INSERT OR REPLACE INTO history (doc_id, version, record) VALUES (?1, ?2, ?3)",
+                params![doc_id.as_bytes(), int(version), history],
+            )?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO metadata (doc_id, metadata) VALUES (?1, ?2)",
+            params![doc_id.as_bytes(), metadata],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_delta(&self, doc_id: Uuid, version: u64) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self
+            .lock()
+            .query_row(
+                "SELECT delta FROM deltas WHERE doc_id = ?1 AND version = ?2",
+                params![doc_id.as_bytes(), int(version)],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn scan_deltas(&self, doc_id: Uuid, from_version: u64) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+        let conn = self.lock();
+        let mut stmt =
+            conn.prepare_cached("SELECT version, delta FROM deltas WHERE doc_id = ?1 AND version >= ?2 ORDER BY version")?;
+        let rows = stmt.query_map(params![doc_id.as_bytes(), int(from_version)], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get(1)?))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn delete_deltas(&self, doc_id: Uuid, up_to_version: u64) -> Result<u64, StoreError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let removed = tx.execute(
+            "DELETE FROM deltas WHERE doc_id = ?1 AND version <= ?2",
+            params![doc_id.as_bytes(), int(up_to_version)],
+        )?;
+        tx.execute(
+            "DELETE FROM history WHERE doc_id = ?1 AND version <= ?2",
+            params![doc_id.as_bytes(), int(up_to_version)],
+        )?;
+        tx.commit()?;
+        Ok(removed as u64)
+    }
+
+    fn scan_history(&self, doc_id: Uuid, up_to_version: u64) -> Result<Vec<Vec<u8>>, StoreError> {
+        let conn = self.lock();
+        let mut stmt =
+            conn.prepare_cached("SELECT record FROM history WHERE doc_id = ?1 AND version <= ?2 ORDER BY version")?;
+        let rows = stmt.query_map(params![doc_id.as_bytes(), int(up_to_version)], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn put_metadata(&self, doc_id: Uuid, metadata: &[u8]) -> Result<(), StoreError> {
+        self.lock().execute(
+            "INSERT OR REPLACE INTO metadata (doc_id, metadata) VALUES (?1, ?2)",
+            params![doc_id.as_bytes(), metadata],
+        )?;
+        Ok(())
+    }
+
+    fn get_metadata(&self, doc_id: Uuid) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self
+            .lock()
+            .query_row("SELECT metadata FROM metadata WHERE doc_id = ?1", [doc_id.as_bytes()], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn list_documents(&self) -> Result<Vec<Uuid>, StoreError> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare_cached("SELECT doc_id FROM metadata ORDER BY doc_id")?;
+        let keys = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))?.collect::<Result<Vec<_>, _>>()?;
+        keys.iter().map(|key| uuid_from(key)).collect()
+    }
+
+    fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        for table in ["documents", "deltas", "history", "metadata"] {
+            tx.execute(&format!("DELETE FROM {table} WHERE doc_id = ?1"), [doc_id.as_bytes()])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn wal_put(&self, seq: u64, doc_id: Uuid, delta: &[u8]) -> Result<(), StoreError> {
+        self.lock().execute(
+            "INSERT OR REPLACE INTO wal (seq, doc_id, delta) VALUES (?1, ?2, ?3)",
+            params![int(seq), doc_id.as_bytes(), delta],
+        )?;
+        Ok(())
+    }
+
+    fn wal_scan(&self, since_seq: u64) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare_cached("SELECT seq, doc_id, delta FROM wal WHERE seq >= ?1 ORDER BY seq")?;
+        let rows = stmt
+            .query_map([int(since_seq)], |row| {
+                Ok((row.get::<_, i64>(0)? as u64, row.get::<_, Vec<u8>>(1)?, row.get::<_, Vec<u8>>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter().map(|(seq, doc_id, delta)| Ok((seq, uuid_from(&doc_id)?, delta))).collect()
+    }
+
+    fn wal_delete(&self, up_to_seq: u64) -> Result<u64, StoreError> {
+        let removed = self.lock().execute("DELETE FROM wal WHERE seq <= ?1", [int(up_to_seq)])?;
+        Ok(removed as u64)
+    }
+
+    fn wal_last(&self) -> Result<Option<u64>, StoreError> {
+        let last: Option<i64> = self.lock().query_row("SELECT MAX(seq) FROM wal", [], |row| row.get(0))?;
+        Ok(last.map(|seq| seq as u64))
+    }
+
+    /// Every write commits its own transaction, so this only writes out
+    /// pages still held in the connection's cache.
+    fn flush(&self) -> Result<(), StoreError> {
+        self.lock().cache_flush()?;
+        Ok(())
+    }
+
+    fn flush_wal(&self) -> Result<(), StoreError> {
+        self.flush()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    /// Size of the database file.
+    fn disk_usage(&self) -> Result<u64, StoreError> {
+        std::fs::metadata(&self.path).map(|m| m.len()).map_err(|e| StoreError::IoError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BackendKind, DocumentStore};
+
+    fn open(path: &Path) -> DocumentStore {
+        DocumentStore::open(StoreConfig { path: path.to_path_buf(), backend: BackendKind::Sqlite, ..StoreConfig::default() })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_single_file_survives_reopen() {
+        let dir = std::env::temp_dir().join(format!("logos_test_sqlite_{}", Uuid::new_v4()));
+        let path = dir.join("store.db");
+        let doc_id = Uuid::new_v4();
+        {
+            let store = open(&path);
+            store.save_snapshot(doc_id, b"snapshot").unwrap();
+            let version = store.wal_append(doc_id, b"delta").unwrap();
+            store.store_delta_by(doc_id, version, Some(doc_id), b"delta").unwrap();
+            store.create_checkpoint(doc_id, "draft", version, None).unwrap();
+            store.wal_append(doc_id, b"pending").unwrap();
+        }
+
+        let store = open(&path);
+        assert_eq!(store.backend_name(), "sqlite");
+        assert_eq!(store.wal_sequence(), 2);
+        assert_eq!(store.load_snapshot(doc_id).unwrap(), b"snapshot");
+        assert_eq!(store.load_all_deltas(doc_id).unwrap(), vec![(0, b"delta".to_vec())]);
+        assert_eq!(store.list_versions(doc_id).unwrap()[0].author, Some(doc_id));
+        assert_eq!(store.checkpoint(doc_id, "draft").unwrap().version, 0);
+        assert_eq!(store.wal_read_since(1).unwrap()[0].2, b"pending");
+        assert!(store.disk_usage().unwrap() > 0);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1, "one file at rest");
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,945 @@
+//! Document store over a pluggable [`StorageBackend`].
+//!
+//! `DocumentStore` owns everything above raw key-value storage: LZ4
+//! compression of snapshots and deltas, metadata bookkeeping, version
+//! history, checkpoints and the WAL sequence counter. Backends only keep
+//! the resulting bytes (see [`super::backend`]).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::backend::StorageBackend;
+use super::memory::MemoryBackend;
+use super::rocks::RocksBackend;
+use super::sqlite::SqliteBackend;
+
+/// Which storage engine [`DocumentStore::open`] uses.
+#[derive(Debug, Clone, Default)]
+pub enum BackendKind {
+    /// RocksDB database directory at `StoreConfig::path`
+    #[default]
+    RocksDb,
+    /// Single SQLite database file at `StoreConfig::path`
+    Sqlite,
+    /// Process memory; `StoreConfig::path` is unused. Clones of the
+    /// backend share data, so a store reopened on one sees earlier writes.
+    Memory(MemoryBackend),
+}
+
+/// Store configuration.
+#[derive(Debug, Clone)]
+pub struct StoreConfig {
+    /// Database directory path (a file path for SQLite)
+    pub path: PathBuf,
+    /// Storage engine (default: RocksDB)
+    pub backend: BackendKind,
+    /// Block cache size in bytes (default: 256MB)
+    pub block_cache_size: usize,
+    /// Bloom filter bits per key (default: 10)
+    pub bloom_filter_bits: i32,
+    /// Enable fsync on every write (default: false — batch fsync instead)
+    pub sync_writes: bool,
+    /// Max open files for RocksDB (default: 512)
+    pub max_open_files: i32,
+    /// Write buffer size per column family (default: 64MB)
+    pub write_buffer_size: usize,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("logos_data"),
+            backend: BackendKind::default(),
+            block_cache_size: 256 * 1024 * 1024, // 256MB
+            bloom_filter_bits: 10,
+            sync_writes: false, // Batch fsync via WAL
+            max_open_files: 512,
+            write_buffer_size: 64 * 1024 * 1024, // 64MB
+        }
+    }
+}
+
+impl StoreConfig {
+    /// Create config for testing (small caches, temp directory).
+    pub fn for_testing(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            backend: BackendKind::default(),
+            block_cache_size: 8 * 1024 * 1024, // 8MB
+            bloom_filter_bits: 10,
+            sync_writes: false,
+            max_open_files: 64,
+            write_buffer_size: 4 * 1024 * 1024, // 4MB
+        }
+    }
+}
+
+/// Document metadata stored alongside snapshots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    /// Document UUID
+    pub doc_id: Uuid,
+    /// Current version (monotonically increasing)
+    pub version: u64,
+    /// Total number of deltas stored
+    pub delta_count: u64,
+    /// Uncompressed snapshot size in bytes
+    pub snapshot_size: u64,
+    /// Compressed snapshot size in bytes
+    pub compressed_size: u64,
+    /// Creation timestamp (seconds since epoch)
+    pub created_at: u64,
+    /// Last modified timestamp (seconds since epoch)
+    pub updated_at: u64,
+    /// Named checkpoints, in creation order
+    pub checkpoints: Vec<Checkpoint>,
+    /// Where this document was forked from, if it is a branch
+    pub forked_from: Option<ForkOrigin>,
+    /// First delta version not yet covered by the snapshot. Older deltas
+    /// stay stored as history payloads but are no longer replayed.
+    pub log_start: u64,
+}
+
+/// Metadata as written before checkpoints and branches existed.
+#[derive(Deserialize)]
+struct LegacyMetadata {
+    doc_id: Uuid,
+    version: u64,
+    delta_count: u64,
+    snapshot_size: u64,
+    compressed_size: u64,
+    created_at: u64,
+    updated_at: u64,
+}
+
+/// A named point in a document's version history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Unique per document, e.g. "v2 handoff"
+    pub name: String,
+    /// History version the checkpoint marks
+    pub version: u64,
+    /// Who created it
+    pub author: Option<Uuid>,
+    /// Creation timestamp (milliseconds since epoch)
+    pub created_at_ms: u64,
+}
+
+/// The checkpoint a branch was forked from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkOrigin {
+    /// Document the branch was forked from
+    pub doc_id: Uuid,
+    /// Checkpoint name at fork time
+    pub checkpoint: String,
+    /// History version of the fork point in `doc_id`
+    pub version: u64,
+}
+
+impl DocumentMetadata {
+    fn new(doc_id: Uuid) -> Self {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self {
+            doc_id,
+            version: 0,
+            delta_count: 0,
+            snapshot_size: 0,
+            compressed_size: 0,
+            created_at: now,
+            updated_at: now,
+            checkpoints: Vec::new(),
+            forked_from: None,
+            log_start: 0,
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, StoreError> {
+        bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .map_err(|e| StoreError::SerializationError(e.to_string()))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, StoreError> {
+        let config = bincode::config::standard();
+        if let Ok((meta, _)) = bincode::serde::decode_from_slice(bytes, config) {
+            return Ok(meta);
+        }
+        let (legacy, _): (LegacyMetadata, _) = bincode::serde::decode_from_slice(bytes, config)
+            .map_err(|e| StoreError::DeserializationError(e.to_string()))?;
+        Ok(Self {
+            doc_id: legacy.doc_id,
+            version: legacy.version,
+            delta_count: legacy.delta_count,
+            snapshot_size: legacy.snapshot_size,
+            compressed_size: legacy.compressed_size,
+            created_at: legacy.created_at,
+            updated_at: legacy.updated_at,
+            checkpoints: Vec::new(),
+            forked_from: None,
+            log_start: 0,
+        })
+    }
+}
+
+/// One entry of a document's version history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    /// Delta version (the WAL sequence it was logged under)
+    pub version: u64,
+    /// Peer that made the change; `None` for server-side and replayed writes
+    pub author: Option<Uuid>,
+    /// When the delta was stored (milliseconds since epoch)
+    pub timestamp_ms: u64,
+    /// Uncompressed delta size in bytes
+    pub size: u64,
+}
+
+/// History records hold only the [`VersionInfo`]; the delta itself is
+/// stored once, in the delta table.
+impl VersionInfo {
+    fn encode(&self) -> Result<Vec<u8>, StoreError> {
+        bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .map_err(|e| StoreError::SerializationError(e.to_string()))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, StoreError> {
+        let (info, _) =
+            bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+                .map_err(|e| StoreError::DeserializationError(e.to_string()))?;
+        Ok(info)
+    }
+}
+
+/// Storage errors.
+#[derive(Debug, Clone)]
+pub enum StoreError {
+    /// Storage engine error
+    DatabaseError(String),
+    /// Document not found
+    NotFound(Uuid),
+    /// No history entry at or before the requested point
+    VersionNotFound { doc_id: Uuid, version: u64 },
+    /// No checkpoint with this name on the document
+    CheckpointNotFound { doc_id: Uuid, name: String },
+    /// The document already has a checkpoint with this name
+    CheckpointExists { doc_id: Uuid, name: String },
+    /// The document is not a branch of another document
+    NotABranch(Uuid),
+    /// A document with this id already exists
+    DocumentExists(Uuid),
+    /// Serialization failed
+    SerializationError(String),
+    /// Deserialization failed
+    DeserializationError(String),
+    /// Compression error
+    CompressionError(String),
+    /// I/O error
+    IoError(String),
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::DatabaseError(e) => write!(f, "Database error: {e}"),
+            StoreError::NotFound(id) => write!(f, "Document not found: {id}"),
+            StoreError::VersionNotFound { doc_id, version } => {
+                write!(f, "Version {version} not found for document {doc_id}")
+            }
+            StoreError::CheckpointNotFound { doc_id, name } => {
+                write!(f, "Checkpoint {name:?} not found for document {doc_id}")
+            }
+            StoreError::CheckpointExists { doc_id, name } => {
+                write!(f, "Checkpoint {name:?} already exists for document {doc_id}")
+            }
+            StoreError::NotABranch(id) => write!(f, "Document {id} is not a branch"),
+            StoreError::DocumentExists(id) => write!(f, "Document already exists: {id}"),
+            StoreError::SerializationError(e) => write!(f, "Serialization error: {e}"),
+            StoreError::DeserializationError(e) => write!(f, "Deserialization error: {e}"),
+            StoreError::CompressionError(e) => write!(f, "Compression error: {e}"),
+            StoreError::IoError(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// Persistent document store.
+///
+/// Provides durable storage for collaborative documents with:
+/// - LZ4-compressed snapshots and deltas
+/// - Authored version history and named checkpoints
+/// - A write-ahead log sharing its sequence with delta versions
+/// - Any [`StorageBackend`] underneath (RocksDB by default)
+pub struct DocumentStore {
+    /// Storage engine holding the encoded values
+    backend: Box<dyn StorageBackend>,
+    /// Global sequence number for WAL entries
+    sequence: AtomicU64,
+    /// Serializes metadata read-modify-writes: deltas and snapshots arrive
+    /// from the persistence task while checkpoints come from callers
+    metadata_lock: Mutex<()>,
+}
+
+impl DocumentStore {
+    /// Open the document store with the configured backend and path.
+    ///
+    /// Creates the database if it doesn't exist.
+    /// Target: <100ms for database with 10,000 documents.
+    pub fn open(config: StoreConfig) -> Result<Self, StoreError> {
+        match &config.backend {
+            BackendKind::RocksDb => Self::with_backend(RocksBackend::open(&config)?),
+            BackendKind::Sqlite => Self::with_backend(SqliteBackend::open(&config)?),
+            BackendKind::Memory(memory) => Self::with_backend(memory.clone()),
+        }
+    }
+
+    /// Build a store over an already opened backend, recovering the WAL
+    /// sequence from what it holds.
+    pub fn with_backend(backend: impl StorageBackend + 'static) -> Result<Self, StoreError> {
+        let sequence = backend.wal_last()?.map_or(0, |seq| seq + 1);
+        let store = Self {
+            backend: Box::new(backend),
+            sequence: AtomicU64::new(sequence),
+            metadata_lock: Mutex::new(()),
+        };
+        // WAL sequence numbers double as delta versions; a truncated WAL
+        // must not hand out versions that stored deltas already use
+        store.sequence.fetch_max(store.next_delta_version()?, Ordering::SeqCst);
+        Ok(store)
+    }
+
+    /// Name of the storage engine, e.g. "rocksdb".
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    // ─── Document Snapshots ───────────────────────────────────────────
+
+    /// Save a full document snapshot (LZ4 compressed).
+    ///
+    /// Used for periodic compaction and initial persistence.
+    /// The snapshot is the full Yrs document state encoded with `encode_v1`.
+    pub fn save_snapshot(
+        &self,
+        doc_id: Uuid,
+        snapshot: &[u8],
+    ) -> Result<DocumentMetadata, StoreError> {
+        // LZ4 compress the snapshot
+        let compressed = lz4_flex::compress_prepend_size(snapshot);
+
+        // Load or create metadata
+        let _guard = self.lock_metadata();
+        let mut meta = self.load_metadata(doc_id).unwrap_or_else(|_| DocumentMetadata::new(doc_id));
+        meta.snapshot_size = snapshot.len() as u64;
+        meta.compressed_size = compressed.len() as u64;
+        meta.updated_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        // Atomic write: snapshot + metadata
+        self.backend.put_snapshot(doc_id, &compressed, &meta.encode()?)?;
+
+        Ok(meta)
+    }
+
+    /// Load a document snapshot (LZ4 decompressed).
+    ///
+    /// Returns the raw Yrs document state for `apply_update`.
+    /// Target: <1ms for cache-hot document.
+    pub fn load_snapshot(&self, doc_id: Uuid) -> Result<Vec<u8>, StoreError> {
+        match self.backend.get_snapshot(doc_id)? {
+            Some(compressed) => {
+                lz4_flex::decompress_size_prepended(&compressed)
+                    .map_err(|e| StoreError::CompressionError(e.to_string()))
+            }
+            None => Err(StoreError::NotFound(doc_id)),
+        }
+    }
+
+    /// Check if a document exists.
+    pub fn document_exists(&self, doc_id: Uuid) -> Result<bool, StoreError> {
+        Ok(self.backend.get_metadata(doc_id)?.is_some())
+    }
+
+    // ─── Deltas ───────────────────────────────────────────────────────
+
+    /// Store a compressed delta for a document.
+    ///
+    /// Value: LZ4-compressed delta payload, keyed by `(doc_id, version)`.
+    /// Target: <50μs per delta write.
+    pub fn store_delta(
+        &self,
+        doc_id: Uuid,
+        version: u64,
+        delta: &[u8],
+    ) -> Result<u64, StoreError> {
+        self.store_delta_by(doc_id, version, None, delta)
+    }
+
+    /// Store a delta and record `author` for it in the version history.
+    ///
+    /// The delta, its history entry and the metadata are written in one
+    /// batch; the delta is kept once and serves both the log and the
+    /// history. Returns the compressed size.
+    pub fn store_delta_by(
+        &self,
+        doc_id: Uuid,
+        version: u64,
+        author: Option<Uuid>,
+        delta: &[u8],
+    ) -> Result<u64, StoreError> {
+        // LZ4 compress the delta
+        let compressed = lz4_flex::compress_prepend_size(delta);
+        let compressed_len = compressed.len() as u64;
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        // Update metadata atomically
+        let _guard = self.lock_metadata();
+        let mut meta = self.load_metadata(doc_id).unwrap_or_else(|_| DocumentMetadata::new(doc_id));
+        meta.version = version;
+        meta.delta_count += 1;
+        meta.updated_at = now.as_secs();
+
+        let info = VersionInfo {
+            version,
+            author,
+            timestamp_ms: now.as_millis() as u64,
+            size: delta.len() as u64,
+        };
+
+        self.backend
+            .put_delta(doc_id, version, Some(&compressed), Some(&info.encode()?), &meta.encode()?)?;
+
+        Ok(compressed_len)
+    }
+
+    /// Load all deltas for a document since a given version.
+    ///
+    /// Returns deltas in version order, LZ4 decompressed. Deltas compacted
+    /// into the snapshot are left out.
+    pub fn load_deltas_since(
+        &self,
+        doc_id: Uuid,
+        since_version: u64,
+    ) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+        let log_start = match self.load_metadata(doc_id) {
+            Ok(meta) => meta.log_start,
+            Err(StoreError::NotFound(_)) => 0,
+            Err(e) => return Err(e),
+        };
+        self.decompress_deltas(doc_id, since_version.max(log_start), u64::MAX)
+    }
+
+    /// Stored deltas from `from_version` through `up_to_version`, compacted
+    /// or not, LZ4 decompressed.
+    fn decompress_deltas(
+        &self,
+        doc_id: Uuid,
+        from_version: u64,
+        up_to_version: u64,
+    ) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+        self.backend
+            .scan_deltas(doc_id, from_version)?
+            .into_iter()
+            .take_while(|(version, _)| *version <= up_to_version)
+            .map(|(version, compressed)| {
+                let delta = lz4_flex::decompress_size_prepended(&compressed)
+                    .map_err(|e| StoreError::CompressionError(e.to_string()))?;
+                Ok((version, delta))
+            })
+            .collect()
+    }
+
+    /// Check whether a delta is stored under `version`, compacted or not.
+    pub fn has_delta(&self, doc_id: Uuid, version: u64) -> Result<bool, StoreError> {
+        Ok(self.backend.get_delta(doc_id, version)?.is_some())
+    }
+
+    /// Load all deltas for a document.
+    pub fn load_all_deltas(&self, doc_id: Uuid) -> Result<Vec<(u64, Vec<u8>)>, StoreError> {
+        self.load_deltas_since(doc_id, 0)
+    }
+
+    /// Count deltas stored for a document.
+    pub fn delta_count(&self, doc_id: Uuid) -> Result<u64, StoreError> {
+        let meta = self.load_metadata(doc_id)?;
+        Ok(meta.delta_count)
+    }
+
+    /// First delta version not used by any stored document.
+    ///
+    /// Delta versions are allocated from one server-wide counter; resuming
+    /// it here on restart keeps new deltas from overwriting old ones.
+    pub fn next_delta_version(&self) -> Result<u64, StoreError> {
+        let mut next = 0;
+        for doc_id in self.list_documents()? {
+            let meta = self.load_metadata(doc_id)?;
+            if meta.delta_count > 0 {
+                next = next.max(meta.version + 1);
+            }
+        }
+        Ok(next)
+    }
+
+    /// Drop the deltas up to a version from the log after a snapshot
+    /// covering them was saved, returning how many left it.
+    ///
+    /// They stay stored as history payloads until [`Self::prune_history`]
+    /// squashes them.
+    pub fn compact_deltas(
+        &self,
+        doc_id: Uuid,
+        up_to_version: u64,
+    ) -> Result<u64, StoreError> {
+        self.update_metadata(doc_id, |meta| {
+            if up_to_version < meta.log_start {
+                return Ok(0);
+            }
+            let compacted = self
+                .backend
+                .scan_deltas(doc_id, meta.log_start)?
+                .iter()
+                .take_while(|(version, _)| *version <= up_to_version)
+                .count();
+            meta.log_start = up_to_version + 1;
+            Ok(compacted as u64)
+        })
+    }
+
+    // ─── History ──────────────────────────────────────────────────────
+
+    /// List every recorded version of a document, oldest first.
+    ///
+    /// Reads only the version records, never the deltas.
+    pub fn list_versions(&self, doc_id: Uuid) -> Result<Vec<VersionInfo>, StoreError> {
+        self.scan_history(doc_id, u64::MAX)
+    }
+
+    /// Latest version stored at or before `timestamp_ms`, if any.
+    pub fn version_at(&self, doc_id: Uuid, timestamp_ms: u64) -> Result<Option<u64>, StoreError> {
+        Ok(self
+            .list_versions(doc_id)?
+            .iter()
+            .take_while(|info| info.timestamp_ms <= timestamp_ms)
+            .last()
+            .map(|info| info.version))
+    }
+
+    /// Load the history of a document up to and including `up_to_version`,
+    /// in version order, LZ4 decompressed.
+    ///
+    /// Unlike [`Self::load_deltas_since`] this is unaffected by snapshot
+    /// compaction.
+    pub fn load_history(
+        &self,
+        doc_id: Uuid,
+        up_to_version: u64,
+    ) -> Result<Vec<(VersionInfo, Vec<u8>)>, StoreError> {
+        let mut deltas: BTreeMap<u64, Vec<u8>> =
+            self.decompress_deltas(doc_id, 0, up_to_version)?.into_iter().collect();
+        self.scan_history(doc_id, up_to_version)?
+            .into_iter()
+            .map(|info| {
+                let delta = deltas
+                    .remove(&info.version)
+                    .ok_or(StoreError::VersionNotFound { doc_id, version: info.version })?;
+                Ok((info, delta))
+            })
+            .collect()
+    }
+
+    /// Squash the history recorded before `before_ms` into one entry, so
+    /// old edits stop costing a delta each. Returns how many versions went.
+    ///
+    /// Only deltas already compacted into the snapshot are squashed, and
+    /// never past the oldest checkpoint, which stays restorable. The
+    /// squashed entry keeps the last squashed version's number, author and
+    /// time; the versions before it can no longer be restored. A crash
+    /// between the write and the delete leaves both behind, which replays
+    /// to the same state.
+    pub fn prune_history(&self, doc_id: Uuid, before_ms: u64) -> Result<u64, StoreError> {
+        let _guard = self.lock_metadata();
+        let meta = self.load_metadata(doc_id)?;
+        if meta.log_start == 0 {
+            return Ok(0);
+        }
+        let pinned = meta.checkpoints.iter().map(|c| c.version).min();
+        let versions = self.scan_history(doc_id, meta.log_start.saturating_sub(1))?;
+        let squashed = versions
+            .iter()
+            .take_while(|info| info.timestamp_ms < before_ms && pinned.is_none_or(|p| info.version <= p))
+            .count();
+        if squashed < 2 {
+            return Ok(0);
+        }
+
+        let last = &versions[squashed - 1];
+        let deltas = self.decompress_deltas(doc_id, 0, last.version)?;
+        let merged = yrs::merge_updates_v1(deltas.iter().map(|(_, delta)| delta.as_slice()))
+            .map_err(|e| StoreError::DeserializationError(e.to_string()))?;
+        let info = VersionInfo { size: merged.len() as u64, ..last.clone() };
+        let compressed = lz4_flex::compress_prepend_size(&merged);
+        self.backend
+            .put_delta(doc_id, last.version, Some(&compressed), Some(&info.encode()?), &meta.encode()?)?;
+        self.backend.delete_deltas(doc_id, last.version - 1)?;
+        Ok(squashed as u64 - 1)
+    }
+
+    /// Decode the history records of a document up to `up_to_version`.
+    fn scan_history(&self, doc_id: Uuid, up_to_version: u64) -> Result<Vec<VersionInfo>, StoreError> {
+        self.backend
+            .scan_history(doc_id, up_to_version)?
+            .iter()
+            .map(|bytes| VersionInfo::decode(bytes))
+            .collect()
+    }
+
+    // ─── Checkpoints ──────────────────────────────────────────────────
+
+    /// Name `version` of a document. Names are unique per document.
+    pub fn create_checkpoint(
+        &self,
+        doc_id: Uuid,
+        name: &str,
+        version: u64,
+        author: Option<Uuid>,
+    ) -> Result<Checkpoint, StoreError> {
+        let checkpoint = Checkpoint {
+            name: name.to_string(),
+            version,
+            author,
+            created_at_ms: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        };
+        self.update_metadata(doc_id, |meta| {
+            if meta.checkpoints.iter().any(|c| c.name == name) {
+                return Err(StoreError::CheckpointExists { doc_id, name: name.to_string() });
+            }
+            meta.checkpoints.push(checkpoint.clone());
+            Ok(checkpoint)
+        })
+    }
+
+    /// Look up a checkpoint by name.
+    pub fn checkpoint(&self, doc_id: Uuid, name: &str) -> Result<Checkpoint, StoreError> {
+        self.load_metadata(doc_id)?
+            .checkpoints
+            .into_iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| StoreError::CheckpointNotFound { doc_id, name: name.to_string() })
+    }
+
+    /// List a document's checkpoints in creation order.
+    pub fn list_checkpoints(&self, doc_id: Uuid) -> Result<Vec<Checkpoint>, StoreError> {
+        Ok(self.load_metadata(doc_id)?.checkpoints)
+    }
+
+    /// Remove a checkpoint; the history it pointed at is kept.
+    pub fn delete_checkpoint(&self, doc_id: Uuid, name: &str) -> Result<Checkpoint, StoreError> {
+        self.update_metadata(doc_id, |meta| {
+            let index = meta
+                .checkpoints
+                .iter()
+                .position(|c| c.name == name)
+                .ok_or_else(|| StoreError::CheckpointNotFound { doc_id, name: name.to_string() })?;
+            Ok(meta.checkpoints.remove(index))
+        })
+    }
+
+    /// Record that `doc_id` is a branch of `origin`, creating its metadata.
+    pub fn set_fork_origin(&self, doc_id: Uuid, origin: ForkOrigin) -> Result<(), StoreError> {
+        let _guard = self.lock_metadata();
+        let mut meta = self.load_metadata(doc_id).unwrap_or_else(|_| DocumentMetadata::new(doc_id));
+        meta.forked_from = Some(origin);
+        self.backend.put_metadata(doc_id, &meta.encode()?)
+    }
+
+    /// Reserve a delta version for a delta written straight to the store
+    /// with [`Self::store_delta_by`] instead of through the WAL.
+    pub fn allocate_version(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::SeqCst)
+    }
+
+    // ─── Import ───────────────────────────────────────────────────────
+
+    /// Write one entry of an imported document: the delta, with its
+    /// history record when `info` is given (keeping the original author
+    /// and time), together with `meta`. Whether the delta is still in the
+    /// log is up to `meta.log_start`.
+    pub(super) fn import_delta(
+        &self,
+        doc_id: Uuid,
+        version: u64,
+        info: Option<VersionInfo>,
+        delta: &[u8],
+        meta: &DocumentMetadata,
+    ) -> Result<(), StoreError> {
+        let compressed = lz4_flex::compress_prepend_size(delta);
+        let history = info.map(|info| VersionInfo { version, ..info }.encode()).transpose()?;
+        self.backend.put_delta(doc_id, version, Some(&compressed), history.as_deref(), &meta.encode()?)
+    }
+
+    /// Write an imported document's snapshot, if it has one, and its final
+    /// metadata with the snapshot sizes filled in.
+    pub(super) fn import_snapshot(
+        &self,
+        doc_id: Uuid,
+        snapshot: Option<&[u8]>,
+        mut meta: DocumentMetadata,
+    ) -> Result<DocumentMetadata, StoreError> {
+        let _guard = self.lock_metadata();
+        match snapshot {
+            Some(snapshot) => {
+                let compressed = lz4_flex::compress_prepend_size(snapshot);
+                meta.snapshot_size = snapshot.len() as u64;
+                meta.compressed_size = compressed.len() as u64;
+                self.backend.put_snapshot(doc_id, &compressed, &meta.encode()?)?;
+            }
+            None => {
+                meta.snapshot_size = 0;
+                meta.compressed_size = 0;
+                self.backend.put_metadata(doc_id, &meta.encode()?)?;
+            }
+        }
+        Ok(meta)
+    }
+
+    // ─── Metadata ─────────────────────────────────────────────────────
+
+    /// Read-modify-write the metadata of an existing document; nothing is
+    /// written if `update` fails.
+    fn update_metadata<T>(
+        &self,
+        doc_id: Uuid,
+        update: impl FnOnce(&mut DocumentMetadata) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        let _guard = self.lock_metadata();
+        let mut meta = self.load_metadata(doc_id)?;
+        let result = update(&mut meta)?;
+        self.backend.put_metadata(doc_id, &meta.encode()?)?;
+        Ok(result)
+    }
+
+    fn lock_metadata(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.metadata_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Load document metadata.
+    pub fn load_metadata(&self, doc_id: Uuid) -> Result<DocumentMetadata, StoreError> {
+        match self.backend.get_metadata(doc_id)? {
+            Some(bytes) => DocumentMetadata::decode(&bytes),
+            None => Err(StoreError::NotFound(doc_id)),
+        }
+    }
+
+    /// List all document IDs in the store.
+    pub fn list_documents(&self) -> Result<Vec<Uuid>, StoreError> {
+        self.backend.list_documents()
+    }
+
+    /// Delete a document and all its deltas/history/metadata.
+    pub fn delete_document(&self, doc_id: Uuid) -> Result<(), StoreError> {
+        self.backend.delete_document(doc_id)
+    }
+
+    // ─── WAL Operations ───────────────────────────────────────────────
+
+    /// Append a WAL entry. Returns the sequence number assigned.
+    ///
+    /// Target: <10μs per append (no fsync, buffered).
+    pub fn wal_append(
+        &self,
+        doc_id: Uuid,
+        delta: &[u8],
+    ) -> Result<u64, StoreError> {
+        let seq = self.sequence.fetch_add(1, Ordering::SeqCst);
+        self.backend.wal_put(seq, doc_id, delta)?;
+        Ok(seq)
+    }
+
+    /// Read all WAL entries since a given sequence number.
+    ///
+    /// Used during crash recovery to replay uncommitted deltas.
+    pub fn wal_read_since(
+        &self,
+        since_seq: u64,
+    ) -> Result<Vec<(u64, Uuid, Vec<u8>)>, StoreError> {
+        self.backend.wal_scan(since_seq)
+    }
+
+    /// Truncate WAL entries up to a sequence number (after successful compaction).
+    pub fn wal_truncate(&self, up_to_seq: u64) -> Result<u64, StoreError> {
+        self.backend.wal_delete(up_to_seq)
+    }
+
+    /// Force fsync on the database (called periodically, e.g., every 1 second).
+    pub fn sync(&self) -> Result<(), StoreError> {
+        self.backend.flush()
+    }
+
+    /// Fsync the backend's log so every write so far survives power loss.
+    ///
+    /// Much cheaper than [`sync`](Self::sync): nothing is compacted.
+    pub fn sync_wal(&self) -> Result<(), StoreError> {
+        self.backend.flush_wal()
+    }
+
+    /// Get the current WAL sequence number.
+    pub fn wal_sequence(&self) -> u64 {
+        self.sequence.load(Ordering::SeqCst)
+    }
+
+    /// Get the database path (`None` for the memory backend).
+    pub fn path(&self) -> Option<&Path> {
+        self.backend.path()
+    }
+
+    /// Bytes the backend occupies: for RocksDB, SST files, its own log and
+    /// manifests under the database directory.
+    pub fn disk_usage(&self) -> Result<u64, StoreError> {
+        self.backend.disk_usage()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_metadata_decodes() {
+        #[derive(Serialize)]
+        struct Legacy(Uuid, u64, u64, u64, u64, u64, u64);
+
+        let doc_id = Uuid::new_v4();
+        let bytes = bincode::serde::encode_to_vec(Legacy(doc_id, 9, 3, 100, 20, 1, 2), bincode::config::standard())
+            .unwrap();
+        let meta = DocumentMetadata::decode(&bytes).unwrap();
+        assert_eq!((meta.doc_id, meta.version, meta.delta_count, meta.updated_at), (doc_id, 9, 3, 2));
+        assert!(meta.checkpoints.is_empty());
+        assert!(meta.forked_from.is_none());
+    }
+
+    #[test]
+    fn test_store_config_default() {
+        let config = StoreConfig::default();
+        assert_eq!(config.block_cache_size, 256 * 1024 * 1024);
+        assert_eq!(config.bloom_filter_bits, 10);
+        assert!(!config.sync_writes);
+        assert!(matches!(config.backend, BackendKind::RocksDb));
+    }
+
+    #[test]
+    fn test_store_error_display() {
+        let err = StoreError::NotFound(Uuid::nil());
+        assert!(err.to_string().contains("not found"));
+
+        let err = StoreError::DatabaseError("test".into());
+        assert!(err.to_string().contains("Database error"));
+    }
+
+    #[test]
+    fn test_memory_store_reopens_with_sequence() {
+        let memory = MemoryBackend::new();
+        let doc_id = Uuid::new_v4();
+        {
+            let store = DocumentStore::with_backend(memory.clone()).unwrap();
+            store.wal_append(doc_id, b"a").unwrap();
+            let version = store.wal_append(doc_id, b"b").unwrap();
+            store.store_delta(doc_id, version, b"b").unwrap();
+            store.wal_truncate(version).unwrap();
+        }
+
+        // The WAL is empty, but versions already stored are not reused
+        let store = DocumentStore::open(StoreConfig {
+            backend: BackendKind::Memory(memory),
+            ..StoreConfig::default()
+        })
+        .unwrap();
+        assert_eq!(store.wal_sequence(), 2);
+        assert_eq!(store.load_all_deltas(doc_id).unwrap(), vec![(1, b"b".to_vec())]);
+        assert_eq!(store.backend_name(), "memory");
+        assert!(store.path().is_none());
+    }
+
+    #[test]
+    fn test_compaction_keeps_history_payloads() {
+        let memory = MemoryBackend::new();
+        let store = DocumentStore::with_backend(memory.clone()).unwrap();
+        let doc_id = Uuid::new_v4();
+        for version in 0..3 {
+            store.store_delta(doc_id, version, &[version as u8; 512]).unwrap();
+        }
+        assert_eq!(store.compact_deltas(doc_id, 1).unwrap(), 2);
+        assert_eq!(store.compact_deltas(doc_id, 0).unwrap(), 0, "already compacted");
+
+        let log: Vec<u64> = store.load_all_deltas(doc_id).unwrap().iter().map(|(v, _)| *v).collect();
+        assert_eq!(log, vec![2]);
+        assert_eq!(store.load_history(doc_id, u64::MAX).unwrap().len(), 3);
+        // One copy of each delta; history records hold only the info
+        assert_eq!(memory.scan_deltas(doc_id, 0).unwrap().len(), 3);
+        assert!(memory.scan_history(doc_id, u64::MAX).unwrap().iter().all(|record| record.len() < 64));
+        assert_eq!(store.list_versions(doc_id).unwrap()[1].size, 512);
+    }
+
+    #[test]
+    fn test_prune_history_squashes_compacted_versions() {
+        use yrs::updates::decoder::Decode;
+        use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+        let store = DocumentStore::with_backend(MemoryBackend::new()).unwrap();
+        let doc_id = Uuid::new_v4();
+        let doc = yrs::Doc::new();
+        for (version, chunk) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            let before = doc.transact().state_vector();
+            {
+                let txn = &mut doc.transact_mut();
+                txn.get_or_insert_text("content").push(txn, chunk);
+            }
+            let update = doc.transact().encode_state_as_update_v1(&before);
+            store.store_delta(doc_id, version as u64, &update).unwrap();
+        }
+        let text_at = |version: u64| {
+            let replayed = yrs::Doc::new();
+            let text = replayed.get_or_insert_text("content");
+            let mut txn = replayed.transact_mut();
+            for (_, delta) in store.load_history(doc_id, version).unwrap() {
+                txn.apply_update(yrs::Update::decode_v1(&delta).unwrap()).unwrap();
+            }
+            text.get_string(&txn)
+        };
+        let versions = |store: &DocumentStore| -> Vec<u64> {
+            store.list_versions(doc_id).unwrap().iter().map(|info| info.version).collect()
+        };
+
+        assert_eq!(store.prune_history(doc_id, u64::MAX).unwrap(), 0, "nothing compacted yet");
+        store.compact_deltas(doc_id, 2).unwrap();
+        store.create_checkpoint(doc_id, "ab", 1, None).unwrap();
+        assert_eq!(store.prune_history(doc_id, 0).unwrap(), 0, "nothing old enough");
+
+        // The checkpoint stops the squash at its version
+        assert_eq!(store.prune_history(doc_id, u64::MAX).unwrap(), 1);
+        assert_eq!(versions(&store), vec![1, 2, 3]);
+        assert_eq!(text_at(1), "ab");
+
+        // Deltas still in the log are never squashed
+        store.delete_checkpoint(doc_id, "ab").unwrap();
+        assert_eq!(store.prune_history(doc_id, u64::MAX).unwrap(), 1);
+        assert_eq!(versions(&store), vec![2, 3]);
+        assert_eq!(text_at(2), "abc");
+        assert_eq!(text_at(3), "abcd");
+        assert_eq!(store.load_all_deltas(doc_id).unwrap().len(), 1);
+    }
+}
@@ -41,6 +41,27 @@ pub enum WalEntryType {
     Checkpoint = 3,
 }
 
+/// FNV-1a offset basis and prime used by the checksum fold.
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Fold `bytes` into `hash` four little-endian bytes at a time.
+fn fold_words(mut hash: u32, bytes: &[u8]) -> u32 {
+    for chunk in bytes.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash ^= u32::from_le_bytes(word);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Checksum `bytes` with the fold [`WalEntry::verify`] uses on payloads,
+/// for data kept outside the WAL such as archive files.
+pub fn checksum(bytes: &[u8]) -> u32 {
+    fold_words(FNV_OFFSET, bytes)
+}
+
 /// A single WAL entry.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalEntry {
@@ -88,28 +109,22 @@ impl WalEntry {
         payload: &[u8],
     ) -> u32 {
         // Simple but effective: XOR-fold all fields
-        let mut hash: u32 = 0x811c_9dc5; // FNV offset basis
+        let mut hash: u32 = FNV_OFFSET;
         // Mix sequence
         hash ^= sequence as u32;
-        hash = hash.wrapping_mul(0x0100_0193); // FNV prime
+        hash = hash.wrapping_mul(FNV_PRIME);
         hash ^= (sequence >> 32) as u32;
-        hash = hash.wrapping_mul(0x0100_0193);
+        hash = hash.wrapping_mul(FNV_PRIME);
         // Mix entry type
         hash ^= entry_type as u32;
-        hash = hash.wrapping_mul(0x0100_0193);
+        hash = hash.wrapping_mul(FNV_PRIME);
         // Mix doc_id
         for byte in doc_id.as_bytes() {
             hash ^= *byte as u32;
-            hash = hash.wrapping_mul(0x0100_0193);
+            hash = hash.wrapping_mul(FNV_PRIME);
         }
         // Mix payload
-        for chunk in payload.chunks(4) {
-            let mut word = [0u8; 4];
-            word[..chunk.len()].copy_from_slice(chunk);
-            hash ^= u32::from_le_bytes(word);
-            hash = hash.wrapping_mul(0x0100_0193);
-        }
-        hash
+        fold_words(hash, payload)
     }
 
     /// Serialize entry to bytes.
@@ -178,6 +193,8 @@ pub enum WalError {
     ChecksumMismatch { sequence: u64 },
     /// WAL is closed
     Closed,
+    /// File I/O failed
+    Io(String),
 }
 
 impl std::fmt::Display for WalError {
@@ -190,6 +207,7 @@ impl std::fmt::Display for WalError {
                 write!(f, "WAL checksum mismatch at sequence {sequence}")
             }
             WalError::Closed => write!(f, "WAL is closed"),
+            WalError::Io(e) => write!(f, "WAL I/O error: {e}"),
         }
     }
 }
@@ -436,6 +454,15 @@ mod tests {
         assert!(!corrupted.verify());
     }
 
+    #[test]
+    fn test_checksum_values_are_stable() {
+        // Written WALs and archives depend on these exact values
+        let entry = WalEntry::new(7, WalEntryType::Delta, Uuid::nil(), b"payload".to_vec());
+        assert_eq!(entry.checksum, 0xc9a0_961c);
+        assert_eq!(checksum(b"hello"), 0xba32_4028);
+        assert_ne!(checksum(b"hello"), checksum(b"hellp"));
+    }
+
     #[test]
     fn test_wal_entry_encode_decode() {
         let entry = WalEntry::new(5, WalEntryType::Delta, Uuid::new_v4(), b"payload".to_vec());
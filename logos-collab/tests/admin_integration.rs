@@ -0,0 +1,187 @@
+//! Integration tests for the HTTP admin endpoint.
+//!
+//! Runs a persistent server with its admin listener, connects peers and
+//! drives metrics, room listing and the admin actions over plain HTTP.
+
+mod common;
+
+use std::sync::Arc;
+
+use logos_collab::admin::AdminServer;
+use logos_collab::client::SyncEvent;
+use logos_collab::protocol::{PeerInfo, Rejection};
+use logos_collab::server::{ServerConfig, SyncServer};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::{GetString, ReadTxn, Transact};
+
+use common::{connect, drain, start_server, text_update, QUIET};
+
+/// Start a persistent server and its admin listener; return the server,
+/// its WebSocket URL and the admin address.
+async fn start_with_admin(db_path: &std::path::Path) -> (Arc<SyncServer>, String, String) {
+    let (server, url) = start_server(ServerConfig {
+        storage_path: Some(db_path.to_path_buf()),
+        ..ServerConfig::default()
+    })
+    .await;
+    let admin = AdminServer::bind("127.0.0.1:0", server.clone()).await.unwrap();
+    let admin_addr = admin.local_addr().unwrap().to_string();
+    tokio::spawn(admin.run());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (server, url, admin_addr)
+}
+
+/// Send one HTTP request, return the status code and body.
+async fn http(addr: &str, method: &str, path: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("{method} {path} HTTP/1.1\r\nHost: {addr}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    timeout(Duration::from_secs(2), stream.read_to_string(&mut response)).await.unwrap().unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+/// Value of an unlabelled sample in a Prometheus exposition.
+fn sample(metrics: &str, name: &str) -> u64 {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no sample {name}"))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_metrics_and_room_listing() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url, admin) = start_with_admin(&dir.path().join("db")).await;
+    let doc_id = Uuid::new_v4();
+    let alice = PeerInfo::new("Alice");
+
+    let (client, _events) = connect(&url, alice.clone(), doc_id).await;
+    client.send_delta(text_update("hello")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (status, metrics) = http(&admin, "GET", "/metrics").await;
+    assert_eq!(status, 200);
+    assert_eq!(sample(&metrics, "logos_collab_connections_active"), 1);
+    assert_eq!(sample(&metrics, "logos_collab_rooms_active"), 1);
+    assert_eq!(sample(&metrics, "logos_collab_peers_active"), 1);
+    assert!(sample(&metrics, "logos_collab_messages_total") >= 2, "join and delta");
+    assert!(sample(&metrics, "logos_collab_received_bytes_total") > 0);
+    assert!(sample(&metrics, "logos_collab_storage_bytes") > 0);
+    assert_eq!(sample(&metrics, "logos_collab_persistence_queue_depth"), server.stats().await.persistence_queue);
+
+    let (status, rooms) = http(&admin, "GET", "/rooms").await;
+    assert_eq!(status, 200);
+    let rooms: serde_json::Value = serde_json::from_str(&rooms).unwrap();
+    assert_eq!(rooms[0]["doc_id"], doc_id.to_string());
+    assert_eq!(rooms[0]["peers"][0]["name"], "Alice");
+    assert_eq!(rooms[0]["deltas_since_snapshot"], 1);
+
+    let (status, room) = http(&admin, "GET", &format!("/rooms/{doc_id}")).await;
+    assert_eq!(status, 200);
+    assert!(room.contains(&alice.peer_id.to_string()));
+    assert_eq!(http(&admin, "GET", &format!("/rooms/{}", Uuid::new_v4())).await.0, 404);
+    assert_eq!(http(&admin, "POST", "/metrics").await.0, 405);
+}
+
+#[tokio::test]
+async fn test_snapshot_room_over_http() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url, admin) = start_with_admin(&dir.path().join("db")).await;
+    let doc_id = Uuid::new_v4();
+
+    let (client, _events) = connect(&url, PeerInfo::new("Alice"), doc_id).await;
+    client.send_delta(text_update("snap me")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (status, body) = http(&admin, "POST", &format!("/rooms/{doc_id}/snapshot")).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(server.stats().await.persisted_snapshots, 1);
+    assert_eq!(server.list_rooms().await[0].deltas_since_snapshot, 0);
+
+    let doc = yrs::Doc::new();
+    let snapshot = server.store().unwrap().load_snapshot(doc_id).unwrap();
+    doc.transact_mut().apply_update(yrs::Update::decode_v1(&snapshot).unwrap()).unwrap();
+    let txn = doc.transact();
+    assert_eq!(txn.get_text("content").unwrap().get_string(&txn), "snap me");
+}
+
+#[tokio::test]
+async fn test_evict_peer_over_http() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url, admin) = start_with_admin(&dir.path().join("db")).await;
+    let doc_id = Uuid::new_v4();
+    let (alice, bob) = (PeerInfo::new("Alice"), PeerInfo::new("Bob"));
+
+    let (_alice_client, mut alice_events) = connect(&url, alice.clone(), doc_id).await;
+    let (_bob_client, mut bob_events) = connect(&url, bob.clone(), doc_id).await;
+    drain(&mut alice_events, QUIET).await;
+
+    let path = format!("/rooms/{doc_id}/peers/{}/evict", bob.peer_id);
+    let (status, body) = http(&admin, "POST", &path).await;
+    assert_eq!(status, 200, "{body}");
+
+    let seen = drain(&mut bob_events, QUIET).await;
+    assert!(seen.iter().any(|e| matches!(e, SyncEvent::Rejected(Rejection::Evicted))), "{seen:?}");
+    assert!(seen.iter().any(|e| matches!(e, SyncEvent::Disconnected)));
+    assert!(
+        !seen.iter().any(|e| matches!(e, SyncEvent::Reconnecting { .. })),
+        "evicted peers do not reconnect"
+    );
+
+    let seen = drain(&mut alice_events, QUIET).await;
+    assert!(seen.iter().any(|e| matches!(e, SyncEvent::PeerLeft(id) if *id == bob.peer_id)));
+
+    let rooms = server.list_rooms().await;
+    assert_eq!(rooms[0].peers.len(), 1);
+    assert_eq!(rooms[0].peers[0].peer_id, alice.peer_id);
+    assert_eq!(server.stats().await.evicted_peers, 1);
+
+    // Gone already
+    assert_eq!(http(&admin, "POST", &path).await.0, 404);
+}
+
+#[tokio::test]
+async fn test_close_room_over_http() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url, admin) = start_with_admin(&dir.path().join("db")).await;
+    let (doc_id, other_doc) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let (alice_client, mut alice_events) = connect(&url, PeerInfo::new("Alice"), doc_id).await;
+    let (_bob_client, mut bob_events) = connect(&url, PeerInfo::new("Bob"), doc_id).await;
+    let (_carol_client, mut carol_events) = connect(&url, PeerInfo::new("Carol"), other_doc).await;
+    alice_client.send_delta(text_update("kept")).await.unwrap();
+    drain(&mut alice_events, QUIET).await;
+
+    let (status, body) = http(&admin, "POST", &format!("/rooms/{doc_id}/close")).await;
+    assert_eq!(status, 200, "{body}");
+
+    for events in [&mut alice_events, &mut bob_events] {
+        let seen = drain(events, QUIET).await;
+        assert!(seen.iter().any(|e| matches!(e, SyncEvent::Rejected(Rejection::RoomClosed))), "{seen:?}");
+        assert!(seen.iter().any(|e| matches!(e, SyncEvent::Disconnected)));
+    }
+    assert!(drain(&mut carol_events, QUIET).await.is_empty(), "other rooms are untouched");
+
+    let rooms = server.list_rooms().await;
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].doc_id, other_doc);
+    let stats = server.stats().await;
+    assert_eq!(stats.active_rooms, 1);
+    assert_eq!(stats.evicted_peers, 2);
+
+    // The document was snapshotted on close and reopens on the next join
+    let (_late_client, _) = connect(&url, PeerInfo::new("Dave"), doc_id).await;
+    assert_eq!(server.list_rooms().await.len(), 2);
+    assert!(server.store().unwrap().load_snapshot(doc_id).is_ok());
+    assert_eq!(http(&admin, "POST", &format!("/rooms/{}/close", Uuid::new_v4())).await.0, 404);
+}
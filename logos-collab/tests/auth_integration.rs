@@ -0,0 +1,191 @@
+//! Integration tests for authenticated joins.
+//!
+//! Starts a server with an `HmacAuthenticator` and checks that only peers
+//! presenting a valid token for their document get in, and that everyone
+//! else receives a typed rejection and is disconnected.
+
+mod common;
+
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use logos_collab::auth::{AuthClaims, AuthError, HmacAuthenticator};
+use logos_collab::client::{SyncClient, SyncEvent};
+use logos_collab::protocol::{MessageType, PeerInfo, Rejection, SyncMessage};
+use logos_collab::server::{ServerConfig, SyncServer};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+use common::free_port;
+
+const SECRET: &[u8] = b"integration-secret";
+
+/// Start an authenticating server, return it with its URL.
+async fn start_auth_server() -> (Arc<SyncServer>, String) {
+    let port = free_port().await;
+    let server = Arc::new(SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        authenticator: Some(Arc::new(HmacAuthenticator::new(SECRET))),
+        ..ServerConfig::default()
+    }));
+    let runner = server.clone();
+    tokio::spawn(async move {
+        runner.run().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (server, format!("ws://127.0.0.1:{port}"))
+}
+
+fn token(user_id: Uuid, doc_id: Uuid, ttl_secs: u64) -> String {
+    HmacAuthenticator::new(SECRET).issue(&AuthClaims::new(user_id, doc_id, ttl_secs))
+}
+
+/// Wait for the next event that is not `Connected` or `Negotiated`.
+async fn next_event(events: &mut mpsc::Receiver<SyncEvent>) -> Option<SyncEvent> {
+    loop {
+        match timeout(Duration::from_secs(2), events.recv()).await.ok()? {
+            Some(SyncEvent::Connected | SyncEvent::Negotiated(_)) => continue,
+            other => return other,
+        }
+    }
+}
+
+/// Connect a client with an optional token and return its first event.
+async fn join(url: &str, info: PeerInfo, doc_id: Uuid, token: Option<String>) -> (SyncClient, mpsc::Receiver<SyncEvent>, SyncEvent) {
+    let mut client = SyncClient::new(info, doc_id, url);
+    if let Some(token) = token {
+        client = client.with_auth_token(token);
+    }
+    let mut events = client.take_event_rx().unwrap();
+    client.connect().await.unwrap();
+    let first = next_event(&mut events).await.expect("no event from server");
+    (client, events, first)
+}
+
+async fn expect_rejected(url: &str, info: PeerInfo, doc_id: Uuid, token: Option<String>, expected: AuthError) {
+    let (_client, mut events, first) = join(url, info, doc_id, token).await;
+    match first {
+        SyncEvent::Rejected(Rejection::Unauthorized(e)) => assert_eq!(e, expected),
+        other => panic!("Expected rejection, got {other:?}"),
+    }
+    match next_event(&mut events).await {
+        Some(SyncEvent::Disconnected) => {}
+        other => panic!("Expected disconnect after rejection, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_valid_token_joins() {
+    let (server, url) = start_auth_server().await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Alice");
+    let token = token(info.peer_id, doc_id, 60);
+
+    let (_client, _events, first) = join(&url, info, doc_id, Some(token)).await;
+    assert!(matches!(first, SyncEvent::StateSynced(_)), "got {first:?}");
+    assert_eq!(server.stats().await.rejected_joins, 0);
+    assert_eq!(server.stats().await.active_rooms, 1);
+}
+
+#[tokio::test]
+async fn test_missing_token_rejected() {
+    let (server, url) = start_auth_server().await;
+    expect_rejected(&url, PeerInfo::new("Mallory"), Uuid::new_v4(), None, AuthError::MissingToken).await;
+
+    let stats = server.stats().await;
+    assert_eq!(stats.rejected_joins, 1);
+    assert_eq!(stats.active_rooms, 0, "rejected joins must not create rooms");
+}
+
+#[tokio::test]
+async fn test_forged_token_rejected() {
+    let (_server, url) = start_auth_server().await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Mallory");
+    let forged = HmacAuthenticator::new("guessed").issue(&AuthClaims::new(info.peer_id, doc_id, 60));
+    expect_rejected(&url, info, doc_id, Some(forged), AuthError::InvalidSignature).await;
+}
+
+#[tokio::test]
+async fn test_expired_token_rejected() {
+    let (_server, url) = start_auth_server().await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Alice");
+    let claims = AuthClaims { user_id: info.peer_id, doc_id, expires_at: 1 };
+    let expired = HmacAuthenticator::new(SECRET).issue(&claims);
+    expect_rejected(&url, info, doc_id, Some(expired), AuthError::Expired { expires_at: 1 }).await;
+}
+
+#[tokio::test]
+async fn test_token_for_other_document_rejected() {
+    let (_server, url) = start_auth_server().await;
+    let granted = Uuid::new_v4();
+    let info = PeerInfo::new("Alice");
+    let token = token(info.peer_id, granted, 60);
+    expect_rejected(&url, info, Uuid::new_v4(), Some(token), AuthError::WrongDocument { token_doc_id: granted })
+        .await;
+}
+
+#[tokio::test]
+async fn test_borrowed_token_rejected() {
+    let (_server, url) = start_auth_server().await;
+    let doc_id = Uuid::new_v4();
+    let alice = Uuid::new_v4();
+    let token = token(alice, doc_id, 60);
+    expect_rejected(&url, PeerInfo::new("Mallory"), doc_id, Some(token), AuthError::PeerMismatch { user_id: alice })
+        .await;
+}
+
+#[tokio::test]
+async fn test_rejected_socket_cannot_write() {
+    let (_server, url) = start_auth_server().await;
+    let doc_id = Uuid::new_v4();
+
+    // Legitimate peer in the room
+    let alice = PeerInfo::new("Alice");
+    let (_client, mut alice_events, _) = join(&url, alice.clone(), doc_id, Some(token(alice.peer_id, doc_id, 60))).await;
+
+    // Raw socket impersonating Alice without a token, then pushing a delta
+    let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let hello = SyncMessage::peer_joined(alice.peer_id, doc_id, &alice);
+    ws.send(Message::Binary(hello.encode().unwrap().into())).await.unwrap();
+    let delta = SyncMessage::delta(Uuid::new_v4(), doc_id, 1, vec![1, 2, 3]);
+    let _ = ws.send(Message::Binary(delta.encode().unwrap().into())).await;
+
+    // The socket sees the rejection and then the close
+    let reply = timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+    let reply = SyncMessage::decode(&reply.into_data()).unwrap();
+    assert_eq!(reply.msg_type, MessageType::Rejected);
+    assert_eq!(reply.rejection().unwrap(), Rejection::Unauthorized(AuthError::MissingToken));
+    match timeout(Duration::from_secs(2), ws.next()).await.unwrap() {
+        Some(Ok(Message::Close(_))) | None | Some(Err(_)) => {}
+        Some(Ok(other)) => panic!("Unexpected frame after rejection: {other:?}"),
+    }
+
+    // Nothing from the rejected socket reached the room
+    while let Ok(Some(event)) = timeout(Duration::from_millis(200), alice_events.recv()).await {
+        assert!(
+            !matches!(event, SyncEvent::RemoteDelta { .. } | SyncEvent::PeerJoined(_)),
+            "Rejected socket leaked {event:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_open_server_accepts_tokenless_joins() {
+    let port = free_port().await;
+    let server = SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        ..ServerConfig::default()
+    });
+    tokio::spawn(async move {
+        server.run().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let url = format!("ws://127.0.0.1:{port}");
+    let (_client, _events, first) = join(&url, PeerInfo::new("Alice"), Uuid::new_v4(), None).await;
+    assert!(matches!(first, SyncEvent::StateSynced(_)), "got {first:?}");
+}
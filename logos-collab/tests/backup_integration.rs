@@ -0,0 +1,171 @@
+//! Integration tests for backup, export and restore.
+//!
+//! Exports documents from a running RocksDB-backed server (over the API,
+//! the admin endpoint and the `logos-archive` CLI) and restores them into
+//! SQLite-backed servers and stores.
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use logos_collab::admin::AdminServer;
+use logos_collab::client::SyncClient;
+use logos_collab::protocol::PeerInfo;
+use logos_collab::server::SyncServer;
+use logos_collab::storage::{Archive, BackendKind, DocumentStore, IdPolicy, StoreConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+use common::{connect, start_stored_server};
+
+/// Type `s` at the end of a document already holding `len` characters.
+fn append(doc: &yrs::Doc, len: u32, s: &str) -> Vec<u8> {
+    let before = doc.transact().state_vector();
+    {
+        let txn = &mut doc.transact_mut();
+        txn.get_or_insert_text("content").insert(txn, len, s);
+    }
+    doc.transact().encode_state_as_update_v1(&before)
+}
+
+fn text(state: &[u8]) -> String {
+    let doc = yrs::Doc::new();
+    doc.transact_mut().apply_update(yrs::Update::decode_v1(state).unwrap()).unwrap();
+    let txn = doc.transact();
+    txn.get_text("content").map(|t| t.get_string(&txn)).unwrap_or_default()
+}
+
+/// Write "hello" then " world" to `doc_id`, snapshotting in between so the
+/// first delta is compacted away.
+async fn write_document(server: &SyncServer, url: &str, doc_id: Uuid) -> SyncClient {
+    let doc = yrs::Doc::new();
+    let client = connect(url, PeerInfo::new("Alice"), doc_id).await.0;
+    client.send_delta(append(&doc, 0, "hello")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server.snapshot_room(doc_id).await.unwrap();
+    client.send_delta(append(&doc, 5, " world")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client
+}
+
+/// Text of `doc_id` as the room on `server` holds it after a join.
+async fn room_text(server: &SyncServer, url: &str, doc_id: Uuid) -> String {
+    let _client = connect(url, PeerInfo::new("Alice"), doc_id).await.0;
+    server.snapshot_room(doc_id).await.unwrap();
+    text(&server.store().unwrap().load_snapshot(doc_id).unwrap())
+}
+
+#[tokio::test]
+async fn test_export_running_server_and_restore_elsewhere() {
+    let dir = tempfile::tempdir().unwrap();
+    let (source, source_url) = start_stored_server(&dir.path().join("rocks"), BackendKind::RocksDb).await;
+    let doc_id = Uuid::new_v4();
+    let _client = write_document(&source, &source_url, doc_id).await;
+
+    // Exported while the peer is still connected
+    let archive = source.export_documents(None).await.unwrap();
+    assert_eq!(archive.documents(), &[doc_id]);
+    let archive = Archive::decode(&archive.encode().unwrap()).unwrap();
+
+    let (target, target_url) = start_stored_server(&dir.path().join("target.db"), BackendKind::Sqlite).await;
+    let first = target.import_archive(&archive, IdPolicy::RenameOnClash).await.unwrap();
+    assert_eq!(first[0].doc_id, doc_id, "free ids are kept");
+    assert_eq!(first[0].versions, 2);
+    assert_eq!(room_text(&target, &target_url, doc_id).await, "hello world");
+
+    // The id is taken now: importing again makes a copy instead
+    assert!(target.import_archive(&archive, IdPolicy::Keep).await.is_err());
+    let second = target.import_archive(&archive, IdPolicy::RenameOnClash).await.unwrap();
+    let copy_id = second[0].doc_id;
+    assert_ne!(copy_id, doc_id);
+    assert_eq!(room_text(&target, &target_url, copy_id).await, "hello world");
+    assert_eq!(target.list_versions(doc_id).await.unwrap().len(), 2);
+    assert_eq!(target.list_versions(copy_id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_export_over_admin_endpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url) = start_stored_server(&dir.path().join("db"), BackendKind::RocksDb).await;
+    let admin = AdminServer::bind("127.0.0.1:0", server.clone()).await.unwrap();
+    let admin_addr = admin.local_addr().unwrap().to_string();
+    tokio::spawn(admin.run());
+    let doc_id = Uuid::new_v4();
+    let _client = write_document(&server, &url, doc_id).await;
+
+    let mut stream = TcpStream::connect(&admin_addr).await.unwrap();
+    let request = format!("GET /export/{doc_id} HTTP/1.1\r\nHost: {admin_addr}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    timeout(Duration::from_secs(2), stream.read_to_end(&mut response)).await.unwrap().unwrap();
+
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8_lossy(&response[..split]);
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert!(head.contains("application/octet-stream"));
+    let archive = Archive::decode(&response[split + 4..]).unwrap();
+    assert_eq!(archive.documents(), &[doc_id]);
+}
+
+#[tokio::test]
+async fn test_cli_export_import_between_backends() {
+    let dir = tempfile::tempdir().unwrap();
+    let source_path = dir.path().join("rocks");
+    let doc_id = Uuid::new_v4();
+    {
+        let (server, url) = start_stored_server(&source_path, BackendKind::RocksDb).await;
+        let mut client = write_document(&server, &url, doc_id).await;
+        client.disconnect().await;
+        server.snapshot_room(doc_id).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let assets = dir.path().join("assets");
+    std::fs::create_dir_all(assets.join(doc_id.to_string())).unwrap();
+    std::fs::write(assets.join(doc_id.to_string()).join("logo.svg"), b"<svg/>").unwrap();
+
+    let archive_path = dir.path().join("backup.logos");
+    let cli = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_logos-archive")).args(args).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let arg = |path: &Path| path.to_str().unwrap().to_string();
+
+    cli(&["export", &arg(&source_path), &arg(&archive_path), "--assets", &arg(&assets)]);
+    assert!(cli(&["verify", &arg(&archive_path)]).contains("ok: 1 documents"));
+
+    let target_path = dir.path().join("target.db");
+    let restored = dir.path().join("restored");
+    let out = cli(&[
+        "import", &arg(&target_path), &arg(&archive_path), "--backend", "sqlite", "--ids", "fresh", "--assets",
+        &arg(&restored),
+    ]);
+    let new_id: Uuid = out.split_whitespace().nth(2).unwrap().parse().unwrap();
+    assert_ne!(new_id, doc_id);
+    assert_eq!(std::fs::read(restored.join(new_id.to_string()).join("logo.svg")).unwrap(), b"<svg/>");
+
+    let target = DocumentStore::open(StoreConfig {
+        backend: BackendKind::Sqlite,
+        ..StoreConfig::for_testing(&target_path)
+    })
+    .unwrap();
+    assert_eq!(text(&target.load_snapshot(new_id).unwrap()), "hello world");
+    assert_eq!(target.list_versions(new_id).unwrap().len(), 2);
+
+    // A damaged archive is refused
+    let mut bytes = std::fs::read(&archive_path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&archive_path, bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_logos-archive"))
+        .args(["verify", &arg(&archive_path)])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
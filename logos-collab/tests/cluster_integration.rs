@@ -0,0 +1,239 @@
+//! Integration tests for multi-node clustering.
+//!
+//! Runs several server nodes joined by a relay, with engines connected to
+//! different nodes editing the same document, and checks which node ends
+//! up persisting it.
+
+mod common;
+
+use std::sync::Arc;
+
+use logos_collab::cluster::{ClusterConfig, LoopbackHub, MemoryLeases, RoomRelay, StorageLease, TcpMeshRelay};
+use logos_collab::client::ReconnectConfig;
+use logos_collab::engine::{CollaborationEngine, EngineEvent};
+use logos_collab::presence::{AwarenessMessage, Vec2};
+use logos_collab::protocol::PeerInfo;
+use logos_collab::server::{ServerConfig, SyncServer};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration, Instant};
+use uuid::Uuid;
+use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+use common::free_port;
+
+struct Node {
+    server: Arc<SyncServer>,
+    runner: JoinHandle<()>,
+    url: String,
+    node_id: Uuid,
+}
+
+impl Node {
+    /// Stop the node, dropping the server and giving its leases up.
+    async fn stop(self) {
+        self.runner.abort();
+        let _ = self.runner.await;
+        drop(self.server);
+    }
+}
+
+async fn start_node(relay: Arc<dyn RoomRelay>, leases: Arc<dyn StorageLease>, config: ServerConfig) -> Node {
+    let port = free_port().await;
+    let node_id = relay.node_id();
+    let server = Arc::new(SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        cluster: Some(ClusterConfig::new(relay, leases)),
+        ..config
+    }));
+    let runner = server.clone();
+    let runner = tokio::spawn(async move {
+        runner.run().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Node { server, runner, url: format!("ws://127.0.0.1:{port}"), node_id }
+}
+
+async fn engine(url: &str, name: &str, doc_id: Uuid) -> (CollaborationEngine, mpsc::Receiver<EngineEvent>) {
+    let mut engine =
+        CollaborationEngine::new(PeerInfo::new(name), doc_id, url).with_reconnect(ReconnectConfig::disabled());
+    let mut events = engine.take_event_rx().unwrap();
+    engine.connect().await.unwrap();
+    until(&mut events, |e| e.iter().any(|e| matches!(e, EngineEvent::Synced))).await;
+    (engine, events)
+}
+
+fn insert(engine: &CollaborationEngine, text: &str) {
+    let mut txn = engine.doc().transact_mut();
+    let content = txn.get_or_insert_text("content");
+    let len = content.len(&txn);
+    content.insert(&mut txn, len, text);
+}
+
+fn text(engine: &CollaborationEngine) -> String {
+    let txn = engine.doc().transact();
+    txn.get_text("content").map(|t| t.get_string(&txn)).unwrap_or_default()
+}
+
+/// Collect events until `done` holds, failing after two seconds.
+async fn until(
+    events: &mut mpsc::Receiver<EngineEvent>,
+    mut done: impl FnMut(&[EngineEvent]) -> bool,
+) -> Vec<EngineEvent> {
+    let mut seen = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while !done(&seen) {
+        match timeout(deadline.saturating_duration_since(Instant::now()), events.recv()).await {
+            Ok(Some(event)) => seen.push(event),
+            _ => panic!("timed out; saw {seen:?}"),
+        }
+    }
+    seen
+}
+
+#[tokio::test]
+async fn test_edits_and_presence_cross_nodes() {
+    let hub = LoopbackHub::new();
+    let leases = Arc::new(MemoryLeases::new());
+    let a = start_node(Arc::new(hub.join()), leases.clone(), ServerConfig::default()).await;
+    let b = start_node(Arc::new(hub.join()), leases.clone(), ServerConfig::default()).await;
+    let doc_id = Uuid::new_v4();
+
+    let (alice, mut alice_events) = engine(&a.url, "Alice", doc_id).await;
+    let (bob, mut bob_events) = engine(&b.url, "Bob", doc_id).await;
+    until(&mut alice_events, |e| {
+        e.iter().any(|e| matches!(e, EngineEvent::PeerJoined(info) if info.name == "Bob"))
+    })
+    .await;
+
+    insert(&alice, "Hello");
+    until(&mut bob_events, |_| text(&bob) == "Hello").await;
+    insert(&bob, ", world");
+    until(&mut alice_events, |_| text(&alice) == "Hello, world").await;
+
+    let cursor = AwarenessMessage::Cursor {
+        user_id: alice.peer_info().peer_id,
+        position: Vec2 { x: 4.0, y: 2.0 },
+        timestamp: 1,
+    };
+    alice.send_presence(cursor).await.unwrap();
+    let alice_id = alice.peer_info().peer_id;
+    until(&mut bob_events, |e| {
+        e.iter().any(|e| {
+            matches!(e, EngineEvent::Presence { peer_id, message: AwarenessMessage::Cursor { .. } } if *peer_id == alice_id)
+        })
+    })
+    .await;
+
+    assert!(a.server.stats().await.relay_sent > 0);
+    assert!(b.server.stats().await.relay_received > 0);
+}
+
+#[tokio::test]
+async fn test_room_opened_on_second_node_catches_up() {
+    let hub = LoopbackHub::new();
+    let leases = Arc::new(MemoryLeases::new());
+    let a = start_node(Arc::new(hub.join()), leases.clone(), ServerConfig::default()).await;
+    let b = start_node(Arc::new(hub.join()), leases.clone(), ServerConfig::default()).await;
+    let doc_id = Uuid::new_v4();
+
+    let (alice, mut alice_events) = engine(&a.url, "Alice", doc_id).await;
+    insert(&alice, "written before node B had the room");
+    until(&mut alice_events, |e| e.iter().any(|e| matches!(e, EngineEvent::Changed(_)))).await;
+
+    // Node B opens the room and asks node A for the document
+    let (bob, mut bob_events) = engine(&b.url, "Bob", doc_id).await;
+    until(&mut bob_events, |_| text(&bob) == "written before node B had the room").await;
+    assert_eq!(b.server.list_rooms().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_only_lease_holder_persists() {
+    let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let hub = LoopbackHub::new();
+    let leases = Arc::new(MemoryLeases::new());
+    let stored = |dir: &tempfile::TempDir| ServerConfig {
+        storage_path: Some(dir.path().to_path_buf()),
+        ..ServerConfig::default()
+    };
+    let a = start_node(Arc::new(hub.join()), leases.clone(), stored(&dir_a)).await;
+    let b = start_node(Arc::new(hub.join()), leases.clone(), stored(&dir_b)).await;
+    let doc_id = Uuid::new_v4();
+
+    // The node opening the document first takes its lease
+    let (alice, _alice_events) = engine(&a.url, "Alice", doc_id).await;
+    assert_eq!(leases.holder(doc_id), Some(a.node_id));
+    let (bob, mut bob_events) = engine(&b.url, "Bob", doc_id).await;
+
+    for word in ["one ", "two ", "three "] {
+        insert(&alice, word);
+    }
+    insert(&bob, "four ");
+    insert(&bob, "five");
+    until(&mut bob_events, |_| text(&bob).len() == "one two three four five".len()).await;
+
+    // Node A logged both peers' deltas, node B none
+    let versions = a.server.list_versions(doc_id).await.unwrap();
+    for peer in [alice.peer_info().peer_id, bob.peer_info().peer_id] {
+        assert!(versions.iter().any(|v| v.author == Some(peer)), "{versions:?}");
+    }
+    assert!(b.server.list_versions(doc_id).await.unwrap_or_default().is_empty());
+
+    // Rooms closing on both nodes: only the holder snapshots
+    drop((alice, bob));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let (stats_a, stats_b) = (a.server.stats().await, b.server.stats().await);
+    assert_eq!((stats_a.persisted_snapshots, stats_a.held_leases), (1, 1));
+    assert_eq!((stats_b.persisted_snapshots, stats_b.persisted_deltas, stats_b.held_leases), (0, 0, 0));
+}
+
+#[tokio::test]
+async fn test_lease_taken_over_when_holder_stops() {
+    let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let hub = LoopbackHub::new();
+    let leases = Arc::new(MemoryLeases::new());
+    let stored = |dir: &tempfile::TempDir| ServerConfig {
+        storage_path: Some(dir.path().to_path_buf()),
+        ..ServerConfig::default()
+    };
+    let a = start_node(Arc::new(hub.join()), leases.clone(), stored(&dir_a)).await;
+    let b = start_node(Arc::new(hub.join()), leases.clone(), stored(&dir_b)).await;
+    let doc_id = Uuid::new_v4();
+
+    let (alice, alice_events) = engine(&a.url, "Alice", doc_id).await;
+    let (bob, mut bob_events) = engine(&b.url, "Bob", doc_id).await;
+    insert(&alice, "kept ");
+    until(&mut bob_events, |_| text(&bob) == "kept ").await;
+
+    a.stop().await;
+    drop(alice);
+    drop(alice_events);
+    assert_eq!(leases.holder(doc_id), None, "released on shutdown");
+
+    // Node B's next write takes the lease and snapshots its copy whole
+    insert(&bob, "going");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(leases.holder(doc_id), Some(b.node_id));
+    let stats = b.server.stats().await;
+    assert_eq!((stats.persisted_snapshots, stats.persisted_deltas), (1, 1));
+    let state = b.server.list_versions(doc_id).await.unwrap();
+    assert_eq!(state.len(), 1);
+}
+
+#[tokio::test]
+async fn test_tcp_mesh_cluster() {
+    let leases = Arc::new(MemoryLeases::new());
+    let relay_a = Arc::new(TcpMeshRelay::bind("127.0.0.1:0").await.unwrap());
+    let relay_b = Arc::new(TcpMeshRelay::bind("127.0.0.1:0").await.unwrap());
+    relay_a.connect(relay_b.local_addr()).await.unwrap();
+    let a = start_node(relay_a, leases.clone(), ServerConfig::default()).await;
+    let b = start_node(relay_b, leases.clone(), ServerConfig::default()).await;
+    let doc_id = Uuid::new_v4();
+
+    let (alice, mut alice_events) = engine(&a.url, "Alice", doc_id).await;
+    let (bob, mut bob_events) = engine(&b.url, "Bob", doc_id).await;
+    insert(&alice, "over ");
+    until(&mut bob_events, |_| text(&bob) == "over ").await;
+    insert(&bob, "tcp");
+    until(&mut alice_events, |_| text(&alice) == "over tcp").await;
+}
@@ -0,0 +1,101 @@
+//! Helpers shared by the integration tests: servers on free ports,
+//! clients joined to them and the events they receive.
+
+// Each test crate compiles its own copy and uses only part of it
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::sync::Arc;
+
+use logos_collab::client::{SyncClient, SyncEvent};
+use logos_collab::protocol::PeerInfo;
+use logos_collab::server::{ServerConfig, SyncServer};
+use logos_collab::storage::BackendKind;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+use yrs::{Text, Transact, WriteTxn};
+
+/// How long [`drain`] usually waits for the next event.
+pub const QUIET: Duration = Duration::from_millis(150);
+
+/// Find a free port for testing.
+pub async fn free_port() -> u16 {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+/// Run a server with `config` on a free port; return it and its URL.
+pub async fn start_server(config: ServerConfig) -> (Arc<SyncServer>, String) {
+    let port = free_port().await;
+    let server = Arc::new(SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        ..config
+    }));
+    let runner = server.clone();
+    tokio::spawn(async move {
+        runner.run().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (server, format!("ws://127.0.0.1:{port}"))
+}
+
+/// [`start_server`] with default settings, storing at `path` with `backend`.
+pub async fn start_stored_server(path: &Path, backend: BackendKind) -> (Arc<SyncServer>, String) {
+    start_server(ServerConfig {
+        storage_path: Some(path.to_path_buf()),
+        storage_backend: backend,
+        ..ServerConfig::default()
+    })
+    .await
+}
+
+/// Run a server on its own runtime so a test can kill it, connections
+/// and all, with `shutdown_background`.
+pub fn spawn_bounceable_server(port: u16) -> tokio::runtime::Runtime {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.spawn(async move {
+        // The previous instance may still hold the port for a moment
+        loop {
+            let server = SyncServer::new(ServerConfig {
+                bind_addr: format!("127.0.0.1:{port}"),
+                ..ServerConfig::default()
+            });
+            if server.run().await.is_err() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    });
+    runtime
+}
+
+/// Join `doc_id` as `info`, with the events of the join already drained.
+pub async fn connect(url: &str, info: PeerInfo, doc_id: Uuid) -> (SyncClient, mpsc::Receiver<SyncEvent>) {
+    let mut client = SyncClient::new(info, doc_id, url);
+    let mut events = client.take_event_rx().unwrap();
+    client.connect().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drain(&mut events, QUIET).await;
+    (client, events)
+}
+
+/// Collect events until none arrives for `quiet`.
+pub async fn drain(events: &mut mpsc::Receiver<SyncEvent>, quiet: Duration) -> Vec<SyncEvent> {
+    let mut seen = Vec::new();
+    while let Ok(Some(event)) = timeout(quiet, events.recv()).await {
+        seen.push(event);
+    }
+    seen
+}
+
+/// Encode a Yrs update inserting `s` into the "content" text.
+pub fn text_update(s: &str) -> Vec<u8> {
+    let doc = yrs::Doc::new();
+    let mut txn = doc.transact_mut();
+    txn.get_or_insert_text("content").insert(&mut txn, 0, s);
+    txn.encode_update_v1()
+}
@@ -0,0 +1,147 @@
+//! Integration tests for the document-owning collaboration engine.
+//!
+//! Engines edit their own `yrs::Doc`; the tests only commit transactions
+//! and compare documents, including across a server restart.
+
+mod common;
+
+use logos_collab::client::ReconnectConfig;
+use logos_collab::engine::{ChangeOrigin, CollaborationEngine, EngineEvent};
+use logos_collab::protocol::PeerInfo;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration, Instant};
+use uuid::Uuid;
+use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+use common::{free_port, spawn_bounceable_server};
+
+async fn engine(url: &str, name: &str, doc_id: Uuid) -> (CollaborationEngine, mpsc::Receiver<EngineEvent>) {
+    let mut engine = CollaborationEngine::new(PeerInfo::new(name), doc_id, url)
+        .with_reconnect(ReconnectConfig {
+            initial_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(200),
+            max_attempts: None,
+            ..ReconnectConfig::default()
+        });
+    let events = engine.take_event_rx().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while engine.connect().await.is_err() {
+        assert!(Instant::now() < deadline, "server did not come up");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    (engine, events)
+}
+
+fn insert(engine: &CollaborationEngine, text: &str) {
+    let mut txn = engine.doc().transact_mut();
+    let content = txn.get_or_insert_text("content");
+    let len = content.len(&txn);
+    content.insert(&mut txn, len, text);
+}
+
+fn text(engine: &CollaborationEngine) -> String {
+    let txn = engine.doc().transact();
+    txn.get_text("content").map(|t| t.get_string(&txn)).unwrap_or_default()
+}
+
+/// Collect events until `done` holds, failing after two seconds.
+async fn until(
+    events: &mut mpsc::Receiver<EngineEvent>,
+    mut done: impl FnMut(&[EngineEvent]) -> bool,
+) -> Vec<EngineEvent> {
+    let mut seen = Vec::new();
+    while !done(&seen) {
+        match timeout(Duration::from_secs(2), events.recv()).await {
+            Ok(Some(event)) => seen.push(event),
+            _ => panic!("timed out; saw {seen:?}"),
+        }
+    }
+    seen
+}
+
+fn has(events: &[EngineEvent], pred: impl Fn(&EngineEvent) -> bool) -> bool {
+    events.iter().any(pred)
+}
+
+#[tokio::test]
+async fn test_engines_converge() {
+    let port = free_port().await;
+    let server = spawn_bounceable_server(port);
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    let (alice, mut alice_events) = engine(&url, "Alice", doc_id).await;
+    let (bob, mut bob_events) = engine(&url, "Bob", doc_id).await;
+    until(&mut alice_events, |e| has(e, |e| matches!(e, EngineEvent::Synced))).await;
+    until(&mut bob_events, |e| has(e, |e| matches!(e, EngineEvent::Synced))).await;
+
+    insert(&alice, "Hello");
+    let alice_id = alice.peer_info().peer_id;
+    let seen = until(&mut bob_events, |e| {
+        has(e, |e| matches!(e, EngineEvent::Changed(c) if c.origin == ChangeOrigin::Remote(alice_id)))
+    })
+    .await;
+    assert_eq!(text(&bob), "Hello", "saw {seen:?}");
+    until(&mut alice_events, |e| {
+        has(e, |e| matches!(e, EngineEvent::Changed(c) if c.origin == ChangeOrigin::Local))
+    })
+    .await;
+
+    // Remote changes are applied, not echoed back
+    insert(&bob, ", world");
+    until(&mut alice_events, |_| text(&alice) == "Hello, world").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(text(&bob), "Hello, world");
+
+    // A late joiner gets the whole document from the handshake
+    let (carol, mut carol_events) = engine(&url, "Carol", doc_id).await;
+    let seen = until(&mut carol_events, |e| has(e, |e| matches!(e, EngineEvent::Synced))).await;
+    assert!(has(&seen, |e| matches!(e, EngineEvent::Changed(c) if c.origin == ChangeOrigin::Sync)));
+    assert_eq!(text(&carol), "Hello, world");
+
+    server.shutdown_background();
+}
+
+#[tokio::test]
+async fn test_engine_reconnects_and_merges_offline_edits() {
+    let port = free_port().await;
+    let server = spawn_bounceable_server(port);
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    let (alice, mut alice_events) = engine(&url, "Alice", doc_id).await;
+    let (bob, mut bob_events) = engine(&url, "Bob", doc_id).await;
+    insert(&alice, "shared ");
+    until(&mut bob_events, |_| text(&bob) == "shared ").await;
+
+    // Kill the server; both sides keep editing offline
+    server.shutdown_background();
+    until(&mut alice_events, |e| has(e, |e| matches!(e, EngineEvent::Reconnecting { .. }))).await;
+    until(&mut bob_events, |e| has(e, |e| matches!(e, EngineEvent::Reconnecting { .. }))).await;
+    insert(&alice, "alice ");
+    insert(&bob, "bob ");
+
+    // A fresh, empty server: the handshakes rebuild it from both engines
+    let server = spawn_bounceable_server(port);
+    until(&mut alice_events, |e| has(e, |e| matches!(e, EngineEvent::Connected))).await;
+    until(&mut bob_events, |e| has(e, |e| matches!(e, EngineEvent::Connected))).await;
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while text(&alice) != text(&bob) || !text(&alice).contains("alice") || !text(&alice).contains("bob") {
+        assert!(
+            Instant::now() < deadline,
+            "did not converge: alice={:?} bob={:?}",
+            text(&alice),
+            text(&bob)
+        );
+        let _ = timeout(Duration::from_millis(50), alice_events.recv()).await;
+        let _ = timeout(Duration::from_millis(50), bob_events.recv()).await;
+    }
+    assert!(text(&alice).starts_with("shared "));
+
+    // Still live after the bounce
+    insert(&alice, "again");
+    until(&mut bob_events, |_| text(&bob).ends_with("again")).await;
+
+    server.shutdown_background();
+}
@@ -3,6 +3,8 @@
 //! These tests start a real server and connect real clients,
 //! verifying the full sync pipeline.
 
+mod common;
+
 use logos_collab::protocol::{PeerInfo, SyncMessage, AwarenessState};
 use logos_collab::server::{SyncServer, ServerConfig};
 use logos_collab::client::{SyncClient, ConnectionState, SyncEvent};
@@ -11,23 +13,27 @@ use uuid::Uuid;
 use std::sync::Arc;
 use tokio::time::{timeout, Duration};
 
-/// Find a free port for testing.
-async fn free_port() -> u16 {
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
-    listener.local_addr().unwrap().port()
-}
+use common::{free_port, spawn_bounceable_server};
 
 /// Start a server on a free port, return the port.
 async fn start_test_server() -> u16 {
-    let port = free_port().await;
-    let config = ServerConfig {
-        bind_addr: format!("127.0.0.1:{port}"),
+    start_server_with(ServerConfig {
         max_peers_per_room: 10,
         broadcast_capacity: 64,
         heartbeat_interval_secs: 30,
         storage_path: None,
-    };
-    let server = SyncServer::new(config);
+        ..ServerConfig::default()
+    })
+    .await
+}
+
+/// Start a server with `config` on a free port, return the port.
+async fn start_server_with(config: ServerConfig) -> u16 {
+    let port = free_port().await;
+    let server = SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        ..config
+    });
     tokio::spawn(async move {
         server.run().await.unwrap();
     });
@@ -256,3 +262,475 @@ async fn test_ping_pong() {
     // Send ping — should not error
     client.send_ping().await.unwrap();
 }
+
+#[tokio::test]
+async fn test_lagged_peer_is_resynced() {
+    use futures_util::{SinkExt, StreamExt};
+    use logos_collab::protocol::MessageType;
+    use tokio_tungstenite::tungstenite::Message;
+    use yrs::updates::decoder::Decode;
+    use yrs::updates::encoder::Encode;
+    use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+    // A two-slot channel: any burst the slow peer cannot forward is lost
+    let port = free_port().await;
+    let server = Arc::new(SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        broadcast_capacity: 2,
+        ..ServerConfig::default()
+    }));
+    let runner = server.clone();
+    tokio::spawn(async move {
+        runner.run().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    let join = |info: PeerInfo| {
+        let url = url.clone();
+        async move {
+            let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+            let hello = SyncMessage::peer_joined(info.peer_id, doc_id, &info);
+            ws.send(Message::Binary(hello.encode().unwrap().into())).await.unwrap();
+            // Wait for the handshake so the join is registered
+            let step1 = SyncMessage::sync_step1(info.peer_id, doc_id, yrs::StateVector::default().encode_v1());
+            ws.send(Message::Binary(step1.encode().unwrap().into())).await.unwrap();
+            loop {
+                let frame = ws.next().await.unwrap().unwrap();
+                if SyncMessage::decode(&frame.into_data()).unwrap().msg_type == MessageType::SyncStep1 {
+                    break;
+                }
+            }
+            ws
+        }
+    };
+
+    // The slow peer reads nothing until the burst is over
+    let mut slow = join(PeerInfo::new("Slow")).await;
+    let writer = PeerInfo::new("Writer");
+    let mut fast = join(writer.clone()).await;
+
+    let doc = yrs::Doc::new();
+    let chunk = "x".repeat(16 * 1024);
+    for clock in 0..32 {
+        let before = doc.transact().state_vector();
+        {
+            let mut txn = doc.transact_mut();
+            let text = txn.get_or_insert_text("content");
+            let len = text.len(&txn);
+            text.insert(&mut txn, len, &chunk);
+        }
+        let update = doc.transact().encode_state_as_update_v1(&before);
+        let msg = SyncMessage::delta(writer.peer_id, doc_id, clock, update);
+        fast.send(Message::Binary(msg.encode().unwrap().into())).await.unwrap();
+    }
+
+    // Drain the slow peer and apply whatever it got
+    let replica = yrs::Doc::new();
+    let mut resynced = false;
+    while let Ok(Some(Ok(frame))) = timeout(Duration::from_secs(1), slow.next()).await {
+        let Ok(msg) = SyncMessage::decode(&frame.into_data()) else { continue };
+        if msg.msg_type == MessageType::SyncStep2 {
+            resynced = true;
+        }
+        if matches!(msg.msg_type, MessageType::Delta | MessageType::SyncStep2) {
+            if let Ok(update) = yrs::Update::decode_v1(&msg.payload) {
+                replica.transact_mut().apply_update(update).unwrap();
+            }
+        }
+    }
+
+    assert!(resynced, "lagged peer should receive a SyncStep2 resync");
+    assert!(server.stats().await.lag_resyncs >= 1);
+    let expected = {
+        let txn = doc.transact();
+        txn.get_text("content").unwrap().get_string(&txn).len()
+    };
+    let txn = replica.transact();
+    let got = txn.get_text("content").map(|t| t.get_string(&txn).len()).unwrap_or(0);
+    assert_eq!(got, expected, "slow peer must converge despite the lag");
+}
+
+#[tokio::test]
+async fn test_handshake_converges_after_offline_edits() {
+    use yrs::updates::decoder::Decode;
+    use yrs::updates::encoder::Encode;
+    use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+    let port = start_test_server().await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    // Alice edits online
+    let alice_doc = yrs::Doc::new();
+    let mut alice = SyncClient::new(PeerInfo::new("Alice"), doc_id, &url);
+    let mut alice_events = alice.take_event_rx().unwrap();
+    alice.connect().await.unwrap();
+    let update = {
+        let mut txn = alice_doc.transact_mut();
+        txn.get_or_insert_text("content").insert(&mut txn, 0, "online ");
+        txn.encode_update_v1()
+    };
+    alice.send_delta(update).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    while timeout(Duration::from_millis(100), alice_events.recv()).await.is_ok() {}
+
+    // Bob edited offline and never sent anything
+    let bob_doc = yrs::Doc::new();
+    {
+        let mut txn = bob_doc.transact_mut();
+        txn.get_or_insert_text("content").insert(&mut txn, 0, "offline");
+    }
+    let mut bob = SyncClient::new(PeerInfo::new("Bob"), doc_id, &url);
+    bob.set_state_vector(bob_doc.transact().state_vector().encode_v1()).await;
+    let mut bob_events = bob.take_event_rx().unwrap();
+    bob.connect().await.unwrap();
+
+    let (mut synced, mut answered) = (false, false);
+    while !(synced && answered) {
+        let event = timeout(Duration::from_secs(2), bob_events.recv()).await.unwrap().unwrap();
+        match event {
+            SyncEvent::StateSynced(update) => {
+                bob_doc.transact_mut().apply_update(yrs::Update::decode_v1(&update).unwrap()).unwrap();
+                synced = true;
+            }
+            SyncEvent::SyncRequested(sv) => {
+                let sv = yrs::StateVector::decode_v1(&sv).unwrap();
+                let diff = bob_doc.transact().encode_diff_v1(&sv);
+                bob.send_sync_step2(diff).await.unwrap();
+                answered = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Alice receives Bob's offline edits as an ordinary delta
+    loop {
+        let event = timeout(Duration::from_secs(2), alice_events.recv()).await.unwrap().unwrap();
+        if let SyncEvent::RemoteDelta { update, .. } = event {
+            alice_doc.transact_mut().apply_update(yrs::Update::decode_v1(&update).unwrap()).unwrap();
+            break;
+        }
+    }
+
+    let text = |doc: &yrs::Doc| {
+        let txn = doc.transact();
+        txn.get_text("content").unwrap().get_string(&txn)
+    };
+    assert_eq!(text(&alice_doc), text(&bob_doc));
+    assert!(text(&bob_doc).contains("online") && text(&bob_doc).contains("offline"));
+}
+
+fn fast_reconnect(max_attempts: Option<u32>) -> logos_collab::client::ReconnectConfig {
+    logos_collab::client::ReconnectConfig {
+        initial_delay: Duration::from_millis(20),
+        max_delay: Duration::from_millis(100),
+        max_attempts,
+        ..Default::default()
+    }
+}
+
+async fn next_matching(
+    events: &mut tokio::sync::mpsc::Receiver<SyncEvent>,
+    pred: impl Fn(&SyncEvent) -> bool,
+) -> SyncEvent {
+    loop {
+        let event = timeout(Duration::from_secs(3), events.recv())
+            .await
+            .expect("timed out waiting for event")
+            .expect("event channel closed");
+        if pred(&event) {
+            return event;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_client_reconnects_after_server_bounce() {
+    use yrs::updates::decoder::Decode;
+    use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+    let port = free_port().await;
+    let server = spawn_bounceable_server(port);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    let mut client = SyncClient::new(PeerInfo::new("Alice"), doc_id, &url)
+        .with_reconnect(fast_reconnect(None));
+    let mut events = client.take_event_rx().unwrap();
+    client.connect().await.unwrap();
+    next_matching(&mut events, |e| matches!(e, SyncEvent::Connected)).await;
+
+    server.shutdown_background();
+    match next_matching(&mut events, |e| matches!(e, SyncEvent::Reconnecting { .. })).await {
+        SyncEvent::Reconnecting { attempt, .. } => assert_eq!(attempt, 1),
+        _ => unreachable!(),
+    }
+    assert_eq!(client.connection_state().await, ConnectionState::Reconnecting);
+
+    // Edits while reconnecting are queued...
+    let doc = yrs::Doc::new();
+    let update = {
+        let mut txn = doc.transact_mut();
+        txn.get_or_insert_text("content").insert(&mut txn, 0, "queued");
+        txn.encode_update_v1()
+    };
+    client.send_delta(update).await.unwrap();
+    assert_eq!(client.offline_queue_len().await, 1);
+
+    // ...and replayed once the server is back, after a fresh handshake
+    let server = spawn_bounceable_server(port);
+    next_matching(&mut events, |e| matches!(e, SyncEvent::Connected)).await;
+    next_matching(&mut events, |e| matches!(e, SyncEvent::StateSynced(_))).await;
+    assert_eq!(client.connection_state().await, ConnectionState::Connected);
+    // Dropped from the queue once the server's SyncStep1 confirms it
+    next_matching(&mut events, |e| matches!(e, SyncEvent::SyncRequested(_))).await;
+    assert_eq!(client.offline_queue_len().await, 0);
+
+    // A late joiner sees the replayed edit on the restarted server
+    let mut bob = SyncClient::new(PeerInfo::new("Bob"), doc_id, &url);
+    let mut bob_events = bob.take_event_rx().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    bob.connect().await.unwrap();
+    let SyncEvent::StateSynced(state) =
+        next_matching(&mut bob_events, |e| matches!(e, SyncEvent::StateSynced(_))).await
+    else {
+        unreachable!()
+    };
+    let replica = yrs::Doc::new();
+    replica.transact_mut().apply_update(yrs::Update::decode_v1(&state).unwrap()).unwrap();
+    let txn = replica.transact();
+    assert_eq!(txn.get_text("content").unwrap().get_string(&txn), "queued");
+
+    server.shutdown_background();
+}
+
+#[tokio::test]
+async fn test_client_gives_up_after_max_attempts() {
+    let port = free_port().await;
+    let server = spawn_bounceable_server(port);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut client = SyncClient::new(PeerInfo::new("Alice"), Uuid::new_v4(), format!("ws://127.0.0.1:{port}"))
+        .with_reconnect(fast_reconnect(Some(2)));
+    let mut events = client.take_event_rx().unwrap();
+    client.connect().await.unwrap();
+    next_matching(&mut events, |e| matches!(e, SyncEvent::Connected)).await;
+
+    server.shutdown_background();
+    let mut attempts = Vec::new();
+    while let SyncEvent::Reconnecting { attempt, delay } =
+        next_matching(&mut events, |e| matches!(e, SyncEvent::Reconnecting { .. } | SyncEvent::Disconnected)).await
+    {
+        assert!(delay <= Duration::from_millis(100));
+        attempts.push(attempt);
+    }
+    assert_eq!(attempts, vec![1, 2]);
+    assert_eq!(client.connection_state().await, ConnectionState::Disconnected);
+}
+
+#[tokio::test]
+async fn test_disconnect_stops_reconnecting() {
+    let port = start_test_server().await;
+    let mut client = SyncClient::new(PeerInfo::new("Alice"), Uuid::new_v4(), format!("ws://127.0.0.1:{port}"));
+    let mut events = client.take_event_rx().unwrap();
+    client.connect().await.unwrap();
+    next_matching(&mut events, |e| matches!(e, SyncEvent::Connected)).await;
+
+    client.disconnect().await;
+    next_matching(&mut events, |e| matches!(e, SyncEvent::Disconnected)).await;
+    assert!(timeout(Duration::from_millis(300), events.recv()).await.is_err(), "no reconnect after disconnect");
+    assert_eq!(client.connection_state().await, ConnectionState::Disconnected);
+}
+
+#[tokio::test]
+async fn test_persistent_offline_queue_survives_client_restart() {
+    use logos_collab::client::OfflineQueue;
+    use yrs::updates::decoder::Decode;
+    use yrs::{GetString, ReadTxn, Text, Transact, WriteTxn};
+
+    let dir = tempfile::tempdir().unwrap();
+    let queue_path = dir.path().join("offline.queue");
+    let port = start_test_server().await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Alice");
+
+    // Edit offline, then "crash" before ever connecting
+    {
+        let queue = OfflineQueue::open(&queue_path, doc_id, 1000).unwrap();
+        let client = SyncClient::new(info.clone(), doc_id, &url).with_offline_queue(queue);
+        let doc = yrs::Doc::new();
+        for part in ["saved ", "across ", "restarts"] {
+            let mut txn = doc.transact_mut();
+            let text = txn.get_or_insert_text("content");
+            let len = text.len(&txn);
+            text.insert(&mut txn, len, part);
+            let update = txn.encode_update_v1();
+            drop(txn);
+            client.send_delta(update).await.unwrap();
+        }
+        assert_eq!(client.offline_queue_len().await, 3);
+    }
+
+    // The next run picks the queue up and replays it on connect
+    let queue = OfflineQueue::open(&queue_path, doc_id, 1000).unwrap();
+    let mut client = SyncClient::new(info, doc_id, &url).with_offline_queue(queue);
+    let mut events = client.take_event_rx().unwrap();
+    assert_eq!(client.offline_queue_len().await, 3);
+    client.connect().await.unwrap();
+    // Kept on disk until the server has processed the replay
+    assert_eq!(OfflineQueue::open(&queue_path, doc_id, 1000).unwrap().len(), 3);
+    next_matching(&mut events, |e| matches!(e, SyncEvent::SyncRequested(_))).await;
+    assert_eq!(client.offline_queue_len().await, 0);
+    assert!(OfflineQueue::open(&queue_path, doc_id, 1000).unwrap().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut bob = SyncClient::new(PeerInfo::new("Bob"), doc_id, &url);
+    let mut bob_events = bob.take_event_rx().unwrap();
+    bob.connect().await.unwrap();
+    let SyncEvent::StateSynced(state) =
+        next_matching(&mut bob_events, |e| matches!(e, SyncEvent::StateSynced(_))).await
+    else {
+        unreachable!()
+    };
+    let replica = yrs::Doc::new();
+    replica.transact_mut().apply_update(yrs::Update::decode_v1(&state).unwrap()).unwrap();
+    let txn = replica.transact();
+    assert_eq!(txn.get_text("content").unwrap().get_string(&txn), "saved across restarts");
+}
+
+#[tokio::test]
+async fn test_join_refused_when_room_full() {
+    use logos_collab::protocol::Rejection;
+
+    let port = start_server_with(ServerConfig { max_peers_per_room: 2, ..ServerConfig::default() }).await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    let connect = |name: &str| {
+        let mut client = SyncClient::new(PeerInfo::new(name), doc_id, &url).with_reconnect(fast_reconnect(None));
+        let events = client.take_event_rx().unwrap();
+        (client, events)
+    };
+    let (mut alice, mut alice_events) = connect("Alice");
+    let (mut bob, mut bob_events) = connect("Bob");
+    alice.connect().await.unwrap();
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::Connected)).await;
+    bob.connect().await.unwrap();
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::PeerJoined(_))).await;
+    next_matching(&mut bob_events, |e| matches!(e, SyncEvent::Connected)).await;
+
+    let (mut carol, mut carol_events) = connect("Carol");
+    carol.connect().await.unwrap();
+    let rejected = next_matching(&mut carol_events, |e| matches!(e, SyncEvent::Rejected(_))).await;
+    assert!(matches!(rejected, SyncEvent::Rejected(Rejection::RoomFull { max_peers: 2 })));
+    next_matching(&mut carol_events, |e| matches!(e, SyncEvent::Disconnected)).await;
+    assert!(timeout(Duration::from_millis(300), carol_events.recv()).await.is_err(), "no retry into a full room");
+
+    // A seat frees up once someone leaves
+    bob.disconnect().await;
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::PeerLeft(_))).await;
+    let (mut dave, mut dave_events) = connect("Dave");
+    dave.connect().await.unwrap();
+    next_matching(&mut dave_events, |e| matches!(e, SyncEvent::Connected)).await;
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::PeerJoined(_))).await;
+}
+
+#[tokio::test]
+async fn test_second_join_leaves_previous_room() {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let port = free_port().await;
+    let server = Arc::new(SyncServer::new(ServerConfig {
+        bind_addr: format!("127.0.0.1:{port}"),
+        ..ServerConfig::default()
+    }));
+    let runner = server.clone();
+    tokio::spawn(async move {
+        runner.run().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let (first_doc, second_doc) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let mut alice = SyncClient::new(PeerInfo::new("Alice"), first_doc, &url);
+    let mut alice_events = alice.take_event_rx().unwrap();
+    alice.connect().await.unwrap();
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::Connected)).await;
+
+    // Bob joins Alice's document, then switches documents on the same socket
+    let bob = PeerInfo::new("Bob");
+    let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let hello = SyncMessage::peer_joined(bob.peer_id, first_doc, &bob);
+    ws.send(Message::Binary(hello.encode().unwrap().into())).await.unwrap();
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::PeerJoined(_))).await;
+    let hello = SyncMessage::peer_joined(bob.peer_id, second_doc, &bob);
+    ws.send(Message::Binary(hello.encode().unwrap().into())).await.unwrap();
+
+    let left = next_matching(&mut alice_events, |e| matches!(e, SyncEvent::PeerLeft(_))).await;
+    assert!(matches!(left, SyncEvent::PeerLeft(id) if id == bob.peer_id));
+    // The new room opens right after the old one is left
+    let rooms = timeout(Duration::from_secs(1), async {
+        loop {
+            let rooms = server.list_rooms().await;
+            if rooms.iter().any(|room| room.doc_id == second_doc) {
+                break rooms;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let peers_in = |doc_id: Uuid| -> Vec<Uuid> {
+        let room = rooms.iter().find(|room| room.doc_id == doc_id).unwrap();
+        room.peers.iter().map(|peer| peer.peer_id).collect()
+    };
+    assert_eq!(rooms.len(), 2);
+    assert_eq!(peers_in(first_doc), vec![alice.peer_info().peer_id]);
+    assert_eq!(peers_in(second_doc), vec![bob.peer_id]);
+    drop(ws);
+}
+
+#[tokio::test]
+async fn test_silent_peer_dropped_after_missed_heartbeats() {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let port = start_server_with(ServerConfig {
+        heartbeat_interval_secs: 1,
+        heartbeat_max_missed: 2,
+        ..ServerConfig::default()
+    })
+    .await;
+    let url = format!("ws://127.0.0.1:{port}");
+    let doc_id = Uuid::new_v4();
+
+    let mut alice = SyncClient::new(PeerInfo::new("Alice"), doc_id, &url);
+    let mut alice_events = alice.take_event_rx().unwrap();
+    alice.connect().await.unwrap();
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::Connected)).await;
+
+    // Bob joins, then never reads again, so never answers a ping
+    let bob = PeerInfo::new("Bob");
+    let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let hello = SyncMessage::peer_joined(bob.peer_id, doc_id, &bob);
+    ws.send(Message::Binary(hello.encode().unwrap().into())).await.unwrap();
+    next_matching(&mut alice_events, |e| matches!(e, SyncEvent::PeerJoined(_))).await;
+
+    let started = std::time::Instant::now();
+    let left = loop {
+        let event = timeout(Duration::from_secs(5), alice_events.recv()).await.unwrap().unwrap();
+        assert!(!matches!(event, SyncEvent::Disconnected), "a responsive peer is kept");
+        if let SyncEvent::PeerLeft(id) = event {
+            break id;
+        }
+    };
+    assert_eq!(left, bob.peer_id);
+    assert!(started.elapsed() >= Duration::from_secs(2), "dropped after two missed heartbeats, not before");
+    assert_eq!(alice.connection_state().await, ConnectionState::Connected);
+    drop(ws);
+}
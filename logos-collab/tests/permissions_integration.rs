@@ -0,0 +1,180 @@
+//! Integration tests for role-based write permissions.
+//!
+//! Runs a server, connects peers with different roles and checks which
+//! deltas reach the authoritative document and the other peers.
+
+mod common;
+
+use futures_util::{SinkExt, StreamExt};
+use logos_collab::client::SyncEvent;
+use logos_collab::permissions::{Role, COMMENTS_ROOT};
+use logos_collab::protocol::{MessageType, PeerInfo, Rejection, SyncMessage};
+use logos_collab::server::ServerConfig;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Map, ReadTxn, Transact, WriteTxn};
+
+use common::{connect, drain, start_server, QUIET};
+
+/// Encode a Yrs update that sets `key` in root map `root`.
+fn map_update(root: &str, key: &str, value: &str) -> Vec<u8> {
+    let doc = yrs::Doc::new();
+    let mut txn = doc.transact_mut();
+    txn.get_or_insert_map(root).insert(&mut txn, key, value);
+    txn.encode_update_v1()
+}
+
+/// Fetch the server's full state for `doc_id` over a raw socket.
+async fn server_state(url: &str, doc_id: Uuid) -> yrs::Doc {
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let info = PeerInfo::new("Inspector");
+    let join = SyncMessage::peer_joined(info.peer_id, doc_id, &info);
+    ws.send(Message::Binary(join.encode().unwrap().into())).await.unwrap();
+    // Ask for everything
+    let step1 = SyncMessage::sync_step1(info.peer_id, doc_id, yrs::StateVector::default().encode_v1());
+    ws.send(Message::Binary(step1.encode().unwrap().into())).await.unwrap();
+
+    loop {
+        let frame = timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        let msg = SyncMessage::decode(&frame.into_data()).unwrap();
+        if msg.msg_type == MessageType::SyncStep2 {
+            let doc = yrs::Doc::new();
+            doc.transact_mut().apply_update(yrs::Update::decode_v1(&msg.payload).unwrap()).unwrap();
+            return doc;
+        }
+    }
+}
+
+fn has_key(doc: &yrs::Doc, root: &str, key: &str) -> bool {
+    let txn = doc.transact();
+    txn.get_map(root).is_some_and(|m| m.contains_key(&txn, key))
+}
+
+fn forbidden(events: &[SyncEvent]) -> Option<Role> {
+    events.iter().find_map(|e| match e {
+        SyncEvent::Rejected(Rejection::Forbidden { role, msg_type: MessageType::Delta, .. }) => Some(*role),
+        _ => None,
+    })
+}
+
+fn received_delta(events: &[SyncEvent]) -> bool {
+    events.iter().any(|e| matches!(e, SyncEvent::RemoteDelta { .. }))
+}
+
+#[tokio::test]
+async fn test_viewer_is_read_only() {
+    let (server, url) = start_server(ServerConfig { default_role: Role::Editor, ..ServerConfig::default() }).await;
+    let doc_id = Uuid::new_v4();
+    let viewer_info = PeerInfo::new("Vera");
+    server.set_role(doc_id, viewer_info.peer_id, Role::Viewer);
+
+    let (editor, mut editor_events) = connect(&url, PeerInfo::new("Eddie"), doc_id).await;
+    let (viewer, mut viewer_events) = connect(&url, viewer_info, doc_id).await;
+    drain(&mut editor_events, QUIET).await;
+
+    // Viewer writes are refused and never reach the editor
+    viewer.send_delta(map_update("layers", "v", "{}")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut viewer_events, QUIET).await), Some(Role::Viewer));
+    assert!(!received_delta(&drain(&mut editor_events, QUIET).await));
+    assert!(!has_key(&server_state(&url, doc_id).await, "layers", "v"));
+    assert_eq!(server.stats().await.rejected_writes, 1);
+
+    // ...but the viewer still syncs the editor's changes
+    editor.send_delta(map_update("layers", "e", "{}")).await.unwrap();
+    assert!(received_delta(&drain(&mut viewer_events, QUIET).await));
+    assert!(has_key(&server_state(&url, doc_id).await, "layers", "e"));
+
+    // ...and awareness flows both ways
+    viewer.send_awareness(&Default::default()).await.unwrap();
+    let events = drain(&mut editor_events, QUIET).await;
+    assert!(events.iter().any(|e| matches!(e, SyncEvent::RemoteAwareness { .. })), "got {events:?}");
+}
+
+#[tokio::test]
+async fn test_commenter_limited_to_comments() {
+    let (server, url) = start_server(ServerConfig { default_role: Role::Editor, ..ServerConfig::default() }).await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Cora");
+    server.set_role(doc_id, info.peer_id, Role::Commenter);
+
+    let (_editor, mut editor_events) = connect(&url, PeerInfo::new("Eddie"), doc_id).await;
+    let (commenter, mut commenter_events) = connect(&url, info, doc_id).await;
+    drain(&mut editor_events, QUIET).await;
+
+    commenter.send_delta(map_update(COMMENTS_ROOT, "c1", "Looks good")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut commenter_events, QUIET).await), None);
+    assert!(received_delta(&drain(&mut editor_events, QUIET).await));
+
+    commenter.send_delta(map_update("layers", "x", "{}")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut commenter_events, QUIET).await), Some(Role::Commenter));
+    assert!(!received_delta(&drain(&mut editor_events, QUIET).await));
+
+    let state = server_state(&url, doc_id).await;
+    assert!(has_key(&state, COMMENTS_ROOT, "c1"));
+    assert!(!has_key(&state, "layers", "x"));
+}
+
+#[tokio::test]
+async fn test_role_change_applies_to_live_connection() {
+    let (server, url) = start_server(ServerConfig { default_role: Role::Viewer, ..ServerConfig::default() }).await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Pat");
+    let user_id = info.peer_id;
+    let (client, mut events) = connect(&url, info, doc_id).await;
+
+    client.send_delta(map_update("layers", "a", "{}")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut events, QUIET).await), Some(Role::Viewer));
+
+    // Promote without reconnecting
+    server.set_role(doc_id, user_id, Role::Editor);
+    client.send_delta(map_update("layers", "b", "{}")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut events, QUIET).await), None);
+
+    // Revoke: back to the default role
+    server.permissions().revoke(doc_id, user_id);
+    client.send_delta(map_update("layers", "c", "{}")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut events, QUIET).await), Some(Role::Viewer));
+
+    let state = server_state(&url, doc_id).await;
+    assert!(!has_key(&state, "layers", "a"));
+    assert!(has_key(&state, "layers", "b"));
+    assert!(!has_key(&state, "layers", "c"));
+    assert_eq!(server.stats().await.rejected_writes, 2);
+}
+
+#[tokio::test]
+async fn test_owner_can_edit() {
+    let (server, url) = start_server(ServerConfig { default_role: Role::Viewer, ..ServerConfig::default() }).await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Olga");
+    server.set_role(doc_id, info.peer_id, Role::Owner);
+    let (client, mut events) = connect(&url, info, doc_id).await;
+
+    client.send_delta(map_update("layers", "o", "{}")).await.unwrap();
+    assert_eq!(forbidden(&drain(&mut events, QUIET).await), None);
+    assert!(has_key(&server_state(&url, doc_id).await, "layers", "o"));
+}
+
+#[tokio::test]
+async fn test_viewer_sync_answer_is_checked() {
+    let (server, url) = start_server(ServerConfig { default_role: Role::Editor, ..ServerConfig::default() }).await;
+    let doc_id = Uuid::new_v4();
+    let info = PeerInfo::new("Vera");
+    server.set_role(doc_id, info.peer_id, Role::Viewer);
+    let (viewer, mut events) = connect(&url, info, doc_id).await;
+
+    // Offline edits offered through the handshake get the same check as deltas
+    viewer.send_sync_step2(map_update("layers", "v", "{}")).await.unwrap();
+    let refused = drain(&mut events, QUIET).await.iter().any(|e| {
+        matches!(
+            e,
+            SyncEvent::Rejected(Rejection::Forbidden { role: Role::Viewer, msg_type: MessageType::SyncStep2, .. })
+        )
+    });
+    assert!(refused);
+    assert!(!has_key(&server_state(&url, doc_id).await, "layers", "v"));
+    assert_eq!(server.stats().await.rejected_writes, 1);
+}
@@ -2,22 +2,38 @@
 //!
 //! Verifies:
 //! - Document save/load roundtrip through the full server stack
-//! - Crash recovery: kill server, restart, data survives
+//! - Crash recovery: kill server, restart, data survives (snapshot, deltas, WAL)
 //! - Delta compression meets 10:1 ratio target
 //! - WAL append latency within budget
 //! - Multi-document isolation under persistence
 //! - Snapshot compaction correctness
+//! - Version history browsing and restore over the protocol
+//! - Named checkpoints, forking and merging branches
+//!
+//! Every test touching a store runs once per backend (RocksDB, SQLite,
+//! memory); see `for_each_backend!` at the end.
+
+mod common;
 
 use logos_collab::storage::{
-    DocumentStore, StoreConfig, DeltaLog, CompressedDelta,
+    BackendKind, DocumentStore, MemoryBackend, StoreConfig, DeltaLog, CompressedDelta,
     WriteAheadLog, WalConfig,
 };
-use logos_collab::server::SyncServer;
-
+use logos_collab::history::{HistoryMessage, VersionRef};
+use logos_collab::permissions::Role;
+use logos_collab::protocol::{MessageType, PeerInfo, Rejection, SyncMessage};
+use logos_collab::server::{ServerConfig, SyncServer};
+
+use futures_util::{SinkExt, StreamExt};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tempfile::tempdir;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tempfile::{tempdir, TempDir};
 use uuid::Uuid;
-use yrs::{Doc, Text, Transact, ReadTxn, WriteTxn, GetString};
+use yrs::{Doc, Map, Text, Transact, ReadTxn, WriteTxn, GetString};
 use yrs::updates::decoder::Decode;
 use yrs::updates::encoder::Encode;
 
@@ -59,6 +75,109 @@ fn make_delta(doc: &Doc, insert_text: &str) -> Vec<u8> {
     state
 }
 
+/// Storage for one test on one backend. Reopening it (with `open` or a
+/// new server) sees everything written before, including for the memory
+/// backend, whose clones share data.
+struct Storage {
+    dir: TempDir,
+    backend: BackendKind,
+}
+
+impl Storage {
+    fn new(backend: BackendKind) -> Self {
+        Self { dir: tempdir().unwrap(), backend }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.path().join("db")
+    }
+
+    fn open(&self) -> DocumentStore {
+        DocumentStore::open(StoreConfig {
+            backend: self.backend.clone(),
+            ..StoreConfig::for_testing(self.path())
+        })
+        .unwrap()
+    }
+
+    fn server_config(&self, config: ServerConfig) -> ServerConfig {
+        ServerConfig {
+            storage_path: Some(self.path()),
+            storage_backend: self.backend.clone(),
+            ..config
+        }
+    }
+
+    /// A persistent server that is not running.
+    fn server(&self) -> SyncServer {
+        SyncServer::new(self.server_config(ServerConfig {
+            bind_addr: "127.0.0.1:0".into(),
+            ..ServerConfig::default()
+        }))
+    }
+}
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Start a persistent server on a free port, return it with its URL.
+async fn start_server(storage: &Storage) -> (Arc<SyncServer>, String) {
+    start_server_with(storage, ServerConfig::default()).await
+}
+
+async fn start_server_with(storage: &Storage, config: ServerConfig) -> (Arc<SyncServer>, String) {
+    common::start_server(storage.server_config(config)).await
+}
+
+/// Join `doc_id` over a raw socket and run the handshake up to the
+/// server's SyncStep1, so the join has been processed on return.
+async fn join(url: &str, peer: &PeerInfo, doc_id: Uuid) -> Socket {
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let hello = SyncMessage::peer_joined(peer.peer_id, doc_id, peer);
+    ws.send(Message::Binary(hello.encode().unwrap().into())).await.unwrap();
+    let step1 = SyncMessage::sync_step1(peer.peer_id, doc_id, yrs::StateVector::default().encode_v1());
+    ws.send(Message::Binary(step1.encode().unwrap().into())).await.unwrap();
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        if SyncMessage::decode(&frame.into_data()).unwrap().msg_type == MessageType::SyncStep1 {
+            return ws;
+        }
+    }
+}
+
+async fn send_delta(ws: &mut Socket, peer: &PeerInfo, doc_id: Uuid, clock: u64, update: Vec<u8>) {
+    let msg = SyncMessage::delta(peer.peer_id, doc_id, clock, update);
+    ws.send(Message::Binary(msg.encode().unwrap().into())).await.unwrap();
+}
+
+/// Fetch the server's full text content for `doc_id`.
+async fn server_text(url: &str, doc_id: Uuid) -> String {
+    let peer = PeerInfo::new("Inspector");
+    let mut ws = join(url, &peer, doc_id).await;
+    let step1 = SyncMessage::sync_step1(peer.peer_id, doc_id, yrs::StateVector::default().encode_v1());
+    ws.send(Message::Binary(step1.encode().unwrap().into())).await.unwrap();
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        let msg = SyncMessage::decode(&frame.into_data()).unwrap();
+        if msg.msg_type == MessageType::SyncStep2 {
+            let doc = Doc::new();
+            doc.transact_mut().apply_update(yrs::Update::decode_v1(&msg.payload).unwrap()).unwrap();
+            let txn = doc.transact();
+            return txn.get_text("content").map(|t| t.get_string(&txn)).unwrap_or_default();
+        }
+    }
+}
+
+/// Poll until `done` holds for the server's stats.
+async fn wait_for(server: &SyncServer, done: impl Fn(&logos_collab::ServerStats) -> bool) {
+    for _ in 0..250 {
+        if done(&server.stats().await) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Timed out waiting for persistence: {:?}", server.stats().await);
+}
+
 /// Generate repetitive text of given approximate byte count for compression testing.
 fn repetitive_text(approx_bytes: usize) -> String {
     let pattern = "The quick brown fox jumps over the lazy dog. ";
@@ -67,14 +186,8 @@ fn repetitive_text(approx_bytes: usize) -> String {
 
 // ─── Document Save/Load Roundtrip ────────────────────────────────────────────
 
-#[test]
-fn test_document_roundtrip_via_store() {
-    let dir = tempdir().unwrap();
-    let config = StoreConfig {
-        path: dir.path().join("db"),
-        ..StoreConfig::default()
-    };
-    let store = DocumentStore::open(config).unwrap();
+fn test_document_roundtrip_via_store(storage: Storage) {
+    let store = storage.open();
 
     let doc_id = Uuid::new_v4();
     let (_doc, state) = make_doc_with_text("Hello, persistence world!");
@@ -97,14 +210,8 @@ fn test_document_roundtrip_via_store() {
     }
 }
 
-#[test]
-fn test_document_roundtrip_with_deltas() {
-    let dir = tempdir().unwrap();
-    let config = StoreConfig {
-        path: dir.path().join("db"),
-        ..StoreConfig::default()
-    };
-    let store = DocumentStore::open(config).unwrap();
+fn test_document_roundtrip_with_deltas(storage: Storage) {
+    let store = storage.open();
     let doc_id = Uuid::new_v4();
 
     // Create initial doc and save snapshot
@@ -145,19 +252,12 @@ fn test_document_roundtrip_with_deltas() {
 
 // ─── Crash Recovery ──────────────────────────────────────────────────────────
 
-#[tokio::test]
-async fn test_crash_recovery_snapshot_survives_restart() {
-    let dir = tempdir().unwrap();
-    let db_path = dir.path().join("db");
+async fn test_crash_recovery_snapshot_survives_restart(storage: Storage) {
     let doc_id = Uuid::new_v4();
 
     // Phase 1: Write data directly to store then drop (simulates crash)
     {
-        let store_config = StoreConfig {
-            path: db_path.clone(),
-            ..StoreConfig::default()
-        };
-        let store = DocumentStore::open(store_config).unwrap();
+        let store = storage.open();
 
         let (_, state) = make_doc_with_text("Data that must survive a crash");
         store.save_snapshot(doc_id, &state).unwrap();
@@ -167,7 +267,7 @@ async fn test_crash_recovery_snapshot_survives_restart() {
 
     // Phase 2: New server starts, recovers data
     {
-        let server = SyncServer::with_storage("127.0.0.1:0", &db_path);
+        let server = storage.server();
         let recovered = server.recover().await.unwrap();
         assert_eq!(recovered, 1, "Should recover exactly 1 document");
 
@@ -187,19 +287,12 @@ async fn test_crash_recovery_snapshot_survives_restart() {
     }
 }
 
-#[tokio::test]
-async fn test_crash_recovery_deltas_survive() {
-    let dir = tempdir().unwrap();
-    let db_path = dir.path().join("db");
+async fn test_crash_recovery_deltas_survive(storage: Storage) {
     let doc_id = Uuid::new_v4();
 
     // Phase 1: Write snapshot + deltas, then crash
     {
-        let store_config = StoreConfig {
-            path: db_path.clone(),
-            ..StoreConfig::default()
-        };
-        let store = DocumentStore::open(store_config).unwrap();
+        let store = storage.open();
 
         let (doc, state) = make_doc_with_text("Base");
         store.save_snapshot(doc_id, &state).unwrap();
@@ -213,11 +306,7 @@ async fn test_crash_recovery_deltas_survive() {
 
     // Phase 2: Recover
     {
-        let store_config = StoreConfig {
-            path: db_path.clone(),
-            ..StoreConfig::default()
-        };
-        let store = DocumentStore::open(store_config).unwrap();
+        let store = storage.open();
 
         assert!(store.document_exists(doc_id).unwrap());
         let snapshot = store.load_snapshot(doc_id).unwrap();
@@ -228,19 +317,12 @@ async fn test_crash_recovery_deltas_survive() {
     }
 }
 
-#[tokio::test]
-async fn test_crash_recovery_multiple_documents() {
-    let dir = tempdir().unwrap();
-    let db_path = dir.path().join("db");
+async fn test_crash_recovery_multiple_documents(storage: Storage) {
     let doc_ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
 
     // Phase 1: Write multiple docs
     {
-        let store_config = StoreConfig {
-            path: db_path.clone(),
-            ..StoreConfig::default()
-        };
-        let store = DocumentStore::open(store_config).unwrap();
+        let store = storage.open();
 
         for (i, doc_id) in doc_ids.iter().enumerate() {
             let (_, state) = make_doc_with_text(&format!("Document {i} content"));
@@ -250,12 +332,154 @@ async fn test_crash_recovery_multiple_documents() {
 
     // Phase 2: Recover all
     {
-        let server = SyncServer::with_storage("127.0.0.1:0", &db_path);
+        let server = storage.server();
         let recovered = server.recover().await.unwrap();
         assert_eq!(recovered, 5, "All 5 documents should be recovered");
     }
 }
 
+/// Kill the server mid-session (no room close, so no final snapshot) and
+/// check that the restarted server has every edit: the snapshot from an
+/// earlier session plus the deltas written after it.
+fn test_crash_mid_session_replays_deltas_after_snapshot(storage: Storage) {
+    let doc_id = Uuid::new_v4();
+    let author = PeerInfo::new("Author");
+    let (doc, initial) = make_doc_with_text("Hello");
+
+    // Phase 1: one closed session (snapshot), then a second that crashes
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let (server, url) = start_server(&storage).await;
+
+        let mut ws = join(&url, &author, doc_id).await;
+        send_delta(&mut ws, &author, doc_id, 1, initial).await;
+        wait_for(&server, |s| s.persisted_deltas == 1).await;
+        ws.close(None).await.unwrap();
+        wait_for(&server, |s| s.persisted_snapshots == 1).await;
+        wait_for(&server, |s| s.wal_checkpoints == 1).await;
+        assert!(server.store().unwrap().wal_read_since(0).unwrap().is_empty(), "snapshot truncates the WAL");
+
+        let mut ws = join(&url, &author, doc_id).await;
+        send_delta(&mut ws, &author, doc_id, 2, make_delta(&doc, ", world")).await;
+        send_delta(&mut ws, &author, doc_id, 3, make_delta(&doc, "!")).await;
+        wait_for(&server, |s| s.persisted_deltas == 3).await;
+        assert_eq!(server.stats().await.persisted_snapshots, 1);
+        // Keep the socket open so the crash, not a disconnect, ends the session
+        std::mem::forget(ws);
+    });
+    // Dropping the runtime cancels every task mid-flight: the open session
+    // never gets to write its closing snapshot
+    drop(runtime);
+
+    let store = storage.open();
+    assert_eq!(store.load_all_deltas(doc_id).unwrap().len(), 2, "only post-snapshot deltas remain");
+    assert_eq!(store.wal_read_since(0).unwrap().len(), 2, "post-snapshot edits are still in the WAL");
+    drop(store);
+
+    // Phase 2: restart on the same storage
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let (server, url) = start_server(&storage).await;
+        assert_eq!(server_text(&url, doc_id).await, "Hello, world!");
+        assert_eq!(server.stats().await.wal_replayed, 0, "WAL entries already in the delta log");
+        assert!(server.store().unwrap().wal_read_since(0).unwrap().is_empty());
+    });
+}
+
+/// A kill -9 after the hot path's WAL append but before the background
+/// writer stored the delta: the restarted server restores it from the WAL.
+async fn test_crash_before_delta_log_replays_wal(storage: Storage) {
+    let doc_id = Uuid::new_v4();
+    let (doc, initial) = make_doc_with_text("Logged");
+
+    {
+        let store = storage.open();
+        store.wal_append(doc_id, &initial).unwrap();
+        store.wal_append(doc_id, &make_delta(&doc, " only")).unwrap();
+        store.wal_append(doc_id, &make_delta(&doc, " in the WAL")).unwrap();
+        assert!(store.load_all_deltas(doc_id).unwrap().is_empty());
+    }
+
+    let (server, url) = start_server(&storage).await;
+    assert_eq!(server_text(&url, doc_id).await, "Logged only in the WAL");
+
+    let store = server.store().unwrap();
+    assert_eq!(server.stats().await.wal_replayed, 3);
+    assert_eq!(store.load_all_deltas(doc_id).unwrap().len(), 3);
+    assert!(store.wal_read_since(0).unwrap().is_empty(), "replayed entries are truncated");
+
+    // New deltas continue the sequence instead of reusing restored versions
+    // (those were compacted into the snapshot written when the inspector left)
+    let author = PeerInfo::new("Author");
+    let mut ws = join(&url, &author, doc_id).await;
+    send_delta(&mut ws, &author, doc_id, 1, make_delta(&doc, "!")).await;
+    wait_for(&server, |s| s.persisted_deltas == 1).await;
+    let versions: Vec<u64> = store.load_all_deltas(doc_id).unwrap().iter().map(|(v, _)| *v).collect();
+    assert_eq!(versions, vec![3]);
+}
+
+// ─── Snapshot Scheduling ─────────────────────────────────────────────────────
+
+async fn test_scheduler_snapshots_open_room_every_n_deltas(storage: Storage) {
+    let config = ServerConfig {
+        snapshot_every_deltas: Some(5),
+        snapshot_interval_secs: None,
+        ..ServerConfig::default()
+    };
+    let (server, url) = start_server_with(&storage, config).await;
+    let doc_id = Uuid::new_v4();
+    let author = PeerInfo::new("Author");
+    let (doc, initial) = make_doc_with_text("Library");
+
+    // The author never leaves, so only the scheduler can snapshot
+    let mut ws = join(&url, &author, doc_id).await;
+    send_delta(&mut ws, &author, doc_id, 0, initial).await;
+    for i in 1..12 {
+        send_delta(&mut ws, &author, doc_id, i, make_delta(&doc, &format!(" {i}"))).await;
+        if i % 5 == 4 {
+            let taken = (i + 1) / 5;
+            wait_for(&server, |s| s.scheduled_snapshots == taken).await;
+        }
+    }
+    wait_for(&server, |s| s.persisted_deltas == 12 && s.compacted_deltas == 10).await;
+
+    let stats = server.stats().await;
+    assert_eq!(stats.scheduled_snapshots, 2);
+    assert_eq!(stats.persisted_snapshots, 2);
+    assert_eq!(stats.active_rooms, 1, "room stays open");
+
+    let store = server.store().unwrap();
+    assert_eq!(store.load_all_deltas(doc_id).unwrap().len(), 2, "deltas after the last snapshot");
+    let snapshot = Doc::new();
+    snapshot
+        .transact_mut()
+        .apply_update(yrs::Update::decode_v1(&store.load_snapshot(doc_id).unwrap()).unwrap())
+        .unwrap();
+    let txn = snapshot.transact();
+    assert_eq!(txn.get_text("content").unwrap().get_string(&txn), "Library 1 2 3 4 5 6 7 8 9");
+}
+
+async fn test_scheduler_snapshots_after_interval(storage: Storage) {
+    let config = ServerConfig {
+        snapshot_every_deltas: None,
+        snapshot_interval_secs: Some(1),
+        ..ServerConfig::default()
+    };
+    let (server, url) = start_server_with(&storage, config).await;
+    let doc_id = Uuid::new_v4();
+    let author = PeerInfo::new("Author");
+    let (_doc, initial) = make_doc_with_text("Always open");
+
+    let mut ws = join(&url, &author, doc_id).await;
+    send_delta(&mut ws, &author, doc_id, 0, initial).await;
+    wait_for(&server, |s| s.scheduled_snapshots == 1 && s.compacted_deltas == 1).await;
+    assert!(server.store().unwrap().load_all_deltas(doc_id).unwrap().is_empty());
+
+    // Nothing new: no further snapshots
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(server.stats().await.scheduled_snapshots, 1);
+}
+
 // ─── Delta Compression ──────────────────────────────────────────────────────
 
 #[test]
@@ -306,14 +530,8 @@ fn test_10000_deltas_compressed_under_1mb() {
     assert_eq!(stats.delta_count, 10_000);
 }
 
-#[test]
-fn test_delta_compaction_reduces_storage() {
-    let dir = tempdir().unwrap();
-    let config = StoreConfig {
-        path: dir.path().join("db"),
-        ..StoreConfig::default()
-    };
-    let store = DocumentStore::open(config).unwrap();
+fn test_delta_compaction_reduces_storage(storage: Storage) {
+    let store = storage.open();
     let doc_id = Uuid::new_v4();
 
     // Store 100 deltas
@@ -398,14 +616,8 @@ fn test_wal_recovery_with_checksum_verification() {
 
 // ─── Multi-Document Isolation ────────────────────────────────────────────────
 
-#[test]
-fn test_multi_document_isolation() {
-    let dir = tempdir().unwrap();
-    let config = StoreConfig {
-        path: dir.path().join("db"),
-        ..StoreConfig::default()
-    };
-    let store = DocumentStore::open(config).unwrap();
+fn test_multi_document_isolation(storage: Storage) {
+    let store = storage.open();
 
     let doc_a = Uuid::new_v4();
     let doc_b = Uuid::new_v4();
@@ -446,12 +658,180 @@ fn test_multi_document_isolation() {
     assert_eq!(store.load_all_deltas(doc_b).unwrap().len(), 10);
 }
 
+// ─── Version History ─────────────────────────────────────────────────────────
+
+/// Read frames until one of type `msg_type` arrives.
+async fn next_of(ws: &mut Socket, msg_type: MessageType) -> SyncMessage {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        let msg = SyncMessage::decode(&frame.into_data()).unwrap();
+        if msg.msg_type == msg_type {
+            return msg;
+        }
+    }
+}
+
+async fn ask_history(ws: &mut Socket, peer: &PeerInfo, doc_id: Uuid, request: HistoryMessage) -> HistoryMessage {
+    let msg = SyncMessage::history(peer.peer_id, doc_id, &request);
+    ws.send(Message::Binary(msg.encode().unwrap().into())).await.unwrap();
+    next_of(ws, MessageType::History).await.history_message().unwrap()
+}
+
+fn state_text(state: &[u8]) -> String {
+    let doc = Doc::new();
+    doc.transact_mut().apply_update(yrs::Update::decode_v1(state).unwrap()).unwrap();
+    let txn = doc.transact();
+    txn.get_text("content").map(|t| t.get_string(&txn)).unwrap_or_default()
+}
+
+async fn test_history_browse_and_restore_over_protocol(storage: Storage) {
+    let (server, url) = start_server(&storage).await;
+    let doc_id = Uuid::new_v4();
+
+    let alice = PeerInfo::new("Alice");
+    let bob = PeerInfo::new("Bob");
+    let mut alice_ws = join(&url, &alice, doc_id).await;
+    let mut bob_ws = join(&url, &bob, doc_id).await;
+
+    let doc = Doc::new();
+    for (clock, text) in [(1, "Hello"), (2, ", world"), (3, "!")] {
+        send_delta(&mut alice_ws, &alice, doc_id, clock, make_delta(&doc, text)).await;
+        next_of(&mut bob_ws, MessageType::Delta).await;
+    }
+
+    // Every delta is listed with its author, even before it is persisted
+    let HistoryMessage::Versions(versions) = ask_history(&mut bob_ws, &bob, doc_id, HistoryMessage::ListVersions).await
+    else {
+        panic!("expected a version list");
+    };
+    assert_eq!(versions.len(), 3);
+    assert!(versions.iter().all(|v| v.author == Some(alice.peer_id)));
+
+    let at = VersionRef::Version(versions[1].version);
+    match ask_history(&mut bob_ws, &bob, doc_id, HistoryMessage::GetSnapshot(at)).await {
+        HistoryMessage::Snapshot { version, state } => {
+            assert_eq!(version, versions[1].version);
+            assert_eq!(state_text(&state), "Hello, world");
+        }
+        other => panic!("expected a snapshot, got {other:?}"),
+    }
+
+    // Bob restores the first version; both peers receive the change. The
+    // pause keeps the restore out of the last edit's millisecond, which
+    // the restore by time below relies on.
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    let first = VersionRef::Version(versions[0].version);
+    assert_eq!(
+        ask_history(&mut bob_ws, &bob, doc_id, HistoryMessage::Restore(first)).await,
+        HistoryMessage::Restored { version: versions[0].version }
+    );
+    let restore = next_of(&mut alice_ws, MessageType::Delta).await;
+    assert_eq!(restore.peer_id, Uuid::nil());
+    doc.transact_mut().apply_update(yrs::Update::decode_v1(&restore.payload).unwrap()).unwrap();
+    assert_eq!(doc.transact().get_text("content").unwrap().get_string(&doc.transact()), "Hello");
+    assert_eq!(server_text(&url, doc_id).await, "Hello");
+
+    // The restore is a new version; the ones it undid are still there
+    let after = server.list_versions(doc_id).await.unwrap();
+    assert_eq!(after.len(), 4);
+    assert_eq!(&after[..3], &versions[..]);
+    assert_eq!(after[3].author, Some(bob.peer_id));
+    let (_, state) = server.version_snapshot(doc_id, VersionRef::Version(versions[2].version)).await.unwrap();
+    assert_eq!(state_text(&state), "Hello, world!");
+
+    // Restoring by time through the server API
+    let restored = server.restore_version(doc_id, VersionRef::At(after[2].timestamp_ms), None).await.unwrap();
+    assert!(restored >= versions[2].version);
+    assert_eq!(server_text(&url, doc_id).await, "Hello, world!");
+
+    // Viewers may browse but not restore
+    let carol = PeerInfo::new("Carol");
+    server.set_role(doc_id, carol.peer_id, Role::Viewer);
+    let mut carol_ws = join(&url, &carol, doc_id).await;
+    assert!(matches!(
+        ask_history(&mut carol_ws, &carol, doc_id, HistoryMessage::ListVersions).await,
+        HistoryMessage::Versions(v) if v.len() == 5
+    ));
+    let msg = SyncMessage::history(carol.peer_id, doc_id, &HistoryMessage::Restore(first));
+    carol_ws.send(Message::Binary(msg.encode().unwrap().into())).await.unwrap();
+    let rejection = next_of(&mut carol_ws, MessageType::Rejected).await.rejection().unwrap();
+    assert!(matches!(rejection, Rejection::Forbidden { msg_type: MessageType::History, .. }));
+}
+
+/// Set `layers[key] = value` on `doc`, returning the update.
+fn set_layer(doc: &Doc, key: &str, value: &str) -> Vec<u8> {
+    let sv = doc.transact().state_vector();
+    {
+        let mut txn = doc.transact_mut();
+        let layers = txn.get_or_insert_map("layers");
+        layers.insert(&mut txn, key, value);
+    }
+    doc.transact().encode_state_as_update_v1(&sv)
+}
+
+async fn test_checkpoint_fork_and_merge_over_protocol(storage: Storage) {
+    let (server, url) = start_server(&storage).await;
+    let main_id = Uuid::new_v4();
+    let alice = PeerInfo::new("Alice");
+    let mut main_ws = join(&url, &alice, main_id).await;
+
+    let main = Doc::new();
+    send_delta(&mut main_ws, &alice, main_id, 1, set_layer(&main, "a", "{a}")).await;
+    send_delta(&mut main_ws, &alice, main_id, 2, set_layer(&main, "b", "{b}")).await;
+    let request = HistoryMessage::CreateCheckpoint("v2 handoff".into());
+    let HistoryMessage::CheckpointCreated(checkpoint) = ask_history(&mut main_ws, &alice, main_id, request).await else {
+        panic!("expected a checkpoint");
+    };
+    assert_eq!(checkpoint.author, Some(alice.peer_id));
+    let request = HistoryMessage::CreateCheckpoint("v2 handoff".into());
+    assert!(matches!(
+        ask_history(&mut main_ws, &alice, main_id, request).await,
+        HistoryMessage::Failed(_)
+    ));
+
+    let fork = HistoryMessage::Fork("v2 handoff".into());
+    let HistoryMessage::Forked { doc_id: branch_id, origin } = ask_history(&mut main_ws, &alice, main_id, fork).await
+    else {
+        panic!("expected a fork");
+    };
+    assert_eq!((origin.doc_id, origin.version), (main_id, checkpoint.version));
+    assert_eq!(server.permissions().role(branch_id, alice.peer_id), Role::Owner);
+
+    // Edit the branch from a second connection while main moves on
+    let mut branch_ws = join(&url, &alice, branch_id).await;
+    let branch = Doc::new();
+    let (_, state) = server.version_snapshot(branch_id, VersionRef::At(u64::MAX)).await.unwrap();
+    branch.transact_mut().apply_update(yrs::Update::decode_v1(&state).unwrap()).unwrap();
+    send_delta(&mut branch_ws, &alice, branch_id, 1, set_layer(&branch, "a", "{a: branch}")).await;
+    send_delta(&mut branch_ws, &alice, branch_id, 2, set_layer(&branch, "c", "{c}")).await;
+    send_delta(&mut main_ws, &alice, main_id, 3, set_layer(&main, "a", "{a: main}")).await;
+
+    let merge = HistoryMessage::MergeBranch(branch_id);
+    let HistoryMessage::Merged(report) = ask_history(&mut main_ws, &alice, main_id, merge).await else {
+        panic!("expected a merge report");
+    };
+    assert_eq!(report.branch_layers, vec!["a", "c"]);
+    assert_eq!(report.target_layers, vec!["a"]);
+    assert_eq!(report.conflicts, vec!["a"]);
+
+    let merged = next_of(&mut main_ws, MessageType::Delta).await;
+    main.transact_mut().apply_update(yrs::Update::decode_v1(&merged.payload).unwrap()).unwrap();
+    let txn = main.transact();
+    assert_eq!(txn.get_map("layers").unwrap().len(&txn), 3);
+    drop(txn);
+
+    // A branch merges only into the document it came from
+    let merge = HistoryMessage::MergeBranch(branch_id);
+    assert!(matches!(
+        ask_history(&mut branch_ws, &alice, branch_id, merge).await,
+        HistoryMessage::Failed(_)
+    ));
+}
+
 // ─── Server Integration ─────────────────────────────────────────────────────
 
-#[tokio::test]
-async fn test_server_persistence_config() {
-    let dir = tempdir().unwrap();
-    let server = SyncServer::with_storage("127.0.0.1:0", dir.path().join("db"));
+async fn test_server_persistence_config(storage: Storage) {
+    let server = storage.server();
     assert!(server.store().is_some());
 
     let stats = server.stats().await;
@@ -465,25 +845,18 @@ async fn test_server_in_memory_mode_no_store() {
     assert!(server.store().is_none());
 }
 
-#[tokio::test]
-async fn test_server_recovery_preserves_content() {
-    let dir = tempdir().unwrap();
-    let db_path = dir.path().join("db");
+async fn test_server_recovery_preserves_content(storage: Storage) {
     let doc_id = Uuid::new_v4();
 
     // Pre-populate store
     {
-        let store_config = StoreConfig {
-            path: db_path.clone(),
-            ..StoreConfig::default()
-        };
-        let store = DocumentStore::open(store_config).unwrap();
+        let store = storage.open();
         let (_, state) = make_doc_with_text("Recoverable design file content");
         store.save_snapshot(doc_id, &state).unwrap();
     }
 
     // Server recovery
-    let server = SyncServer::with_storage("127.0.0.1:0", &db_path);
+    let server = storage.server();
     let recovered = server.recover().await.unwrap();
     assert_eq!(recovered, 1);
 
@@ -495,14 +868,8 @@ async fn test_server_recovery_preserves_content() {
 
 // ─── Snapshot Versioning ─────────────────────────────────────────────────────
 
-#[test]
-fn test_snapshot_overwrite_preserves_latest() {
-    let dir = tempdir().unwrap();
-    let config = StoreConfig {
-        path: dir.path().join("db"),
-        ..StoreConfig::default()
-    };
-    let store = DocumentStore::open(config).unwrap();
+fn test_snapshot_overwrite_preserves_latest(storage: Storage) {
+    let store = storage.open();
     let doc_id = Uuid::new_v4();
 
     // Save v1
@@ -528,14 +895,8 @@ fn test_snapshot_overwrite_preserves_latest() {
 
 // ─── Large Document Stress ───────────────────────────────────────────────────
 
-#[test]
-fn test_large_document_persistence() {
-    let dir = tempdir().unwrap();
-    let config = StoreConfig {
-        path: dir.path().join("db"),
-        ..StoreConfig::default()
-    };
-    let store = DocumentStore::open(config).unwrap();
+fn test_large_document_persistence(storage: Storage) {
+    let store = storage.open();
     let doc_id = Uuid::new_v4();
 
     // Create a large document (~500KB of text)
@@ -593,3 +954,56 @@ fn test_delta_log_full_lifecycle() {
     let after_stats = log.stats();
     assert_eq!(after_stats.delta_count, 0, "Deltas cleared after compaction");
 }
+
+// ─── Backends ────────────────────────────────────────────────────────────────
+
+/// Instantiate each store-backed test above once per storage backend, as
+/// `rocksdb::test_*`, `sqlite::test_*` and `memory::test_*`.
+macro_rules! for_each_backend {
+    (sync { $($sync:ident),* $(,)? } async { $($async:ident),* $(,)? }) => {
+        for_each_backend!(@module rocksdb, BackendKind::RocksDb, [$($sync),*], [$($async),*]);
+        for_each_backend!(@module sqlite, BackendKind::Sqlite, [$($sync),*], [$($async),*]);
+        for_each_backend!(@module memory, BackendKind::Memory(MemoryBackend::new()), [$($sync),*], [$($async),*]);
+    };
+    (@module $module:ident, $backend:expr, [$($sync:ident),*], [$($async:ident),*]) => {
+        mod $module {
+            use super::*;
+            $(
+                #[test]
+                fn $sync() {
+                    super::$sync(Storage::new($backend))
+                }
+            )*
+            $(
+                #[tokio::test]
+                async fn $async() {
+                    super::$async(Storage::new($backend)).await
+                }
+            )*
+        }
+    };
+}
+
+for_each_backend! {
+    sync {
+        test_document_roundtrip_via_store,
+        test_document_roundtrip_with_deltas,
+        test_crash_mid_session_replays_deltas_after_snapshot,
+        test_delta_compaction_reduces_storage,
+        test_multi_document_isolation,
+        test_snapshot_overwrite_preserves_latest,
+        test_large_document_persistence,
+    }
+    async {
+        test_crash_recovery_snapshot_survives_restart,
+        test_crash_recovery_deltas_survive,
+        test_crash_recovery_multiple_documents,
+        test_crash_before_delta_log_replays_wal,
+        test_scheduler_snapshots_open_room_every_n_deltas,
+        test_scheduler_snapshots_after_interval,
+        test_history_browse_and_restore_over_protocol,
+        test_checkpoint_fork_and_merge_over_protocol,
+        test_server_persistence_config,
+        test_server_recovery_preserves_content,
+    }
+}
@@ -4,6 +4,8 @@
 //! verifying cursor position broadcast, selection sync, and
 //! AwarenessMessage encode/decode through the full network stack.
 
+mod common;
+
 use logos_collab::presence::{
     AwarenessMessage, CursorColor, CursorRenderData, PresenceRoom, Vec2,
     build_cursor_instances,
@@ -14,11 +16,7 @@ use logos_collab::client::{SyncClient, SyncEvent};
 use uuid::Uuid;
 use tokio::time::{timeout, Duration};
 
-/// Find a free port for testing.
-async fn free_port() -> u16 {
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
-    listener.local_addr().unwrap().port()
-}
+use common::free_port;
 
 /// Start a server on a free port, return the port.
 async fn start_test_server() -> u16 {
@@ -29,6 +27,7 @@ async fn start_test_server() -> u16 {
         broadcast_capacity: 64,
         heartbeat_interval_secs: 30,
         storage_path: None,
+        ..ServerConfig::default()
     };
     let server = SyncServer::new(config);
     tokio::spawn(async move {
@@ -0,0 +1,367 @@
+//! Integration tests for protocol version negotiation.
+//!
+//! Old-format clients are raw sockets speaking the wire format from before
+//! the hello handshake, decoded with a frozen copy of its types. They run
+//! against the same server as current clients, in every pairing.
+//! Messages between current clients may be compressed; old ones must
+//! never see that.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use logos_collab::client::{SyncClient, SyncEvent};
+use logos_collab::protocol::{Capabilities, Hello, PeerInfo, PROTOCOL_VERSION};
+use logos_collab::server::ServerConfig;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+use yrs::updates::encoder::Encode;
+
+use common::{start_server, text_update};
+
+/// The wire format before the hello handshake, as deployed clients
+/// decode it. Must not change.
+mod legacy {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum MessageType {
+        SyncStep1,
+        SyncStep2,
+        Delta,
+        Awareness,
+        PeerJoined,
+        PeerLeft,
+        Ping,
+        Pong,
+        Rejected,
+        History,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SyncMessage {
+        pub msg_type: MessageType,
+        pub peer_id: Uuid,
+        pub doc_id: Uuid,
+        pub clock: u64,
+        pub payload: Vec<u8>,
+    }
+
+    impl SyncMessage {
+        pub fn new(msg_type: MessageType, peer_id: Uuid, doc_id: Uuid, payload: Vec<u8>) -> Self {
+            Self { msg_type, peer_id, doc_id, clock: 0, payload }
+        }
+
+        pub fn encode(&self) -> Vec<u8> {
+            bincode::serde::encode_to_vec(self, bincode::config::standard()).unwrap()
+        }
+
+        pub fn decode(bytes: &[u8]) -> Option<Self> {
+            bincode::serde::decode_from_slice(bytes, bincode::config::standard()).ok().map(|(msg, _)| msg)
+        }
+    }
+}
+
+type WsWriter = futures_util::stream::SplitSink<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    Message,
+>;
+
+/// A client built before the hello handshake.
+struct LegacyClient {
+    info: PeerInfo,
+    doc_id: Uuid,
+    writer: WsWriter,
+    messages: mpsc::UnboundedReceiver<legacy::SyncMessage>,
+    /// Frames it could not decode
+    undecodable: Arc<AtomicUsize>,
+}
+
+impl LegacyClient {
+    async fn connect(url: &str, info: PeerInfo, doc_id: Uuid) -> Self {
+        let (ws, _) = tokio_tungstenite::connect_async(format!("{url}/{doc_id}")).await.unwrap();
+        let (writer, mut reader) = ws.split();
+        let (tx, messages) = mpsc::unbounded_channel();
+        let undecodable = Arc::new(AtomicUsize::new(0));
+        let count = undecodable.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(frame)) = reader.next().await {
+                if let Message::Binary(data) = frame {
+                    match legacy::SyncMessage::decode(&data) {
+                        Some(msg) => {
+                            let _ = tx.send(msg);
+                        }
+                        None => {
+                            count.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut client = Self { info, doc_id, writer, messages, undecodable };
+        let info = bincode::serde::encode_to_vec(&client.info, bincode::config::standard()).unwrap();
+        client.send(legacy::MessageType::PeerJoined, info).await;
+        let sv = yrs::StateVector::default().encode_v1();
+        client.send(legacy::MessageType::SyncStep1, sv).await;
+        client
+    }
+
+    async fn send(&mut self, msg_type: legacy::MessageType, payload: Vec<u8>) {
+        let msg = legacy::SyncMessage::new(msg_type, self.info.peer_id, self.doc_id, payload);
+        self.writer.send(Message::Binary(msg.encode().into())).await.unwrap();
+    }
+}
+
+/// Either kind of client, with the deltas it received from others.
+enum Peer {
+    Old(LegacyClient),
+    New(SyncClient, mpsc::Receiver<SyncEvent>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Old,
+    New,
+}
+
+impl Peer {
+    async fn join(format: Format, url: &str, name: &str, doc_id: Uuid) -> Self {
+        let info = PeerInfo::new(name);
+        let peer = match format {
+            Format::Old => Peer::Old(LegacyClient::connect(url, info, doc_id).await),
+            Format::New => {
+                let mut client = SyncClient::new(info, doc_id, url);
+                let events = client.take_event_rx().unwrap();
+                client.connect().await.unwrap();
+                Peer::New(client, events)
+            }
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        peer
+    }
+
+    fn peer_id(&self) -> Uuid {
+        match self {
+            Peer::Old(client) => client.info.peer_id,
+            Peer::New(client, _) => client.peer_info().peer_id,
+        }
+    }
+
+    async fn send_delta(&mut self, update: Vec<u8>) {
+        match self {
+            Peer::Old(client) => client.send(legacy::MessageType::Delta, update).await,
+            Peer::New(client, _) => client.send_delta(update).await.unwrap(),
+        }
+    }
+
+    /// Deltas received since the last call, with their senders.
+    async fn deltas(&mut self) -> Vec<(Uuid, Vec<u8>)> {
+        let mut deltas = Vec::new();
+        let quiet = Duration::from_millis(200);
+        match self {
+            Peer::Old(client) => {
+                while let Ok(Some(msg)) = timeout(quiet, client.messages.recv()).await {
+                    if msg.msg_type == legacy::MessageType::Delta {
+                        deltas.push((msg.peer_id, msg.payload));
+                    }
+                }
+            }
+            Peer::New(_, events) => {
+                while let Ok(Some(event)) = timeout(quiet, events.recv()).await {
+                    if let SyncEvent::RemoteDelta { peer_id, update, .. } = event {
+                        deltas.push((peer_id, update));
+                    }
+                }
+            }
+        }
+        deltas
+    }
+}
+
+#[tokio::test]
+async fn test_old_and_new_clients_matrix() {
+    let (server, url) = start_server(ServerConfig::default()).await;
+    let matrix = [
+        (Format::Old, Format::Old),
+        (Format::Old, Format::New),
+        (Format::New, Format::Old),
+        (Format::New, Format::New),
+    ];
+
+    for (first, second) in matrix {
+        let doc_id = Uuid::new_v4();
+        let mut a = Peer::join(first, &url, "A", doc_id).await;
+        let mut b = Peer::join(second, &url, "B", doc_id).await;
+        a.deltas().await;
+        b.deltas().await;
+
+        let (from_a, from_b) = (text_update("from a"), text_update("from b"));
+        a.send_delta(from_a.clone()).await;
+        b.send_delta(from_b.clone()).await;
+
+        assert_eq!(b.deltas().await, vec![(a.peer_id(), from_a)], "{first:?} -> {second:?}");
+        assert_eq!(a.deltas().await, vec![(b.peer_id(), from_b)], "{second:?} -> {first:?}");
+
+        // Large enough to be compressed wherever both ends agreed to it
+        let paste = text_update(&"pasted text ".repeat(2000));
+        a.send_delta(paste.clone()).await;
+        assert_eq!(b.deltas().await, vec![(a.peer_id(), paste)], "{first:?} -> {second:?}");
+
+        for peer in [&a, &b] {
+            match peer {
+                // Nothing the server sent was beyond version 1
+                Peer::Old(client) => {
+                    assert_eq!(client.undecodable.load(Ordering::SeqCst), 0, "{first:?}/{second:?}");
+                }
+                Peer::New(client, _) => {
+                    let agreed = client.protocol().await;
+                    assert_eq!(agreed.version, PROTOCOL_VERSION);
+                    assert_eq!(agreed.capabilities, Capabilities::COMMENTS | Capabilities::COMPRESSION);
+                }
+            }
+        }
+    }
+
+    // Two old peers in the first pairing, one in each mixed pairing
+    let stats = server.stats().await;
+    assert_eq!(stats.legacy_joins, 4);
+    // To the new receivers of the second and last pairing
+    assert_eq!(stats.compressed_messages, 2);
+}
+
+#[tokio::test]
+async fn test_new_client_reports_negotiated_capabilities() {
+    let (_server, url) = start_server(ServerConfig::default()).await;
+    let mut client = SyncClient::new(PeerInfo::new("Alice"), Uuid::new_v4(), &url)
+        .with_capabilities(Capabilities::COMMENTS | Capabilities::COMPRESSION)
+        .with_auth_token("unchecked");
+    let mut events = client.take_event_rx().unwrap();
+    assert_eq!(client.protocol().await, Hello::legacy());
+    client.connect().await.unwrap();
+
+    let agreed = timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(SyncEvent::Negotiated(hello)) = events.recv().await {
+                break hello;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    // No authenticator on this server
+    let both = Capabilities::COMMENTS | Capabilities::COMPRESSION;
+    assert_eq!(agreed, Hello { version: PROTOCOL_VERSION, capabilities: both });
+    assert_eq!(client.protocol().await, agreed);
+}
+
+#[tokio::test]
+async fn test_new_client_against_legacy_server() {
+    // A server from before the handshake: it cannot decode the hello and
+    // skips it, as it does any undecodable message
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let undecodable = Arc::new(AtomicUsize::new(0));
+    let count = undecodable.clone();
+    let remote = Uuid::new_v4();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        while let Some(Ok(frame)) = ws.next().await {
+            let Message::Binary(data) = frame else { continue };
+            let Some(msg) = legacy::SyncMessage::decode(&data) else {
+                count.fetch_add(1, Ordering::SeqCst);
+                continue;
+            };
+            if msg.msg_type == legacy::MessageType::PeerJoined {
+                let delta = legacy::SyncMessage::new(legacy::MessageType::Delta, remote, msg.doc_id, text_update("old"));
+                ws.send(Message::Binary(delta.encode().into())).await.unwrap();
+            }
+        }
+    });
+
+    let mut client = SyncClient::new(PeerInfo::new("Alice"), Uuid::new_v4(), &url);
+    let mut events = client.take_event_rx().unwrap();
+    client.connect().await.unwrap();
+
+    let mut seen = Vec::new();
+    while let Ok(Some(event)) = timeout(Duration::from_millis(300), events.recv()).await {
+        seen.push(event);
+    }
+    assert!(seen.iter().any(|e| matches!(e, SyncEvent::RemoteDelta { peer_id, .. } if *peer_id == remote)), "{seen:?}");
+    assert!(!seen.iter().any(|e| matches!(e, SyncEvent::Negotiated(_) | SyncEvent::Disconnected)));
+    assert_eq!(client.protocol().await, Hello::legacy());
+    assert_eq!(undecodable.load(Ordering::SeqCst), 1, "only the hello is new");
+}
+
+#[tokio::test]
+async fn test_unknown_message_type_skipped() {
+    let (server, url) = start_server(ServerConfig::default()).await;
+    let doc_id = Uuid::new_v4();
+    let mut old = LegacyClient::connect(&url, PeerInfo::new("Old"), doc_id).await;
+    let mut future = LegacyClient::connect(&url, PeerInfo::new("Future"), doc_id).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    while old.messages.try_recv().is_ok() {}
+
+    // A message type from some later version, then an ordinary delta
+    #[derive(Serialize)]
+    struct FutureMessage {
+        msg_type: u32,
+        peer_id: Uuid,
+        doc_id: Uuid,
+        clock: u64,
+        payload: Vec<u8>,
+    }
+    let unknown = FutureMessage { msg_type: 99, peer_id: future.info.peer_id, doc_id, clock: 1, payload: vec![7; 16] };
+    let bytes = bincode::serde::encode_to_vec(&unknown, bincode::config::standard()).unwrap();
+    future.writer.send(Message::Binary(bytes.into())).await.unwrap();
+    future.send(legacy::MessageType::Delta, text_update("still here")).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(server.stats().await.unknown_messages, 1);
+    let mut received = Vec::new();
+    while let Ok(msg) = old.messages.try_recv() {
+        received.push((msg.msg_type, msg.peer_id));
+    }
+    // Not relayed, and the sender is still served
+    assert_eq!(received, vec![(legacy::MessageType::Delta, future.info.peer_id)]);
+    assert_eq!(old.undecodable.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_large_messages_compressed_on_the_wire() {
+    let (server, url) = start_server(ServerConfig::default()).await;
+    let doc_id = Uuid::new_v4();
+    let mut alice = Peer::join(Format::New, &url, "Alice", doc_id).await;
+    let mut bob = Peer::join(Format::New, &url, "Bob", doc_id).await;
+    // Opted out: gets the same messages uncompressed
+    let mut carol = SyncClient::new(PeerInfo::new("Carol"), doc_id, &url).with_capabilities(Capabilities::COMMENTS);
+    let mut carol_events = carol.take_event_rx().unwrap();
+    carol.connect().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    bob.deltas().await;
+    let before = server.stats().await;
+
+    let paste = text_update(&"pasted text ".repeat(5000));
+    alice.send_delta(paste.clone()).await;
+    assert_eq!(bob.deltas().await, vec![(alice.peer_id(), paste.clone())]);
+    let mut carol_deltas = Vec::new();
+    while let Ok(Some(event)) = timeout(Duration::from_millis(200), carol_events.recv()).await {
+        if let SyncEvent::RemoteDelta { update, .. } = event {
+            carol_deltas.push(update);
+        }
+    }
+    assert_eq!(carol_deltas, vec![paste.clone()]);
+
+    let after = server.stats().await;
+    // Alice sent it compressed, only Bob got it compressed
+    assert!(after.total_bytes - before.total_bytes < paste.len() as u64 / 10);
+    assert_eq!(after.compressed_messages - before.compressed_messages, 1);
+    assert!(after.compression_saved_bytes - before.compression_saved_bytes > paste.len() as u64 / 2);
+    assert_eq!(carol.protocol().await.capabilities, Capabilities::COMMENTS);
+}
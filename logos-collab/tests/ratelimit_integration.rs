@@ -0,0 +1,179 @@
+//! Integration tests for per-peer rate limits and the message size limit.
+//!
+//! Runs a server with tight limits, floods it from one peer and checks
+//! what reaches the other peers and what the flooding peer is told.
+
+mod common;
+
+use futures_util::{SinkExt, StreamExt};
+use logos_collab::client::SyncEvent;
+use logos_collab::presence::{AwarenessMessage, Vec2};
+use logos_collab::protocol::{MessageType, PeerInfo, Rejection, SyncMessage};
+use logos_collab::ratelimit::{RateLimit, RateLimits};
+use logos_collab::server::ServerConfig;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+use common::{connect, drain, start_server, text_update};
+
+#[tokio::test]
+async fn test_delta_flood_rejected_over_limit() {
+    let (server, url) = start_server(ServerConfig {
+        rate_limits: RateLimits {
+            delta: Some(RateLimit::new(1.0, 3.0)),
+            ..RateLimits::unlimited()
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let doc_id = Uuid::new_v4();
+    let (alice, mut alice_events) = connect(&url, PeerInfo::new("Alice"), doc_id).await;
+    let (_bob, mut bob_events) = connect(&url, PeerInfo::new("Bob"), doc_id).await;
+    drain(&mut alice_events, Duration::from_millis(150)).await;
+
+    for i in 0..5 {
+        alice.send_delta(text_update(&format!("edit {i}"))).await.unwrap();
+    }
+
+    let rejected: Vec<_> = drain(&mut alice_events, Duration::from_millis(300))
+        .await
+        .into_iter()
+        .filter_map(|e| match e {
+            SyncEvent::Rejected(r) => Some(r),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rejected.len(), 2, "burst of three, then refused: {rejected:?}");
+    assert!(rejected.iter().all(|r| matches!(r, Rejection::RateLimited { msg_type: MessageType::Delta, .. })));
+    assert!(!rejected[0].is_final(), "throttled peers stay connected");
+
+    let delivered = drain(&mut bob_events, Duration::from_millis(300))
+        .await
+        .into_iter()
+        .filter(|e| matches!(e, SyncEvent::RemoteDelta { .. }))
+        .count();
+    assert_eq!(delivered, 3);
+    assert_eq!(server.stats().await.throttled_deltas, 2);
+
+    // The bucket refills
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    alice.send_delta(text_update("later")).await.unwrap();
+    let seen = drain(&mut bob_events, Duration::from_millis(300)).await;
+    assert!(seen.iter().any(|e| matches!(e, SyncEvent::RemoteDelta { .. })));
+}
+
+#[tokio::test]
+async fn test_sync_step2_flood_charged_as_deltas() {
+    let (server, url) = start_server(ServerConfig {
+        rate_limits: RateLimits {
+            delta: Some(RateLimit::new(1.0, 3.0)),
+            ..RateLimits::unlimited()
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let doc_id = Uuid::new_v4();
+    let (_bob, mut bob_events) = connect(&url, PeerInfo::new("Bob"), doc_id).await;
+
+    // Raw socket sending every edit as an unsolicited SyncStep2
+    let mallory = PeerInfo::new("Mallory");
+    let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    let join = SyncMessage::peer_joined(mallory.peer_id, doc_id, &mallory);
+    ws.send(Message::Binary(join.encode().unwrap().into())).await.unwrap();
+    for i in 0..5 {
+        let step2 = SyncMessage::sync_step2(mallory.peer_id, doc_id, text_update(&format!("edit {i}")));
+        ws.send(Message::Binary(step2.encode().unwrap().into())).await.unwrap();
+    }
+
+    let mut rejected = Vec::new();
+    while let Ok(Some(Ok(frame))) = timeout(Duration::from_millis(300), ws.next()).await {
+        let Ok(msg) = SyncMessage::decode(&frame.into_data()) else { continue };
+        if msg.msg_type == MessageType::Rejected {
+            rejected.push(msg.rejection().unwrap());
+        }
+    }
+    assert_eq!(rejected.len(), 2, "burst of three, then refused: {rejected:?}");
+    assert!(rejected
+        .iter()
+        .all(|r| matches!(r, Rejection::RateLimited { msg_type: MessageType::SyncStep2, .. })));
+
+    let delivered = drain(&mut bob_events, Duration::from_millis(300))
+        .await
+        .into_iter()
+        .filter(|e| matches!(e, SyncEvent::RemoteDelta { .. }))
+        .count();
+    assert_eq!(delivered, 3);
+    assert_eq!(server.stats().await.throttled_deltas, 2);
+}
+
+#[tokio::test]
+async fn test_awareness_coalesced_to_latest() {
+    let (server, url) = start_server(ServerConfig {
+        rate_limits: RateLimits {
+            awareness: Some(RateLimit::new(5.0, 1.0)),
+            ..RateLimits::unlimited()
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let doc_id = Uuid::new_v4();
+    let alice = PeerInfo::new("Alice");
+    let (alice_client, mut alice_events) = connect(&url, alice.clone(), doc_id).await;
+    let (_bob, mut bob_events) = connect(&url, PeerInfo::new("Bob"), doc_id).await;
+    drain(&mut alice_events, Duration::from_millis(150)).await;
+
+    for i in 0..20 {
+        let cursor = AwarenessMessage::Cursor {
+            user_id: alice.peer_id,
+            position: Vec2 { x: i as f32, y: 0.0 },
+            timestamp: i,
+        };
+        alice_client.send_presence(&cursor).await.unwrap();
+    }
+
+    let cursors: Vec<u64> = drain(&mut bob_events, Duration::from_millis(600))
+        .await
+        .into_iter()
+        .filter_map(|e| match e {
+            SyncEvent::PresenceUpdate { message: AwarenessMessage::Cursor { timestamp, .. }, .. } => Some(timestamp),
+            _ => None,
+        })
+        .collect();
+    assert!(cursors.len() < 20, "flood was coalesced: {cursors:?}");
+    assert_eq!(cursors.first(), Some(&0));
+    assert_eq!(cursors.last(), Some(&19), "the latest position always arrives");
+    assert!(cursors.windows(2).all(|w| w[0] < w[1]), "never out of order: {cursors:?}");
+
+    assert!(server.stats().await.coalesced_awareness >= 10);
+    let refused = drain(&mut alice_events, Duration::from_millis(100)).await;
+    assert!(!refused.iter().any(|e| matches!(e, SyncEvent::Rejected(_))), "awareness is never rejected");
+}
+
+#[tokio::test]
+async fn test_oversized_message_rejected_before_decode() {
+    let (server, url) = start_server(ServerConfig {
+        max_message_bytes: 1024,
+        ..ServerConfig::default()
+    })
+    .await;
+    let doc_id = Uuid::new_v4();
+    let (alice, mut alice_events) = connect(&url, PeerInfo::new("Alice"), doc_id).await;
+    let (_bob, mut bob_events) = connect(&url, PeerInfo::new("Bob"), doc_id).await;
+    drain(&mut alice_events, Duration::from_millis(150)).await;
+
+    // Over the limit once decompressed, and under the hard cap at twice it
+    alice.send_delta(text_update(&"x".repeat(1500))).await.unwrap();
+    let seen = drain(&mut alice_events, Duration::from_millis(300)).await;
+    assert!(
+        seen.iter().any(|e| matches!(e, SyncEvent::Rejected(Rejection::TooLarge { max: 1024, .. }))),
+        "{seen:?}"
+    );
+    assert!(!seen.iter().any(|e| matches!(e, SyncEvent::Disconnected)));
+    assert_eq!(server.stats().await.oversized_messages, 1);
+
+    // Still connected; small messages go through
+    alice.send_delta(text_update("small")).await.unwrap();
+    let seen = drain(&mut bob_events, Duration::from_millis(300)).await;
+    assert_eq!(seen.iter().filter(|e| matches!(e, SyncEvent::RemoteDelta { .. })).count(), 1);
+}
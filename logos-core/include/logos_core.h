@@ -0,0 +1,224 @@
+/* logos-core C ABI. Generated by cbindgen from src/ffi.rs — do not edit. */
+
+#ifndef LOGOS_CORE_H
+#define LOGOS_CORE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Status code returned by every fallible call.
+typedef enum LogosError {
+  LOGOS_ERROR_OK = 0,
+  LOGOS_ERROR_NULL_POINTER = -1,
+  LOGOS_ERROR_INVALID_ARGUMENT = -2,
+  LOGOS_ERROR_NOT_FOUND = -3,
+  LOGOS_ERROR_SERIALIZATION = -4,
+  LOGOS_ERROR_COLLAB = -5,
+  LOGOS_ERROR_LOCK_POISONED = -6,
+  LOGOS_ERROR_OUT_OF_RANGE = -7,
+} LogosError;
+
+// Layer type tag.
+typedef enum LogosLayerKind {
+  LOGOS_LAYER_KIND_RECT = 0,
+  LOGOS_LAYER_KIND_ELLIPSE = 1,
+  LOGOS_LAYER_KIND_TEXT = 2,
+  LOGOS_LAYER_KIND_FRAME = 3,
+  LOGOS_LAYER_KIND_BOOL = 4,
+} LogosLayerKind;
+
+// Byte buffer owned by the caller; release with `logos_buffer_free`.
+typedef struct LogosBuffer {
+  uint8_t *data;
+  size_t len;
+} LogosBuffer;
+
+// Opaque handle type for C FFI
+typedef struct LogosDocument LogosDocument;
+
+// Axis-aligned bounds in document units.
+typedef struct LogosRect {
+  float x;
+  float y;
+  float width;
+  float height;
+} LogosRect;
+
+// Opaque handle to a collaboration engine
+typedef struct LogosCollab LogosCollab;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Free a string returned by this library. Null is ignored.
+void logos_string_free(char *s);
+
+// Free a buffer returned by this library. An empty buffer is ignored.
+void logos_buffer_free(struct LogosBuffer buffer);
+
+// Static name for a status code, e.g. "not found"; "unknown" for values
+// that are not a `LogosError`. Never free it.
+const char *logos_error_name(int32_t code);
+
+struct LogosDocument *logos_document_new(void);
+
+void logos_document_free(struct LogosDocument *ptr);
+
+enum LogosError logos_document_add_rect(struct LogosDocument *doc,
+                                        float x,
+                                        float y,
+                                        float width,
+                                        float height,
+                                        char **error_out);
+
+// Serialize the whole document as JSON.
+enum LogosError logos_document_to_json(struct LogosDocument *doc,
+                                       char **json_out,
+                                       char **error_out);
+
+// Parse a document from JSON produced by `logos_document_to_json`.
+enum LogosError logos_document_from_json(const char *json,
+                                         struct LogosDocument **doc_out,
+                                         char **error_out);
+
+// Create a layer of `kind` (a `LogosLayerKind`) with `bounds` and append
+// it under `parent_id`. The new id is written to `id_out` when non-null.
+enum LogosError logos_document_add_layer(struct LogosDocument *doc,
+                                         int32_t kind,
+                                         struct LogosRect bounds,
+                                         const char *parent_id,
+                                         char **id_out,
+                                         char **error_out);
+
+// Insert a layer given as JSON (`{"Rect": {...}}`) under `parent_id` at
+// `index`; an index past the end appends.
+enum LogosError logos_document_insert_layer_json(struct LogosDocument *doc,
+                                                 const char *parent_id,
+                                                 size_t index,
+                                                 const char *json,
+                                                 char **error_out);
+
+// Write a layer (with its subtree) as JSON.
+enum LogosError logos_document_get_layer_json(struct LogosDocument *doc,
+                                              const char *id,
+                                              char **json_out,
+                                              char **error_out);
+
+// Replace a layer in place. The JSON must carry the same id.
+enum LogosError logos_document_replace_layer_json(struct LogosDocument *doc,
+                                                  const char *id,
+                                                  const char *json,
+                                                  char **error_out);
+
+// Remove a layer and its subtree.
+enum LogosError logos_document_remove_layer(struct LogosDocument *doc,
+                                            const char *id,
+                                            char **error_out);
+
+// Move a layer under `parent_id` at `index`; an index past the end appends.
+enum LogosError logos_document_move_layer(struct LogosDocument *doc,
+                                          const char *id,
+                                          const char *parent_id,
+                                          size_t index,
+                                          char **error_out);
+
+// Number of children under `parent_id` (page level when null).
+enum LogosError logos_document_child_count(struct LogosDocument *doc,
+                                           const char *parent_id,
+                                           size_t *count_out,
+                                           char **error_out);
+
+// Id of the child at `index` under `parent_id`.
+enum LogosError logos_document_child_id(struct LogosDocument *doc,
+                                        const char *parent_id,
+                                        size_t index,
+                                        char **id_out,
+                                        char **error_out);
+
+// Parent of a layer; writes null for page-level layers.
+enum LogosError logos_document_parent_id(struct LogosDocument *doc,
+                                         const char *id,
+                                         char **parent_out,
+                                         char **error_out);
+
+enum LogosError logos_document_layer_kind(struct LogosDocument *doc,
+                                          const char *id,
+                                          enum LogosLayerKind *kind_out,
+                                          char **error_out);
+
+// Read a property (`name`, `bounds.x`, `content`, ...) as a JSON value.
+enum LogosError logos_layer_get_property(struct LogosDocument *doc,
+                                         const char *id,
+                                         const char *name,
+                                         char **value_json_out,
+                                         char **error_out);
+
+// Set a property from a JSON value, e.g. `"\"Title\""` or `42`.
+enum LogosError logos_layer_set_property(struct LogosDocument *doc,
+                                         const char *id,
+                                         const char *name,
+                                         const char *value_json,
+                                         char **error_out);
+
+enum LogosError logos_layer_get_bounds(struct LogosDocument *doc,
+                                       const char *id,
+                                       struct LogosRect *bounds_out,
+                                       char **error_out);
+
+enum LogosError logos_layer_set_bounds(struct LogosDocument *doc,
+                                       const char *id,
+                                       struct LogosRect bounds,
+                                       char **error_out);
+
+// Create a collaboration engine seeded with the document's top-level
+// layers. Free it with `logos_collab_free`.
+enum LogosError logos_collab_new(struct LogosDocument *doc,
+                                 struct LogosCollab **collab_out,
+                                 char **error_out);
+
+void logos_collab_free(struct LogosCollab *ptr);
+
+// Apply a remote Yrs v1 update.
+enum LogosError logos_collab_apply_update(struct LogosCollab *collab,
+                                          const uint8_t *data,
+                                          size_t len,
+                                          char **error_out);
+
+// Insert or replace a layer from JSON; writes the delta to broadcast.
+enum LogosError logos_collab_upsert_layer_json(struct LogosCollab *collab,
+                                               const char *json,
+                                               struct LogosBuffer *delta_out,
+                                               char **error_out);
+
+// Remove a layer; writes the delta to broadcast.
+enum LogosError logos_collab_remove_layer(struct LogosCollab *collab,
+                                          const char *id,
+                                          struct LogosBuffer *delta_out,
+                                          char **error_out);
+
+// Encoded state vector of the engine.
+enum LogosError logos_collab_state_vector(struct LogosCollab *collab,
+                                          struct LogosBuffer *sv_out,
+                                          char **error_out);
+
+// Encode what a peer with `state_vector` is missing (everything when `len` is 0).
+enum LogosError logos_collab_encode_update(struct LogosCollab *collab,
+                                           const uint8_t *state_vector,
+                                           size_t len,
+                                           struct LogosBuffer *update_out,
+                                           char **error_out);
+
+// Materialize the engine's current state as a new document handle.
+enum LogosError logos_collab_to_document(struct LogosCollab *collab,
+                                         struct LogosDocument **doc_out,
+                                         char **error_out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LOGOS_CORE_H */
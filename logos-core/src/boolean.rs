@@ -0,0 +1,525 @@
+//! Boolean path operations — pure-Rust geometry kernel.
+//!
+//! Same split-and-select approach as the web renderer's `math/bools.rs`, on
+//! flattened polylines instead of skia beziers so every platform (native
+//! renderer, SVG export, wasm) produces bit-identical results:
+//!
+//! ```text
+//!  A, B ── split edges at every A×B crossing
+//!       ── classify each piece by its midpoint: inside / outside / shared
+//!       ── keep pieces per BoolType (B reversed for difference)
+//!       ── chain kept pieces back into closed contours
+//! ```
+//!
+//! Contours are closed polygons. Outer contours wind with positive shoelace
+//! area and holes with negative area, so [`VectorPath::area`] is simply the
+//! signed sum.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Point, Rect};
+
+/// Maximum distance between a curve and its flattened polyline.
+pub const FLATTEN_TOLERANCE: f32 = 0.1;
+
+/// Points closer than this (in document units) are treated as the same vertex.
+const SNAP: f64 = 1e-6;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum BoolType {
+    #[default]
+    Union,
+    Difference,
+    Intersection,
+    Exclusion,
+}
+
+impl BoolType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BoolType::Union => "union",
+            BoolType::Difference => "difference",
+            BoolType::Intersection => "intersection",
+            BoolType::Exclusion => "exclusion",
+        }
+    }
+}
+
+/// A set of closed polygonal contours.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct VectorPath {
+    pub contours: Vec<Vec<Point>>,
+}
+
+impl VectorPath {
+    pub fn is_empty(&self) -> bool {
+        self.contours.is_empty()
+    }
+
+    /// Axis-aligned rectangle as a single contour.
+    pub fn rect(r: &Rect) -> Self {
+        let (x0, y0, x1, y1) = (r.x, r.y, r.x + r.width, r.y + r.height);
+        if r.width <= 0.0 || r.height <= 0.0 {
+            return Self::default();
+        }
+        Self {
+            contours: vec![vec![
+                Point { x: x0, y: y0 },
+                Point { x: x1, y: y0 },
+                Point { x: x1, y: y1 },
+                Point { x: x0, y: y1 },
+            ]],
+        }
+    }
+
+    /// Ellipse inscribed in `r`, flattened within [`FLATTEN_TOLERANCE`].
+    pub fn ellipse(r: &Rect) -> Self {
+        if r.width <= 0.0 || r.height <= 0.0 {
+            return Self::default();
+        }
+        let (rx, ry) = (r.width as f64 / 2.0, r.height as f64 / 2.0);
+        let (cx, cy) = (r.x as f64 + rx, r.y as f64 + ry);
+        let radius = rx.max(ry);
+        let tol = (FLATTEN_TOLERANCE as f64).min(radius);
+        let steps = (std::f64::consts::PI / (1.0 - tol / radius).acos()).ceil();
+        let steps = (steps as usize).clamp(8, 1024);
+        let contour = (0..steps)
+            .map(|i| {
+                let a = i as f64 / steps as f64 * std::f64::consts::TAU;
+                Point { x: (cx + rx * a.cos()) as f32, y: (cy + ry * a.sin()) as f32 }
+            })
+            .collect();
+        Self { contours: vec![contour] }
+    }
+
+    /// Bounding box of all contours, `None` when empty.
+    pub fn bounds(&self) -> Option<Rect> {
+        let mut points = self.contours.iter().flatten();
+        let first = points.next()?;
+        let (mut x0, mut y0, mut x1, mut y1) = (first.x, first.y, first.x, first.y);
+        for p in points {
+            x0 = x0.min(p.x);
+            y0 = y0.min(p.y);
+            x1 = x1.max(p.x);
+            y1 = y1.max(p.y);
+        }
+        Some(Rect { x: x0, y: y0, width: x1 - x0, height: y1 - y0 })
+    }
+
+    /// Filled area (outer contours add, holes subtract).
+    pub fn area(&self) -> f32 {
+        self.contours.iter().map(|c| signed_area(&to_f64(c))).sum::<f64>() as f32
+    }
+
+    /// Even-odd point containment.
+    pub fn contains(&self, p: &Point) -> bool {
+        let contours: Vec<Vec<V>> = self.contours.iter().map(|c| to_f64(c)).collect();
+        contains(&contours, V { x: p.x as f64, y: p.y as f64 })
+    }
+
+    /// SVG path data (`M x y L ... Z`), one subpath per contour.
+    pub fn to_svg_data(&self) -> String {
+        let mut out = String::new();
+        for contour in &self.contours {
+            for (i, p) in contour.iter().enumerate() {
+                let cmd = if i == 0 { 'M' } else { 'L' };
+                if !out.is_empty() {
+                    out.push(' ');
+                }
+                let _ = write!(out, "{cmd}{} {}", p.x, p.y);
+            }
+            out.push_str(" Z");
+        }
+        out
+    }
+}
+
+/// Combine two paths.
+pub fn boolean(bool_type: BoolType, a: &VectorPath, b: &VectorPath) -> VectorPath {
+    let a: Vec<Vec<V>> = a.contours.iter().map(|c| to_f64(c)).collect();
+    let b: Vec<Vec<V>> = b.contours.iter().map(|c| to_f64(c)).collect();
+    let contours = match bool_type {
+        BoolType::Exclusion => {
+            let mut out = combine(BoolType::Difference, &a, &b);
+            out.extend(combine(BoolType::Difference, &b, &a));
+            out
+        }
+        op => combine(op, &a, &b),
+    };
+    VectorPath { contours: contours.iter().map(|c| from_f64(c)).collect() }
+}
+
+/// Fold an operand list left to right: union/intersection/exclusion of all,
+/// or the first operand minus every other one.
+pub fn boolean_all(bool_type: BoolType, operands: &[VectorPath]) -> VectorPath {
+    let mut iter = operands.iter();
+    let Some(first) = iter.next() else {
+        return VectorPath::default();
+    };
+    iter.fold(first.clone(), |acc, next| boolean(bool_type, &acc, next))
+}
+
+// ─── Kernel ─────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct V {
+    x: f64,
+    y: f64,
+}
+
+impl V {
+    fn sub(self, o: V) -> V {
+        V { x: self.x - o.x, y: self.y - o.y }
+    }
+    fn cross(self, o: V) -> f64 {
+        self.x * o.y - self.y * o.x
+    }
+    fn lerp(self, o: V, t: f64) -> V {
+        V { x: self.x + (o.x - self.x) * t, y: self.y + (o.y - self.y) * t }
+    }
+    fn key(self) -> (i64, i64) {
+        ((self.x / SNAP).round() as i64, (self.y / SNAP).round() as i64)
+    }
+}
+
+fn to_f64(contour: &[Point]) -> Vec<V> {
+    contour.iter().map(|p| V { x: p.x as f64, y: p.y as f64 }).collect()
+}
+
+fn from_f64(contour: &[V]) -> Vec<Point> {
+    contour.iter().map(|v| Point { x: v.x as f32, y: v.y as f32 }).collect()
+}
+
+fn signed_area(contour: &[V]) -> f64 {
+    let n = contour.len();
+    (0..n).map(|i| contour[i].cross(contour[(i + 1) % n])).sum::<f64>() / 2.0
+}
+
+fn edges(contours: &[Vec<V>]) -> Vec<(V, V)> {
+    contours
+        .iter()
+        .flat_map(|c| (0..c.len()).map(move |i| (c[i], c[(i + 1) % c.len()])))
+        .filter(|(p, q)| p.key() != q.key())
+        .collect()
+}
+
+fn contains(contours: &[Vec<V>], p: V) -> bool {
+    let mut inside = false;
+    for (a, b) in edges(contours) {
+        if (a.y > p.y) != (b.y > p.y) {
+            let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if p.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Split points (parameter, point) where edge `p→q` meets edge `r→s`.
+fn crossings(p: V, q: V, r: V, s: V) -> Vec<(f64, V, f64)> {
+    let d1 = q.sub(p);
+    let d2 = s.sub(r);
+    let denom = d1.cross(d2);
+    let eps = 1e-12;
+    let (len1, len2) = (d1.x.hypot(d1.y), d2.x.hypot(d2.y));
+
+    if denom.abs() > 1e-9 * len1 * len2 {
+        let t = r.sub(p).cross(d2) / denom;
+        let u = r.sub(p).cross(d1) / denom;
+        if (-eps..=1.0 + eps).contains(&t) && (-eps..=1.0 + eps).contains(&u) {
+            // Prefer an existing vertex so both sides agree on the exact point
+            let point = if u <= eps {
+                r
+            } else if u >= 1.0 - eps {
+                s
+            } else if t <= eps {
+                p
+            } else if t >= 1.0 - eps {
+                q
+            } else {
+                p.lerp(q, t)
+            };
+            return vec![(t.clamp(0.0, 1.0), point, u.clamp(0.0, 1.0))];
+        }
+        return Vec::new();
+    }
+
+    // Parallel: only collinear overlaps matter
+    if len1 == 0.0 || len2 == 0.0 || r.sub(p).cross(d1).abs() > SNAP * len1 {
+        return Vec::new();
+    }
+    let (sq1, sq2) = (len1 * len1, len2 * len2);
+    let project = |v: V, o: V, d: V, l2: f64| (v.sub(o).x * d.x + v.sub(o).y * d.y) / l2;
+    let mut out = Vec::new();
+    for v in [r, s] {
+        let t = project(v, p, d1, sq1);
+        if (0.0..=1.0).contains(&t) {
+            out.push((t, v, project(v, r, d2, sq2)));
+        }
+    }
+    for v in [p, q] {
+        let u = project(v, r, d2, sq2);
+        if (0.0..=1.0).contains(&u) {
+            out.push((project(v, p, d1, sq1), v, u));
+        }
+    }
+    out
+}
+
+/// Cut every edge of both operands at their mutual crossings.
+type Edge = (V, V);
+
+fn split(a: &[Edge], b: &[Edge]) -> (Vec<Edge>, Vec<Edge>) {
+    let mut cuts_a: Vec<Vec<(f64, V)>> = vec![Vec::new(); a.len()];
+    let mut cuts_b: Vec<Vec<(f64, V)>> = vec![Vec::new(); b.len()];
+    for (i, &(p, q)) in a.iter().enumerate() {
+        for (j, &(r, s)) in b.iter().enumerate() {
+            for (t, point, u) in crossings(p, q, r, s) {
+                cuts_a[i].push((t, point));
+                cuts_b[j].push((u, point));
+            }
+        }
+    }
+    (apply_cuts(a, cuts_a), apply_cuts(b, cuts_b))
+}
+
+fn apply_cuts(edges: &[(V, V)], cuts: Vec<Vec<(f64, V)>>) -> Vec<(V, V)> {
+    let mut out = Vec::new();
+    for (&(p, q), mut cuts) in edges.iter().zip(cuts) {
+        cuts.sort_by(|x, y| x.0.total_cmp(&y.0));
+        let mut start = p;
+        for (_, point) in cuts {
+            if point.key() != start.key() && point.key() != q.key() {
+                out.push((start, point));
+                start = point;
+            }
+        }
+        out.push((start, q));
+    }
+    out
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Side {
+    Inside,
+    Outside,
+    /// Coincides with an edge of the other operand running the same way
+    SharedSame,
+    /// Coincides with an edge of the other operand running the other way
+    SharedOpposite,
+}
+
+fn classify(pieces: &[(V, V)], other_pieces: &[(V, V)], other: &[Vec<V>]) -> Vec<Side> {
+    let forward: HashSet<_> = other_pieces.iter().map(|(p, q)| (p.key(), q.key())).collect();
+    pieces
+        .iter()
+        .map(|&(p, q)| {
+            if forward.contains(&(p.key(), q.key())) {
+                Side::SharedSame
+            } else if forward.contains(&(q.key(), p.key())) {
+                Side::SharedOpposite
+            } else if contains(other, p.lerp(q, 0.5)) {
+                Side::Inside
+            } else {
+                Side::Outside
+            }
+        })
+        .collect()
+}
+
+fn combine(op: BoolType, a: &[Vec<V>], b: &[Vec<V>]) -> Vec<Vec<V>> {
+    let (pieces_a, pieces_b) = split(&edges(a), &edges(b));
+    let sides_a = classify(&pieces_a, &pieces_b, b);
+    let sides_b = classify(&pieces_b, &pieces_a, a);
+
+    let mut kept: Vec<(V, V)> = Vec::new();
+    for (&piece, side) in pieces_a.iter().zip(sides_a) {
+        let keep = match op {
+            BoolType::Union => matches!(side, Side::Outside | Side::SharedSame),
+            BoolType::Intersection => matches!(side, Side::Inside | Side::SharedSame),
+            BoolType::Difference => matches!(side, Side::Outside | Side::SharedOpposite),
+            BoolType::Exclusion => unreachable!("exclusion is two differences"),
+        };
+        if keep {
+            kept.push(piece);
+        }
+    }
+    // Shared pieces were already taken from A
+    for (&(p, q), side) in pieces_b.iter().zip(sides_b) {
+        match (op, side) {
+            (BoolType::Union, Side::Outside) | (BoolType::Intersection, Side::Inside) => kept.push((p, q)),
+            (BoolType::Difference, Side::Inside) => kept.push((q, p)),
+            _ => {}
+        }
+    }
+    chain(kept)
+}
+
+/// Link pieces end-to-start into closed contours; open fragments are dropped.
+fn chain(pieces: Vec<(V, V)>) -> Vec<Vec<V>> {
+    let mut by_start: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, (p, _)) in pieces.iter().enumerate() {
+        by_start.entry(p.key()).or_default().push(i);
+    }
+    let mut used = vec![false; pieces.len()];
+    let mut contours = Vec::new();
+
+    for start in 0..pieces.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let origin = pieces[start].0.key();
+        let mut contour = vec![pieces[start].0];
+        let mut end = pieces[start].1;
+        let closed = loop {
+            if end.key() == origin {
+                break true;
+            }
+            contour.push(end);
+            let next = by_start
+                .get(&end.key())
+                .and_then(|candidates| candidates.iter().copied().find(|&i| !used[i]));
+            match next {
+                Some(i) => {
+                    used[i] = true;
+                    end = pieces[i].1;
+                }
+                None => break false,
+            }
+        };
+        if closed {
+            let contour = simplify(contour);
+            if contour.len() >= 3 && signed_area(&contour).abs() > SNAP {
+                contours.push(contour);
+            }
+        }
+    }
+    contours
+}
+
+/// Drop vertices that lie on the straight line between their neighbours.
+fn simplify(mut contour: Vec<V>) -> Vec<V> {
+    let mut i = 0;
+    while contour.len() >= 3 && i < contour.len() {
+        let n = contour.len();
+        let (prev, cur, next) = (contour[(i + n - 1) % n], contour[i], contour[(i + 1) % n]);
+        let d1 = cur.sub(prev);
+        let d2 = next.sub(cur);
+        let scale = (d1.x.abs() + d1.y.abs()).max(d2.x.abs() + d2.y.abs()).max(1.0);
+        if d1.cross(d2).abs() <= SNAP * scale && d1.x * d2.x + d1.y * d2.y >= 0.0 {
+            contour.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+    contour
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> VectorPath {
+        VectorPath::rect(&Rect { x, y, width: size, height: size })
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_overlapping_squares() {
+        let a = square(0.0, 0.0, 10.0);
+        let b = square(5.0, 5.0, 10.0);
+
+        let union = boolean(BoolType::Union, &a, &b);
+        assert_eq!(union.contours.len(), 1);
+        assert_eq!(union.contours[0].len(), 8, "L-shaped octagon after simplification");
+        assert!(approx(union.area(), 175.0));
+
+        let inter = boolean(BoolType::Intersection, &a, &b);
+        assert!(approx(inter.area(), 25.0));
+        let r = inter.bounds().unwrap();
+        assert_eq!((r.x, r.y, r.width, r.height), (5.0, 5.0, 5.0, 5.0));
+
+        let diff = boolean(BoolType::Difference, &a, &b);
+        assert!(approx(diff.area(), 75.0));
+        assert!(diff.contains(&Point { x: 2.0, y: 2.0 }));
+        assert!(!diff.contains(&Point { x: 7.0, y: 7.0 }));
+
+        let xor = boolean(BoolType::Exclusion, &a, &b);
+        assert!(approx(xor.area(), 150.0));
+        assert!(!xor.contains(&Point { x: 7.0, y: 7.0 }));
+        assert!(xor.contains(&Point { x: 12.0, y: 12.0 }));
+    }
+
+    #[test]
+    fn test_disjoint_and_contained() {
+        let a = square(0.0, 0.0, 10.0);
+        let far = square(20.0, 0.0, 10.0);
+        assert_eq!(boolean(BoolType::Union, &a, &far).contours.len(), 2);
+        assert!(boolean(BoolType::Intersection, &a, &far).is_empty());
+        assert_eq!(boolean(BoolType::Difference, &a, &far), a);
+
+        // Punching a hole leaves an outer contour and a reversed inner one
+        let inner = square(2.0, 2.0, 4.0);
+        let holed = boolean(BoolType::Difference, &a, &inner);
+        assert_eq!(holed.contours.len(), 2);
+        assert!(approx(holed.area(), 84.0));
+        assert!(!holed.contains(&Point { x: 3.0, y: 3.0 }));
+        assert!(holed.contains(&Point { x: 1.0, y: 1.0 }));
+    }
+
+    #[test]
+    fn test_shared_edges() {
+        // Side by side squares touching along x = 10
+        let a = square(0.0, 0.0, 10.0);
+        let b = square(10.0, 0.0, 10.0);
+        let union = boolean(BoolType::Union, &a, &b);
+        assert_eq!(union.contours.len(), 1);
+        assert_eq!(union.contours[0].len(), 4, "merged into one rectangle");
+        assert!(approx(union.area(), 200.0));
+
+        // Identical operands
+        assert!(approx(boolean(BoolType::Intersection, &a, &a).area(), 100.0));
+        assert!(boolean(BoolType::Difference, &a, &a).is_empty());
+    }
+
+    #[test]
+    fn test_ellipse_flattening() {
+        let circle = VectorPath::ellipse(&Rect { x: 0.0, y: 0.0, width: 20.0, height: 20.0 });
+        let expected = std::f32::consts::PI * 100.0;
+        assert!((circle.area() - expected).abs() / expected < 0.02);
+        assert!(circle.area() > 0.0, "outer contours wind positively");
+
+        let half = boolean(BoolType::Intersection, &circle, &square(0.0, 0.0, 10.0).union_with(&square(0.0, 10.0, 10.0)));
+        assert!((half.area() - expected / 2.0).abs() / expected < 0.02);
+    }
+
+    #[test]
+    fn test_fold_over_operands() {
+        let ops = [square(0.0, 0.0, 10.0), square(5.0, 0.0, 10.0), square(10.0, 0.0, 10.0)];
+        assert!(approx(boolean_all(BoolType::Union, &ops).area(), 200.0));
+        assert!(approx(boolean_all(BoolType::Difference, &ops).area(), 50.0));
+        assert!(approx(boolean_all(BoolType::Intersection, &ops).area(), 0.0));
+        assert!(boolean_all(BoolType::Union, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_svg_data() {
+        let path = square(0.0, 0.0, 1.0);
+        assert_eq!(path.to_svg_data(), "M0 0 L1 0 L1 1 L0 1 Z");
+    }
+
+    impl VectorPath {
+        fn union_with(&self, other: &VectorPath) -> VectorPath {
+            boolean(BoolType::Union, self, other)
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use yrs::*;
 use yrs::types::{Map, MapRef};
 use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
 use std::sync::{Arc, RwLock};
 use std::sync::atomic::AtomicU64;
 use uuid::Uuid;
@@ -118,6 +119,36 @@ impl CollaborationEngine {
         Ok(Vec::new()) 
     }
 
+    /// Remove a layer locally and return the delta to broadcast
+    pub fn remove_layer_local(&mut self, id: Uuid) -> Result<Vec<u8>, CollabError> {
+        let mut txn = yrs::Transact::transact_mut(&self.doc);
+        if self.layers_map.remove(&mut txn, &id.to_string()).is_none() {
+            return Err(CollabError::InvalidOperation(format!("no layer {id}")));
+        }
+        Ok(txn.encode_update_v1())
+    }
+
+    /// Encoded state vector, for the other side of a sync to diff against
+    pub fn state_vector(&self) -> Vec<u8> {
+        yrs::Transact::transact(&self.doc).state_vector().encode_v1()
+    }
+
+    /// Encode everything the holder of `state_vector` is missing.
+    /// An empty state vector yields the full document state.
+    pub fn encode_state_as_update(&self, state_vector: &[u8]) -> Result<Vec<u8>, CollabError> {
+        let sv = if state_vector.is_empty() {
+            StateVector::default()
+        } else {
+            StateVector::decode_v1(state_vector)?
+        };
+        Ok(yrs::Transact::transact(&self.doc).encode_state_as_update_v1(&sv))
+    }
+
+    /// Materialize the current CRDT state as a page
+    pub fn to_page(&self) -> Result<Page, CollabError> {
+        page_from_update(&self.encode_state_as_update(&[])?)
+    }
+
     pub fn get_snapshot(&self) -> Arc<RwLock<DocumentSnapshot>> {
         self.snapshot.clone()
     }
@@ -135,6 +166,82 @@ impl CollaborationEngine {
     }
 }
 
+/// Rebuild a [`Page`] from an encoded Yrs update, such as a snapshot loaded
+/// from the document store.
+///
+/// Entries may carry a `parent_id` naming another frame entry, the flat form
+/// of [`CollabOp::AddLayer`]; those are nested under their parent. Links that
+/// dangle or loop leave the layer at page level (see [`crate::validate`]).
+/// The map carries no ordering, so siblings come back sorted by id.
+pub fn page_from_update(update: &[u8]) -> Result<Page, CollabError> {
+    let doc = Doc::new();
+    let layers_map = doc.get_or_insert_map("layers");
+    let mut txn = yrs::Transact::transact_mut(&doc);
+    let update_obj = Update::decode_v1(update)?;
+    txn.apply_update(update_obj)
+        .map_err(|e| CollabError::YrsError(e.to_string()))?;
+
+    let mut layers = std::collections::BTreeMap::new();
+    let mut parents = std::collections::HashMap::new();
+    for (_, value) in layers_map.iter(&txn) {
+        let json = value.to_string(&txn);
+        let layer: Layer = serde_json::from_str(&json)
+            .map_err(|e| CollabError::SerializationError(e.to_string()))?;
+        let parent_id = serde_json::from_str::<Value>(&json)
+            .ok()
+            .and_then(|v| v.as_object()?.values().next()?.get("parent_id")?.as_str()?.parse::<Uuid>().ok());
+        if let Some(parent_id) = parent_id {
+            parents.insert(layer.id(), parent_id);
+        }
+        layers.insert(layer.id(), layer);
+    }
+
+    // Attach children whose parent is a frame or boolean group that itself
+    // reaches the root
+    let mut children: std::collections::BTreeMap<Uuid, Vec<Uuid>> = Default::default();
+    let mut roots = Vec::new();
+    for &id in layers.keys() {
+        match parents.get(&id) {
+            Some(p) if matches!(layers.get(p), Some(Layer::Frame(_) | Layer::Bool(_))) => {
+                children.entry(*p).or_default().push(id)
+            }
+            _ => roots.push(id),
+        }
+    }
+
+    fn attach(
+        id: Uuid,
+        layers: &mut std::collections::BTreeMap<Uuid, Layer>,
+        children: &std::collections::BTreeMap<Uuid, Vec<Uuid>>,
+    ) -> Option<Layer> {
+        let mut layer = layers.remove(&id)?;
+        if let Some(slot) = layer.children_mut() {
+            for child in children.get(&id).into_iter().flatten() {
+                slot.extend(attach(*child, layers, children));
+            }
+        }
+        Some(layer)
+    }
+
+    let mut page = Page::new();
+    for id in roots {
+        page.layers.extend(attach(id, &mut layers, &children));
+    }
+    // Whatever is left sits on a parent cycle; surface it at page level
+    while let Some(&id) = layers.keys().next() {
+        page.layers.extend(attach(id, &mut layers, &children));
+    }
+    page.recompute_booleans();
+    Ok(page)
+}
+
+/// Structural diff between two encoded Yrs snapshots.
+pub fn diff_updates(before: &[u8], after: &[u8]) -> Result<crate::diff::DocumentDiff, CollabError> {
+    let before = page_from_update(before)?;
+    let after = page_from_update(after)?;
+    Ok(crate::diff::diff_pages(&before, &after))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,4 +403,59 @@ mod tests {
         // CollabOp has DeleteLayer but no method in engine.
         assert!(true);
     }
+
+    #[test]
+    fn test_diff_between_snapshots() {
+        let doc = Document::new();
+        let mut engine = CollaborationEngine::new(&doc);
+        let encode = |engine: &CollaborationEngine| {
+            let txn = yrs::Transact::transact(&engine.doc);
+            txn.encode_state_as_update_v1(&StateVector::default())
+        };
+
+        let rect = RectLayer::new(0.0, 0.0, 10.0, 10.0);
+        let rect_id = rect.id;
+        engine.add_layer_local(Layer::Rect(rect.clone())).unwrap();
+        let before = encode(&engine);
+
+        let mut resized = rect;
+        resized.bounds.width = 20.0;
+        engine.add_layer_local(Layer::Rect(resized)).unwrap();
+        engine.add_layer_local(Layer::Rect(RectLayer::new(5.0, 5.0, 1.0, 1.0))).unwrap();
+        let after = encode(&engine);
+
+        let page = page_from_update(&after).unwrap();
+        assert_eq!(page.layers.len(), 2);
+
+        let diff = diff_updates(&before, &after).unwrap();
+        assert_eq!(diff.summary(), "1 added, 0 removed, 0 moved, 1 modified");
+        assert!(diff.modified().any(|c| c.id() == rect_id));
+    }
+
+    #[test]
+    fn test_page_from_update_nests_by_parent_id() {
+        use crate::FrameLayer;
+        let frame = FrameLayer { id: Uuid::new_v4(), ..Default::default() };
+        let child = RectLayer::new(1.0, 1.0, 2.0, 2.0);
+        let orphan = RectLayer::new(3.0, 3.0, 2.0, 2.0);
+
+        let doc = Doc::new();
+        let map = doc.get_or_insert_map("layers");
+        {
+            let mut txn = yrs::Transact::transact_mut(&doc);
+            let mut child_json = serde_json::to_value(Layer::Rect(child.clone())).unwrap();
+            child_json["Rect"]["parent_id"] = serde_json::json!(frame.id);
+            let mut orphan_json = serde_json::to_value(Layer::Rect(orphan.clone())).unwrap();
+            orphan_json["Rect"]["parent_id"] = serde_json::json!(Uuid::new_v4());
+            map.insert(&mut txn, frame.id.to_string(), serde_json::to_string(&Layer::Frame(frame.clone())).unwrap());
+            map.insert(&mut txn, child.id.to_string(), child_json.to_string());
+            map.insert(&mut txn, orphan.id.to_string(), orphan_json.to_string());
+        }
+        let update = yrs::Transact::transact(&doc).encode_state_as_update_v1(&StateVector::default());
+
+        let page = page_from_update(&update).unwrap();
+        assert_eq!(page.layers.len(), 2, "frame and dangling orphan at page level");
+        let frame_layer = page.layers.iter().find(|l| l.id() == frame.id).unwrap();
+        assert_eq!(frame_layer.children()[0].id(), child.id);
+    }
 }
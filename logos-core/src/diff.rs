@@ -0,0 +1,511 @@
+//! Structural diff between two document states.
+//!
+//! Layers are matched by id. Each matched layer is compared property by
+//! property (see [`Layer::properties`]) and by position in the tree; unmatched
+//! layers are reported as added or removed.
+//!
+//! ```text
+//!  before ──┐                      ┌── Added    { id, parent, index }
+//!           ├── diff_pages() ──────┼── Removed  { id, parent, index }
+//!  after  ──┘                      ├── Moved    { from, to }
+//!                                  └── Modified { [property: before → after] }
+//! ```
+//!
+//! A layer counts as moved when its parent changes, or when it falls outside
+//! the longest common subsequence of its surviving siblings — so inserting
+//! or deleting a neighbour does not make every following sibling "move".
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::query::LayerTree;
+use crate::{Document, LayerKind, Page};
+
+/// Position of a layer in the tree.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct LayerPosition {
+    /// Parent frame, `None` for page-level layers
+    pub parent_id: Option<Uuid>,
+    /// Index among the parent's children
+    pub index: usize,
+}
+
+/// A single property that differs between the two versions.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PropertyChange {
+    /// Dotted property path, e.g. `bounds.x`
+    pub property: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// One structural change to a layer.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum LayerChange {
+    Added {
+        id: Uuid,
+        kind: LayerKind,
+        name: String,
+        position: LayerPosition,
+    },
+    Removed {
+        id: Uuid,
+        kind: LayerKind,
+        name: String,
+        position: LayerPosition,
+    },
+    Moved {
+        id: Uuid,
+        kind: LayerKind,
+        name: String,
+        from: LayerPosition,
+        to: LayerPosition,
+    },
+    Modified {
+        id: Uuid,
+        kind: LayerKind,
+        name: String,
+        changes: Vec<PropertyChange>,
+    },
+}
+
+impl LayerChange {
+    pub fn id(&self) -> Uuid {
+        match self {
+            LayerChange::Added { id, .. }
+            | LayerChange::Removed { id, .. }
+            | LayerChange::Moved { id, .. }
+            | LayerChange::Modified { id, .. } => *id,
+        }
+    }
+}
+
+/// The full change set between two document states.
+///
+/// Removals come first (in `before` tree order), followed by additions,
+/// moves and modifications in `after` tree order. A layer that was both
+/// moved and edited appears once as `Moved` and once as `Modified`.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct DocumentDiff {
+    pub changes: Vec<LayerChange>,
+}
+
+impl DocumentDiff {
+    /// Whether the two versions are structurally identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &LayerChange> {
+        self.changes.iter().filter(|c| matches!(c, LayerChange::Added { .. }))
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &LayerChange> {
+        self.changes.iter().filter(|c| matches!(c, LayerChange::Removed { .. }))
+    }
+
+    pub fn moved(&self) -> impl Iterator<Item = &LayerChange> {
+        self.changes.iter().filter(|c| matches!(c, LayerChange::Moved { .. }))
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &LayerChange> {
+        self.changes.iter().filter(|c| matches!(c, LayerChange::Modified { .. }))
+    }
+
+    /// One-line count summary, e.g. `2 added, 1 removed, 0 moved, 3 modified`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} removed, {} moved, {} modified",
+            self.added().count(),
+            self.removed().count(),
+            self.moved().count(),
+            self.modified().count(),
+        )
+    }
+}
+
+/// Multi-line human-readable report: the summary line followed by one line
+/// per change (`+` added, `-` removed, `>` moved, `~` modified).
+impl fmt::Display for DocumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())?;
+        for change in &self.changes {
+            writeln!(f)?;
+            match change {
+                LayerChange::Added { id, kind, name, position } => {
+                    write!(f, "+ {} {name:?} ({id}) at {}", kind.as_str(), DisplayPos(position))?;
+                }
+                LayerChange::Removed { id, kind, name, position } => {
+                    write!(f, "- {} {name:?} ({id}) from {}", kind.as_str(), DisplayPos(position))?;
+                }
+                LayerChange::Moved { id, kind, name, from, to } => {
+                    write!(
+                        f,
+                        "> {} {name:?} ({id}) {} -> {}",
+                        kind.as_str(),
+                        DisplayPos(from),
+                        DisplayPos(to),
+                    )?;
+                }
+                LayerChange::Modified { id, kind, name, changes } => {
+                    write!(f, "~ {} {name:?} ({id}):", kind.as_str())?;
+                    for (i, c) in changes.iter().enumerate() {
+                        let sep = if i == 0 { " " } else { "; " };
+                        write!(f, "{sep}{} {} -> {}", c.property, c.before, c.after)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DisplayPos<'a>(&'a LayerPosition);
+
+impl fmt::Display for DisplayPos<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.parent_id {
+            Some(parent) => write!(f, "{parent}[{}]", self.0.index),
+            None => write!(f, "page[{}]", self.0.index),
+        }
+    }
+}
+
+/// Compute the structural diff between two pages.
+pub fn diff_pages(before: &Page, after: &Page) -> DocumentDiff {
+    let old = LayerTree::build(&before.layers);
+    let new = LayerTree::build(&after.layers);
+
+    let old_index: HashMap<Uuid, usize> =
+        old.nodes.iter().enumerate().map(|(i, n)| (n.layer.id(), i)).collect();
+    let new_index: HashMap<Uuid, usize> =
+        new.nodes.iter().enumerate().map(|(i, n)| (n.layer.id(), i)).collect();
+
+    let position = |tree: &LayerTree<'_>, i: usize| LayerPosition {
+        parent_id: tree.parent_id(i),
+        index: tree.nodes[i].position,
+    };
+
+    let mut changes = Vec::new();
+
+    for (i, node) in old.nodes.iter().enumerate() {
+        if !new_index.contains_key(&node.layer.id()) {
+            changes.push(LayerChange::Removed {
+                id: node.layer.id(),
+                kind: node.layer.kind(),
+                name: node.layer.name().to_string(),
+                position: position(&old, i),
+            });
+        }
+    }
+
+    let reordered = reordered_siblings(&old, &new, &old_index, &new_index);
+
+    for (j, node) in new.nodes.iter().enumerate() {
+        let layer = node.layer;
+        let id = layer.id();
+        let Some(&i) = old_index.get(&id) else {
+            changes.push(LayerChange::Added {
+                id,
+                kind: layer.kind(),
+                name: layer.name().to_string(),
+                position: position(&new, j),
+            });
+            continue;
+        };
+
+        let from = position(&old, i);
+        let to = position(&new, j);
+        if from.parent_id != to.parent_id || reordered.contains(&id) {
+            changes.push(LayerChange::Moved {
+                id,
+                kind: layer.kind(),
+                name: layer.name().to_string(),
+                from,
+                to,
+            });
+        }
+
+        let before_props = old.nodes[i].layer.properties();
+        let after_props = layer.properties();
+        let mut props = Vec::new();
+        for (key, after_value) in &after_props {
+            let before_value = before_props
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or(Value::Null, |(_, v)| v.clone());
+            if before_value != *after_value {
+                props.push(PropertyChange {
+                    property: key.to_string(),
+                    before: before_value,
+                    after: after_value.clone(),
+                });
+            }
+        }
+        // Properties that only exist on the old layer (e.g. kind changed away from text)
+        for (key, before_value) in &before_props {
+            if !after_props.iter().any(|(k, _)| k == key) {
+                props.push(PropertyChange {
+                    property: key.to_string(),
+                    before: before_value.clone(),
+                    after: Value::Null,
+                });
+            }
+        }
+        if !props.is_empty() {
+            changes.push(LayerChange::Modified {
+                id,
+                kind: layer.kind(),
+                name: layer.name().to_string(),
+                changes: props,
+            });
+        }
+    }
+
+    DocumentDiff { changes }
+}
+
+/// Ids that kept their parent but changed order relative to their
+/// surviving siblings (those outside the LCS of the two sibling orders).
+fn reordered_siblings(
+    old: &LayerTree<'_>,
+    new: &LayerTree<'_>,
+    old_index: &HashMap<Uuid, usize>,
+    new_index: &HashMap<Uuid, usize>,
+) -> HashSet<Uuid> {
+    let siblings = |tree: &LayerTree<'_>, other: &HashMap<Uuid, usize>, other_tree: &LayerTree<'_>| {
+        let mut groups: HashMap<Option<Uuid>, Vec<Uuid>> = HashMap::new();
+        for (i, node) in tree.nodes.iter().enumerate() {
+            let id = node.layer.id();
+            let parent = tree.parent_id(i);
+            // Only siblings that survive under the same parent take part
+            if other.get(&id).is_some_and(|&k| other_tree.parent_id(k) == parent) {
+                groups.entry(parent).or_default().push(id);
+            }
+        }
+        groups
+    };
+
+    let old_groups = siblings(old, new_index, new);
+    let new_groups = siblings(new, old_index, old);
+
+    let mut reordered = HashSet::new();
+    for (parent, after) in &new_groups {
+        let Some(before) = old_groups.get(parent) else { continue };
+        if before == after {
+            continue;
+        }
+        let kept = lcs(before, after);
+        reordered.extend(after.iter().filter(|id| !kept.contains(id)).copied());
+    }
+    reordered
+}
+
+/// Longest common subsequence of two id sequences.
+fn lcs(a: &[Uuid], b: &[Uuid]) -> HashSet<Uuid> {
+    let (n, m) = (a.len(), b.len());
+    let mut table = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut kept = HashSet::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            kept.insert(a[i]);
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    kept
+}
+
+impl Document {
+    /// Diff this document (as "before") against `other` (as "after").
+    pub fn diff(&self, other: &Document) -> Result<DocumentDiff, String> {
+        let before = self.root.read().map_err(|e| e.to_string())?;
+        let after = other.root.read().map_err(|e| e.to_string())?;
+        Ok(diff_pages(&before, &after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameLayer, Layer, Rect, RectLayer, TextLayer};
+    use serde_json::json;
+
+    fn named_rect(name: &str) -> RectLayer {
+        let mut r = RectLayer::new(0.0, 0.0, 10.0, 10.0);
+        r.name = name.into();
+        r
+    }
+
+    fn page_of(layers: Vec<Layer>) -> Page {
+        let mut page = Page::new();
+        page.layers = layers;
+        page
+    }
+
+    #[test]
+    fn test_identical_pages_have_empty_diff() {
+        let page = page_of(vec![Layer::Rect(named_rect("A"))]);
+        let diff = diff_pages(&page, &page.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "0 added, 0 removed, 0 moved, 0 modified");
+    }
+
+    #[test]
+    fn test_added_and_removed() {
+        let a = named_rect("A");
+        let b = named_rect("B");
+        let c = named_rect("C");
+        let (b_id, c_id) = (b.id, c.id);
+
+        let before = page_of(vec![Layer::Rect(a.clone()), Layer::Rect(b)]);
+        let after = page_of(vec![Layer::Rect(a), Layer::Rect(c)]);
+        let diff = diff_pages(&before, &after);
+
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(
+            diff.changes[0],
+            LayerChange::Removed {
+                id: b_id,
+                kind: LayerKind::Rect,
+                name: "B".into(),
+                position: LayerPosition { parent_id: None, index: 1 },
+            }
+        );
+        assert!(matches!(diff.changes[1], LayerChange::Added { id, .. } if id == c_id));
+        assert_eq!(diff.moved().count(), 0, "A keeps its place");
+    }
+
+    #[test]
+    fn test_modified_properties_carry_before_and_after() {
+        let rect = named_rect("Button");
+        let mut edited = rect.clone();
+        edited.bounds.x = 25.0;
+        edited.name = "Primary Button".into();
+
+        let diff = diff_pages(&page_of(vec![Layer::Rect(rect)]), &page_of(vec![Layer::Rect(edited)]));
+        let LayerChange::Modified { changes, .. } = &diff.changes[0] else {
+            panic!("expected Modified, got {:?}", diff.changes[0]);
+        };
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].property, "name");
+        assert_eq!(changes[0].before, json!("Button"));
+        assert_eq!(changes[0].after, json!("Primary Button"));
+        assert_eq!(changes[1].property, "bounds.x");
+        assert_eq!(changes[1].after, json!(25.0));
+    }
+
+    #[test]
+    fn test_text_content_change() {
+        let text = TextLayer { id: Uuid::new_v4(), content: "Hello".into(), ..Default::default() };
+        let mut edited = text.clone();
+        edited.content = "Hello, world".into();
+        let diff = diff_pages(&page_of(vec![Layer::Text(text)]), &page_of(vec![Layer::Text(edited)]));
+        let LayerChange::Modified { changes, .. } = &diff.changes[0] else { panic!() };
+        assert_eq!(changes[0].property, "content");
+    }
+
+    #[test]
+    fn test_reparent_is_a_move() {
+        let child = named_rect("Child");
+        let child_id = child.id;
+        let frame = FrameLayer {
+            id: Uuid::new_v4(),
+            bounds: Rect { x: 0.0, y: 0.0, width: 100.0, height: 100.0 },
+            ..Default::default()
+        };
+        let frame_id = frame.id;
+
+        let before = page_of(vec![Layer::Frame(frame.clone()), Layer::Rect(child.clone())]);
+        let mut moved_frame = frame;
+        moved_frame.children.push(Layer::Rect(child));
+        let after = page_of(vec![Layer::Frame(moved_frame)]);
+
+        let diff = diff_pages(&before, &after);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(
+            diff.changes[0],
+            LayerChange::Moved {
+                id: child_id,
+                kind: LayerKind::Rect,
+                name: "Child".into(),
+                from: LayerPosition { parent_id: None, index: 1 },
+                to: LayerPosition { parent_id: Some(frame_id), index: 0 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_reorder_reports_minimal_moves() {
+        let layers: Vec<RectLayer> = ["A", "B", "C", "D"].iter().map(|n| named_rect(n)).collect();
+        let before = page_of(layers.iter().cloned().map(Layer::Rect).collect());
+        // Move A to the end: B C D A
+        let mut order = layers.clone();
+        order.rotate_left(1);
+        let after = page_of(order.into_iter().map(Layer::Rect).collect());
+
+        let diff = diff_pages(&before, &after);
+        let moved: Vec<Uuid> = diff.moved().map(LayerChange::id).collect();
+        assert_eq!(moved, vec![layers[0].id]);
+    }
+
+    #[test]
+    fn test_insertion_does_not_move_siblings() {
+        let a = named_rect("A");
+        let b = named_rect("B");
+        let before = page_of(vec![Layer::Rect(a.clone()), Layer::Rect(b.clone())]);
+        let after = page_of(vec![Layer::Rect(named_rect("New")), Layer::Rect(a), Layer::Rect(b)]);
+        let diff = diff_pages(&before, &after);
+        assert_eq!(diff.summary(), "1 added, 0 removed, 0 moved, 0 modified");
+    }
+
+    #[test]
+    fn test_document_diff_and_display() {
+        let before = Document::new();
+        let after = Document::new();
+        let rect = named_rect("Box");
+        before.add_layer(Layer::Rect(rect.clone())).unwrap();
+        let mut edited = rect;
+        edited.bounds.width = 42.0;
+        after.add_layer(Layer::Rect(edited)).unwrap();
+        after.add_layer(Layer::Rect(named_rect("Extra"))).unwrap();
+
+        let diff = before.diff(&after).unwrap();
+        let report = diff.to_string();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "1 added, 0 removed, 0 moved, 1 modified");
+        assert!(lines[1].starts_with("~ rect \"Box\""));
+        assert!(lines[1].ends_with("bounds.width 10.0 -> 42.0"));
+        assert!(lines[2].starts_with("+ rect \"Extra\""));
+        assert!(lines[2].ends_with("at page[1]"));
+    }
+
+    #[test]
+    fn test_diff_serializes() {
+        let before = page_of(vec![]);
+        let after = page_of(vec![Layer::Rect(named_rect("A"))]);
+        let diff = diff_pages(&before, &after);
+        let json = serde_json::to_string(&diff).unwrap();
+        let back: DocumentDiff = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, diff);
+    }
+}
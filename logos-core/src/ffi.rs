@@ -1,13 +1,133 @@
-use std::sync::Arc;
-use std::ffi::{CString, c_char};
-use crate::{Document, RectLayer, Layer};
+//! C ABI for embedding logos-core in a native host.
+//!
+//! Conventions (see ADR-001):
+//! - Handles are opaque; every `*_new` has a matching `*_free`.
+//! - Fallible calls return a [`LogosError`] and, when `error_out` is non-null,
+//!   store a message there that the caller releases with [`logos_string_free`].
+//! - Layer ids cross the boundary as UUID strings. A null `parent_id` means
+//!   the page root.
+//! - Strings returned through `*_out` parameters are owned by the caller and
+//!   released with [`logos_string_free`]; byte buffers with [`logos_buffer_free`].
+//!
+//! The C header lives at `include/logos_core.h` and is generated with
+//! `cbindgen --config cbindgen.toml --output include/logos_core.h`.
+
+// Entry points are called from C, where `unsafe` means nothing; pointer
+// arguments are null-checked at the boundary instead.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::sync::{Arc, Mutex};
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+use uuid::Uuid;
+use crate::collab::CollaborationEngine;
+use crate::{BoolLayer, Document, EllipseLayer, FrameLayer, Layer, Rect, RectLayer, TextLayer};
+
+// Handles are deliberately not `repr(C)`: cbindgen then declares them as
+// incomplete structs (`typedef struct X X;`) and C only holds pointers.
 
 /// Opaque handle type for C FFI
-#[repr(C)]
 pub struct LogosDocument {
     _private: [u8; 0],  // Prevent direct construction
 }
 
+/// Opaque handle to a collaboration engine
+pub struct LogosCollab {
+    _private: [u8; 0],
+}
+
+/// Status code returned by every fallible call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogosError {
+    Ok = 0,
+    NullPointer = -1,
+    InvalidArgument = -2,
+    NotFound = -3,
+    Serialization = -4,
+    Collab = -5,
+    LockPoisoned = -6,
+    OutOfRange = -7,
+}
+
+impl LogosError {
+    const ALL: [Self; 8] = [
+        Self::Ok,
+        Self::NullPointer,
+        Self::InvalidArgument,
+        Self::NotFound,
+        Self::Serialization,
+        Self::Collab,
+        Self::LockPoisoned,
+        Self::OutOfRange,
+    ];
+}
+
+/// Layer type tag.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogosLayerKind {
+    Rect = 0,
+    Ellipse = 1,
+    Text = 2,
+    Frame = 3,
+    Bool = 4,
+}
+
+impl LogosLayerKind {
+    const ALL: [Self; 5] = [Self::Rect, Self::Ellipse, Self::Text, Self::Frame, Self::Bool];
+}
+
+/// Axis-aligned bounds in document units.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LogosRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Byte buffer owned by the caller; release with `logos_buffer_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct LogosBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl From<&Rect> for LogosRect {
+    fn from(r: &Rect) -> Self {
+        Self { x: r.x, y: r.y, width: r.width, height: r.height }
+    }
+}
+
+impl From<LogosRect> for Rect {
+    fn from(r: LogosRect) -> Self {
+        Self { x: r.x, y: r.y, width: r.width, height: r.height }
+    }
+}
+
+impl From<crate::LayerKind> for LogosLayerKind {
+    fn from(kind: crate::LayerKind) -> Self {
+        match kind {
+            crate::LayerKind::Rect => LogosLayerKind::Rect,
+            crate::LayerKind::Ellipse => LogosLayerKind::Ellipse,
+            crate::LayerKind::Text => LogosLayerKind::Text,
+            crate::LayerKind::Frame => LogosLayerKind::Frame,
+            crate::LayerKind::Bool => LogosLayerKind::Bool,
+        }
+    }
+}
+
+// ─── Handle & Argument Helpers ──────────────────────────────────────────────
+
+type FfiResult<T> = Result<T, (LogosError, String)>;
+
+fn fail<T>(code: LogosError, message: impl Into<String>) -> FfiResult<T> {
+    Err((code, message.into()))
+}
+
 /// Convert between Arc<Document> and FFI handle
 fn into_ffi_handle(arc: Arc<Document>) -> *mut LogosDocument {
     let arc_ptr: *mut Arc<Document> = Box::into_raw(Box::new(arc));
@@ -23,6 +143,165 @@ unsafe fn arc_from_ffi(ptr: *mut LogosDocument) -> Option<Arc<Document>> {
     Some((*arc_ptr).clone()) // Clones the Arc, increasing ref count
 }
 
+unsafe fn document_arg(ptr: *mut LogosDocument) -> FfiResult<Arc<Document>> {
+    arc_from_ffi(ptr).ok_or((LogosError::NullPointer, "Null document pointer".to_string()))
+}
+
+unsafe fn collab_arg<'a>(ptr: *mut LogosCollab) -> FfiResult<&'a Mutex<CollaborationEngine>> {
+    if ptr.is_null() {
+        return fail(LogosError::NullPointer, "Null collab pointer");
+    }
+    Ok(&*(ptr as *const Mutex<CollaborationEngine>))
+}
+
+unsafe fn str_arg<'a>(ptr: *const c_char, what: &str) -> FfiResult<&'a str> {
+    if ptr.is_null() {
+        return fail(LogosError::NullPointer, format!("Null {what}"));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|e| (LogosError::InvalidArgument, format!("{what} is not UTF-8: {e}")))
+}
+
+unsafe fn id_arg(ptr: *const c_char, what: &str) -> FfiResult<Uuid> {
+    str_arg(ptr, what)?
+        .parse()
+        .map_err(|e| (LogosError::InvalidArgument, format!("{what} is not a UUID: {e}")))
+}
+
+/// Null means the page root.
+unsafe fn parent_arg(ptr: *const c_char) -> FfiResult<Option<Uuid>> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        id_arg(ptr, "parent id").map(Some)
+    }
+}
+
+unsafe fn bytes_arg<'a>(data: *const u8, len: usize) -> FfiResult<&'a [u8]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return fail(LogosError::NullPointer, "Null data pointer");
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+unsafe fn out_arg<'a, T>(ptr: *mut T) -> FfiResult<&'a mut T> {
+    ptr.as_mut().ok_or((LogosError::NullPointer, "Null output pointer".to_string()))
+}
+
+unsafe fn write_string(out: *mut *mut c_char, value: String) -> FfiResult<()> {
+    let out = out_arg(out)?;
+    let c = CString::new(value)
+        .map_err(|e| (LogosError::Serialization, format!("String contains NUL: {e}")))?;
+    *out = c.into_raw();
+    Ok(())
+}
+
+unsafe fn write_buffer(out: *mut LogosBuffer, bytes: Vec<u8>) -> FfiResult<()> {
+    let out = out_arg(out)?;
+    let boxed = bytes.into_boxed_slice();
+    let len = boxed.len();
+    *out = LogosBuffer { data: Box::into_raw(boxed) as *mut u8, len };
+    Ok(())
+}
+
+fn lock_error(e: impl ToString) -> (LogosError, String) {
+    (LogosError::LockPoisoned, e.to_string())
+}
+
+fn serde_error(e: impl ToString) -> (LogosError, String) {
+    (LogosError::Serialization, e.to_string())
+}
+
+fn collab_error(e: crate::collab::CollabError) -> (LogosError, String) {
+    (LogosError::Collab, format!("{e:?}"))
+}
+
+fn not_found(id: Uuid) -> (LogosError, String) {
+    (LogosError::NotFound, format!("No layer {id}"))
+}
+
+/// Run a call body and translate its result into a status code.
+fn run(error_out: *mut *mut c_char, body: impl FnOnce() -> FfiResult<()>) -> LogosError {
+    match body() {
+        Ok(()) => LogosError::Ok,
+        Err((code, message)) => {
+            if !error_out.is_null() {
+                let error_msg = CString::new(message).unwrap_or_default();
+                unsafe { *error_out = error_msg.into_raw() };
+            }
+            code
+        }
+    }
+}
+
+/// Insert under `parent` at `index`, appending when `index` is past the end.
+fn insert_layer(doc: &Document, parent: Option<Uuid>, index: usize, layer: Layer) -> FfiResult<()> {
+    let mut page = doc.root.write().map_err(lock_error)?;
+    if page.find_layer(layer.id()).is_some() {
+        return fail(LogosError::InvalidArgument, format!("Layer {} already exists", layer.id()));
+    }
+    let siblings = page.children_of_mut(parent).ok_or_else(|| match parent {
+        Some(p) => (LogosError::NotFound, format!("No frame or boolean group {p}")),
+        None => (LogosError::NotFound, "No page".to_string()),
+    })?;
+    let index = index.min(siblings.len());
+    siblings.insert(index, layer);
+    if let Some(p) = parent {
+        page.update_layer(p, |_| ());
+    }
+    Ok(())
+}
+
+// ─── Memory ─────────────────────────────────────────────────────────────────
+
+/// Free a string returned by this library. Null is ignored.
+#[no_mangle]
+pub extern "C" fn logos_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        unsafe {
+            let _ = CString::from_raw(s);
+        }
+    }
+}
+
+/// Free a buffer returned by this library. An empty buffer is ignored.
+#[no_mangle]
+pub extern "C" fn logos_buffer_free(buffer: LogosBuffer) {
+    if !buffer.data.is_null() {
+        unsafe {
+            let slice = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+            let _ = Box::from_raw(slice);
+        }
+    }
+}
+
+/// Static name for a status code, e.g. "not found"; "unknown" for values
+/// that are not a `LogosError`. Never free it.
+#[no_mangle]
+pub extern "C" fn logos_error_name(code: i32) -> *const c_char {
+    // C may pass any int; never turn it into a `LogosError` unchecked
+    let Some(code) = LogosError::ALL.into_iter().find(|e| *e as i32 == code) else {
+        return c"unknown".as_ptr();
+    };
+    let name: &'static CStr = match code {
+        LogosError::Ok => c"ok",
+        LogosError::NullPointer => c"null pointer",
+        LogosError::InvalidArgument => c"invalid argument",
+        LogosError::NotFound => c"not found",
+        LogosError::Serialization => c"serialization error",
+        LogosError::Collab => c"collaboration error",
+        LogosError::LockPoisoned => c"lock poisoned",
+        LogosError::OutOfRange => c"index out of range",
+    };
+    name.as_ptr()
+}
+
+// ─── Document ───────────────────────────────────────────────────────────────
+
 #[no_mangle]
 pub extern "C" fn logos_document_new() -> *mut LogosDocument {
     into_ffi_handle(Arc::new(Document::new()))
@@ -46,37 +325,510 @@ pub extern "C" fn logos_document_add_rect(
     width: f32,
     height: f32,
     error_out: *mut *mut c_char,
-) -> i32 {
-    let doc_arc = unsafe { arc_from_ffi(doc) };
-    
-    let Some(doc) = doc_arc else {
-        if !error_out.is_null() {
-             let error_msg = CString::new("Null document pointer").unwrap();
-             unsafe { *error_out = error_msg.into_raw() };
+) -> LogosError {
+    run(error_out, || {
+        let doc = unsafe { document_arg(doc)? };
+        let rect = RectLayer::new(x, y, width, height);
+        doc.add_layer(Layer::Rect(rect)).map_err(lock_error)
+    })
+}
+
+/// Serialize the whole document as JSON.
+#[no_mangle]
+pub extern "C" fn logos_document_to_json(
+    doc: *mut LogosDocument,
+    json_out: *mut *mut c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let json = serde_json::to_string(&*doc).map_err(serde_error)?;
+        write_string(json_out, json)
+    })
+}
+
+/// Parse a document from JSON produced by `logos_document_to_json`.
+#[no_mangle]
+pub extern "C" fn logos_document_from_json(
+    json: *const c_char,
+    doc_out: *mut *mut LogosDocument,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let out = out_arg(doc_out)?;
+        let doc: Document = serde_json::from_str(str_arg(json, "json")?).map_err(serde_error)?;
+        *out = into_ffi_handle(Arc::new(doc));
+        Ok(())
+    })
+}
+
+// ─── Layer CRUD ─────────────────────────────────────────────────────────────
+
+/// Create a layer of `kind` (a `LogosLayerKind`) with `bounds` and append
+/// it under `parent_id`. The new id is written to `id_out` when non-null.
+#[no_mangle]
+pub extern "C" fn logos_document_add_layer(
+    doc: *mut LogosDocument,
+    kind: i32,
+    bounds: LogosRect,
+    parent_id: *const c_char,
+    id_out: *mut *mut c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let parent = parent_arg(parent_id)?;
+        // C may pass any int; never turn it into a `LogosLayerKind` unchecked
+        let Some(kind) = LogosLayerKind::ALL.into_iter().find(|k| *k as i32 == kind) else {
+            return fail(LogosError::InvalidArgument, format!("Unknown layer kind {kind}"));
+        };
+        let id = Uuid::new_v4();
+        let bounds = Rect::from(bounds);
+        let layer = match kind {
+            LogosLayerKind::Rect => Layer::Rect(RectLayer { id, bounds, ..Default::default() }),
+            LogosLayerKind::Ellipse => Layer::Ellipse(EllipseLayer { id, bounds, ..Default::default() }),
+            LogosLayerKind::Text => Layer::Text(TextLayer { id, bounds, ..Default::default() }),
+            LogosLayerKind::Frame => Layer::Frame(FrameLayer { id, bounds, ..Default::default() }),
+            // Bounds of a boolean group follow its operands
+            LogosLayerKind::Bool => Layer::Bool(BoolLayer { id, ..Default::default() }),
+        };
+        insert_layer(&doc, parent, usize::MAX, layer)?;
+        if !id_out.is_null() {
+            write_string(id_out, id.to_string())?;
         }
-        return -1;
-    };
-    
-    let rect = RectLayer::new(x, y, width, height);
-    
-    match doc.add_layer(Layer::Rect(rect)) {
-        Ok(_) => 0,
-        Err(e) => {
-            if !error_out.is_null() {
-                let error_msg = CString::new(e).unwrap_or_default();
-                unsafe { *error_out = error_msg.into_raw() };
+        Ok(())
+    })
+}
+
+/// Insert a layer given as JSON (`{"Rect": {...}}`) under `parent_id` at
+/// `index`; an index past the end appends.
+#[no_mangle]
+pub extern "C" fn logos_document_insert_layer_json(
+    doc: *mut LogosDocument,
+    parent_id: *const c_char,
+    index: usize,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let parent = parent_arg(parent_id)?;
+        let layer: Layer = serde_json::from_str(str_arg(json, "json")?).map_err(serde_error)?;
+        insert_layer(&doc, parent, index, layer)
+    })
+}
+
+/// Write a layer (with its subtree) as JSON.
+#[no_mangle]
+pub extern "C" fn logos_document_get_layer_json(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let page = doc.root.read().map_err(lock_error)?;
+        let layer = page.find_layer(id).ok_or_else(|| not_found(id))?;
+        write_string(json_out, serde_json::to_string(layer).map_err(serde_error)?)
+    })
+}
+
+/// Replace a layer in place. The JSON must carry the same id.
+#[no_mangle]
+pub extern "C" fn logos_document_replace_layer_json(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let layer: Layer = serde_json::from_str(str_arg(json, "json")?).map_err(serde_error)?;
+        if layer.id() != id {
+            return fail(LogosError::InvalidArgument, format!("JSON has id {}, expected {id}", layer.id()));
+        }
+        let mut page = doc.root.write().map_err(lock_error)?;
+        page.update_layer(id, |slot| *slot = layer).ok_or_else(|| not_found(id))
+    })
+}
+
+/// Remove a layer and its subtree.
+#[no_mangle]
+pub extern "C" fn logos_document_remove_layer(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let mut page = doc.root.write().map_err(lock_error)?;
+        page.remove_layer(id).map(|_| ()).ok_or_else(|| not_found(id))
+    })
+}
+
+/// Move a layer under `parent_id` at `index`; an index past the end appends.
+#[no_mangle]
+pub extern "C" fn logos_document_move_layer(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    parent_id: *const c_char,
+    index: usize,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let parent = parent_arg(parent_id)?;
+        let mut page = doc.root.write().map_err(lock_error)?;
+        let layer = page.find_layer(id).ok_or_else(|| not_found(id))?;
+        if let Some(p) = parent {
+            // A group cannot become its own descendant
+            let mut subtree = crate::query::LayerTree::build(std::slice::from_ref(layer)).nodes.into_iter();
+            if subtree.any(|n| n.layer.id() == p) {
+                return fail(LogosError::InvalidArgument, "Cannot move a layer into itself");
+            }
+            if !matches!(page.find_layer(p), Some(Layer::Frame(_) | Layer::Bool(_))) {
+                return fail(LogosError::NotFound, format!("No frame or boolean group {p}"));
             }
-            -1
+        }
+        let layer = page.remove_layer(id).ok_or_else(|| not_found(id))?;
+        let siblings = page.children_of_mut(parent).ok_or_else(|| not_found(id))?;
+        let index = index.min(siblings.len());
+        siblings.insert(index, layer);
+        if let Some(p) = parent {
+            page.update_layer(p, |_| ());
+        }
+        Ok(())
+    })
+}
+
+// ─── Tree Traversal ─────────────────────────────────────────────────────────
+
+/// Number of children under `parent_id` (page level when null).
+#[no_mangle]
+pub extern "C" fn logos_document_child_count(
+    doc: *mut LogosDocument,
+    parent_id: *const c_char,
+    count_out: *mut usize,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let parent = parent_arg(parent_id)?;
+        let out = out_arg(count_out)?;
+        let page = doc.root.read().map_err(lock_error)?;
+        let children = page
+            .children_of(parent)
+            .ok_or_else(|| (LogosError::NotFound, "No such frame".to_string()))?;
+        *out = children.len();
+        Ok(())
+    })
+}
+
+/// Id of the child at `index` under `parent_id`.
+#[no_mangle]
+pub extern "C" fn logos_document_child_id(
+    doc: *mut LogosDocument,
+    parent_id: *const c_char,
+    index: usize,
+    id_out: *mut *mut c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let parent = parent_arg(parent_id)?;
+        let page = doc.root.read().map_err(lock_error)?;
+        let children = page
+            .children_of(parent)
+            .ok_or_else(|| (LogosError::NotFound, "No such frame".to_string()))?;
+        let child = children
+            .get(index)
+            .ok_or_else(|| (LogosError::OutOfRange, format!("Index {index} of {}", children.len())))?;
+        write_string(id_out, child.id().to_string())
+    })
+}
+
+/// Parent of a layer; writes null for page-level layers.
+#[no_mangle]
+pub extern "C" fn logos_document_parent_id(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    parent_out: *mut *mut c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let page = doc.root.read().map_err(lock_error)?;
+        match page.parent_of(id).ok_or_else(|| not_found(id))? {
+            Some(parent) => write_string(parent_out, parent.to_string()),
+            None => {
+                *out_arg(parent_out)? = ptr::null_mut();
+                Ok(())
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn logos_document_layer_kind(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    kind_out: *mut LogosLayerKind,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let out = out_arg(kind_out)?;
+        let page = doc.root.read().map_err(lock_error)?;
+        *out = page.find_layer(id).ok_or_else(|| not_found(id))?.kind().into();
+        Ok(())
+    })
+}
+
+// ─── Properties ─────────────────────────────────────────────────────────────
+
+/// Read a property (`name`, `bounds.x`, `content`, ...) as a JSON value.
+#[no_mangle]
+pub extern "C" fn logos_layer_get_property(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    name: *const c_char,
+    value_json_out: *mut *mut c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let name = str_arg(name, "property name")?;
+        let page = doc.root.read().map_err(lock_error)?;
+        let layer = page.find_layer(id).ok_or_else(|| not_found(id))?;
+        let value = layer
+            .property(name)
+            .ok_or_else(|| (LogosError::NotFound, format!("No property {name}")))?;
+        write_string(value_json_out, value.to_string())
+    })
+}
+
+/// Set a property from a JSON value, e.g. `"\"Title\""` or `42`.
+#[no_mangle]
+pub extern "C" fn logos_layer_set_property(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    name: *const c_char,
+    value_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let name = str_arg(name, "property name")?;
+        let value: serde_json::Value =
+            serde_json::from_str(str_arg(value_json, "value")?).map_err(serde_error)?;
+        let mut page = doc.root.write().map_err(lock_error)?;
+        page.update_layer(id, |layer| layer.set_property(name, value))
+            .ok_or_else(|| not_found(id))?
+            .map_err(|e| (LogosError::InvalidArgument, e))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn logos_layer_get_bounds(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    bounds_out: *mut LogosRect,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let out = out_arg(bounds_out)?;
+        let page = doc.root.read().map_err(lock_error)?;
+        *out = page.find_layer(id).ok_or_else(|| not_found(id))?.bounds().into();
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn logos_layer_set_bounds(
+    doc: *mut LogosDocument,
+    id: *const c_char,
+    bounds: LogosRect,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let doc = document_arg(doc)?;
+        let id = id_arg(id, "layer id")?;
+        let mut page = doc.root.write().map_err(lock_error)?;
+        page.update_layer(id, |layer| {
+            for (key, value) in [
+                ("bounds.x", bounds.x),
+                ("bounds.y", bounds.y),
+                ("bounds.width", bounds.width),
+                ("bounds.height", bounds.height),
+            ] {
+                layer.set_property(key, serde_json::json!(value))?;
+            }
+            Ok(())
+        })
+        .ok_or_else(|| not_found(id))?
+        .map_err(|e| (LogosError::InvalidArgument, e))
+    })
+}
+
+// ─── Collaboration ──────────────────────────────────────────────────────────
+
+/// Create a collaboration engine seeded with the document's top-level
+/// layers. Free it with `logos_collab_free`.
+#[no_mangle]
+pub extern "C" fn logos_collab_new(
+    doc: *mut LogosDocument,
+    collab_out: *mut *mut LogosCollab,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let out = out_arg(collab_out)?;
+        let doc = document_arg(doc)?;
+        let mut engine = CollaborationEngine::new(&doc);
+        let layers = doc.root.read().map_err(lock_error)?.layers.clone();
+        for layer in layers {
+            engine.add_layer_local(layer).map_err(collab_error)?;
+        }
+        *out = Box::into_raw(Box::new(Mutex::new(engine))) as *mut LogosCollab;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn logos_collab_free(ptr: *mut LogosCollab) {
+    if !ptr.is_null() {
+        unsafe {
+            let _ = Box::from_raw(ptr as *mut Mutex<CollaborationEngine>);
         }
     }
 }
 
+/// Apply a remote Yrs v1 update.
+#[no_mangle]
+pub extern "C" fn logos_collab_apply_update(
+    collab: *mut LogosCollab,
+    data: *const u8,
+    len: usize,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let engine = collab_arg(collab)?;
+        let update = bytes_arg(data, len)?;
+        engine.lock().map_err(lock_error)?.apply_remote_update(update).map_err(collab_error)?;
+        Ok(())
+    })
+}
+
+/// Insert or replace a layer from JSON; writes the delta to broadcast.
+#[no_mangle]
+pub extern "C" fn logos_collab_upsert_layer_json(
+    collab: *mut LogosCollab,
+    json: *const c_char,
+    delta_out: *mut LogosBuffer,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let engine = collab_arg(collab)?;
+        let layer: Layer = serde_json::from_str(str_arg(json, "json")?).map_err(serde_error)?;
+        let delta = engine.lock().map_err(lock_error)?.add_layer_local(layer).map_err(collab_error)?;
+        write_buffer(delta_out, delta)
+    })
+}
+
+/// Remove a layer; writes the delta to broadcast.
+#[no_mangle]
+pub extern "C" fn logos_collab_remove_layer(
+    collab: *mut LogosCollab,
+    id: *const c_char,
+    delta_out: *mut LogosBuffer,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let engine = collab_arg(collab)?;
+        let id = id_arg(id, "layer id")?;
+        let delta = engine.lock().map_err(lock_error)?.remove_layer_local(id).map_err(|e| match e {
+            crate::collab::CollabError::InvalidOperation(msg) => (LogosError::NotFound, msg),
+            other => collab_error(other),
+        })?;
+        write_buffer(delta_out, delta)
+    })
+}
+
+/// Encoded state vector of the engine.
+#[no_mangle]
+pub extern "C" fn logos_collab_state_vector(
+    collab: *mut LogosCollab,
+    sv_out: *mut LogosBuffer,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let engine = collab_arg(collab)?;
+        let sv = engine.lock().map_err(lock_error)?.state_vector();
+        write_buffer(sv_out, sv)
+    })
+}
+
+/// Encode what a peer with `state_vector` is missing (everything when `len` is 0).
+#[no_mangle]
+pub extern "C" fn logos_collab_encode_update(
+    collab: *mut LogosCollab,
+    state_vector: *const u8,
+    len: usize,
+    update_out: *mut LogosBuffer,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let engine = collab_arg(collab)?;
+        let sv = bytes_arg(state_vector, len)?;
+        let update = engine
+            .lock()
+            .map_err(lock_error)?
+            .encode_state_as_update(sv)
+            .map_err(collab_error)?;
+        write_buffer(update_out, update)
+    })
+}
+
+/// Materialize the engine's current state as a new document handle.
+#[no_mangle]
+pub extern "C" fn logos_collab_to_document(
+    collab: *mut LogosCollab,
+    doc_out: *mut *mut LogosDocument,
+    error_out: *mut *mut c_char,
+) -> LogosError {
+    run(error_out, || unsafe {
+        let engine = collab_arg(collab)?;
+        let out = out_arg(doc_out)?;
+        let page = engine.lock().map_err(lock_error)?.to_page().map_err(collab_error)?;
+        let doc = Document::new();
+        *doc.root.write().map_err(lock_error)? = page;
+        *out = into_ffi_handle(Arc::new(doc));
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::ffi::CStr;
     use std::ptr; // Imported here for tests
 
+    fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let owned = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        logos_string_free(s);
+        owned
+    }
+
     #[test]
     fn test_ffi_lifecycle() {
         let doc = logos_document_new();
@@ -88,11 +840,11 @@ mod tests {
     fn test_add_rect() {
         let doc = logos_document_new();
         let mut err_ptr: *mut c_char = ptr::null_mut();
-        
+
         let result = logos_document_add_rect(doc, 10.0, 10.0, 100.0, 100.0, &mut err_ptr);
-        assert_eq!(result, 0);
+        assert_eq!(result, LogosError::Ok);
         assert!(err_ptr.is_null());
-        
+
         logos_document_free(doc);
     }
 
@@ -100,15 +852,149 @@ mod tests {
     fn test_null_pointer_handling() {
         let mut err_ptr: *mut c_char = ptr::null_mut();
         let result = logos_document_add_rect(ptr::null_mut(), 0.0, 0.0, 0.0, 0.0, &mut err_ptr);
-        assert_eq!(result, -1);
-        
+        assert_eq!(result as i32, -1);
+
         assert!(!err_ptr.is_null());
         unsafe {
             let err_str = CStr::from_ptr(err_ptr).to_str().unwrap();
             assert_eq!(err_str, "Null document pointer");
-            // In real C code, we'd need to free this string. 
-            // Here, we just let it leak for the test or manually free it using CString::from_raw
-            let _ = CString::from_raw(err_ptr); 
         }
+        logos_string_free(err_ptr);
+    }
+
+    #[test]
+    fn test_layer_crud_and_traversal() {
+        let doc = logos_document_new();
+        let mut frame_id = ptr::null_mut();
+        let bounds = LogosRect { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+        let rc = logos_document_add_layer(doc, LogosLayerKind::Frame as i32, bounds, ptr::null(), &mut frame_id, ptr::null_mut());
+        assert_eq!(rc, LogosError::Ok);
+
+        let mut child_id = ptr::null_mut();
+        let rc = logos_document_add_layer(doc, LogosLayerKind::Rect as i32, bounds, frame_id, &mut child_id, ptr::null_mut());
+        assert_eq!(rc, LogosError::Ok);
+
+        let mut count = 0;
+        logos_document_child_count(doc, frame_id, &mut count, ptr::null_mut());
+        assert_eq!(count, 1);
+
+        let mut parent = ptr::null_mut();
+        logos_document_parent_id(doc, child_id, &mut parent, ptr::null_mut());
+        let frame = take_string(frame_id);
+        assert_eq!(take_string(parent), frame);
+
+        // Move the child to the page root, then remove it
+        let rc = logos_document_move_layer(doc, child_id, ptr::null(), 0, ptr::null_mut());
+        assert_eq!(rc, LogosError::Ok);
+        logos_document_child_count(doc, ptr::null(), &mut count, ptr::null_mut());
+        assert_eq!(count, 2);
+
+        assert_eq!(logos_document_remove_layer(doc, child_id, ptr::null_mut()), LogosError::Ok);
+        assert_eq!(logos_document_remove_layer(doc, child_id, ptr::null_mut()), LogosError::NotFound);
+        take_string(child_id);
+        logos_document_free(doc);
+    }
+
+    #[test]
+    fn test_cannot_move_frame_into_itself() {
+        let doc = logos_document_new();
+        let mut frame_id = ptr::null_mut();
+        logos_document_add_layer(doc, LogosLayerKind::Frame as i32, LogosRect::default(), ptr::null(), &mut frame_id, ptr::null_mut());
+        let mut err = ptr::null_mut();
+        let rc = logos_document_move_layer(doc, frame_id, frame_id, 0, &mut err);
+        assert_eq!(rc, LogosError::InvalidArgument);
+        take_string(err);
+        take_string(frame_id);
+        logos_document_free(doc);
+    }
+
+    #[test]
+    fn test_properties() {
+        let doc = logos_document_new();
+        let mut id = ptr::null_mut();
+        logos_document_add_layer(doc, LogosLayerKind::Text as i32, LogosRect::default(), ptr::null(), &mut id, ptr::null_mut());
+
+        let rc = logos_layer_set_property(doc, id, c"content".as_ptr(), c"\"Hello\"".as_ptr(), ptr::null_mut());
+        assert_eq!(rc, LogosError::Ok);
+        let mut value = ptr::null_mut();
+        logos_layer_get_property(doc, id, c"content".as_ptr(), &mut value, ptr::null_mut());
+        assert_eq!(take_string(value), "\"Hello\"");
+
+        let mut err = ptr::null_mut();
+        let rc = logos_layer_set_property(doc, id, c"kind".as_ptr(), c"\"rect\"".as_ptr(), &mut err);
+        assert_eq!(rc, LogosError::InvalidArgument);
+        assert_eq!(take_string(err), "kind is read-only");
+
+        let bounds = LogosRect { x: 1.0, y: 2.0, width: 3.0, height: 4.0 };
+        logos_layer_set_bounds(doc, id, bounds, ptr::null_mut());
+        let mut read = LogosRect::default();
+        logos_layer_get_bounds(doc, id, &mut read, ptr::null_mut());
+        assert_eq!(read, bounds);
+
+        take_string(id);
+        logos_document_free(doc);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let doc = logos_document_new();
+        logos_document_add_rect(doc, 1.0, 2.0, 3.0, 4.0, ptr::null_mut());
+        let mut json = ptr::null_mut();
+        assert_eq!(logos_document_to_json(doc, &mut json, ptr::null_mut()), LogosError::Ok);
+
+        let mut copy = ptr::null_mut();
+        assert_eq!(logos_document_from_json(json, &mut copy, ptr::null_mut()), LogosError::Ok);
+        let mut count = 0;
+        logos_document_child_count(copy, ptr::null(), &mut count, ptr::null_mut());
+        assert_eq!(count, 1);
+
+        take_string(json);
+        logos_document_free(copy);
+        logos_document_free(doc);
+    }
+
+    #[test]
+    fn test_collab_delta_exchange() {
+        let doc = logos_document_new();
+        let (mut a, mut b) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(logos_collab_new(doc, &mut a, ptr::null_mut()), LogosError::Ok);
+        assert_eq!(logos_collab_new(doc, &mut b, ptr::null_mut()), LogosError::Ok);
+        assert_eq!(logos_collab_new(ptr::null_mut(), &mut a, ptr::null_mut()), LogosError::NullPointer);
+
+        let json = serde_json::to_string(&Layer::Rect(RectLayer::new(0.0, 0.0, 5.0, 5.0))).unwrap();
+        let json = CString::new(json).unwrap();
+        let mut delta = LogosBuffer { data: ptr::null_mut(), len: 0 };
+        assert_eq!(logos_collab_upsert_layer_json(a, json.as_ptr(), &mut delta, ptr::null_mut()), LogosError::Ok);
+        assert_eq!(logos_collab_apply_update(b, delta.data, delta.len, ptr::null_mut()), LogosError::Ok);
+        logos_buffer_free(delta);
+
+        // Catch `a` up on anything from `b` via state vectors
+        let mut sv = LogosBuffer { data: ptr::null_mut(), len: 0 };
+        logos_collab_state_vector(a, &mut sv, ptr::null_mut());
+        let mut missing = LogosBuffer { data: ptr::null_mut(), len: 0 };
+        assert_eq!(logos_collab_encode_update(b, sv.data, sv.len, &mut missing, ptr::null_mut()), LogosError::Ok);
+        assert_eq!(logos_collab_apply_update(a, missing.data, missing.len, ptr::null_mut()), LogosError::Ok);
+        logos_buffer_free(sv);
+        logos_buffer_free(missing);
+
+        let mut materialized = ptr::null_mut();
+        assert_eq!(logos_collab_to_document(b, &mut materialized, ptr::null_mut()), LogosError::Ok);
+        let mut count = 0;
+        logos_document_child_count(materialized, ptr::null(), &mut count, ptr::null_mut());
+        assert_eq!(count, 1);
+
+        logos_document_free(materialized);
+        logos_collab_free(a);
+        logos_collab_free(b);
+        logos_document_free(doc);
+    }
+
+    #[test]
+    fn test_error_names_are_static() {
+        let name = |code| unsafe { CStr::from_ptr(logos_error_name(code)) }.to_str().unwrap();
+        assert_eq!(name(LogosError::NotFound as i32), "not found");
+        assert_eq!(name(LogosError::OutOfRange as i32), "index out of range");
+        assert_eq!(name(42), "unknown");
+        assert_eq!(name(i32::MIN), "unknown");
     }
 }
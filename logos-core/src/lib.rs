@@ -14,7 +14,7 @@ pub struct SpatialHash {
     pub cell_size: f32,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -55,8 +55,9 @@ impl Document {
     }
 
     /// Adds a layer to the root page. Thread-safe.
-    pub fn add_layer(&self, layer: Layer) -> Result<(), String> {
+    pub fn add_layer(&self, mut layer: Layer) -> Result<(), String> {
         let mut page = self.root.write().map_err(|e| e.to_string())?;
+        layer.recompute_booleans();
         page.layers.push(layer);
         Ok(())
     }
@@ -79,6 +80,107 @@ impl Page {
             spatial_index: None,
         }
     }
+
+    /// Find a layer anywhere in the tree.
+    pub fn find_layer(&self, id: Uuid) -> Option<&Layer> {
+        fn find(layers: &[Layer], id: Uuid) -> Option<&Layer> {
+            layers.iter().find_map(|l| if l.id() == id { Some(l) } else { find(l.children(), id) })
+        }
+        find(&self.layers, id)
+    }
+
+    /// Find a layer anywhere in the tree, mutably.
+    pub fn find_layer_mut(&mut self, id: Uuid) -> Option<&mut Layer> {
+        fn find(layers: &mut [Layer], id: Uuid) -> Option<&mut Layer> {
+            for layer in layers {
+                if layer.id() == id {
+                    return Some(layer);
+                }
+                if let Some(found) = layer.children_mut().and_then(|c| find(c, id)) {
+                    return Some(found);
+                }
+            }
+            None
+        }
+        find(&mut self.layers, id)
+    }
+
+    /// Parent of a layer: `Some(None)` at page level, `None` if not found.
+    pub fn parent_of(&self, id: Uuid) -> Option<Option<Uuid>> {
+        fn find(layers: &[Layer], parent: Option<Uuid>, id: Uuid) -> Option<Option<Uuid>> {
+            layers.iter().find_map(|l| {
+                if l.id() == id {
+                    Some(parent)
+                } else {
+                    find(l.children(), Some(l.id()), id)
+                }
+            })
+        }
+        find(&self.layers, None, id)
+    }
+
+    /// Children of a frame or boolean group, or the page's top-level layers
+    /// for `None`. Returns `None` if the parent is missing or is a leaf.
+    pub fn children_of(&self, parent: Option<Uuid>) -> Option<&[Layer]> {
+        match parent {
+            None => Some(&self.layers),
+            Some(id) => match self.find_layer(id)? {
+                Layer::Frame(f) => Some(&f.children),
+                Layer::Bool(b) => Some(&b.children),
+                _ => None,
+            },
+        }
+    }
+
+    /// Mutable form of [`Page::children_of`].
+    pub fn children_of_mut(&mut self, parent: Option<Uuid>) -> Option<&mut Vec<Layer>> {
+        match parent {
+            None => Some(&mut self.layers),
+            Some(id) => self.find_layer_mut(id)?.children_mut(),
+        }
+    }
+
+    /// Detach a layer (with its subtree) from wherever it sits.
+    pub fn remove_layer(&mut self, id: Uuid) -> Option<Layer> {
+        let parent = self.parent_of(id)?;
+        let siblings = self.children_of_mut(parent)?;
+        let index = siblings.iter().position(|l| l.id() == id)?;
+        let removed = siblings.remove(index);
+        if let Some(parent) = parent {
+            self.update_layer(parent, |_| ());
+        }
+        Some(removed)
+    }
+
+    /// Edit a layer in place, then recompute every boolean group that
+    /// contains it (innermost first) so their result paths stay live.
+    pub fn update_layer<R>(&mut self, id: Uuid, edit: impl FnOnce(&mut Layer) -> R) -> Option<R> {
+        fn walk<R>(layers: &mut [Layer], id: Uuid, edit: &mut Option<impl FnOnce(&mut Layer) -> R>) -> Option<R> {
+            for layer in layers {
+                let result = if layer.id() == id {
+                    edit.take().map(|f| f(layer))
+                } else {
+                    layer.children_mut().and_then(|children| walk(children, id, edit))
+                };
+                if result.is_some() {
+                    if let Layer::Bool(group) = layer {
+                        group.refresh();
+                    }
+                    return result;
+                }
+            }
+            None
+        }
+        walk(&mut self.layers, id, &mut Some(edit))
+    }
+
+    /// Recompute every boolean group on the page, e.g. after bulk edits
+    /// that bypassed [`Page::update_layer`].
+    pub fn recompute_booleans(&mut self) {
+        for layer in &mut self.layers {
+            layer.recompute_booleans();
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -87,41 +189,151 @@ pub enum Layer {
     Ellipse(EllipseLayer),
     Text(TextLayer),
     Frame(FrameLayer),
+    Bool(BoolLayer),
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct RectLayer { 
     pub id: Uuid, 
-    pub bounds: Rect 
+    pub bounds: Rect,
+    /// Display name shown in the layers panel
+    #[serde(default)]
+    pub name: String,
+    /// Shared style this layer references, if any
+    #[serde(default)]
+    pub style_id: Option<Uuid>,
+    /// Main component this layer is an instance of, if any
+    #[serde(default)]
+    pub component_id: Option<Uuid>,
 }
 
 impl RectLayer {
     pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
         Self {
             id: Uuid::new_v4(),
-            bounds: Rect { x, y, width, height }
+            bounds: Rect { x, y, width, height },
+            ..Default::default()
         }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct EllipseLayer { 
     pub id: Uuid, 
-    pub bounds: Rect 
+    pub bounds: Rect,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub style_id: Option<Uuid>,
+    #[serde(default)]
+    pub component_id: Option<Uuid>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct TextLayer { 
     pub id: Uuid, 
     pub content: String, 
-    pub bounds: Rect 
+    pub bounds: Rect,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub style_id: Option<Uuid>,
+    #[serde(default)]
+    pub component_id: Option<Uuid>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct FrameLayer { 
     pub id: Uuid, 
     pub children: Vec<Layer>, 
-    pub bounds: Rect 
+    pub bounds: Rect,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub style_id: Option<Uuid>,
+    #[serde(default)]
+    pub component_id: Option<Uuid>,
+}
+
+/// Boolean group: its children are the operands, combined left to right.
+///
+/// `result` and `bounds` are derived; [`Page::update_layer`] and
+/// [`BoolLayer::recompute`] keep them in step with the operands.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct BoolLayer {
+    pub id: Uuid,
+    pub bool_type: BoolType,
+    pub children: Vec<Layer>,
+    /// Bounding box of `result`
+    pub bounds: Rect,
+    /// Combined outline of the operands
+    #[serde(default)]
+    pub result: VectorPath,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub style_id: Option<Uuid>,
+    #[serde(default)]
+    pub component_id: Option<Uuid>,
+}
+
+impl BoolLayer {
+    pub fn new(bool_type: BoolType, children: Vec<Layer>) -> Self {
+        let mut group = Self { id: Uuid::new_v4(), bool_type, children, ..Default::default() };
+        group.recompute();
+        group
+    }
+
+    /// Recompute nested groups at any depth (including inside frames),
+    /// then this group's result and bounds.
+    pub fn recompute(&mut self) {
+        for child in &mut self.children {
+            child.recompute_booleans();
+        }
+        self.refresh();
+    }
+
+    /// Recompute this group only, trusting nested results.
+    fn refresh(&mut self) {
+        let outlines: Vec<VectorPath> = self.children.iter().map(Layer::outline).collect();
+        self.result = boolean::boolean_all(self.bool_type, &outlines);
+        self.bounds = self.result.bounds().unwrap_or_default();
+    }
+}
+
+/// Discriminant of a [`Layer`], used by queries and selectors.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+pub enum LayerKind {
+    Rect,
+    Ellipse,
+    Text,
+    Frame,
+    Bool,
+}
+
+impl LayerKind {
+    /// Selector keyword for this kind (`rect`, `ellipse`, `text`, `frame`, `bool`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LayerKind::Rect => "rect",
+            LayerKind::Ellipse => "ellipse",
+            LayerKind::Text => "text",
+            LayerKind::Frame => "frame",
+            LayerKind::Bool => "bool",
+        }
+    }
+
+    /// Parse a selector keyword back into a kind.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "rect" => Some(LayerKind::Rect),
+            "ellipse" => Some(LayerKind::Ellipse),
+            "text" => Some(LayerKind::Text),
+            "frame" => Some(LayerKind::Frame),
+            "bool" => Some(LayerKind::Bool),
+            _ => None,
+        }
+    }
 }
 
 impl Layer {
@@ -131,12 +343,202 @@ impl Layer {
             Layer::Ellipse(l) => l.id,
             Layer::Text(l) => l.id,
             Layer::Frame(l) => l.id,
+            Layer::Bool(l) => l.id,
+        }
+    }
+
+    pub fn kind(&self) -> LayerKind {
+        match self {
+            Layer::Rect(_) => LayerKind::Rect,
+            Layer::Ellipse(_) => LayerKind::Ellipse,
+            Layer::Text(_) => LayerKind::Text,
+            Layer::Frame(_) => LayerKind::Frame,
+            Layer::Bool(_) => LayerKind::Bool,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Layer::Rect(l) => &l.name,
+            Layer::Ellipse(l) => &l.name,
+            Layer::Text(l) => &l.name,
+            Layer::Frame(l) => &l.name,
+            Layer::Bool(l) => &l.name,
+        }
+    }
+
+    pub fn bounds(&self) -> &Rect {
+        match self {
+            Layer::Rect(l) => &l.bounds,
+            Layer::Ellipse(l) => &l.bounds,
+            Layer::Text(l) => &l.bounds,
+            Layer::Frame(l) => &l.bounds,
+            Layer::Bool(l) => &l.bounds,
+        }
+    }
+
+    pub fn style_id(&self) -> Option<Uuid> {
+        match self {
+            Layer::Rect(l) => l.style_id,
+            Layer::Ellipse(l) => l.style_id,
+            Layer::Text(l) => l.style_id,
+            Layer::Frame(l) => l.style_id,
+            Layer::Bool(l) => l.style_id,
+        }
+    }
+
+    pub fn component_id(&self) -> Option<Uuid> {
+        match self {
+            Layer::Rect(l) => l.component_id,
+            Layer::Ellipse(l) => l.component_id,
+            Layer::Text(l) => l.component_id,
+            Layer::Frame(l) => l.component_id,
+            Layer::Bool(l) => l.component_id,
+        }
+    }
+
+    /// Direct children (frames and boolean groups have them).
+    pub fn children(&self) -> &[Layer] {
+        match self {
+            Layer::Frame(l) => &l.children,
+            Layer::Bool(l) => &l.children,
+            _ => &[],
+        }
+    }
+
+    /// Direct children, mutably (`None` for non-frames).
+    pub fn children_mut(&mut self) -> Option<&mut Vec<Layer>> {
+        match self {
+            Layer::Frame(l) => Some(&mut l.children),
+            Layer::Bool(l) => Some(&mut l.children),
+            _ => None,
+        }
+    }
+
+    /// Recompute every boolean group in this layer's subtree, this layer
+    /// included, innermost first.
+    pub fn recompute_booleans(&mut self) {
+        match self {
+            Layer::Bool(group) => group.recompute(),
+            other => {
+                for child in other.children_mut().into_iter().flatten() {
+                    child.recompute_booleans();
+                }
+            }
+        }
+    }
+
+    /// Look up one entry of [`Layer::properties`] by key.
+    pub fn property(&self, key: &str) -> Option<serde_json::Value> {
+        self.properties().into_iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Set a property by its [`Layer::properties`] key. `kind` is read-only.
+    pub fn set_property(&mut self, key: &str, value: serde_json::Value) -> Result<(), String> {
+        fn number(value: &serde_json::Value) -> Result<f32, String> {
+            value.as_f64().map(|v| v as f32).ok_or_else(|| format!("expected a number, got {value}"))
+        }
+        fn optional_id(value: &serde_json::Value) -> Result<Option<Uuid>, String> {
+            serde_json::from_value(value.clone()).map_err(|e| e.to_string())
+        }
+
+        if let Layer::Bool(group) = self {
+            match key {
+                "bool_type" => {
+                    group.bool_type = serde_json::from_value(value).map_err(|e| e.to_string())?;
+                    group.refresh();
+                    return Ok(());
+                }
+                k if k.starts_with("bounds.") => {
+                    return Err("bounds of a boolean group are derived from its operands".to_string());
+                }
+                _ => {}
+            }
+        }
+
+        let (name, bounds, style_id, component_id) = match self {
+            Layer::Rect(l) => (&mut l.name, &mut l.bounds, &mut l.style_id, &mut l.component_id),
+            Layer::Ellipse(l) => (&mut l.name, &mut l.bounds, &mut l.style_id, &mut l.component_id),
+            Layer::Text(l) => (&mut l.name, &mut l.bounds, &mut l.style_id, &mut l.component_id),
+            Layer::Frame(l) => (&mut l.name, &mut l.bounds, &mut l.style_id, &mut l.component_id),
+            Layer::Bool(l) => (&mut l.name, &mut l.bounds, &mut l.style_id, &mut l.component_id),
+        };
+        match key {
+            "name" => {
+                *name = value.as_str().ok_or_else(|| format!("expected a string, got {value}"))?.to_string();
+            }
+            "bounds.x" => bounds.x = number(&value)?,
+            "bounds.y" => bounds.y = number(&value)?,
+            "bounds.width" => bounds.width = number(&value)?,
+            "bounds.height" => bounds.height = number(&value)?,
+            "style_id" => *style_id = optional_id(&value)?,
+            "component_id" => *component_id = optional_id(&value)?,
+            "content" => match self {
+                Layer::Text(t) => {
+                    t.content = value.as_str().ok_or_else(|| format!("expected a string, got {value}"))?.to_string();
+                }
+                _ => return Err("only text layers have content".to_string()),
+            },
+            "kind" => return Err("kind is read-only".to_string()),
+            _ => return Err(format!("unknown property {key}")),
+        }
+        Ok(())
+    }
+
+    /// Flat property view used by diffing and scripting.
+    ///
+    /// Keys are dotted paths (`bounds.x`); children are not included.
+    pub fn properties(&self) -> Vec<(&'static str, serde_json::Value)> {
+        use serde_json::json;
+        let b = self.bounds();
+        let mut props = vec![
+            ("kind", json!(self.kind().as_str())),
+            ("name", json!(self.name())),
+            ("bounds.x", json!(b.x)),
+            ("bounds.y", json!(b.y)),
+            ("bounds.width", json!(b.width)),
+            ("bounds.height", json!(b.height)),
+            ("style_id", json!(self.style_id())),
+            ("component_id", json!(self.component_id())),
+        ];
+        if let Layer::Text(t) = self {
+            props.push(("content", json!(t.content)));
+        }
+        if let Layer::Bool(b) = self {
+            props.push(("bool_type", json!(b.bool_type)));
+        }
+        props
+    }
+
+    /// Filled outline used as a boolean operand. Text and frames contribute
+    /// their bounds.
+    pub fn outline(&self) -> VectorPath {
+        match self {
+            Layer::Ellipse(l) => VectorPath::ellipse(&l.bounds),
+            Layer::Bool(l) => l.result.clone(),
+            other => VectorPath::rect(other.bounds()),
         }
     }
 }
 
+impl Rect {
+    /// Whether two rectangles overlap (touching edges do not count).
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
 pub mod ffi;
 pub mod collab;
+pub mod query;
+pub mod diff;
+pub mod validate;
+pub mod boolean;
+
+pub use boolean::{BoolType, VectorPath};
 
 #[cfg(test)]
 mod tests {
@@ -163,4 +565,97 @@ mod tests {
             _ => panic!("Wrong layer type"),
         }
     }
+
+    #[test]
+    fn test_page_tree_helpers() {
+        let child = RectLayer::new(0.0, 0.0, 1.0, 1.0);
+        let child_id = child.id;
+        let frame = FrameLayer { id: Uuid::new_v4(), children: vec![Layer::Rect(child)], ..Default::default() };
+        let frame_id = frame.id;
+        let mut page = Page::new();
+        page.layers.push(Layer::Frame(frame));
+
+        assert_eq!(page.parent_of(child_id), Some(Some(frame_id)));
+        assert_eq!(page.parent_of(frame_id), Some(None));
+        assert_eq!(page.children_of(Some(frame_id)).unwrap().len(), 1);
+        assert!(page.children_of(Some(child_id)).is_none(), "rects have no child list");
+
+        let removed = page.remove_layer(child_id).unwrap();
+        assert_eq!(removed.id(), child_id);
+        assert!(page.find_layer(child_id).is_none());
+    }
+
+    #[test]
+    fn test_set_property() {
+        let mut layer = Layer::Rect(RectLayer::new(0.0, 0.0, 1.0, 1.0));
+        layer.set_property("bounds.x", serde_json::json!(5)).unwrap();
+        layer.set_property("name", serde_json::json!("Card")).unwrap();
+        assert_eq!(layer.property("bounds.x"), Some(serde_json::json!(5.0)));
+        assert_eq!(layer.name(), "Card");
+        assert!(layer.set_property("content", serde_json::json!("x")).is_err());
+        assert!(layer.set_property("bounds.y", serde_json::json!("x")).is_err());
+    }
+
+    #[test]
+    fn test_bool_group_stays_live() {
+        let a = RectLayer::new(0.0, 0.0, 10.0, 10.0);
+        let b = RectLayer::new(5.0, 5.0, 10.0, 10.0);
+        let b_id = b.id;
+        let group = BoolLayer::new(BoolType::Union, vec![Layer::Rect(a), Layer::Rect(b)]);
+        let group_id = group.id;
+        assert_eq!(group.bounds.width, 15.0);
+
+        let mut page = Page::new();
+        page.layers.push(Layer::Bool(group));
+
+        // Moving an operand recomputes the group
+        page.update_layer(b_id, |l| l.set_property("bounds.x", serde_json::json!(20))).unwrap().unwrap();
+        let Some(Layer::Bool(group)) = page.find_layer(group_id) else { panic!("group missing") };
+        assert_eq!(group.bounds.width, 30.0);
+        assert_eq!(group.result.contours.len(), 2);
+
+        // Changing the operation does too; bounds themselves are derived
+        page.update_layer(group_id, |l| l.set_property("bool_type", serde_json::json!("Intersection")))
+            .unwrap()
+            .unwrap();
+        assert!(page.find_layer(group_id).unwrap().outline().is_empty());
+        assert!(page.find_layer_mut(group_id).unwrap().set_property("bounds.x", serde_json::json!(1)).is_err());
+
+        // Removing an operand refreshes the parent group
+        page.update_layer(group_id, |l| l.set_property("bool_type", serde_json::json!("Union"))).unwrap().unwrap();
+        page.remove_layer(b_id).unwrap();
+        assert_eq!(page.find_layer(group_id).unwrap().bounds().width, 10.0);
+
+        // Serde round trip keeps the derived path
+        let json = serde_json::to_string(&page.layers[0]).unwrap();
+        let back: Layer = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.outline(), page.layers[0].outline());
+        assert_eq!(back.kind(), LayerKind::Bool);
+    }
+
+    #[test]
+    fn test_recompute_reaches_groups_inside_frames() {
+        let rect = RectLayer::new(0.0, 0.0, 10.0, 10.0);
+        let rect_id = rect.id;
+        let inner = BoolLayer::new(BoolType::Union, vec![Layer::Rect(rect)]);
+        let inner_id = inner.id;
+        let frame = FrameLayer { id: Uuid::new_v4(), children: vec![Layer::Bool(inner)], ..Default::default() };
+        let outer = BoolLayer::new(BoolType::Union, vec![Layer::Frame(frame)]);
+        let outer_id = outer.id;
+        let mut page = Page::new();
+        page.layers.push(Layer::Bool(outer));
+
+        // Bool → Frame → Bool, edited without update_layer
+        let Some(Layer::Rect(rect)) = page.find_layer_mut(rect_id) else { panic!("rect missing") };
+        rect.bounds.width = 40.0;
+        let Some(Layer::Bool(outer)) = page.find_layer_mut(outer_id) else { panic!("group missing") };
+        outer.recompute();
+        assert_eq!(page.find_layer(inner_id).unwrap().bounds().width, 40.0);
+
+        // The page-level walk goes through the same path
+        let Some(Layer::Rect(rect)) = page.find_layer_mut(rect_id) else { panic!("rect missing") };
+        rect.bounds.width = 25.0;
+        page.recompute_booleans();
+        assert_eq!(page.find_layer(inner_id).unwrap().bounds().width, 25.0);
+    }
 }
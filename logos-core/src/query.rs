@@ -0,0 +1,768 @@
+//! Structured layer queries over a [`Document`].
+//!
+//! Two front-ends share the same matcher:
+//! - [`LayerQuery`] — a builder of typed filters (kind, name glob, style,
+//!   bounds intersection, component instance, ancestor)
+//! - [`Document::select`] — a small CSS-like selector syntax, e.g.
+//!   `frame[name^="Card"] > text`
+//!
+//! Both return layer ids in stable tree order: a pre-order walk of
+//! `Page.layers`, visiting each frame before its children.
+//!
+//! Selector grammar:
+//! ```text
+//! list     := complex ("," complex)*
+//! complex  := compound ((">" | " ") compound)*
+//! compound := ("*" | "rect" | "ellipse" | "text" | "frame")? part*
+//! part     := "#" uuid
+//!           | "[" ("name" | "style" | "component") (op "\"" value "\"")? "]"
+//!           | ":intersects(" x "," y "," w "," h ")"
+//! op       := "=" | "^=" | "$=" | "*=" | "~="      (~= is a glob: * and ?)
+//! ```
+
+use uuid::Uuid;
+
+use crate::{Document, Layer, LayerKind, Page, Rect};
+
+/// Errors produced while parsing or running a query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    /// Selector text could not be parsed
+    Parse { position: usize, message: String },
+    /// The document lock was poisoned
+    LockPoisoned(String),
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Parse { position, message } => {
+                write!(f, "Selector parse error at {position}: {message}")
+            }
+            QueryError::LockPoisoned(e) => write!(f, "Document lock poisoned: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Typed layer filter. All configured filters must match (logical AND);
+/// repeated `kind` calls accept any of the given kinds.
+#[derive(Debug, Clone, Default)]
+pub struct LayerQuery {
+    kinds: Vec<LayerKind>,
+    name_glob: Option<String>,
+    style_id: Option<Uuid>,
+    intersects: Option<Rect>,
+    component_id: Option<Uuid>,
+    ancestor: Option<Uuid>,
+}
+
+impl LayerQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match layers of this kind (may be called several times).
+    pub fn kind(mut self, kind: LayerKind) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    /// Match layer names against a glob (`*` any run, `?` one character).
+    pub fn name_glob(mut self, pattern: impl Into<String>) -> Self {
+        self.name_glob = Some(pattern.into());
+        self
+    }
+
+    /// Match layers referencing the given shared style.
+    pub fn style(mut self, style_id: Uuid) -> Self {
+        self.style_id = Some(style_id);
+        self
+    }
+
+    /// Match layers whose bounds overlap `area`.
+    pub fn intersects(mut self, area: Rect) -> Self {
+        self.intersects = Some(area);
+        self
+    }
+
+    /// Match instances of the given main component.
+    pub fn instance_of(mut self, component_id: Uuid) -> Self {
+        self.component_id = Some(component_id);
+        self
+    }
+
+    /// Match only descendants of the given layer.
+    pub fn within(mut self, ancestor_id: Uuid) -> Self {
+        self.ancestor = Some(ancestor_id);
+        self
+    }
+
+    fn matches(&self, tree: &LayerTree<'_>, index: usize) -> bool {
+        let layer = tree.nodes[index].layer;
+        if !self.kinds.is_empty() && !self.kinds.contains(&layer.kind()) {
+            return false;
+        }
+        if let Some(ref pattern) = self.name_glob {
+            if !glob_match(pattern, layer.name()) {
+                return false;
+            }
+        }
+        if self.style_id.is_some() && layer.style_id() != self.style_id {
+            return false;
+        }
+        if let Some(ref area) = self.intersects {
+            if !layer.bounds().intersects(area) {
+                return false;
+            }
+        }
+        if self.component_id.is_some() && layer.component_id() != self.component_id {
+            return false;
+        }
+        if let Some(ancestor) = self.ancestor {
+            if !tree.ancestors(index).any(|i| tree.nodes[i].layer.id() == ancestor) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// ─── Tree Walk ──────────────────────────────────────────────────────────
+
+pub(crate) struct Node<'a> {
+    pub(crate) layer: &'a Layer,
+    /// Index of the parent node, `None` for page-level layers
+    pub(crate) parent: Option<usize>,
+    /// Position among the parent's children (or among `Page.layers`)
+    pub(crate) position: usize,
+}
+
+/// Pre-order flattening of a layer forest with parent links.
+pub(crate) struct LayerTree<'a> {
+    pub(crate) nodes: Vec<Node<'a>>,
+}
+
+impl<'a> LayerTree<'a> {
+    pub(crate) fn build(layers: &'a [Layer]) -> Self {
+        let mut nodes = Vec::new();
+        let mut stack: Vec<(&'a Layer, Option<usize>, usize)> =
+            layers.iter().enumerate().rev().map(|(i, l)| (l, None, i)).collect();
+        while let Some((layer, parent, position)) = stack.pop() {
+            let index = nodes.len();
+            nodes.push(Node { layer, parent, position });
+            stack.extend(
+                layer.children().iter().enumerate().rev().map(|(i, c)| (c, Some(index), i)),
+            );
+        }
+        Self { nodes }
+    }
+
+    pub(crate) fn parent_id(&self, index: usize) -> Option<Uuid> {
+        self.nodes[index].parent.map(|p| self.nodes[p].layer.id())
+    }
+
+    fn ancestors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.nodes[index].parent, |&i| self.nodes[i].parent)
+    }
+
+    fn collect(&self, mut pred: impl FnMut(usize) -> bool) -> Vec<Uuid> {
+        (0..self.nodes.len())
+            .filter(|&i| pred(i))
+            .map(|i| self.nodes[i].layer.id())
+            .collect()
+    }
+}
+
+impl Page {
+    /// Run a typed query, returning matching ids in tree order.
+    pub fn query(&self, query: &LayerQuery) -> Vec<Uuid> {
+        let tree = LayerTree::build(&self.layers);
+        tree.collect(|i| query.matches(&tree, i))
+    }
+
+    /// Run a parsed selector, returning matching ids in tree order.
+    pub fn select(&self, selector: &Selector) -> Vec<Uuid> {
+        let tree = LayerTree::build(&self.layers);
+        tree.collect(|i| selector.matches(&tree, i))
+    }
+}
+
+impl Document {
+    /// Run a typed query against the root page.
+    pub fn query(&self, query: &LayerQuery) -> Result<Vec<Uuid>, QueryError> {
+        let page = self.root.read().map_err(|e| QueryError::LockPoisoned(e.to_string()))?;
+        Ok(page.query(query))
+    }
+
+    /// Parse and run a selector string against the root page.
+    pub fn select(&self, selector: &str) -> Result<Vec<Uuid>, QueryError> {
+        let selector = Selector::parse(selector)?;
+        let page = self.root.read().map_err(|e| QueryError::LockPoisoned(e.to_string()))?;
+        Ok(page.select(&selector))
+    }
+}
+
+// ─── Selectors ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    Child,
+    Descendant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NameOp {
+    Equals,
+    Prefix,
+    Suffix,
+    Contains,
+    Glob,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Id(Uuid),
+    Name(NameOp, String),
+    HasStyle,
+    Style(Uuid),
+    IsInstance,
+    Component(Uuid),
+    Intersects(Rect),
+}
+
+impl Part {
+    fn matches(&self, layer: &Layer) -> bool {
+        match self {
+            Part::Id(id) => layer.id() == *id,
+            Part::Name(op, value) => {
+                let name = layer.name();
+                match op {
+                    NameOp::Equals => name == value,
+                    NameOp::Prefix => name.starts_with(value.as_str()),
+                    NameOp::Suffix => name.ends_with(value.as_str()),
+                    NameOp::Contains => name.contains(value.as_str()),
+                    NameOp::Glob => glob_match(value, name),
+                }
+            }
+            Part::HasStyle => layer.style_id().is_some(),
+            Part::Style(id) => layer.style_id() == Some(*id),
+            Part::IsInstance => layer.component_id().is_some(),
+            Part::Component(id) => layer.component_id() == Some(*id),
+            Part::Intersects(area) => layer.bounds().intersects(area),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Compound {
+    kind: Option<LayerKind>,
+    parts: Vec<Part>,
+}
+
+impl Compound {
+    fn matches(&self, layer: &Layer) -> bool {
+        self.kind.is_none_or(|k| layer.kind() == k) && self.parts.iter().all(|p| p.matches(layer))
+    }
+}
+
+/// One chain such as `frame > text`: the first compound has no combinator.
+#[derive(Debug, Clone)]
+struct Complex {
+    steps: Vec<(Combinator, Compound)>,
+}
+
+impl Complex {
+    fn matches(&self, tree: &LayerTree<'_>, index: usize) -> bool {
+        self.matches_step(tree, self.steps.len() - 1, index)
+    }
+
+    /// Right-to-left match with backtracking over descendant combinators.
+    fn matches_step(&self, tree: &LayerTree<'_>, step: usize, index: usize) -> bool {
+        let (combinator, ref compound) = self.steps[step];
+        if !compound.matches(tree.nodes[index].layer) {
+            return false;
+        }
+        if step == 0 {
+            return true;
+        }
+        match combinator {
+            Combinator::Child => tree.nodes[index]
+                .parent
+                .is_some_and(|p| self.matches_step(tree, step - 1, p)),
+            Combinator::Descendant => tree
+                .ancestors(index)
+                .any(|a| self.matches_step(tree, step - 1, a)),
+        }
+    }
+}
+
+/// A parsed selector list, reusable across documents.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    alternatives: Vec<Complex>,
+}
+
+impl Selector {
+    /// Parse selector text.
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        Parser { input, pos: 0 }.parse_list()
+    }
+
+    fn matches(&self, tree: &LayerTree<'_>, index: usize) -> bool {
+        self.alternatives.iter().any(|c| c.matches(tree, index))
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Selector::parse(s)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, QueryError> {
+        Err(QueryError::Parse { position: self.pos, message: message.into() })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), QueryError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(format!("expected `{token}`"))
+        }
+    }
+
+    /// Skip whitespace, returning whether any was consumed.
+    fn skip_ws(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+        self.pos > start
+    }
+
+    fn ident(&mut self) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse_list(&mut self) -> Result<Selector, QueryError> {
+        let mut alternatives = Vec::new();
+        loop {
+            self.skip_ws();
+            alternatives.push(self.parse_complex()?);
+            self.skip_ws();
+            if self.peek().is_none() {
+                break;
+            }
+            self.expect(",")?;
+        }
+        Ok(Selector { alternatives })
+    }
+
+    fn parse_complex(&mut self) -> Result<Complex, QueryError> {
+        let mut steps = vec![(Combinator::Descendant, self.parse_compound()?)];
+        loop {
+            let had_ws = self.skip_ws();
+            let combinator = if self.eat(">") {
+                self.skip_ws();
+                Combinator::Child
+            } else if had_ws && !matches!(self.peek(), None | Some(',')) {
+                Combinator::Descendant
+            } else {
+                break;
+            };
+            steps.push((combinator, self.parse_compound()?));
+        }
+        Ok(Complex { steps })
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound, QueryError> {
+        let start = self.pos;
+        let kind = if self.eat("*") {
+            None
+        } else {
+            let keyword = self.ident().to_string();
+            if keyword.is_empty() {
+                None
+            } else {
+                match LayerKind::from_keyword(&keyword) {
+                    Some(kind) => Some(kind),
+                    None => {
+                        self.pos = start;
+                        return self.error(format!("unknown layer kind `{keyword}`"));
+                    }
+                }
+            }
+        };
+
+        let mut parts = Vec::new();
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.bump();
+                    parts.push(Part::Id(self.uuid_token()?));
+                }
+                Some('[') => {
+                    self.bump();
+                    parts.push(self.parse_attribute()?);
+                }
+                Some(':') => {
+                    self.bump();
+                    parts.push(self.parse_pseudo()?);
+                }
+                _ => break,
+            }
+        }
+
+        if self.pos == start {
+            return self.error("expected a selector");
+        }
+        Ok(Compound { kind, parts })
+    }
+
+    fn parse_attribute(&mut self) -> Result<Part, QueryError> {
+        self.skip_ws();
+        let attr = self.ident().to_string();
+        self.skip_ws();
+
+        let op = if self.eat("]") {
+            return match attr.as_str() {
+                "style" => Ok(Part::HasStyle),
+                "component" => Ok(Part::IsInstance),
+                _ => self.error(format!("attribute `{attr}` needs a value")),
+            };
+        } else if self.eat("^=") {
+            NameOp::Prefix
+        } else if self.eat("$=") {
+            NameOp::Suffix
+        } else if self.eat("*=") {
+            NameOp::Contains
+        } else if self.eat("~=") {
+            NameOp::Glob
+        } else if self.eat("=") {
+            NameOp::Equals
+        } else {
+            return self.error("expected an attribute operator");
+        };
+
+        self.skip_ws();
+        let value = self.string()?;
+        self.skip_ws();
+        self.expect("]")?;
+
+        match attr.as_str() {
+            "name" => Ok(Part::Name(op, value)),
+            "style" | "component" if op == NameOp::Equals => {
+                let id = Uuid::parse_str(&value).or_else(|e| self.error(e.to_string()))?;
+                Ok(if attr == "style" { Part::Style(id) } else { Part::Component(id) })
+            }
+            "style" | "component" => self.error(format!("`{attr}` only supports `=`")),
+            _ => self.error(format!("unknown attribute `{attr}`")),
+        }
+    }
+
+    fn parse_pseudo(&mut self) -> Result<Part, QueryError> {
+        let name = self.ident().to_string();
+        if name != "intersects" {
+            return self.error(format!("unknown pseudo-class `:{name}`"));
+        }
+        self.expect("(")?;
+        let mut values = [0.0f32; 4];
+        for (i, value) in values.iter_mut().enumerate() {
+            if i > 0 {
+                self.skip_ws();
+                self.expect(",")?;
+            }
+            self.skip_ws();
+            *value = self.number()?;
+        }
+        self.skip_ws();
+        self.expect(")")?;
+        let [x, y, width, height] = values;
+        Ok(Part::Intersects(Rect { x, y, width, height }))
+    }
+
+    fn string(&mut self) -> Result<String, QueryError> {
+        let quote = match self.peek() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => return self.error("expected a quoted string"),
+        };
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => match self.bump() {
+                    Some(c) => out.push(c),
+                    None => return self.error("unterminated string"),
+                },
+                Some(c) if c == quote => return Ok(out),
+                Some(c) => out.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, QueryError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')) {
+            self.bump();
+        }
+        match self.input[start..self.pos].parse() {
+            Ok(v) => Ok(v),
+            Err(_) => {
+                self.pos = start;
+                self.error("expected a number")
+            }
+        }
+    }
+
+    fn uuid_token(&mut self) -> Result<Uuid, QueryError> {
+        let start = self.pos;
+        let token = self.ident().to_string();
+        match Uuid::parse_str(&token) {
+            Ok(id) => Ok(id),
+            Err(e) => {
+                self.pos = start;
+                self.error(e.to_string())
+            }
+        }
+    }
+}
+
+/// Glob match supporting `*` (any run, including empty) and `?` (one char).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameLayer, RectLayer, TextLayer};
+
+    fn rect_at(name: &str, x: f32, y: f32) -> Layer {
+        let mut r = RectLayer::new(x, y, 10.0, 10.0);
+        r.name = name.into();
+        Layer::Rect(r)
+    }
+
+    fn text(name: &str) -> Layer {
+        Layer::Text(TextLayer {
+            id: Uuid::new_v4(),
+            content: name.into(),
+            name: name.into(),
+            ..Default::default()
+        })
+    }
+
+    fn frame(name: &str, children: Vec<Layer>) -> Layer {
+        Layer::Frame(FrameLayer {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            children,
+            bounds: Rect { x: 0.0, y: 0.0, width: 500.0, height: 500.0 },
+            ..Default::default()
+        })
+    }
+
+    /// Card A { Title, Body { Label } }, Card B { Title }, Sidebar { Title }, Background
+    fn sample() -> (Document, Vec<Uuid>) {
+        let doc = Document::new();
+        let layers = vec![
+            frame("Card A", vec![text("Title"), frame("Body", vec![text("Label")])]),
+            frame("Card B", vec![text("Title")]),
+            frame("Sidebar", vec![text("Title")]),
+            rect_at("Background", 1000.0, 1000.0),
+        ];
+        for l in layers {
+            doc.add_layer(l).unwrap();
+        }
+        let page = doc.root.read().unwrap();
+        let ids = LayerTree::build(&page.layers).collect(|_| true);
+        drop(page);
+        (doc, ids)
+    }
+
+    #[test]
+    fn test_query_returns_tree_order() {
+        let (doc, ids) = sample();
+        let all = doc.query(&LayerQuery::new()).unwrap();
+        assert_eq!(all, ids);
+        assert_eq!(all.len(), 9);
+    }
+
+    #[test]
+    fn test_query_by_kind_and_name_glob() {
+        let (doc, ids) = sample();
+        let frames = doc.query(&LayerQuery::new().kind(LayerKind::Frame).name_glob("Card ?")).unwrap();
+        assert_eq!(frames, vec![ids[0], ids[4]]);
+
+        let either = doc
+            .query(&LayerQuery::new().kind(LayerKind::Rect).kind(LayerKind::Frame).name_glob("*a*"))
+            .unwrap();
+        assert_eq!(either, vec![ids[0], ids[4], ids[6], ids[8]]);
+    }
+
+    #[test]
+    fn test_query_style_component_and_bounds() {
+        let doc = Document::new();
+        let style = Uuid::new_v4();
+        let component = Uuid::new_v4();
+
+        let mut styled = RectLayer::new(0.0, 0.0, 10.0, 10.0);
+        styled.style_id = Some(style);
+        let mut instance = RectLayer::new(100.0, 100.0, 10.0, 10.0);
+        instance.component_id = Some(component);
+        let (styled_id, instance_id) = (styled.id, instance.id);
+        doc.add_layer(Layer::Rect(styled)).unwrap();
+        doc.add_layer(Layer::Rect(instance)).unwrap();
+
+        assert_eq!(doc.query(&LayerQuery::new().style(style)).unwrap(), vec![styled_id]);
+        assert_eq!(doc.query(&LayerQuery::new().instance_of(component)).unwrap(), vec![instance_id]);
+
+        let area = Rect { x: 95.0, y: 95.0, width: 10.0, height: 10.0 };
+        assert_eq!(doc.query(&LayerQuery::new().intersects(area)).unwrap(), vec![instance_id]);
+    }
+
+    #[test]
+    fn test_query_within_ancestor() {
+        let (doc, ids) = sample();
+        let inside = doc.query(&LayerQuery::new().within(ids[0])).unwrap();
+        assert_eq!(inside, vec![ids[1], ids[2], ids[3]]);
+
+        let texts = doc.query(&LayerQuery::new().kind(LayerKind::Text).within(ids[2])).unwrap();
+        assert_eq!(texts, vec![ids[3]]);
+    }
+
+    #[test]
+    fn test_selector_child_combinator() {
+        let (doc, ids) = sample();
+        let titles = doc.select(r#"frame[name^="Card"] > text"#).unwrap();
+        assert_eq!(titles, vec![ids[1], ids[5]]);
+    }
+
+    #[test]
+    fn test_selector_descendant_combinator() {
+        let (doc, ids) = sample();
+        let texts = doc.select(r#"frame[name^="Card"] text"#).unwrap();
+        assert_eq!(texts, vec![ids[1], ids[3], ids[5]]);
+
+        let nested = doc.select("frame frame text").unwrap();
+        assert_eq!(nested, vec![ids[3]]);
+    }
+
+    #[test]
+    fn test_selector_list_and_attributes() {
+        let (doc, ids) = sample();
+        let hits = doc.select(r#"rect, [name$="bar"]"#).unwrap();
+        assert_eq!(hits, vec![ids[6], ids[8]]);
+
+        let globbed = doc.select(r#"*[name~="C*d ?"]"#).unwrap();
+        assert_eq!(globbed, vec![ids[0], ids[4]]);
+
+        let contains = doc.select("[name*='abe']").unwrap();
+        assert_eq!(contains, vec![ids[3]]);
+    }
+
+    #[test]
+    fn test_selector_id_and_intersects() {
+        let (doc, ids) = sample();
+        let by_id = doc.select(&format!("#{} > *", ids[2])).unwrap();
+        assert_eq!(by_id, vec![ids[3]]);
+
+        let far = doc.select("rect:intersects(990, 990, 20, 20)").unwrap();
+        assert_eq!(far, vec![ids[8]]);
+    }
+
+    #[test]
+    fn test_selector_style_and_component_attributes() {
+        let doc = Document::new();
+        let component = Uuid::new_v4();
+        let mut instance = RectLayer::new(0.0, 0.0, 1.0, 1.0);
+        instance.component_id = Some(component);
+        let id = instance.id;
+        doc.add_layer(Layer::Rect(instance)).unwrap();
+        doc.add_layer(rect_at("plain", 0.0, 0.0)).unwrap();
+
+        assert_eq!(doc.select("[component]").unwrap(), vec![id]);
+        assert_eq!(doc.select(&format!("rect[component=\"{component}\"]")).unwrap(), vec![id]);
+        assert!(doc.select("[style]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_selector_parse_errors() {
+        assert!(matches!(Selector::parse("circle"), Err(QueryError::Parse { position: 0, .. })));
+        assert!(Selector::parse("frame >").is_err());
+        assert!(Selector::parse("[name=Card]").is_err());
+        assert!(Selector::parse("[color=\"red\"]").is_err());
+        assert!(Selector::parse("[style^=\"abc\"]").is_err());
+        assert!(Selector::parse(":hover").is_err());
+        assert!(Selector::parse("").is_err());
+        assert!("frame > text, rect".parse::<Selector>().is_ok());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("Card*", "Card"));
+        assert!(glob_match("Card*", "Card / Large"));
+        assert!(glob_match("*Large", "Card / Large"));
+        assert!(glob_match("C?rd", "Cord"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("Card?", "Card"));
+        assert!(!glob_match("a*b", "axxbyy"));
+    }
+}
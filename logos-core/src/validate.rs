@@ -0,0 +1,692 @@
+//! Structural invariant checks and automatic repair.
+//!
+//! Layers travel through Yrs as loose JSON strings in the "layers" map, so a
+//! buggy client can write states the typed model cannot express. The
+//! validator runs at two levels:
+//!
+//! - [`validate_page`] / [`repair_page`] — the typed tree: duplicate ids and
+//!   bad bounds.
+//! - [`validate_doc`] / [`repair_doc`] — the raw Yrs map, which additionally
+//!   catches malformed entries, key/id mismatches, and broken `parent_id`
+//!   links (dangling parents, non-frame parents, cycles).
+//!
+//! Repair is conservative: nothing is deleted unless it cannot be parsed as a
+//! layer at all. Broken parent links are detached to the page root, duplicate
+//! ids get fresh ids, non-finite bounds become `0.0` and negative sizes are
+//! normalized by moving the origin.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value};
+use uuid::Uuid;
+use yrs::types::Map;
+use yrs::{Doc, ReadTxn, StateVector, Transact, Update};
+use yrs::updates::decoder::Decode;
+
+use crate::collab::CollabError;
+use crate::{Document, Layer, Page, Rect};
+
+/// A single invariant violation.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// Entry is not valid layer JSON
+    Malformed { key: String, error: String },
+    /// Map key and the layer's own `id` disagree
+    IdMismatch { key: String, id: Uuid },
+    /// The same id appears more than once in the tree
+    DuplicateId { id: Uuid },
+    /// `parent_id` references a layer that does not exist
+    DanglingParent { id: Uuid, parent_id: Uuid },
+    /// `parent_id` references a layer that cannot have children
+    ParentNotFrame { id: Uuid, parent_id: Uuid },
+    /// Following `parent_id` links loops back (ids sorted)
+    Cycle { ids: Vec<Uuid> },
+    /// A bounds field is NaN, infinite, or missing
+    NonFiniteBounds { id: Uuid, field: String },
+    /// Width or height is negative
+    NegativeSize { id: Uuid, field: String, value: f32 },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Malformed { key, error } => write!(f, "malformed layer {key}: {error}"),
+            Diagnostic::IdMismatch { key, id } => write!(f, "layer stored under {key} has id {id}"),
+            Diagnostic::DuplicateId { id } => write!(f, "duplicate layer id {id}"),
+            Diagnostic::DanglingParent { id, parent_id } => {
+                write!(f, "layer {id} references missing parent {parent_id}")
+            }
+            Diagnostic::ParentNotFrame { id, parent_id } => {
+                write!(f, "layer {id} has non-frame parent {parent_id}")
+            }
+            Diagnostic::Cycle { ids } => {
+                let ids: Vec<String> = ids.iter().map(Uuid::to_string).collect();
+                write!(f, "parent cycle through {}", ids.join(", "))
+            }
+            Diagnostic::NonFiniteBounds { id, field } => write!(f, "layer {id} has non-finite {field}"),
+            Diagnostic::NegativeSize { id, field, value } => {
+                write!(f, "layer {id} has negative {field} ({value})")
+            }
+        }
+    }
+}
+
+/// Outcome of a repair pass.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct RepairReport {
+    /// Violations found and fixed
+    pub fixed: Vec<Diagnostic>,
+    /// Violations still present after repair
+    pub remaining: Vec<Diagnostic>,
+}
+
+impl RepairReport {
+    /// Whether the input already satisfied every invariant.
+    pub fn is_clean(&self) -> bool {
+        self.fixed.is_empty() && self.remaining.is_empty()
+    }
+}
+
+// ─── Typed Tree ─────────────────────────────────────────────────────────────
+
+/// Check a page against all invariants expressible in the typed model.
+pub fn validate_page(page: &Page) -> Vec<Diagnostic> {
+    let mut layers = page.layers.clone();
+    let mut out = Vec::new();
+    check_layers(&mut layers, &mut HashSet::new(), false, &mut out);
+    out
+}
+
+/// Fix every violation in place.
+pub fn repair_page(page: &mut Page) -> RepairReport {
+    let mut fixed = Vec::new();
+    check_layers(&mut page.layers, &mut HashSet::new(), true, &mut fixed);
+    RepairReport { fixed, remaining: validate_page(page) }
+}
+
+impl Document {
+    /// Validate the document's page tree.
+    pub fn validate(&self) -> Result<Vec<Diagnostic>, String> {
+        let page = self.root.read().map_err(|e| e.to_string())?;
+        Ok(validate_page(&page))
+    }
+
+    /// Repair the document's page tree in place.
+    pub fn repair(&self) -> Result<RepairReport, String> {
+        let mut page = self.root.write().map_err(|e| e.to_string())?;
+        Ok(repair_page(&mut page))
+    }
+}
+
+fn check_layers(layers: &mut [Layer], seen: &mut HashSet<Uuid>, repair: bool, out: &mut Vec<Diagnostic>) {
+    for layer in layers {
+        let (id, bounds, children) = match layer {
+            Layer::Rect(l) => (&mut l.id, &mut l.bounds, None),
+            Layer::Ellipse(l) => (&mut l.id, &mut l.bounds, None),
+            Layer::Text(l) => (&mut l.id, &mut l.bounds, None),
+            Layer::Frame(l) => (&mut l.id, &mut l.bounds, Some(&mut l.children)),
+            Layer::Bool(l) => (&mut l.id, &mut l.bounds, Some(&mut l.children)),
+        };
+        if !seen.insert(*id) {
+            out.push(Diagnostic::DuplicateId { id: *id });
+            if repair {
+                *id = Uuid::new_v4();
+                seen.insert(*id);
+            }
+        }
+        check_rect(*id, bounds, repair, out);
+        if let Some(children) = children {
+            check_layers(children, seen, repair, out);
+        }
+    }
+}
+
+fn check_rect(id: Uuid, rect: &mut Rect, repair: bool, out: &mut Vec<Diagnostic>) {
+    for (field, value) in [
+        ("bounds.x", &mut rect.x),
+        ("bounds.y", &mut rect.y),
+        ("bounds.width", &mut rect.width),
+        ("bounds.height", &mut rect.height),
+    ] {
+        if !value.is_finite() {
+            out.push(Diagnostic::NonFiniteBounds { id, field: field.to_string() });
+            if repair {
+                *value = 0.0;
+            }
+        }
+    }
+    if rect.width < 0.0 {
+        out.push(Diagnostic::NegativeSize { id, field: "bounds.width".into(), value: rect.width });
+        if repair {
+            rect.x += rect.width;
+            rect.width = -rect.width;
+        }
+    }
+    if rect.height < 0.0 {
+        out.push(Diagnostic::NegativeSize { id, field: "bounds.height".into(), value: rect.height });
+        if repair {
+            rect.y += rect.height;
+            rect.height = -rect.height;
+        }
+    }
+}
+
+// ─── Yrs Layers Map ─────────────────────────────────────────────────────────
+
+/// A top-level entry of the "layers" map, parsed as far as it will go.
+struct Entry {
+    key: String,
+    /// Variant tag (`Rect`, `Frame`, ...) and the layer body
+    tag: String,
+    body: JsonMap<String, Value>,
+    changed: bool,
+}
+
+impl Entry {
+    fn id(&self) -> Option<Uuid> {
+        self.body.get("id")?.as_str()?.parse().ok()
+    }
+
+    fn parent_id(&self) -> Option<Uuid> {
+        match self.body.get("parent_id") {
+            None | Some(Value::Null) => None,
+            // An unparseable parent can never resolve
+            Some(v) => Some(v.as_str().and_then(|s| s.parse().ok()).unwrap_or_else(Uuid::nil)),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut outer = JsonMap::new();
+        outer.insert(self.tag.clone(), Value::Object(self.body.clone()));
+        Value::Object(outer).to_string()
+    }
+}
+
+/// Split `{"Rect": {...}}` into its tag and body.
+fn split_layer(value: Value) -> Result<(String, JsonMap<String, Value>), String> {
+    let Value::Object(outer) = value else {
+        return Err("expected a JSON object".into());
+    };
+    let mut iter = outer.into_iter();
+    match (iter.next(), iter.next()) {
+        (Some((tag, Value::Object(body))), None)
+            if matches!(tag.as_str(), "Rect" | "Ellipse" | "Text" | "Frame" | "Bool") =>
+        {
+            Ok((tag, body))
+        }
+        _ => Err("expected a single Rect, Ellipse, Text, Frame or Bool variant".into()),
+    }
+}
+
+/// Check the "layers" map of a Yrs document.
+pub fn validate_doc(doc: &Doc) -> Vec<Diagnostic> {
+    let layers_map = doc.get_or_insert_map("layers");
+    let txn = doc.transact();
+    let raw: Vec<(String, String)> = layers_map
+        .iter(&txn)
+        .map(|(k, v)| (k.to_string(), v.to_string(&txn)))
+        .collect();
+    drop(txn);
+    let mut out = Vec::new();
+    check_entries(raw, false, &mut out);
+    out
+}
+
+/// Repair the "layers" map of a Yrs document in a single transaction.
+///
+/// Returns the report and the encoded update for the repair, which is empty
+/// when nothing needed fixing. Callers should broadcast and persist it like
+/// any other delta.
+pub fn repair_doc(doc: &Doc) -> (RepairReport, Vec<u8>) {
+    let layers_map = doc.get_or_insert_map("layers");
+    let before = doc.transact().state_vector();
+    let raw: Vec<(String, String)> = {
+        let txn = doc.transact();
+        layers_map.iter(&txn).map(|(k, v)| (k.to_string(), v.to_string(&txn))).collect()
+    };
+
+    let mut fixed = Vec::new();
+    let writes = check_entries(raw, true, &mut fixed);
+    if !writes.is_empty() {
+        let mut txn = doc.transact_mut();
+        for write in writes {
+            match write {
+                Write::Remove(key) => {
+                    layers_map.remove(&mut txn, &key);
+                }
+                Write::Insert(key, json) => {
+                    layers_map.insert(&mut txn, key, json);
+                }
+            }
+        }
+    }
+
+    let update = if fixed.is_empty() {
+        Vec::new()
+    } else {
+        doc.transact().encode_state_as_update_v1(&before)
+    };
+    (RepairReport { fixed, remaining: validate_doc(doc) }, update)
+}
+
+/// Validate an encoded Yrs update (e.g. a snapshot from the document store).
+pub fn validate_update(update: &[u8]) -> Result<Vec<Diagnostic>, CollabError> {
+    let doc = Doc::new();
+    {
+        let mut txn = doc.transact_mut();
+        txn.apply_update(Update::decode_v1(update)?)
+            .map_err(|e| CollabError::YrsError(e.to_string()))?;
+    }
+    Ok(validate_doc(&doc))
+}
+
+/// Check a full snapshot of an encoded Yrs state for invariants and repair it;
+/// returns the report and the repaired state as a full update.
+pub fn repair_update(update: &[u8]) -> Result<(RepairReport, Vec<u8>), CollabError> {
+    let doc = Doc::new();
+    {
+        let mut txn = doc.transact_mut();
+        txn.apply_update(Update::decode_v1(update)?)
+            .map_err(|e| CollabError::YrsError(e.to_string()))?;
+    }
+    let (report, _) = repair_doc(&doc);
+    let state = doc.transact().encode_state_as_update_v1(&StateVector::default());
+    Ok((report, state))
+}
+
+enum Write {
+    Remove(String),
+    Insert(String, String),
+}
+
+fn check_entries(raw: Vec<(String, String)>, repair: bool, out: &mut Vec<Diagnostic>) -> Vec<Write> {
+    let mut writes = Vec::new();
+
+    // Parse; anything that isn't recognisably a layer is dropped on repair.
+    let mut entries: Vec<Entry> = Vec::new();
+    for (key, json) in raw {
+        let parsed = serde_json::from_str::<Value>(&json)
+            .map_err(|e| e.to_string())
+            .and_then(split_layer);
+        match parsed {
+            Ok((tag, body)) => entries.push(Entry { key, tag, body, changed: false }),
+            Err(error) => {
+                out.push(Diagnostic::Malformed { key: key.clone(), error });
+                if repair {
+                    writes.push(Write::Remove(key));
+                }
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+    // Map keys are the authoritative ids; they are unique by construction.
+    let keys: HashSet<String> = entries.iter().map(|e| e.key.clone()).collect();
+    for entry in &mut entries {
+        let key_id = entry.key.parse::<Uuid>().ok();
+        let body_id = entry.id();
+        if key_id.is_some() && key_id == body_id {
+            continue;
+        }
+        match (key_id, body_id) {
+            (Some(key_id), body_id) => {
+                if let Some(id) = body_id {
+                    out.push(Diagnostic::IdMismatch { key: entry.key.clone(), id });
+                } else {
+                    out.push(Diagnostic::Malformed {
+                        key: entry.key.clone(),
+                        error: "missing or invalid id".into(),
+                    });
+                }
+                if repair {
+                    entry.body.insert("id".into(), Value::String(key_id.to_string()));
+                    entry.changed = true;
+                }
+            }
+            (None, id) => {
+                let id = id.unwrap_or_else(Uuid::nil);
+                out.push(Diagnostic::IdMismatch { key: entry.key.clone(), id });
+                if repair {
+                    // Re-key under the layer's own id, or a fresh one if taken
+                    let new_id = if id.is_nil() || keys.contains(&id.to_string()) {
+                        Uuid::new_v4()
+                    } else {
+                        id
+                    };
+                    writes.push(Write::Remove(entry.key.clone()));
+                    entry.key = new_id.to_string();
+                    entry.body.insert("id".into(), Value::String(entry.key.clone()));
+                    entry.changed = true;
+                }
+            }
+        }
+    }
+
+    // Duplicate ids and bounds, walking nested children too. Top-level
+    // entries claim their ids first so nested copies are the ones renamed.
+    let mut seen: HashSet<Uuid> = entries.iter().filter_map(|e| e.key.parse().ok()).collect();
+    for entry in &mut entries {
+        let id = entry.key.parse().unwrap_or_else(|_| Uuid::nil());
+        entry.changed |= check_body_bounds(id, &mut entry.body, repair, out);
+        if let Some(Value::Array(children)) = entry.body.get_mut("children") {
+            entry.changed |= check_json_children(children, &mut seen, repair, out);
+        }
+    }
+
+    // Parent links between top-level entries.
+    let by_id: HashMap<Uuid, usize> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| Some((e.key.parse().ok()?, i)))
+        .collect();
+    let mut parents: BTreeMap<Uuid, Uuid> = BTreeMap::new();
+    let mut detach: Vec<usize> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let (Ok(id), Some(parent_id)) = (entry.key.parse::<Uuid>(), entry.parent_id()) else {
+            continue;
+        };
+        match by_id.get(&parent_id) {
+            None => {
+                out.push(Diagnostic::DanglingParent { id, parent_id });
+                detach.push(i);
+            }
+            Some(&p) if !matches!(entries[p].tag.as_str(), "Frame" | "Bool") => {
+                out.push(Diagnostic::ParentNotFrame { id, parent_id });
+                detach.push(i);
+            }
+            Some(_) => {
+                parents.insert(id, parent_id);
+            }
+        }
+    }
+    for cycle in find_cycles(&parents) {
+        // Detach the smallest member to break the loop
+        detach.push(by_id[&cycle[0]]);
+        out.push(Diagnostic::Cycle { ids: cycle });
+    }
+    if repair {
+        for i in detach {
+            entries[i].body.remove("parent_id");
+            entries[i].changed = true;
+        }
+    }
+
+    // Anything still unparseable after bounds repair cannot be salvaged.
+    for entry in &entries {
+        if let Err(e) = serde_json::from_str::<Layer>(&entry.to_json()) {
+            let bounds_issue = out.iter().any(|d| {
+                matches!(d, Diagnostic::NonFiniteBounds { id, .. } if id.to_string() == entry.key)
+            });
+            if repair || !bounds_issue {
+                out.push(Diagnostic::Malformed { key: entry.key.clone(), error: e.to_string() });
+            }
+            if repair {
+                writes.push(Write::Remove(entry.key.clone()));
+            }
+        } else if repair && entry.changed {
+            writes.push(Write::Insert(entry.key.clone(), entry.to_json()));
+        }
+    }
+
+    writes
+}
+
+fn check_json_children(
+    children: &mut [Value],
+    seen: &mut HashSet<Uuid>,
+    repair: bool,
+    out: &mut Vec<Diagnostic>,
+) -> bool {
+    let mut changed = false;
+    for child in children {
+        let Some(body) = child.as_object_mut().and_then(|o| o.values_mut().next()).and_then(Value::as_object_mut)
+        else {
+            continue;
+        };
+        let Some(mut id) = body.get("id").and_then(Value::as_str).and_then(|s| s.parse::<Uuid>().ok()) else {
+            continue;
+        };
+        if !seen.insert(id) {
+            out.push(Diagnostic::DuplicateId { id });
+            if repair {
+                id = Uuid::new_v4();
+                seen.insert(id);
+                body.insert("id".into(), Value::String(id.to_string()));
+                changed = true;
+            }
+        }
+        changed |= check_body_bounds(id, body, repair, out);
+        if let Some(Value::Array(grandchildren)) = body.get_mut("children") {
+            changed |= check_json_children(grandchildren, seen, repair, out);
+        }
+    }
+    changed
+}
+
+/// JSON cannot carry NaN, so serde_json writes it as `null`; treat anything
+/// that isn't a number as non-finite.
+fn check_body_bounds(id: Uuid, body: &mut JsonMap<String, Value>, repair: bool, out: &mut Vec<Diagnostic>) -> bool {
+    let bounds = body.get("bounds");
+    let field = |name: &str| {
+        bounds
+            .and_then(|b| b.get(name))
+            .and_then(Value::as_f64)
+            .map_or(f32::NAN, |v| v as f32)
+    };
+    let mut rect = Rect { x: field("x"), y: field("y"), width: field("width"), height: field("height") };
+    let before = out.len();
+    check_rect(id, &mut rect, repair, out);
+    let changed = out.len() > before;
+    if repair && changed {
+        body.insert("bounds".into(), serde_json::to_value(&rect).unwrap_or(Value::Null));
+    }
+    changed && repair
+}
+
+/// Cycles in a child → parent map, each reported once with sorted ids.
+fn find_cycles(parents: &BTreeMap<Uuid, Uuid>) -> Vec<Vec<Uuid>> {
+    let mut done: HashSet<Uuid> = HashSet::new();
+    let mut cycles = Vec::new();
+    for &start in parents.keys() {
+        let mut path: Vec<Uuid> = Vec::new();
+        let mut current = start;
+        loop {
+            if done.contains(&current) {
+                break;
+            }
+            if let Some(pos) = path.iter().position(|&id| id == current) {
+                let mut cycle = path[pos..].to_vec();
+                cycle.sort();
+                cycles.push(cycle);
+                break;
+            }
+            path.push(current);
+            match parents.get(&current) {
+                Some(&parent) => current = parent,
+                None => break,
+            }
+        }
+        done.extend(path);
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameLayer, RectLayer};
+    use serde_json::json;
+
+    fn doc_with(entries: &[(String, String)]) -> Doc {
+        let doc = Doc::new();
+        let map = doc.get_or_insert_map("layers");
+        let mut txn = doc.transact_mut();
+        for (k, v) in entries {
+            map.insert(&mut txn, k.clone(), v.clone());
+        }
+        drop(txn);
+        doc
+    }
+
+    fn rect_json(id: Uuid, parent: Option<Uuid>) -> String {
+        let mut body = json!({ "id": id, "bounds": { "x": 0.0, "y": 0.0, "width": 10.0, "height": 10.0 } });
+        if let Some(p) = parent {
+            body["parent_id"] = json!(p);
+        }
+        json!({ "Rect": body }).to_string()
+    }
+
+    fn frame_json(id: Uuid, parent: Option<Uuid>) -> String {
+        let mut body = json!({
+            "id": id,
+            "children": [],
+            "bounds": { "x": 0.0, "y": 0.0, "width": 100.0, "height": 100.0 },
+        });
+        if let Some(p) = parent {
+            body["parent_id"] = json!(p);
+        }
+        json!({ "Frame": body }).to_string()
+    }
+
+    #[test]
+    fn test_valid_page_has_no_diagnostics() {
+        let mut page = Page::new();
+        page.layers.push(Layer::Rect(RectLayer::new(0.0, 0.0, 10.0, 10.0)));
+        assert!(validate_page(&page).is_empty());
+        assert!(repair_page(&mut page).is_clean());
+    }
+
+    #[test]
+    fn test_page_duplicate_ids_and_bounds() {
+        let rect = RectLayer::new(0.0, 0.0, -10.0, f32::NAN);
+        let id = rect.id;
+        let frame = FrameLayer {
+            id: Uuid::new_v4(),
+            children: vec![Layer::Rect(rect.clone())],
+            ..Default::default()
+        };
+        let mut page = Page::new();
+        page.layers = vec![Layer::Rect(rect), Layer::Frame(frame)];
+
+        let diagnostics = validate_page(&page);
+        assert!(diagnostics.contains(&Diagnostic::DuplicateId { id }));
+        assert!(diagnostics.contains(&Diagnostic::NonFiniteBounds { id, field: "bounds.height".into() }));
+        assert!(diagnostics.contains(&Diagnostic::NegativeSize { id, field: "bounds.width".into(), value: -10.0 }));
+
+        let report = repair_page(&mut page);
+        assert!(report.remaining.is_empty(), "{:?}", report.remaining);
+        let b = page.layers[0].bounds();
+        assert_eq!((b.x, b.width, b.height), (-10.0, 10.0, 0.0));
+        assert_ne!(page.layers[1].children()[0].id(), id, "nested duplicate renamed");
+    }
+
+    #[test]
+    fn test_document_validate_and_repair() {
+        let doc = Document::new();
+        doc.add_layer(Layer::Rect(RectLayer::new(0.0, 0.0, f32::INFINITY, 1.0))).unwrap();
+        assert_eq!(doc.validate().unwrap().len(), 1);
+        assert_eq!(doc.repair().unwrap().fixed.len(), 1);
+        assert!(doc.validate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_doc_dangling_and_non_frame_parents() {
+        let (a, b, missing) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let doc = doc_with(&[
+            (a.to_string(), rect_json(a, Some(missing))),
+            (b.to_string(), rect_json(b, Some(a))),
+        ]);
+        let diagnostics = validate_doc(&doc);
+        assert!(diagnostics.contains(&Diagnostic::DanglingParent { id: a, parent_id: missing }));
+        assert!(diagnostics.contains(&Diagnostic::ParentNotFrame { id: b, parent_id: a }));
+
+        let (report, update) = repair_doc(&doc);
+        assert_eq!(report.fixed.len(), 2);
+        assert!(report.remaining.is_empty());
+        assert!(!update.is_empty());
+        assert!(validate_doc(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_doc_cycle_detected_and_broken() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let doc = doc_with(&[
+            (a.to_string(), frame_json(a, Some(b))),
+            (b.to_string(), frame_json(b, Some(a))),
+            (c.to_string(), rect_json(c, Some(a))),
+        ]);
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(validate_doc(&doc), vec![Diagnostic::Cycle { ids: expected }]);
+
+        let (report, _) = repair_doc(&doc);
+        assert_eq!(report.fixed.len(), 1);
+        assert!(report.remaining.is_empty());
+    }
+
+    #[test]
+    fn test_doc_id_mismatch_duplicate_and_malformed() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let nested = json!({ "Frame": {
+            "id": b,
+            "children": [{ "Rect": { "id": a, "bounds": { "x": 0, "y": 0, "width": 1, "height": 1 } } }],
+            "bounds": { "x": 0, "y": 0, "width": 5, "height": 5 },
+        }});
+        let doc = doc_with(&[
+            (a.to_string(), rect_json(Uuid::new_v4(), None)),
+            (b.to_string(), nested.to_string()),
+            ("garbage".to_string(), "{not json".to_string()),
+        ]);
+
+        let diagnostics = validate_doc(&doc);
+        assert_eq!(diagnostics.len(), 3, "{diagnostics:?}");
+        assert!(matches!(diagnostics[0], Diagnostic::Malformed { ref key, .. } if key == "garbage"));
+        assert!(matches!(diagnostics[1], Diagnostic::IdMismatch { ref key, .. } if *key == a.to_string()));
+        assert_eq!(diagnostics[2], Diagnostic::DuplicateId { id: a });
+
+        let (report, _) = repair_doc(&doc);
+        assert!(report.remaining.is_empty(), "{:?}", report.remaining);
+        let map = doc.get_or_insert_map("layers");
+        assert_eq!(map.len(&doc.transact()), 2, "malformed entry removed");
+    }
+
+    #[test]
+    fn test_null_bounds_are_repaired() {
+        let a = Uuid::new_v4();
+        let json = json!({ "Rect": { "id": a, "bounds": { "x": null, "y": 0, "width": 1, "height": 1 } } });
+        let doc = doc_with(&[(a.to_string(), json.to_string())]);
+        assert_eq!(
+            validate_doc(&doc),
+            vec![Diagnostic::NonFiniteBounds { id: a, field: "bounds.x".into() }]
+        );
+        let (report, _) = repair_doc(&doc);
+        assert!(report.remaining.is_empty());
+        let page = crate::collab::page_from_update(
+            &doc.transact().encode_state_as_update_v1(&StateVector::default()),
+        )
+        .unwrap();
+        assert_eq!(page.layers[0].bounds().x, 0.0);
+    }
+
+    #[test]
+    fn test_repair_update_roundtrip() {
+        let a = Uuid::new_v4();
+        let doc = doc_with(&[(a.to_string(), rect_json(a, Some(Uuid::new_v4())))]);
+        let state = doc.transact().encode_state_as_update_v1(&StateVector::default());
+        assert_eq!(validate_update(&state).unwrap().len(), 1);
+        let (report, repaired) = repair_update(&state).unwrap();
+        assert_eq!(report.fixed.len(), 1);
+        assert!(validate_update(&repaired).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_clean_doc_produces_no_update() {
+        let a = Uuid::new_v4();
+        let doc = doc_with(&[(a.to_string(), rect_json(a, None))]);
+        let (report, update) = repair_doc(&doc);
+        assert!(report.is_clean());
+        assert!(update.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+/* Exercises the logos-core C ABI from plain C. Built and run by tests/c_abi.rs. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "logos_core.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            failures++;                                                    \
+        }                                                                  \
+    } while (0)
+
+#define CHECK_OK(call)                                                     \
+    do {                                                                   \
+        char *err_ = NULL;                                                 \
+        LogosError rc_ = (call);                                           \
+        if (rc_ != LOGOS_ERROR_OK) {                                       \
+            fprintf(stderr, "%s:%d: %s returned %s: %s\n", __FILE__, __LINE__, #call, \
+                    logos_error_name(rc_), err_ ? err_ : "(no message)");  \
+            failures++;                                                    \
+        }                                                                  \
+        logos_string_free(err_);                                           \
+    } while (0)
+
+static void test_errors(void) {
+    char *err = NULL;
+    LogosError rc = logos_document_add_rect(NULL, 0, 0, 1, 1, &err);
+    CHECK(rc == LOGOS_ERROR_NULL_POINTER);
+    CHECK(err != NULL && strcmp(err, "Null document pointer") == 0);
+    logos_string_free(err);
+
+    LogosDocument *doc = logos_document_new();
+    err = NULL;
+    rc = logos_document_remove_layer(doc, "not-a-uuid", &err);
+    CHECK(rc == LOGOS_ERROR_INVALID_ARGUMENT);
+    logos_string_free(err);
+    CHECK(strcmp(logos_error_name(LOGOS_ERROR_NOT_FOUND), "not found") == 0);
+    CHECK(strcmp(logos_error_name(12345), "unknown") == 0);
+
+    /* Layer kinds outside the enum are refused, not reinterpreted */
+    LogosRect bounds = {0, 0, 10, 10};
+    char *id = NULL;
+    err = NULL;
+    rc = logos_document_add_layer(doc, 99, bounds, NULL, &id, &err);
+    CHECK(rc == LOGOS_ERROR_INVALID_ARGUMENT);
+    CHECK(err != NULL && strcmp(err, "Unknown layer kind 99") == 0);
+    CHECK(id == NULL);
+    logos_string_free(err);
+    err = NULL;
+    CHECK(logos_document_add_layer(doc, -1, bounds, NULL, &id, &err) == LOGOS_ERROR_INVALID_ARGUMENT);
+    logos_string_free(err);
+    size_t count = 1;
+    CHECK_OK(logos_document_child_count(doc, NULL, &count, &err_));
+    CHECK(count == 0);
+    logos_document_free(doc);
+
+    /* Freeing null is always safe */
+    logos_string_free(NULL);
+    logos_document_free(NULL);
+    logos_collab_free(NULL);
+}
+
+static void test_tree(void) {
+    LogosDocument *doc = logos_document_new();
+    LogosRect bounds = {0, 0, 200, 100};
+    char *frame = NULL, *rect = NULL, *text = NULL;
+
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_FRAME, bounds, NULL, &frame, &err_));
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_RECT, bounds, frame, &rect, &err_));
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_TEXT, bounds, frame, &text, &err_));
+
+    size_t count = 0;
+    CHECK_OK(logos_document_child_count(doc, NULL, &count, &err_));
+    CHECK(count == 1);
+    CHECK_OK(logos_document_child_count(doc, frame, &count, &err_));
+    CHECK(count == 2);
+
+    char *second = NULL;
+    CHECK_OK(logos_document_child_id(doc, frame, 1, &second, &err_));
+    CHECK(second != NULL && strcmp(second, text) == 0);
+    logos_string_free(second);
+
+    char *unused = NULL;
+    char *err = NULL;
+    CHECK(logos_document_child_id(doc, frame, 5, &unused, &err) == LOGOS_ERROR_OUT_OF_RANGE);
+    logos_string_free(err);
+
+    char *parent = NULL;
+    CHECK_OK(logos_document_parent_id(doc, rect, &parent, &err_));
+    CHECK(parent != NULL && strcmp(parent, frame) == 0);
+    logos_string_free(parent);
+
+    LogosLayerKind kind;
+    CHECK_OK(logos_document_layer_kind(doc, text, &kind, &err_));
+    CHECK(kind == LOGOS_LAYER_KIND_TEXT);
+
+    /* Move the text to the page root, in front of the frame */
+    CHECK_OK(logos_document_move_layer(doc, text, NULL, 0, &err_));
+    CHECK_OK(logos_document_parent_id(doc, text, &parent, &err_));
+    CHECK(parent == NULL);
+    CHECK_OK(logos_document_child_count(doc, NULL, &count, &err_));
+    CHECK(count == 2);
+
+    CHECK_OK(logos_document_remove_layer(doc, frame, &err_));
+    err = NULL;
+    CHECK(logos_document_layer_kind(doc, rect, &kind, &err) == LOGOS_ERROR_NOT_FOUND);
+    logos_string_free(err);
+
+    logos_string_free(frame);
+    logos_string_free(rect);
+    logos_string_free(text);
+    logos_document_free(doc);
+}
+
+static void test_properties_and_json(void) {
+    LogosDocument *doc = logos_document_new();
+    LogosRect bounds = {10, 20, 30, 40};
+    char *id = NULL;
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_TEXT, bounds, NULL, &id, &err_));
+
+    CHECK_OK(logos_layer_set_property(doc, id, "name", "\"Title\"", &err_));
+    CHECK_OK(logos_layer_set_property(doc, id, "content", "\"Hello from C\"", &err_));
+    CHECK_OK(logos_layer_set_property(doc, id, "bounds.width", "64", &err_));
+
+    char *value = NULL;
+    CHECK_OK(logos_layer_get_property(doc, id, "name", &value, &err_));
+    CHECK(value != NULL && strcmp(value, "\"Title\"") == 0);
+    logos_string_free(value);
+
+    LogosRect read;
+    CHECK_OK(logos_layer_get_bounds(doc, id, &read, &err_));
+    CHECK(read.x == 10 && read.width == 64);
+
+    LogosRect moved = {1, 2, 3, 4};
+    CHECK_OK(logos_layer_set_bounds(doc, id, moved, &err_));
+    CHECK_OK(logos_layer_get_bounds(doc, id, &read, &err_));
+    CHECK(read.x == 1 && read.height == 4);
+
+    /* Layer JSON round trip through replace */
+    char *layer_json = NULL;
+    CHECK_OK(logos_document_get_layer_json(doc, id, &layer_json, &err_));
+    CHECK(layer_json != NULL && strstr(layer_json, "Hello from C") != NULL);
+    CHECK_OK(logos_document_replace_layer_json(doc, id, layer_json, &err_));
+    logos_string_free(layer_json);
+
+    /* Whole-document JSON round trip */
+    char *json = NULL;
+    CHECK_OK(logos_document_to_json(doc, &json, &err_));
+    LogosDocument *copy = NULL;
+    CHECK_OK(logos_document_from_json(json, &copy, &err_));
+    CHECK_OK(logos_layer_get_property(copy, id, "content", &value, &err_));
+    CHECK(value != NULL && strcmp(value, "\"Hello from C\"") == 0);
+    logos_string_free(value);
+    logos_string_free(json);
+
+    logos_string_free(id);
+    logos_document_free(copy);
+    logos_document_free(doc);
+}
+
+static void test_bool_group(void) {
+    LogosDocument *doc = logos_document_new();
+    LogosRect ignored = {0, 0, 0, 0};
+    LogosRect left = {0, 0, 10, 10};
+    LogosRect right = {5, 5, 10, 10};
+    char *group = NULL, *a = NULL, *b = NULL;
+
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_BOOL, ignored, NULL, &group, &err_));
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_RECT, left, group, &a, &err_));
+    CHECK_OK(logos_document_add_layer(doc, LOGOS_LAYER_KIND_RECT, right, group, &b, &err_));
+
+    /* Bounds follow the operands */
+    LogosRect read;
+    CHECK_OK(logos_layer_get_bounds(doc, group, &read, &err_));
+    CHECK(read.width == 15 && read.height == 15);
+    CHECK_OK(logos_layer_set_property(doc, group, "bool_type", "\"Intersection\"", &err_));
+    CHECK_OK(logos_layer_get_bounds(doc, group, &read, &err_));
+    CHECK(read.x == 5 && read.width == 5);
+
+    char *err = NULL;
+    CHECK(logos_layer_set_bounds(doc, group, left, &err) == LOGOS_ERROR_INVALID_ARGUMENT);
+    logos_string_free(err);
+
+    logos_string_free(group);
+    logos_string_free(a);
+    logos_string_free(b);
+    logos_document_free(doc);
+}
+
+static void test_collab(void) {
+    LogosDocument *doc = logos_document_new();
+    LogosCollab *a = NULL;
+    LogosCollab *b = NULL;
+    CHECK_OK(logos_collab_new(doc, &a, &err_));
+    CHECK_OK(logos_collab_new(doc, &b, &err_));
+    CHECK(a != NULL && b != NULL);
+    char *null_err = NULL;
+    CHECK(logos_collab_new(NULL, &a, &null_err) == LOGOS_ERROR_NULL_POINTER);
+    CHECK(null_err != NULL);
+    logos_string_free(null_err);
+
+    const char *layer =
+        "{\"Rect\":{\"id\":\"6f1c2a8e-0b5d-4c1e-9a57-3d2f7e8b9c10\","
+        "\"bounds\":{\"x\":0,\"y\":0,\"width\":5,\"height\":5}}}";
+    LogosBuffer delta = {NULL, 0};
+    CHECK_OK(logos_collab_upsert_layer_json(a, layer, &delta, &err_));
+    CHECK(delta.len > 0);
+    CHECK_OK(logos_collab_apply_update(b, delta.data, delta.len, &err_));
+    logos_buffer_free(delta);
+
+    LogosBuffer sv = {NULL, 0};
+    LogosBuffer missing = {NULL, 0};
+    CHECK_OK(logos_collab_state_vector(a, &sv, &err_));
+    CHECK_OK(logos_collab_encode_update(b, sv.data, sv.len, &missing, &err_));
+    CHECK_OK(logos_collab_apply_update(a, missing.data, missing.len, &err_));
+    logos_buffer_free(sv);
+    logos_buffer_free(missing);
+
+    LogosDocument *materialized = NULL;
+    size_t count = 0;
+    CHECK_OK(logos_collab_to_document(b, &materialized, &err_));
+    CHECK_OK(logos_document_child_count(materialized, NULL, &count, &err_));
+    CHECK(count == 1);
+    logos_document_free(materialized);
+
+    CHECK_OK(logos_collab_remove_layer(a, "6f1c2a8e-0b5d-4c1e-9a57-3d2f7e8b9c10", &delta, &err_));
+    CHECK_OK(logos_collab_apply_update(b, delta.data, delta.len, &err_));
+    logos_buffer_free(delta);
+    CHECK_OK(logos_collab_to_document(b, &materialized, &err_));
+    CHECK_OK(logos_document_child_count(materialized, NULL, &count, &err_));
+    CHECK(count == 0);
+    logos_document_free(materialized);
+
+    char *err = NULL;
+    CHECK(logos_collab_apply_update(a, (const uint8_t *)"junk", 4, &err) == LOGOS_ERROR_COLLAB);
+    logos_string_free(err);
+
+    logos_collab_free(a);
+    logos_collab_free(b);
+    logos_document_free(doc);
+}
+
+int main(void) {
+    test_errors();
+    test_tree();
+    test_properties_and_json();
+    test_bool_group();
+    test_collab();
+    if (failures) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return 1;
+    }
+    printf("all C ABI checks passed\n");
+    return 0;
+}
@@ -0,0 +1,81 @@
+//! C ABI tests.
+//!
+//! Compiles `tests/c/abi_test.c` against `include/logos_core.h`, links it
+//! with the `staticlib` flavour of logos-core and runs the program. Cargo
+//! builds every crate type of the library before this test, and writes the
+//! archive next to the test binary in `deps/`. Set `CC` to pick a compiler;
+//! the test is skipped when none is available.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// The `liblogos_core.a` built alongside this test binary.
+fn staticlib() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().join("liblogos_core.a")
+}
+
+#[test]
+fn test_c_program_against_header_and_staticlib() {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&cc).arg("--version").output().is_err() {
+        eprintln!("skipping C ABI test: no C compiler ({cc})");
+        return;
+    }
+
+    let lib = staticlib();
+    assert!(lib.exists(), "static library not found at {}", lib.display());
+
+    let out_dir = tempfile::tempdir().unwrap();
+    let exe = out_dir.path().join("abi_test");
+    let compile = Command::new(&cc)
+        .arg("-std=c11")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-pedantic-errors")
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg(manifest_dir().join("tests/c/abi_test.c"))
+        .arg(&lib)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&exe)
+        .output()
+        .expect("failed to run C compiler");
+    assert!(
+        compile.status.success(),
+        "C compilation failed:\n{}",
+        String::from_utf8_lossy(&compile.stderr)
+    );
+
+    let run = Command::new(&exe).output().expect("failed to run C test");
+    assert!(
+        run.status.success(),
+        "C ABI checks failed:\n{}{}",
+        String::from_utf8_lossy(&run.stdout),
+        String::from_utf8_lossy(&run.stderr)
+    );
+}
+
+/// Every exported function must be declared in the committed header.
+#[test]
+fn test_header_declares_every_export() {
+    let source = std::fs::read_to_string(manifest_dir().join("src/ffi.rs")).unwrap();
+    let header = std::fs::read_to_string(manifest_dir().join("include/logos_core.h")).unwrap();
+    let exports: Vec<&str> = source
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("pub extern \"C\" fn "))
+        .filter_map(|l| l.split('(').next())
+        .collect();
+    assert!(exports.len() > 20);
+    for name in exports {
+        assert!(
+            header.contains(&format!("{name}(")),
+            "{name} is missing from include/logos_core.h; regenerate it with cbindgen"
+        );
+    }
+}
@@ -567,6 +567,7 @@ mod tests {
             id: Uuid::new_v4(),
             children: vec![child1, child2],
             bounds: LogosRect { x: 0.0, y: 0.0, width: 200.0, height: 100.0 },
+            ..Default::default()
         };
         let _frame_id = frame.id;
         let frame_layer = Layer::Frame(frame);
@@ -119,6 +119,22 @@ impl LayoutEngine {
                 },
                 ..Style::default()
             },
+            // Boolean groups are leaves for layout: their operands are geometry,
+            // not flex children, and the bounds are derived from them.
+            Layer::Bool(group) => Style {
+                size: Size {
+                    width: Dimension::length(group.bounds.width),
+                    height: Dimension::length(group.bounds.height),
+                },
+                position: Position::Absolute,
+                inset: taffy::Rect {
+                    left: LengthPercentageAuto::length(group.bounds.x),
+                    top: LengthPercentageAuto::length(group.bounds.y),
+                    right: LengthPercentageAuto::auto(),
+                    bottom: LengthPercentageAuto::auto(),
+                },
+                ..Style::default()
+            },
             Layer::Frame(frame) => Style {
                 display: Display::Flex,
                 size: Size {
@@ -493,6 +509,7 @@ mod tests {
         let updated_rect = RectLayer {
             id,
             bounds: LogosRect { x: 0.0, y: 0.0, width: 200.0, height: 100.0 },
+            ..Default::default()
         };
         let updated = Layer::Rect(updated_rect);
         engine.add_or_update_layer(&updated).unwrap();
@@ -552,16 +569,19 @@ mod tests {
         let ellipse = Layer::Ellipse(logos_core::EllipseLayer {
             id: Uuid::new_v4(),
             bounds: LogosRect { x: 0.0, y: 0.0, width: 20.0, height: 20.0 },
+            ..Default::default()
         });
         let text = Layer::Text(logos_core::TextLayer {
             id: Uuid::new_v4(),
             content: "hi".into(),
             bounds: LogosRect { x: 0.0, y: 0.0, width: 30.0, height: 12.0 },
+            ..Default::default()
         });
         let frame = Layer::Frame(FrameLayer {
             id: Uuid::new_v4(),
             children: vec![],
             bounds: LogosRect { x: 0.0, y: 0.0, width: 400.0, height: 300.0 },
+            ..Default::default()
         });
 
         let mut engine = LayoutEngine::new();
@@ -1,8 +1,18 @@
 //! Document → GPU bridge: converts `logos_core::Layer` trees with
 //! computed `logos_layout` results into `RectInstance` arrays for
 //! the rendering pipeline.
+//!
+//! Boolean groups draw their computed `result` path, tessellated into
+//! horizontal spans so the rect pipeline can fill them:
+//!
+//! ```text
+//!  vertex ys ── split the path into bands between consecutive vertices
+//!            ── slanted bands are cut again every BOOL_SCANLINE pixels
+//!            ── each band's even-odd spans become one RectInstance each
+//!            ── identical spans in adjacent bands merge vertically
+//! ```
 
-use logos_core::Layer;
+use logos_core::{Layer, VectorPath};
 use logos_layout::engine::LayoutEngine;
 use uuid::Uuid;
 
@@ -13,6 +23,10 @@ const COLOR_RECT: [f32; 4] = [0.26, 0.52, 0.96, 1.0]; // Blue
 const COLOR_ELLIPSE: [f32; 4] = [0.96, 0.26, 0.42, 1.0]; // Red
 const COLOR_TEXT: [f32; 4] = [0.96, 0.78, 0.26, 1.0]; // Yellow
 const COLOR_FRAME: [f32; 4] = [0.22, 0.22, 0.24, 0.8]; // Dark gray
+const COLOR_BOOL: [f32; 4] = [0.36, 0.78, 0.46, 1.0]; // Green
+
+/// Tallest band used for slanted edges of a boolean result, in pixels.
+const BOOL_SCANLINE: f32 = 1.0;
 
 /// Build a list of `RectInstance`s from the layout engine's computed results.
 ///
@@ -37,8 +51,26 @@ pub fn collect_instances(
             Layer::Ellipse(_) => COLOR_ELLIPSE,
             Layer::Text(_) => COLOR_TEXT,
             Layer::Frame(_) => COLOR_FRAME,
+            Layer::Bool(_) => COLOR_BOOL,
         };
 
+        // Same geometry as the SVG export; the bounding rect only stands in
+        // for groups without a result.
+        if let Layer::Bool(group) = layer {
+            if !group.result.is_empty() {
+                let offset = (
+                    layout.location.x - group.bounds.x,
+                    layout.location.y - group.bounds.y,
+                );
+                instances.extend(
+                    tessellate(&group.result, offset, color)
+                        .into_iter()
+                        .map(|span| span.with_z(i as f32)),
+                );
+                continue;
+            }
+        }
+
         let instance = RectInstance::new(
             layout.location.x,
             layout.location.y,
@@ -54,6 +86,82 @@ pub fn collect_instances(
     instances
 }
 
+/// Fill `path` (even-odd) with axis-aligned spans, shifted by `offset`.
+///
+/// Exact for rectilinear paths; slanted edges are stepped at
+/// [`BOOL_SCANLINE`] resolution.
+fn tessellate(path: &VectorPath, offset: (f32, f32), color: [f32; 4]) -> Vec<RectInstance> {
+    let edges: Vec<((f32, f32), (f32, f32))> = path
+        .contours
+        .iter()
+        .flat_map(|contour| {
+            contour
+                .iter()
+                .zip(contour.iter().cycle().skip(1))
+                .map(|(a, b)| ((a.x, a.y), (b.x, b.y)))
+        })
+        .filter(|((_, y0), (_, y1))| y0 != y1)
+        .collect();
+
+    let mut ys: Vec<f32> = path.contours.iter().flatten().map(|p| p.y).collect();
+    ys.sort_by(f32::total_cmp);
+    ys.dedup();
+
+    // Band boundaries: every vertex, plus scanline cuts where edges slant
+    let mut cuts = Vec::with_capacity(ys.len());
+    for pair in ys.windows(2) {
+        let (top, bottom) = (pair[0], pair[1]);
+        let mid = (top + bottom) / 2.0;
+        let slanted = edges
+            .iter()
+            .any(|&((x0, y0), (x1, y1))| (y0 <= mid) != (y1 <= mid) && x0 != x1);
+        let steps = if slanted {
+            ((bottom - top) / BOOL_SCANLINE).ceil().max(1.0) as usize
+        } else {
+            1
+        };
+        for k in 0..steps {
+            cuts.push(top + (bottom - top) * k as f32 / steps as f32);
+        }
+    }
+    if let Some(&last) = ys.last() {
+        cuts.push(last);
+    }
+
+    let mut instances = Vec::new();
+    // Spans of the band being extended downwards, and where it started
+    let mut open: (Vec<(f32, f32)>, f32) = (Vec::new(), 0.0);
+    let mut flush = |spans: &[(f32, f32)], top: f32, bottom: f32| {
+        for &(x0, x1) in spans {
+            let (x, y) = (x0 + offset.0, top + offset.1);
+            instances.push(RectInstance::new(x, y, x1 - x0, bottom - top, color));
+        }
+    };
+    for band in cuts.windows(2) {
+        let (top, bottom) = (band[0], band[1]);
+        let mid = (top + bottom) / 2.0;
+        let mut xs: Vec<f32> = edges
+            .iter()
+            .filter(|((_, y0), (_, y1))| (*y0 <= mid) != (*y1 <= mid))
+            .map(|&((x0, y0), (x1, y1))| x0 + (mid - y0) * (x1 - x0) / (y1 - y0))
+            .collect();
+        xs.sort_by(f32::total_cmp);
+        let spans: Vec<(f32, f32)> = xs
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .filter(|(x0, x1)| x1 > x0)
+            .collect();
+        if spans != open.0 {
+            flush(&open.0, open.1, top);
+            open = (spans, top);
+        }
+    }
+    if let Some(&bottom) = cuts.last() {
+        flush(&open.0, open.1, bottom);
+    }
+    instances
+}
+
 /// Build instances directly from position/size data (no layout engine needed).
 ///
 /// Useful for testing, demos, and initial bring-up before the full
@@ -75,7 +183,28 @@ pub fn collect_instances_direct(rects: &[(f32, f32, f32, f32, [f32; 4])]) -> Vec
 #[cfg(test)]
 mod tests {
     use super::*;
-    use logos_core::RectLayer;
+    use logos_core::{BoolLayer, BoolType, RectLayer};
+
+    /// Lay out `layer` on its own and collect its instances.
+    fn render_alone(layer: &Layer) -> Vec<RectInstance> {
+        let mut engine = LayoutEngine::new();
+        engine.add_or_update_layer(layer).unwrap();
+        engine.compute_layout(layer.id()).unwrap();
+        collect_instances(&engine, &[(layer.id(), layer)])
+    }
+
+    fn covers(instances: &[RectInstance], x: f32, y: f32) -> bool {
+        instances.iter().any(|r| {
+            x >= r.position[0]
+                && x <= r.position[0] + r.size[0]
+                && y >= r.position[1]
+                && y <= r.position[1] + r.size[1]
+        })
+    }
+
+    fn covered_area(instances: &[RectInstance]) -> f32 {
+        instances.iter().map(|r| r.size[0] * r.size[1]).sum()
+    }
 
     #[test]
     fn test_collect_instances_direct() {
@@ -125,16 +254,19 @@ mod tests {
         let ellipse = Layer::Ellipse(logos_core::EllipseLayer {
             id: Uuid::new_v4(),
             bounds: logos_core::Rect { x: 0.0, y: 0.0, width: 10.0, height: 10.0 },
+            ..Default::default()
         });
         let text = Layer::Text(logos_core::TextLayer {
             id: Uuid::new_v4(),
             content: "hi".into(),
             bounds: logos_core::Rect { x: 0.0, y: 0.0, width: 10.0, height: 10.0 },
+            ..Default::default()
         });
         let frame = Layer::Frame(logos_core::FrameLayer {
             id: Uuid::new_v4(),
             children: vec![],
             bounds: logos_core::Rect { x: 0.0, y: 0.0, width: 10.0, height: 10.0 },
+            ..Default::default()
         });
 
         let all = [&rect, &ellipse, &text, &frame];
@@ -155,4 +287,61 @@ mod tests {
         assert_eq!(instances[2].color, COLOR_TEXT);
         assert_eq!(instances[3].color, COLOR_FRAME);
     }
+
+    #[test]
+    fn test_bool_draws_result_geometry() {
+        // A square with a hole: the bounding rect would cover the hole
+        let group = BoolLayer::new(
+            BoolType::Difference,
+            vec![
+                Layer::Rect(RectLayer::new(0.0, 0.0, 100.0, 100.0)),
+                Layer::Rect(RectLayer::new(30.0, 30.0, 40.0, 40.0)),
+            ],
+        );
+        let area = group.result.area();
+        let layer = Layer::Bool(group);
+
+        let instances = render_alone(&layer);
+        assert!(instances.len() > 1);
+        assert!(instances.iter().all(|r| r.color == COLOR_BOOL && r.z_index == 0.0));
+        assert!((covered_area(&instances) - area).abs() < 1e-3);
+        assert!(covers(&instances, 5.0, 5.0));
+        assert!(covers(&instances, 90.0, 90.0));
+        assert!(!covers(&instances, 50.0, 50.0), "the hole stays empty");
+        assert!(!covers(&instances, 105.0, 50.0));
+    }
+
+    #[test]
+    fn test_bool_curved_result_is_stepped() {
+        let group = BoolLayer::new(
+            BoolType::Union,
+            vec![
+                Layer::Rect(RectLayer::new(0.0, 0.0, 50.0, 50.0)),
+                Layer::Ellipse(logos_core::EllipseLayer {
+                    id: Uuid::new_v4(),
+                    bounds: logos_core::Rect { x: 25.0, y: 25.0, width: 50.0, height: 50.0 },
+                    ..Default::default()
+                }),
+            ],
+        );
+        let area = group.result.area();
+        let instances = render_alone(&Layer::Bool(group));
+
+        // Within one scanline of error along the curved outline
+        assert!((covered_area(&instances) - area).abs() / area < 0.02);
+        assert!(covers(&instances, 50.0, 50.0));
+        assert!(!covers(&instances, 72.0, 28.0), "outside the ellipse's curve");
+    }
+
+    #[test]
+    fn test_bool_without_result_falls_back_to_bounds() {
+        let layer = Layer::Bool(BoolLayer {
+            bounds: logos_core::Rect { x: 0.0, y: 0.0, width: 20.0, height: 10.0 },
+            ..Default::default()
+        });
+        let instances = render_alone(&layer);
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].size, [20.0, 10.0]);
+        assert_eq!(instances[0].color, COLOR_BOOL);
+    }
 }